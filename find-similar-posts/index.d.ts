@@ -4,22 +4,529 @@
 /* auto-generated by NAPI-RS */
 
 export interface PostData {
+  /** An optional identifier of the post, such as its primary key in the source data. */
+  id?: string
   title: string
   content: string
+  /**
+   * Structured attributes of the post, such as `category`, `tags`, `author`, `language` and
+   * `createdAt`, which can be used to filter the candidates before scoring.
+   */
+  metadata?: Record<string, any>
+  /**
+   * A vector computed upstream from the post, such as a sentence embedding, which is indexed
+   * for `PostStore#findNearestPosts()`.
+   */
+  embedding?: Float32Array
 }
 export interface Match {
   target: PostData
   score: number
+  /**
+   * The rank of the post among the lexical matches, counting from 1. Only set by hybrid
+   * queries.
+   */
+  lexicalRank?: number
+  /**
+   * The rank of the post among the nearest embeddings, counting from 1. Only set by hybrid
+   * queries.
+   */
+  vectorRank?: number
+  /**
+   * The best matching pair of chunks of the contents, only set when the store scores by
+   * chunks.
+   */
+  chunks?: ChunkMatch
+  /**
+   * The names of the facets the post shares with the source, only set when the store
+   * extracts facets.
+   */
+  sharedFacets?: Array<string>
+  /**
+   * The fingerprint of the stack trace of the post, only set when the store compares
+   * traces.
+   */
+  fingerprint?: string
+  /** The symbols the post shares with the source, only set when the store compares symbols. */
+  sharedSymbols?: Array<string>
+}
+export interface ChunkMatch {
+  /** The chunk of the content of the source. */
+  source: string
+  /** The chunk of the content of the post. */
+  target: string
+  score: number
 }
 export interface FindTopNResult {
   matches: Array<Match>
   processTime: number
+  /**
+   * The generation of the store snapshot the query was run against, only set when querying a
+   * `PostStore`.
+   */
+  generation?: number
+  /** What each stage did, only set when the query ran through the pipeline of a `PostStore`. */
+  stages?: Array<StageStats>
+}
+/** What a stage of a pipeline query did. */
+export interface StageStats {
+  /** `retrieve`, `rerank` or `select`. */
+  stage: string
+  /** How much time the stage took, in milliseconds. */
+  time: number
+  /** How many candidates the stage received. */
+  input: number
+  /** How many candidates the stage passed on. */
+  output: number
+}
+/**
+ * How `findSimilarPostsNativeParallel()` and `findSimilarPostsNativeAsync()` score the
+ * candidates.
+ */
+export interface FindOptions {
+  /**
+   * When given, the overlap of the symbols mentioned by the candidates, such as `Deno.kill`,
+   * with the ones of the source is combined with their scores, and each match reports the
+   * symbols it shares.
+   */
+  symbols?: SymbolOptions
+}
+export interface LoadError {
+  /** The 1-based line number where the malformed record starts. */
+  line: number
+  message: string
+}
+export interface LoadReport {
+  /** How many posts have been loaded into the store. */
+  loaded: number
+  /** The records which have been skipped because they are malformed. */
+  errors: Array<LoadError>
+}
+export interface CsvOptions {
+  /** The column holding the title of the post, defaults to `title`. */
+  titleColumn?: string
+  /** The column holding the content of the post, defaults to `content`. */
+  contentColumn?: string
+  /**
+   * The column holding the ID of the post, defaults to `id`. Unlike the title and content
+   * columns, the default one may be absent from the file.
+   */
+  idColumn?: string
+  /** The field delimiter, which must be a single ASCII character, defaults to `,`. */
+  delimiter?: string
+}
+export interface DbColumns {
+  /** The column holding the ID of the post, defaults to `id`, which may be absent. */
+  id?: string
+  /** The column holding the title of the post, defaults to `title`. */
+  title?: string
+  /** The column holding the content of the post, defaults to `content`. */
+  content?: string
+  /** The columns copied into the metadata of the post, defaults to all the other columns. */
+  metadata?: Array<string>
+}
+export interface DbOptions {
+  /** The database URL, its scheme (`mysql:`, `postgres:` or `sqlite:`) selects the driver. */
+  url: string
+  /**
+   * The table to read the posts from, either `table` or `query` must be given.
+   * It must be a plain or a dotted identifier, such as `posts` or `public.posts`.
+   */
+  table?: string
+  /** A `select` statement to read the posts with, takes precedence over `table`. */
+  query?: string
+  columns?: DbColumns
+}
+export interface JsonlOptions {
+  /** The field holding the ID of the post, defaults to `id`. */
+  idField?: string
+  /** The field holding the title of the post, defaults to `title`. */
+  titleField?: string
+  /** The field holding the content of the post, defaults to `content`. */
+  contentField?: string
+  /**
+   * The field holding the metadata object of the post, defaults to `metadata`. Other unknown
+   * fields are merged into the metadata as well, except `embedding`, which holds the
+   * embedding of the post as an array of numbers.
+   */
+  metadataField?: string
+  /**
+   * Whether the input is gzip-compressed, detected from the content when omitted. Only used by
+   * `loadJsonl()`.
+   */
+  gzip?: boolean
+}
+export interface RankOptions {
+  /**
+   * The fields of the matched posts to return, any of `id`, `title`, `content` and
+   * `metadata`, defaults to `["id"]`. An empty array returns only the indices and scores.
+   */
+  fields?: Array<string>
+  /**
+   * When given, only the posts whose metadata satisfy it are scored, see [Filter] for the
+   * syntax.
+   */
+  filter?: any
+}
+/** A match given by the position of the post, carrying only the requested fields of it. */
+export interface RankedMatch {
+  /** The position of the post in the candidate set or in the store snapshot. */
+  index: number
+  score: number
+  id?: string
+  title?: string
+  content?: string
+  metadata?: Record<string, any>
+}
+export interface RankResult {
+  matches: Array<RankedMatch>
+  processTime: number
+  /**
+   * The generation of the store snapshot the query was run against, only set when querying a
+   * `PostStore`.
+   */
+  generation?: number
+}
+/**
+ * The threads the queries of a store run on, the rayon global pool shared by the whole process
+ * is used by default.
+ */
+export interface PoolOptions {
+  /** How many threads score the candidates, the rayon global pool is used when omitted. */
+  threads?: number
+  /**
+   * The prefix of the names of the threads, followed by their indices, defaults to
+   * `similar-`.
+   */
+  threadNamePrefix?: string
+  /** How many queries may run at the same time, unlimited when omitted. */
+  maxConcurrentQueries?: number
+  /**
+   * How many queries may wait for a running one to finish, the excess ones are rejected.
+   * Unlimited when omitted, `0` rejects the queries which can't run right away. A waiting
+   * query doesn't hold any thread, and a running one runs on the threads of the store
+   * instead of the libuv pool.
+   */
+  maxQueuedQueries?: number
+  /** Candidate sets smaller than this are scanned by a single thread, defaults to 256. */
+  sequentialThreshold?: number
+}
+/** How `findSimilarPosts()` fuses the lexical matches with the nearest embeddings. */
+export interface HybridOptions {
+  /**
+   * `rrf` for reciprocal rank fusion, or `weighted` for a weighted sum of the scores
+   * normalized to `0 - 1`, defaults to `rrf`.
+   */
+  fusion?: string
+  /**
+   * The `k` of the reciprocal rank fusion, a match scores `1 / (k + rank)` for each
+   * retriever, defaults to 60.
+   */
+  k?: number
+  /** The weight of the lexical score in the weighted fusion, defaults to 0.5. */
+  lexicalWeight?: number
+  /** The weight of the vector score in the weighted fusion, defaults to 0.5. */
+  vectorWeight?: number
+  /** How many matches each retriever returns before they are fused, defaults to 50. */
+  candidates?: number
+}
+/** How `findSimilarPosts()` narrows down the posts before scoring them with the edit distance. */
+export interface PipelineOptions {
+  /**
+   * The measure ranking the posts in the first stage, `ngram` for the character n-grams,
+   * `bm25` for the relevance to the words of the source, or `simhash` for the fingerprints
+   * of the words, defaults to `ngram`.
+   */
+  retriever?: string
+  /** The length of the n-grams, defaults to 3. */
+  n?: number
+  /** The term frequency saturation of BM25, defaults to 1.2. */
+  k1?: number
+  /** The length normalization of BM25, defaults to 0.75. */
+  b?: number
+  /** How many candidates the first stage keeps, defaults to 100. */
+  candidates?: number
+  /**
+   * How the candidates are scored, `levenshtein` for the whole texts, `partial` for the best
+   * part of the longer text, or `alignment` for the sentences of the texts in any order,
+   * defaults to `levenshtein`.
+   */
+  rescorer?: string
+}
+/** How `findSimilarPosts()` splits the contents of long posts into chunks and scores them. */
+export interface ChunkOptions {
+  /**
+   * `paragraph` for runs of lines separated by blank lines, `sentence` for sentences, or
+   * `window` for windows of a fixed number of characters, defaults to `paragraph`.
+   */
+  split?: string
+  /**
+   * How many paragraphs or sentences a chunk shares with the next one, or how many
+   * characters for the windows. Defaults to 0 paragraph, 1 sentence or 100 characters.
+   */
+  overlap?: number
+  /** The number of characters of a window, defaults to 500. */
+  size?: number
+  /**
+   * How the similarities of the chunks of the source to their best matching chunks are
+   * combined, `max` for the best one, `mean` for the mean of the `k` best ones, or
+   * `coverage` for the share of the source whose chunks have a match of at least
+   * `threshold`, defaults to `max`.
+   */
+  aggregation?: string
+  /** How many similarities `mean` averages, defaults to 3. */
+  k?: number
+  /** The similarity `coverage` counts a chunk of the source as matched from, defaults to 0.8. */
+  threshold?: number
+}
+/** How `learnBoilerplate()` finds the lines or the runs of words shared by many documents. */
+export interface BoilerplateOptions {
+  /** `line` for whole lines, or `ngram` for runs of words, defaults to `line`. */
+  unit?: string
+  /** How many words a run is made of, defaults to 4. */
+  n?: number
+  /** The share of the documents a line or a run of words must appear in, defaults to 0.3. */
+  minShare?: number
+  /** How many documents a line or a run of words must appear in, defaults to 3. */
+  minDocuments?: number
+}
+export interface BoilerplateEntry {
+  /** The line or the run of words, with its whitespace collapsed and lowercased. */
+  text: string
+  /** The share of the documents it was found in. */
+  share: number
+}
+/**
+ * The boilerplate learned by `learnBoilerplate()`, which can be edited before it's given to
+ * `setBoilerplate()`.
+ */
+export interface Boilerplate {
+  /** `line` or `ngram`. */
+  unit: string
+  /** How many words a run is made of, only set for `ngram`. */
+  n?: number
+  entries: Array<BoilerplateEntry>
+}
+export interface FacetRule {
+  /** The name of the facet, such as `os`. */
+  name: string
+  /**
+   * A regular expression, the value of the facet is its `value` group, or the whole match if
+   * it has none.
+   */
+  pattern: string
+}
+/**
+ * How the facets of the posts, such as their version and OS, are extracted by the queries of
+ * the store.
+ */
+export interface FacetOptions {
+  /** Rules taking precedence over the built-in ones. */
+  rules?: Array<FacetRule>
+  /**
+   * Whether the built-in rules for the `version`, `os`, `arch` and `runtime` facets are
+   * used, defaults to `true`.
+   */
+  builtin?: boolean
+  /**
+   * Added to the score of a match sharing every facet of the source, a share of it for a
+   * match sharing some of them, from 0 to 1, defaults to 0.
+   */
+  boost?: number
+}
+/** How the stack traces of the posts are compared with the one of the source. */
+export interface TraceOptions {
+  /** How many frames from the top of a trace are fingerprinted and compared, defaults to 5. */
+  depth?: number
+  /**
+   * `signal` to combine the similarity of the traces with the one of the texts, or `bucket`
+   * to score the posts with the fingerprint of the source as exact matches, defaults to
+   * `signal`.
+   */
+  matching?: string
+  /**
+   * The share of the score made up by the similarity of the traces for `signal`, from 0 to
+   * 1, defaults to 0.3.
+   */
+  weight?: number
+}
+/**
+ * How the symbols mentioned by the posts, such as `Deno.kill`, `ERR_INVALID_ARG_TYPE` or
+ * `SIGTERM`, are compared with the ones of the source.
+ */
+export interface SymbolOptions {
+  /**
+   * The share of the score made up by the overlap of the symbols, from 0 to 1, defaults to
+   * 0.25.
+   */
+  weight?: number
+}
+/** How a store is set up, every option is off when left out. */
+export interface StoreOptions {
+  /** The threads the queries run on. */
+  pool?: PoolOptions
+  /** How the embeddings of the posts are indexed. */
+  vectors?: VectorIndexOptions
+  /**
+   * When given, `findSimilarPosts()` only scores the candidates retrieved by its first
+   * stage, and reports the time and the candidates of each stage.
+   */
+  pipeline?: PipelineOptions
+  /**
+   * When given, the contents are scored by their chunks, and each match reports its best
+   * matching pair of chunks.
+   */
+  chunking?: ChunkOptions
+  /**
+   * How the posts are scored without a pipeline, `levenshtein` for the whole texts,
+   * `partial` for the best part of the longer text, or `alignment` for the sentences of the
+   * texts in any order, defaults to `levenshtein`.
+   */
+  metric?: string
+  /**
+   * When given, the facets of the posts are extracted into the metadata the queries see under
+   * `facets`, where they can be filtered on, and each match reports the ones it shares with
+   * the source.
+   */
+  facets?: FacetOptions
+  /**
+   * When given, the stack traces of the posts are fingerprinted into the metadata the queries
+   * see under `trace`, and compared with the one of the source.
+   */
+  traces?: TraceOptions
+  /**
+   * When given, the symbols the posts mention are extracted into the metadata the queries see
+   * under `symbols`, and each match reports the ones it shares with the source.
+   */
+  symbols?: SymbolOptions
+}
+/** How the embeddings of the posts are indexed for `findNearestPosts()`. */
+export interface VectorIndexOptions {
+  /** `cosine`, `dot` or `l2`, defaults to `cosine`. */
+  distance?: string
+  /** How many neighbors a node links to on each layer of the graph, defaults to 16. */
+  m?: number
+  /** How many candidates are considered when indexing an embedding, defaults to 200. */
+  efConstruction?: number
+  /** How many candidates are considered when querying, defaults to 64. */
+  efSearch?: number
 }
 export declare function findSimilarPostsNative(source: PostData, candidates: Array<PostData>, topN: number): FindTopNResult
-export declare function findSimilarPostsNativeParallel(source: PostData, candidates: Array<PostData>, topN: number): FindTopNResult
-export declare function findSimilarPostsNativeAsync(source: PostData, candidates: Array<PostData>, topN: number): Promise<FindTopNResult>
+/**
+ * Like `findSimilarPostsNative()`, but scores the candidates in parallel as set up by
+ * `options`.
+ */
+export declare function findSimilarPostsNativeParallel(source: PostData, candidates: Array<PostData>, topN: number, options?: FindOptions | undefined | null): FindTopNResult
+export declare function findSimilarPostsNativeAsync(source: PostData, candidates: Array<PostData>, topN: number, options?: FindOptions | undefined | null): Promise<FindTopNResult>
 export declare class PostStore {
-  constructor()
+  /** Saves the posts to a binary snapshot file, which can be restored with `PostStore.load()`. */
+  save(path: string): Promise<void>
+  /** Loads a store from a binary snapshot file created by `PostStore#save()`. */
+  static load(path: string): Promise<PostStore>
+  /**
+   * Replaces the posts in the store with the ones in a CSV file, which is parsed on a worker
+   * thread. Columns other than the title, content and ID ones are kept as string metadata.
+   * Malformed records are skipped and reported with their line numbers.
+   */
+  loadCsv(path: string, options?: CsvOptions | undefined | null): Promise<LoadReport>
+  /**
+   * Writes the posts in the store to a CSV file, using the same column options as
+   * `loadCsv()`. Metadata values which are not strings are written as JSON.
+   */
+  dumpCsv(path: string, options?: CsvOptions | undefined | null): Promise<void>
+  /**
+   * Loads a store from a MySQL, PostgreSQL or SQLite database, mapping the columns of `table`
+   * or the rows returned by `query` to posts.
+   */
+  static fromDb(options: DbOptions): Promise<PostStore>
+  /**
+   * Replaces the posts in the store with the ones in a JSON Lines file, which is streamed line
+   * by line on a worker thread. Malformed lines are skipped and reported with their line
+   * numbers.
+   */
+  loadJsonl(path: string, options?: JsonlOptions | undefined | null): Promise<LoadReport>
+  /**
+   * Writes the posts in the store to a JSON Lines file, using the same field options as
+   * `loadJsonl()`.
+   */
+  dumpJsonl(path: string, options?: JsonlOptions | undefined | null): Promise<void>
+  /**
+   * Opens a binary snapshot file created by `PostStore#save()` in read-only mode by memory
+   * mapping it. Posts added afterwards with `append()` are kept in memory on top of the mapped
+   * ones, while `preload()` replaces both.
+   */
+  static open(path: string): Promise<PostStore>
+  /** Creates an empty store set up by `options`. */
+  constructor(options?: StoreOptions | undefined | null)
+  /**
+   * Attaches to the process-global store named `name`, which is shared by all the worker
+   * threads of the process. It's created empty on the first call, and dropped when the last
+   * instance attached to it is disposed or garbage collected.
+   */
+  static shared(name: string): PostStore
+  /** The name of the shared store this instance is attached to. */
+  get sharedName(): string | null
+  /**
+   * Releases the posts held by this instance, which becomes an empty, unshared store. The
+   * posts of a shared store are dropped once every instance attached to it is disposed.
+   */
+  dispose(): void
+  /** The generation of the current snapshot, increased by every write. */
+  get generation(): number
+  /** Replaces all the posts in the store, including the ones of an opened snapshot file. */
   preload(posts: Array<PostData>): void
-  findSimilarPosts(source: PostData, topN: number): Promise<FindTopNResult>
+  /** Adds posts to the store, keeping the existing ones. */
+  append(posts: Array<PostData>): void
+  /**
+   * Learns the lines or the runs of words shared by many contents of the posts in the current
+   * snapshot, such as the headers of a template. It isn't updated by later writes.
+   */
+  learnBoilerplate(options?: BoilerplateOptions | undefined | null): Promise<Boilerplate>
+  /**
+   * The boilerplate removed from the contents before they are scored by
+   * `findSimilarPosts()`, if any.
+   */
+  get boilerplate(): Boilerplate | null
+  /**
+   * Removes `boilerplate` from the contents of the posts and the source before they are
+   * scored by `findSimilarPosts()` of this instance, or stops removing it if `null`. When
+   * scoring by chunks, the chunks made of boilerplate are skipped instead.
+   */
+  setBoilerplate(boilerplate?: Boilerplate | undefined | null): void
+  /**
+   * Finds the `top_n` posts most similar to `source`. When `filter` is given, only the posts
+   * whose metadata satisfy it are scored, see [Filter] for the syntax. When `hybrid` is
+   * given, the lexical matches are fused with the posts whose embeddings are the nearest to
+   * the one of `source`, and each match reports its rank in both.
+   */
+  findSimilarPosts(source: PostData, topN: number, filter?: any | undefined | null, hybrid?: HybridOptions | undefined | null): Promise<FindTopNResult>
+  /**
+   * Like `findSimilarPosts()`, but returns the positions of the posts in the snapshot along
+   * with the fields picked by `options` instead of the whole posts.
+   */
+  rankSimilarPosts(source: PostData, topN: number, options?: RankOptions | undefined | null): Promise<RankResult>
+  /**
+   * Finds the `top_n` posts whose embeddings are the nearest to `embedding`, the score of a
+   * match is the cosine similarity, the dot product or `1 / (1 + distance)` for `l2`. The
+   * embeddings are indexed by the first query after a write, except `append()`, which adds
+   * the new ones to the existing index. There is no text to compare, so the metric and the
+   * chunking of the store don't apply, but `filter` sees the facets, the stack traces and the
+   * symbols it extracts.
+   */
+  findNearestPosts(embedding: Float32Array, topN: number, filter?: any | undefined | null): Promise<FindTopNResult>
+}
+/**
+ * A set of candidate posts which is marshalled once and can be queried many times, unlike
+ * the arrays passed to `findSimilarPostsNative()`.
+ */
+export declare class CandidateSet {
+  constructor(posts: Array<PostData>)
+  get length(): number
+  /** Returns the post at `index`, such as the one of a `RankedMatch`. */
+  get(index: number): PostData | null
+  /**
+   * Finds the `top_n` posts most similar to `source`, returning their positions and scores
+   * along with the fields picked by `options`.
+   */
+  rankSimilarPosts(source: PostData, topN: number, options?: RankOptions | undefined | null): Promise<RankResult>
 }
@@ -310,8 +310,9 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { PostStore, findSimilarPostsNative, findSimilarPostsNativeParallel, findSimilarPostsNativeAsync } = nativeBinding
+const { CandidateSet, PostStore, findSimilarPostsNative, findSimilarPostsNativeParallel, findSimilarPostsNativeAsync } = nativeBinding
 
+module.exports.CandidateSet = CandidateSet
 module.exports.PostStore = PostStore
 module.exports.findSimilarPostsNative = findSimilarPostsNative
 module.exports.findSimilarPostsNativeParallel = findSimilarPostsNativeParallel
@@ -2,6 +2,7 @@ import { deepStrictEqual, strictEqual } from "node:assert"
 import { parse } from "@std/csv"
 // @ts-types="./index.d.ts"
 import {
+    CandidateSet,
     findSimilarPostsNative,
     findSimilarPostsNativeAsync,
     findSimilarPostsNativeParallel,
@@ -159,5 +160,67 @@ let match: Match | undefined
     deepStrictEqual(matches[0], match)
 }
 
+{
+    const store = new PostStore()
+    const report = await store.loadCsv(filename, { contentColumn: "text" })
+    strictEqual(report.loaded, posts.length)
+    strictEqual(report.errors.length, 0)
+
+    const start = Date.now()
+    const { matches, processTime } = await store.findSimilarPosts(newPost, 3)
+    const callTime = Date.now() - start
+    results.push({
+        fn_name: "PostStore#findSimilarPosts (loadCsv)",
+        call_time_ms: callTime,
+        process_time_ms: processTime,
+        data_passing_ms: callTime - processTime,
+    })
+    strictEqual(matches.length, 1)
+    strictEqual(matches[0].target.title, match!.target.title)
+    strictEqual(matches[0].score, match!.score)
+}
+
+{
+    const candidates = new CandidateSet(posts)
+
+    const start = Date.now()
+    const { matches, processTime } = await candidates.rankSimilarPosts(
+        newPost,
+        3,
+        { fields: ["title"] },
+    )
+    const callTime = Date.now() - start
+    results.push({
+        fn_name: "CandidateSet#rankSimilarPosts",
+        call_time_ms: callTime,
+        process_time_ms: processTime,
+        data_passing_ms: callTime - processTime,
+    })
+    strictEqual(matches.length, 1)
+    strictEqual(matches[0].title, match!.target.title)
+    strictEqual(matches[0].score, match!.score)
+    deepStrictEqual(candidates.get(matches[0].index), match!.target)
+}
+
+{
+    const store = new PostStore()
+    store.preload(posts)
+
+    const start = Date.now()
+    const { matches, processTime } = await store.rankSimilarPosts(newPost, 3, {
+        fields: [],
+    })
+    const callTime = Date.now() - start
+    results.push({
+        fn_name: "PostStore#rankSimilarPosts",
+        call_time_ms: callTime,
+        process_time_ms: processTime,
+        data_passing_ms: callTime - processTime,
+    })
+    strictEqual(matches.length, 1)
+    strictEqual(posts[matches[0].index].title, match!.target.title)
+    strictEqual(matches[0].score, match!.score)
+}
+
 console.table(results)
 Deno.exit(0)
@@ -1,20 +1,97 @@
 #![deny(clippy::all)]
-use std::time::Instant;
-
-use napi::{bindgen_prelude::AsyncTask, Env, Error, Result, Task};
-use rapidfuzz::distance::levenshtein::normalized_similarity;
+use napi::{
+    bindgen_prelude::{
+        AsyncTask, Float32Array, FromNapiValue, ToNapiValue, TypeName, ValidateNapiValue,
+    },
+    sys, Env, Result, Task, ValueType,
+};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde_json::{Map, Value};
+use similar_core::{
+    post::{self, PostRef},
+    symbol,
+};
+pub(crate) use similar_napi::{spawn_task, to_napi_error};
+
+use crate::store::SymbolOptions;
 
 #[macro_use]
 extern crate napi_derive;
 
+pub mod rank;
 pub mod store;
 
+/// A vector computed by the caller, passed as a `Float32Array`. It's copied out of the
+/// JavaScript heap, so it can be moved to the threads scoring the posts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding(pub Vec<f32>);
+
+impl TypeName for Embedding {
+    fn type_name() -> &'static str {
+        Float32Array::type_name()
+    }
+
+    fn value_type() -> ValueType {
+        Float32Array::value_type()
+    }
+}
+
+impl ValidateNapiValue for Embedding {
+    unsafe fn validate(env: sys::napi_env, napi_val: sys::napi_value) -> Result<sys::napi_value> {
+        Float32Array::validate(env, napi_val)
+    }
+}
+
+impl FromNapiValue for Embedding {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> Result<Self> {
+        let array = Float32Array::from_napi_value(env, napi_val)?;
+        Ok(Embedding(array.to_vec()))
+    }
+}
+
+impl ToNapiValue for Embedding {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> Result<sys::napi_value> {
+        Float32Array::to_napi_value(env, Float32Array::new(val.0))
+    }
+}
+
 #[napi(object)]
 #[derive(Debug, Clone)]
 pub struct PostData {
+    /// An optional identifier of the post, such as its primary key in the source data.
+    pub id: Option<String>,
     pub title: String,
     pub content: String,
+    /// Structured attributes of the post, such as `category`, `tags`, `author`, `language` and
+    /// `createdAt`, which can be used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+    /// A vector computed upstream from the post, such as a sentence embedding, which is indexed
+    /// for `PostStore#findNearestPosts()`.
+    pub embedding: Option<Embedding>,
+}
+
+impl From<PostData> for post::PostData {
+    fn from(post: PostData) -> Self {
+        post::PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: post.embedding.map(|embedding| embedding.0),
+        }
+    }
+}
+
+impl From<post::PostData> for PostData {
+    fn from(post: post::PostData) -> Self {
+        PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: post.embedding.map(Embedding),
+        }
+    }
 }
 
 #[napi(object)]
@@ -22,6 +99,33 @@ pub struct PostData {
 pub struct Match {
     pub target: PostData,
     pub score: f64,
+    /// The rank of the post among the lexical matches, counting from 1. Only set by hybrid
+    /// queries.
+    pub lexical_rank: Option<u32>,
+    /// The rank of the post among the nearest embeddings, counting from 1. Only set by hybrid
+    /// queries.
+    pub vector_rank: Option<u32>,
+    /// The best matching pair of chunks of the contents, only set when the store scores by
+    /// chunks.
+    pub chunks: Option<ChunkMatch>,
+    /// The names of the facets the post shares with the source, only set when the store
+    /// extracts facets.
+    pub shared_facets: Option<Vec<String>>,
+    /// The fingerprint of the stack trace of the post, only set when the store compares
+    /// traces.
+    pub fingerprint: Option<String>,
+    /// The symbols the post shares with the source, only set when the store compares symbols.
+    pub shared_symbols: Option<Vec<String>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ChunkMatch {
+    /// The chunk of the content of the source.
+    pub source: String,
+    /// The chunk of the content of the post.
+    pub target: String,
+    pub score: f64,
 }
 
 #[napi(object)]
@@ -29,128 +133,137 @@ pub struct Match {
 pub struct FindTopNResult {
     pub matches: Vec<Match>,
     pub process_time: i64, // how many time is used for processing
+    /// The generation of the store snapshot the query was run against, only set when querying a
+    /// `PostStore`.
+    pub generation: Option<i64>,
+    /// What each stage did, only set when the query ran through the pipeline of a `PostStore`.
+    pub stages: Option<Vec<StageStats>>,
 }
 
-fn get_weights(source: &PostData) -> Result<(f64, f64)> {
-    let title_chars = source.title.chars().count();
-    let content_chars = source.content.chars().count();
-    let total_chars = title_chars + content_chars;
+/// What a stage of a pipeline query did.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct StageStats {
+    /// `retrieve`, `rerank` or `select`.
+    pub stage: String,
+    /// How much time the stage took, in milliseconds.
+    pub time: f64,
+    /// How many candidates the stage received.
+    pub input: u32,
+    /// How many candidates the stage passed on.
+    pub output: u32,
+}
 
-    if total_chars == 0 {
-        Err(Error::from_reason("source is invalid"))
-    } else {
-        Ok((
-            title_chars as f64 / total_chars as f64,
-            content_chars as f64 / total_chars as f64,
-        ))
+impl From<post::FindTopNResult> for FindTopNResult {
+    fn from(result: post::FindTopNResult) -> Self {
+        FindTopNResult::from_query(result, "")
     }
 }
 
+impl FindTopNResult {
+    /// Converts the result of a query whose source has `content`, which the chunks of the
+    /// matches refer to.
+    pub(crate) fn from_query(result: post::FindTopNResult, content: &str) -> Self {
+        FindTopNResult {
+            matches: result
+                .matches
+                .into_iter()
+                .map(|m| Match {
+                    chunks: m.chunks.map(|chunks| ChunkMatch {
+                        source: content.get(chunks.source).unwrap_or_default().to_string(),
+                        target: m.target.content[chunks.target].to_string(),
+                        score: chunks.score,
+                    }),
+                    target: m.target.into(),
+                    score: m.score,
+                    lexical_rank: m.lexical_rank.map(|rank| rank as u32),
+                    vector_rank: m.vector_rank.map(|rank| rank as u32),
+                    shared_facets: (!m.shared_facets.is_empty()).then_some(m.shared_facets),
+                    fingerprint: m.fingerprint,
+                    shared_symbols: (!m.shared_symbols.is_empty()).then_some(m.shared_symbols),
+                })
+                .collect(),
+            process_time: result.process_time.as_millis() as i64,
+            generation: result.generation.map(|generation| generation as i64),
+            stages: (!result.stages.is_empty()).then(|| {
+                result
+                    .stages
+                    .iter()
+                    .map(|stats| StageStats {
+                        stage: stats.stage.as_str().to_string(),
+                        time: stats.time.as_secs_f64() * 1000.0,
+                        input: stats.input as u32,
+                        output: stats.output as u32,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+fn into_post_data(posts: Vec<PostData>) -> Vec<post::PostData> {
+    posts.into_iter().map(post::PostData::from).collect()
+}
+
 #[napi]
 pub fn find_similar_posts_native(
     source: PostData,
     candidates: Vec<PostData>,
     top_n: u32,
 ) -> Result<FindTopNResult> {
-    let start = Instant::now();
-    let (title_weight, content_weight) = get_weights(&source)?;
-    let mut matches = vec![];
-
-    for candidate in candidates.into_iter() {
-        let title_score =
-            normalized_similarity(source.title.chars(), candidate.title.chars()) * title_weight;
-        let content_score =
-            normalized_similarity(source.content.chars(), candidate.content.chars())
-                * content_weight;
-        let score = title_score + content_score;
-
-        // 0.5 is the threshold to consider a match
-        if score > 0.5 {
-            matches.push(Match {
-                target: candidate,
-                score,
-            });
-        }
-    }
-
-    matches.sort_by(|a, b| {
-        let order = b.score.partial_cmp(&a.score);
-        order.unwrap_or(std::cmp::Ordering::Equal)
-    });
+    post::find_similar_posts(&source.into(), into_post_data(candidates), top_n as usize)
+        .map(FindTopNResult::from)
+        .map_err(to_napi_error)
+}
 
-    let top_n_matches = if matches.len() <= top_n as usize {
-        matches
-    } else {
-        matches.into_iter().take(top_n as usize).collect()
-    };
-    let duration = start.elapsed();
+/// How `findSimilarPostsNativeParallel()` and `findSimilarPostsNativeAsync()` score the
+/// candidates.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct FindOptions {
+    /// When given, the overlap of the symbols mentioned by the candidates, such as `Deno.kill`,
+    /// with the ones of the source is combined with their scores, and each match reports the
+    /// symbols it shares.
+    pub symbols: Option<SymbolOptions>,
+}
 
-    Ok(FindTopNResult {
-        matches: top_n_matches,
-        process_time: duration.as_millis() as i64,
-    })
+impl FindOptions {
+    fn symbols(options: Option<FindOptions>) -> Result<Option<symbol::SymbolOptions>> {
+        options
+            .and_then(|options| options.symbols)
+            .map(|symbols| symbols.build())
+            .transpose()
+    }
 }
 
+/// Like `findSimilarPostsNative()`, but scores the candidates in parallel as set up by
+/// `options`.
 #[napi]
 fn find_similar_posts_native_parallel(
     source: PostData,
     candidates: Vec<PostData>,
     top_n: u32,
+    options: Option<FindOptions>,
 ) -> Result<FindTopNResult> {
-    do_find_similar_posts_native_parallel(&source, &candidates, top_n)
-}
-
-fn do_find_similar_posts_native_parallel(
-    source: &PostData,
-    candidates: &Vec<PostData>,
-    top_n: u32,
-) -> Result<FindTopNResult> {
-    let start = Instant::now();
-    let (title_weight, content_weight) = get_weights(source)?;
-
-    let mut matches: Vec<Match> = candidates
-        .par_iter()
-        .filter_map(|candidate| {
-            let title_score =
-                normalized_similarity(source.title.chars(), candidate.title.chars()) * title_weight;
-            let content_score =
-                normalized_similarity(source.content.chars(), candidate.content.chars())
-                    * content_weight;
-            let score = title_score + content_score;
-
-            if score > 0.5 {
-                Some(Match {
-                    target: candidate.clone(),
-                    score,
-                })
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    matches.sort_by(|a, b| {
-        let order = b.score.partial_cmp(&a.score);
-        order.unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    let top_n_matches = if matches.len() <= top_n as usize {
-        matches
-    } else {
-        matches.into_iter().take(top_n as usize).collect()
-    };
-    let duration = start.elapsed();
-
-    Ok(FindTopNResult {
-        matches: top_n_matches,
-        process_time: duration.as_millis() as i64,
-    })
+    let candidates = into_post_data(candidates);
+    let symbols = FindOptions::symbols(options)?;
+
+    post::find_similar_posts_parallel(
+        &source.into(),
+        candidates.par_iter().map(PostRef::from),
+        top_n as usize,
+        None,
+        symbols.as_ref(),
+    )
+    .map(FindTopNResult::from)
+    .map_err(to_napi_error)
 }
 
 pub struct AsyncFindSimilarPosts {
-    source: PostData,
-    candidates: Vec<PostData>,
+    source: post::PostData,
+    candidates: Vec<post::PostData>,
     top_n: u32,
+    symbols: Option<symbol::SymbolOptions>,
 }
 
 #[napi]
@@ -159,7 +272,15 @@ impl Task for AsyncFindSimilarPosts {
     type JsValue = FindTopNResult;
 
     fn compute(&mut self) -> Result<Self::Output> {
-        do_find_similar_posts_native_parallel(&self.source, &self.candidates, self.top_n)
+        post::find_similar_posts_parallel(
+            &self.source,
+            self.candidates.par_iter().map(PostRef::from),
+            self.top_n as usize,
+            None,
+            self.symbols.as_ref(),
+        )
+        .map(FindTopNResult::from)
+        .map_err(to_napi_error)
     }
 
     fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
@@ -172,23 +293,28 @@ pub fn find_similar_posts_native_async(
     source: PostData,
     candidates: Vec<PostData>,
     top_n: u32,
-) -> AsyncTask<AsyncFindSimilarPosts> {
-    AsyncTask::new(AsyncFindSimilarPosts {
-        source,
-        candidates,
+    options: Option<FindOptions>,
+) -> Result<AsyncTask<AsyncFindSimilarPosts>> {
+    Ok(AsyncTask::new(AsyncFindSimilarPosts {
+        source: source.into(),
+        candidates: into_post_data(candidates),
         top_n,
-    })
+        symbols: FindOptions::symbols(options)?,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::LazyLock;
 
+    use serde_json::json;
+
     use super::*;
 
     #[allow(non_upper_case_globals)]
     static source: LazyLock<PostData> = LazyLock::new(|| {
         PostData {
+        id: None,
         title: "Deno.kill not working on windows".to_string(),
         content: r#"
 Version: Deno 2.3.3
@@ -197,6 +323,8 @@ OS: Windows 11
 Sending a SIGINT OS signal on windows like: Deno.kill(Deno.pid, 'SIGINT');
 Results in: TypeError: Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK), but got SIGINT
             "#.to_string(),
+        metadata: None,
+        embedding: None,
     }
     });
 
@@ -204,6 +332,7 @@ Results in: TypeError: Windows only supports ctrl-c (SIGINT) and ctrl-break (SIG
     static candidates: LazyLock<Vec<PostData>> = LazyLock::new(|| {
         vec![
             PostData {
+                id: Some("1".to_string()),
                 title: "Deno.kill on windows".to_string(),
             content: r#"
 Version: Deno 2.3.3
@@ -216,14 +345,22 @@ Same goes for SIGBREAK
 
 Registering event listeners with: Deno.addSignalListener('SIGINT', doSomething); Works correctly
             "#.to_string(),
+                metadata: match json!({ "category": "bug", "tags": ["windows", "signal"] }) {
+                    Value::Object(map) => Some(map),
+                    _ => None,
+                },
+                embedding: None,
             },
             PostData {
+                id: Some("2".to_string()),
                 title: "denojs on termux like nodejs".to_string(),
                 content: r#"
 We want a smooth download for Deno.js like Node.js, Python, etc., instead of downloading extra
 libraries on Termux. We seek a streamlined download process for Deno.js similar to that of Node.js
 and Python, rather than having to download additional libraries on Termux.
 "#.to_string(),
+                metadata: None,
+                embedding: None,
             },
         ]
     });
@@ -239,8 +376,42 @@ and Python, rather than having to download additional libraries on Termux.
     #[test]
     fn test_find_similar_posts_native_parallel() {
         let FindTopNResult { matches, .. } =
-            find_similar_posts_native_parallel(source.clone(), candidates.clone(), 1).unwrap();
+            find_similar_posts_native_parallel(source.clone(), candidates.clone(), 1, None)
+                .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].target.title, "Deno.kill on windows");
+        assert_eq!(matches[0].shared_symbols, None);
+    }
+
+    #[test]
+    fn test_find_similar_posts_native_parallel_with_symbols() {
+        let plain = find_similar_posts_native_parallel(source.clone(), candidates.clone(), 5, None)
+            .unwrap();
+        let FindTopNResult { matches, .. } = find_similar_posts_native_parallel(
+            source.clone(),
+            candidates.clone(),
+            5,
+            Some(FindOptions {
+                symbols: Some(SymbolOptions { weight: None }),
+            }),
+        )
+        .unwrap();
         assert_eq!(matches.len(), 1);
         assert_eq!(matches[0].target.title, "Deno.kill on windows");
+        assert_ne!(matches[0].score, plain.matches[0].score);
+        let shared = matches[0].shared_symbols.clone().unwrap();
+        for symbol in ["Deno.kill", "Deno.pid", "SIGINT"] {
+            assert!(shared.iter().any(|shared| shared == symbol), "{symbol}");
+        }
+
+        assert!(find_similar_posts_native_parallel(
+            source.clone(),
+            candidates.clone(),
+            5,
+            Some(FindOptions {
+                symbols: Some(SymbolOptions { weight: Some(2.0) }),
+            }),
+        )
+        .is_err());
     }
 }
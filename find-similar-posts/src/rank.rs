@@ -0,0 +1,316 @@
+use std::sync::Arc;
+
+use napi::{bindgen_prelude::AsyncTask, Env, Error, Result, Task};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde_json::{Map, Value};
+use similar_core::{
+    filter::Filter,
+    pool::QueryPool,
+    post::{self, PostRef},
+};
+
+use crate::{into_post_data, to_napi_error, PostData};
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct RankOptions {
+    /// The fields of the matched posts to return, any of `id`, `title`, `content` and
+    /// `metadata`, defaults to `["id"]`. An empty array returns only the indices and scores.
+    pub fields: Option<Vec<String>>,
+    /// When given, only the posts whose metadata satisfy it are scored, see [Filter] for the
+    /// syntax.
+    pub filter: Option<Value>,
+}
+
+/// A match given by the position of the post, carrying only the requested fields of it.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct RankedMatch {
+    /// The position of the post in the candidate set or in the store snapshot.
+    pub index: u32,
+    pub score: f64,
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub metadata: Option<Map<String, Value>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct RankResult {
+    pub matches: Vec<RankedMatch>,
+    pub process_time: i64, // how many time is used for processing
+    /// The generation of the store snapshot the query was run against, only set when querying a
+    /// `PostStore`.
+    pub generation: Option<i64>,
+}
+
+/// The fields of the matched posts copied into the results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Projection {
+    id: bool,
+    title: bool,
+    content: bool,
+    metadata: bool,
+}
+
+impl Projection {
+    fn parse(fields: Option<&[String]>) -> Result<Self> {
+        let Some(fields) = fields else {
+            return Ok(Projection {
+                id: true,
+                ..Default::default()
+            });
+        };
+        let mut projection = Projection::default();
+
+        for field in fields {
+            match field.as_str() {
+                "id" => projection.id = true,
+                "title" => projection.title = true,
+                "content" => projection.content = true,
+                "metadata" => projection.metadata = true,
+                _ => {
+                    return Err(Error::from_reason(format!(
+                        "Unknown field '{}', it must be one of id, title, content and metadata",
+                        field
+                    )))
+                }
+            }
+        }
+
+        Ok(projection)
+    }
+
+    /// Builds the result, looking the matched posts up by their positions.
+    fn apply<'a>(
+        &self,
+        result: post::RankResult,
+        get: impl Fn(usize) -> Option<PostRef<'a>>,
+    ) -> RankResult {
+        let matches = result
+            .matches
+            .into_iter()
+            .map(|m| {
+                let post = get(m.index);
+                let pick = |selected: bool, field: fn(&PostRef<'a>) -> Option<String>| {
+                    post.as_ref().filter(|_| selected).and_then(field)
+                };
+
+                RankedMatch {
+                    index: m.index as u32,
+                    score: m.score,
+                    id: pick(self.id, |post| post.id.map(str::to_string)),
+                    title: pick(self.title, |post| Some(post.title.to_string())),
+                    content: pick(self.content, |post| Some(post.content.to_string())),
+                    metadata: post
+                        .as_ref()
+                        .filter(|_| self.metadata)
+                        .and_then(|post| post.metadata.cloned()),
+                }
+            })
+            .collect();
+
+        RankResult {
+            matches,
+            process_time: result.process_time.as_millis() as i64,
+            generation: result.generation.map(|generation| generation as i64),
+        }
+    }
+}
+
+/// A set of candidate posts which is marshalled once and can be queried many times, unlike
+/// the arrays passed to `findSimilarPostsNative()`.
+#[napi]
+pub struct CandidateSet {
+    posts: Arc<Vec<post::PostData>>,
+}
+
+#[napi]
+impl CandidateSet {
+    #[napi(constructor)]
+    pub fn new(posts: Vec<PostData>) -> Self {
+        CandidateSet {
+            posts: Arc::new(into_post_data(posts)),
+        }
+    }
+
+    #[napi(getter)]
+    pub fn length(&self) -> u32 {
+        self.posts.len() as u32
+    }
+
+    /// Returns the post at `index`, such as the one of a `RankedMatch`.
+    #[napi]
+    pub fn get(&self, index: u32) -> Option<PostData> {
+        self.posts.get(index as usize).cloned().map(PostData::from)
+    }
+
+    /// Finds the `top_n` posts most similar to `source`, returning their positions and scores
+    /// along with the fields picked by `options`.
+    #[napi(ts_return_type = "Promise<RankResult>")]
+    pub fn rank_similar_posts(
+        &self,
+        source: PostData,
+        top_n: u32,
+        options: Option<RankOptions>,
+    ) -> AsyncTask<AsyncRankSimilarPosts> {
+        AsyncTask::new(AsyncRankSimilarPosts {
+            source: source.into(),
+            candidates: Candidates::Set(self.posts.clone()),
+            top_n,
+            options: options.unwrap_or_default(),
+        })
+    }
+}
+
+pub(crate) enum Candidates {
+    Set(Arc<Vec<post::PostData>>),
+    Store(post::PostStore),
+}
+
+pub struct AsyncRankSimilarPosts {
+    pub(crate) source: post::PostData,
+    pub(crate) candidates: Candidates,
+    pub(crate) top_n: u32,
+    pub(crate) options: RankOptions,
+}
+
+#[napi]
+impl Task for AsyncRankSimilarPosts {
+    type Output = RankResult;
+    type JsValue = RankResult;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let projection = Projection::parse(self.options.fields.as_deref())?;
+        let filter = self
+            .options
+            .filter
+            .as_ref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(to_napi_error)?;
+        let top_n = self.top_n as usize;
+
+        match &self.candidates {
+            Candidates::Set(posts) => {
+                let result = QueryPool::default()
+                    .run(posts.len(), |parallel| {
+                        if parallel {
+                            let candidates = posts
+                                .par_iter()
+                                .enumerate()
+                                .map(|(i, post)| (i, PostRef::from(post)));
+                            post::rank_similar_posts(
+                                &self.source,
+                                candidates,
+                                top_n,
+                                filter.as_ref(),
+                            )
+                        } else {
+                            let candidates = posts.iter().map(PostRef::from).enumerate();
+                            post::rank_similar_posts_sequential(
+                                &self.source,
+                                candidates,
+                                top_n,
+                                filter.as_ref(),
+                            )
+                        }
+                    })
+                    .map_err(to_napi_error)?;
+
+                Ok(projection.apply(result, |i| posts.get(i).map(PostRef::from)))
+            }
+            Candidates::Store(store) => {
+                let (snapshot, result) = store
+                    .rank_similar_posts(&self.source, top_n, filter.as_ref())
+                    .map_err(to_napi_error)?;
+
+                Ok(projection.apply(result, |i| snapshot.get(i)))
+            }
+        }
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(id: &str, title: &str, content: &str) -> PostData {
+        PostData {
+            id: Some(id.to_string()),
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: None,
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_projection_parse() {
+        assert_eq!(
+            Projection::parse(None).unwrap(),
+            Projection {
+                id: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(Projection::parse(Some(&[])).unwrap(), Projection::default());
+        assert_eq!(
+            Projection::parse(Some(&["title".to_string(), "metadata".to_string()])).unwrap(),
+            Projection {
+                title: true,
+                metadata: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Projection::parse(Some(&["body".to_string()]))
+                .unwrap_err()
+                .reason,
+            "Unknown field 'body', it must be one of id, title, content and metadata"
+        );
+    }
+
+    #[test]
+    fn test_candidate_set_rank_similar_posts() {
+        let set = CandidateSet::new(vec![
+            post("1", "How to embed V8", "Embedding V8 into a Rust program"),
+            post(
+                "2",
+                "Deno.kill on windows",
+                "SIGINT is not supported on Windows",
+            ),
+        ]);
+        let mut task = AsyncRankSimilarPosts {
+            source: post(
+                "",
+                "Deno.kill on Windows",
+                "SIGINT is not supported on Windows",
+            )
+            .into(),
+            candidates: Candidates::Set(set.posts.clone()),
+            top_n: 5,
+            options: RankOptions {
+                fields: Some(vec!["title".to_string()]),
+                filter: None,
+            },
+        };
+        let result = task.compute().unwrap();
+
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].index, 1);
+        assert_eq!(result.matches[0].id, None);
+        assert_eq!(
+            result.matches[0].title.as_deref(),
+            Some("Deno.kill on windows")
+        );
+        assert_eq!(result.matches[0].content, None);
+        assert_eq!(result.generation, None);
+        assert_eq!(set.get(1).unwrap().id.as_deref(), Some("2"));
+    }
+}
@@ -1,55 +1,683 @@
-use std::sync::{Arc, RwLock};
+use napi::{Env, JsObject, Result, Task};
+use serde_json::Value;
+use similar_core::{
+    boilerplate, chunk, facet, filter::Filter, hybrid, metric, pipeline, pool, post,
+    shared::Shared, symbol, trace, vector,
+};
 
-use napi::{bindgen_prelude::AsyncTask, Env, Error, Result, Task};
+use crate::{
+    into_post_data,
+    rank::{AsyncRankSimilarPosts, Candidates, RankOptions},
+    spawn_task, to_napi_error, Embedding, FindTopNResult, PostData,
+};
 
-use crate::{do_find_similar_posts_native_parallel, FindTopNResult, PostData};
+mod ext;
+
+/// The threads the queries of a store run on, the rayon global pool shared by the whole process
+/// is used by default.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct PoolOptions {
+    /// How many threads score the candidates, the rayon global pool is used when omitted.
+    pub threads: Option<u32>,
+    /// The prefix of the names of the threads, followed by their indices, defaults to
+    /// `similar-`.
+    pub thread_name_prefix: Option<String>,
+    /// How many queries may run at the same time, unlimited when omitted.
+    pub max_concurrent_queries: Option<u32>,
+    /// How many queries may wait for a running one to finish, the excess ones are rejected.
+    /// Unlimited when omitted, `0` rejects the queries which can't run right away. A waiting
+    /// query doesn't hold any thread, and a running one runs on the threads of the store
+    /// instead of the libuv pool.
+    pub max_queued_queries: Option<u32>,
+    /// Candidate sets smaller than this are scanned by a single thread, defaults to 256.
+    pub sequential_threshold: Option<u32>,
+}
+
+impl PoolOptions {
+    pub(crate) fn build(&self) -> Result<pool::QueryPool> {
+        pool::QueryPool::new(&pool::PoolOptions {
+            threads: self.threads.map(|threads| threads as usize),
+            thread_name_prefix: self.thread_name_prefix.clone(),
+            max_concurrent_queries: self.max_concurrent_queries.map(|max| max as usize),
+            max_queued_queries: self.max_queued_queries.map(|max| max as usize),
+            sequential_threshold: self
+                .sequential_threshold
+                .map(|threshold| threshold as usize),
+        })
+        .map_err(to_napi_error)
+    }
+}
+
+/// How the embeddings of the posts are indexed for `findNearestPosts()`.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct VectorIndexOptions {
+    /// `cosine`, `dot` or `l2`, defaults to `cosine`.
+    pub distance: Option<String>,
+    /// How many neighbors a node links to on each layer of the graph, defaults to 16.
+    pub m: Option<u32>,
+    /// How many candidates are considered when indexing an embedding, defaults to 200.
+    pub ef_construction: Option<u32>,
+    /// How many candidates are considered when querying, defaults to 64.
+    pub ef_search: Option<u32>,
+}
+
+impl VectorIndexOptions {
+    pub(crate) fn build(&self) -> Result<vector::HnswOptions> {
+        let defaults = vector::HnswOptions::default();
+
+        Ok(vector::HnswOptions {
+            distance: match &self.distance {
+                Some(distance) => vector::Distance::parse(distance).map_err(to_napi_error)?,
+                None => defaults.distance,
+            },
+            m: self.m.map_or(defaults.m, |m| m as usize),
+            ef_construction: self
+                .ef_construction
+                .map_or(defaults.ef_construction, |ef| ef as usize),
+            ef_search: self.ef_search.map_or(defaults.ef_search, |ef| ef as usize),
+        })
+    }
+}
+
+/// How `findSimilarPosts()` fuses the lexical matches with the nearest embeddings.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct HybridOptions {
+    /// `rrf` for reciprocal rank fusion, or `weighted` for a weighted sum of the scores
+    /// normalized to `0 - 1`, defaults to `rrf`.
+    pub fusion: Option<String>,
+    /// The `k` of the reciprocal rank fusion, a match scores `1 / (k + rank)` for each
+    /// retriever, defaults to 60.
+    pub k: Option<f64>,
+    /// The weight of the lexical score in the weighted fusion, defaults to 0.5.
+    pub lexical_weight: Option<f64>,
+    /// The weight of the vector score in the weighted fusion, defaults to 0.5.
+    pub vector_weight: Option<f64>,
+    /// How many matches each retriever returns before they are fused, defaults to 50.
+    pub candidates: Option<u32>,
+}
+
+impl HybridOptions {
+    pub(crate) fn build(&self) -> Result<hybrid::HybridOptions> {
+        let fusion = match hybrid::Fusion::parse(self.fusion.as_deref().unwrap_or("rrf"))
+            .map_err(to_napi_error)?
+        {
+            hybrid::Fusion::Rrf { k } => hybrid::Fusion::Rrf {
+                k: self.k.unwrap_or(k),
+            },
+            hybrid::Fusion::Weighted { lexical, vector } => hybrid::Fusion::Weighted {
+                lexical: self.lexical_weight.unwrap_or(lexical),
+                vector: self.vector_weight.unwrap_or(vector),
+            },
+        };
+
+        Ok(hybrid::HybridOptions {
+            fusion,
+            candidates: self.candidates.map(|candidates| candidates as usize),
+        })
+    }
+}
+
+/// How `findSimilarPosts()` narrows down the posts before scoring them with the edit distance.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    /// The measure ranking the posts in the first stage, `ngram` for the character n-grams,
+    /// `bm25` for the relevance to the words of the source, or `simhash` for the fingerprints
+    /// of the words, defaults to `ngram`.
+    pub retriever: Option<String>,
+    /// The length of the n-grams, defaults to 3.
+    pub n: Option<u32>,
+    /// The term frequency saturation of BM25, defaults to 1.2.
+    pub k1: Option<f64>,
+    /// The length normalization of BM25, defaults to 0.75.
+    pub b: Option<f64>,
+    /// How many candidates the first stage keeps, defaults to 100.
+    pub candidates: Option<u32>,
+    /// How the candidates are scored, `levenshtein` for the whole texts, `partial` for the best
+    /// part of the longer text, or `alignment` for the sentences of the texts in any order,
+    /// defaults to `levenshtein`.
+    pub rescorer: Option<String>,
+}
+
+impl PipelineOptions {
+    pub(crate) fn build(&self) -> Result<pipeline::PipelineOptions> {
+        let retriever =
+            match pipeline::Retriever::parse(self.retriever.as_deref().unwrap_or("ngram"))
+                .map_err(to_napi_error)?
+            {
+                pipeline::Retriever::NGram { n } => pipeline::Retriever::NGram {
+                    n: self.n.map_or(n, |n| n as usize),
+                },
+                pipeline::Retriever::Bm25 { k1, b } => pipeline::Retriever::Bm25 {
+                    k1: self.k1.unwrap_or(k1),
+                    b: self.b.unwrap_or(b),
+                },
+                pipeline::Retriever::SimHash => pipeline::Retriever::SimHash,
+            };
+
+        Ok(pipeline::PipelineOptions {
+            retriever,
+            candidates: self.candidates.map(|candidates| candidates as usize),
+            rescorer: match &self.rescorer {
+                Some(rescorer) => metric::Metric::parse(rescorer).map_err(to_napi_error)?,
+                None => metric::Metric::default(),
+            },
+        })
+    }
+}
+
+/// How `findSimilarPosts()` splits the contents of long posts into chunks and scores them.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct ChunkOptions {
+    /// `paragraph` for runs of lines separated by blank lines, `sentence` for sentences, or
+    /// `window` for windows of a fixed number of characters, defaults to `paragraph`.
+    pub split: Option<String>,
+    /// How many paragraphs or sentences a chunk shares with the next one, or how many
+    /// characters for the windows. Defaults to 0 paragraph, 1 sentence or 100 characters.
+    pub overlap: Option<u32>,
+    /// The number of characters of a window, defaults to 500.
+    pub size: Option<u32>,
+    /// How the similarities of the chunks of the source to their best matching chunks are
+    /// combined, `max` for the best one, `mean` for the mean of the `k` best ones, or
+    /// `coverage` for the share of the source whose chunks have a match of at least
+    /// `threshold`, defaults to `max`.
+    pub aggregation: Option<String>,
+    /// How many similarities `mean` averages, defaults to 3.
+    pub k: Option<u32>,
+    /// The similarity `coverage` counts a chunk of the source as matched from, defaults to 0.8.
+    pub threshold: Option<f64>,
+}
+
+impl ChunkOptions {
+    pub(crate) fn build(&self) -> Result<chunk::ChunkOptions> {
+        let overlap = |default: usize| self.overlap.map_or(default, |overlap| overlap as usize);
+        let split = match chunk::Split::parse(self.split.as_deref().unwrap_or("paragraph"))
+            .map_err(to_napi_error)?
+        {
+            chunk::Split::Paragraph { overlap: default } => chunk::Split::Paragraph {
+                overlap: overlap(default),
+            },
+            chunk::Split::Sentence { overlap: default } => chunk::Split::Sentence {
+                overlap: overlap(default),
+            },
+            chunk::Split::Window {
+                size,
+                overlap: default,
+            } => chunk::Split::Window {
+                size: self.size.map_or(size, |size| size as usize),
+                overlap: overlap(default),
+            },
+        };
+        let aggregation =
+            match chunk::Aggregation::parse(self.aggregation.as_deref().unwrap_or("max"))
+                .map_err(to_napi_error)?
+            {
+                chunk::Aggregation::MeanTopK { k } => chunk::Aggregation::MeanTopK {
+                    k: self.k.map_or(k, |k| k as usize),
+                },
+                chunk::Aggregation::Coverage { threshold } => chunk::Aggregation::Coverage {
+                    threshold: self.threshold.unwrap_or(threshold),
+                },
+                aggregation => aggregation,
+            };
+
+        Ok(chunk::ChunkOptions { split, aggregation })
+    }
+}
+
+/// How `learnBoilerplate()` finds the lines or the runs of words shared by many documents.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct BoilerplateOptions {
+    /// `line` for whole lines, or `ngram` for runs of words, defaults to `line`.
+    pub unit: Option<String>,
+    /// How many words a run is made of, defaults to 4.
+    pub n: Option<u32>,
+    /// The share of the documents a line or a run of words must appear in, defaults to 0.3.
+    pub min_share: Option<f64>,
+    /// How many documents a line or a run of words must appear in, defaults to 3.
+    pub min_documents: Option<u32>,
+}
+
+impl BoilerplateOptions {
+    pub(crate) fn build(&self) -> Result<boilerplate::BoilerplateOptions> {
+        let defaults = boilerplate::BoilerplateOptions::default();
+
+        Ok(boilerplate::BoilerplateOptions {
+            unit: build_unit(self.unit.as_deref(), self.n)?,
+            min_share: self.min_share.unwrap_or(defaults.min_share),
+            min_documents: self
+                .min_documents
+                .map_or(defaults.min_documents, |min| min as usize),
+        })
+    }
+}
+
+fn build_unit(unit: Option<&str>, n: Option<u32>) -> Result<boilerplate::Unit> {
+    match boilerplate::Unit::parse(unit.unwrap_or("line")).map_err(to_napi_error)? {
+        boilerplate::Unit::Line => Ok(boilerplate::Unit::Line),
+        boilerplate::Unit::NGram { n: default } => Ok(boilerplate::Unit::NGram {
+            n: n.map_or(default, |n| n as usize),
+        }),
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoilerplateEntry {
+    /// The line or the run of words, with its whitespace collapsed and lowercased.
+    pub text: String,
+    /// The share of the documents it was found in.
+    pub share: f64,
+}
+
+/// The boilerplate learned by `learnBoilerplate()`, which can be edited before it's given to
+/// `setBoilerplate()`.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boilerplate {
+    /// `line` or `ngram`.
+    pub unit: String,
+    /// How many words a run is made of, only set for `ngram`.
+    pub n: Option<u32>,
+    pub entries: Vec<BoilerplateEntry>,
+}
+
+impl From<&boilerplate::Boilerplate> for Boilerplate {
+    fn from(boilerplate: &boilerplate::Boilerplate) -> Self {
+        let (unit, n) = match boilerplate.unit() {
+            boilerplate::Unit::Line => ("line", None),
+            boilerplate::Unit::NGram { n } => ("ngram", Some(n as u32)),
+        };
+
+        Boilerplate {
+            unit: unit.to_string(),
+            n,
+            entries: boilerplate
+                .entries()
+                .iter()
+                .map(|entry| BoilerplateEntry {
+                    text: entry.text.clone(),
+                    share: entry.share,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Boilerplate {
+    pub(crate) fn build(&self) -> Result<boilerplate::Boilerplate> {
+        let unit = build_unit(Some(&self.unit), self.n)?;
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| boilerplate::BoilerplateEntry {
+                text: entry.text.clone(),
+                share: entry.share,
+            })
+            .collect();
+
+        Ok(boilerplate::Boilerplate::new(unit, entries))
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct FacetRule {
+    /// The name of the facet, such as `os`.
+    pub name: String,
+    /// A regular expression, the value of the facet is its `value` group, or the whole match if
+    /// it has none.
+    pub pattern: String,
+}
+
+/// How the facets of the posts, such as their version and OS, are extracted by the queries of
+/// the store.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct FacetOptions {
+    /// Rules taking precedence over the built-in ones.
+    pub rules: Option<Vec<FacetRule>>,
+    /// Whether the built-in rules for the `version`, `os`, `arch` and `runtime` facets are
+    /// used, defaults to `true`.
+    pub builtin: Option<bool>,
+    /// Added to the score of a match sharing every facet of the source, a share of it for a
+    /// match sharing some of them, from 0 to 1, defaults to 0.
+    pub boost: Option<f64>,
+}
+
+impl FacetOptions {
+    pub(crate) fn build(&self) -> Result<facet::FacetOptions> {
+        let rules = self
+            .rules
+            .iter()
+            .flatten()
+            .map(|rule| facet::FacetRule::new(&rule.name, &rule.pattern).map_err(to_napi_error))
+            .collect::<Result<Vec<_>>>()?;
+        let extractor = if self.builtin.unwrap_or(true) {
+            facet::FacetExtractor::builtin().with_rules(rules)
+        } else {
+            facet::FacetExtractor::new(rules)
+        };
+
+        Ok(facet::FacetOptions {
+            extractor,
+            boost: self.boost.unwrap_or(facet::DEFAULT_BOOST),
+        })
+    }
+}
+
+/// How the stack traces of the posts are compared with the one of the source.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// How many frames from the top of a trace are fingerprinted and compared, defaults to 5.
+    pub depth: Option<u32>,
+    /// `signal` to combine the similarity of the traces with the one of the texts, or `bucket`
+    /// to score the posts with the fingerprint of the source as exact matches, defaults to
+    /// `signal`.
+    pub matching: Option<String>,
+    /// The share of the score made up by the similarity of the traces for `signal`, from 0 to
+    /// 1, defaults to 0.3.
+    pub weight: Option<f64>,
+}
+
+impl TraceOptions {
+    pub(crate) fn build(&self) -> Result<trace::TraceOptions> {
+        let matching =
+            match trace::TraceMatching::parse(self.matching.as_deref().unwrap_or("signal"))
+                .map_err(to_napi_error)?
+            {
+                trace::TraceMatching::Signal { weight } => trace::TraceMatching::Signal {
+                    weight: self.weight.unwrap_or(weight),
+                },
+                trace::TraceMatching::Bucket => trace::TraceMatching::Bucket,
+            };
+
+        Ok(trace::TraceOptions {
+            depth: self
+                .depth
+                .map_or(trace::DEFAULT_DEPTH, |depth| depth as usize),
+            matching,
+        })
+    }
+}
+
+/// How the symbols mentioned by the posts, such as `Deno.kill`, `ERR_INVALID_ARG_TYPE` or
+/// `SIGTERM`, are compared with the ones of the source.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct SymbolOptions {
+    /// The share of the score made up by the overlap of the symbols, from 0 to 1, defaults to
+    /// 0.25.
+    pub weight: Option<f64>,
+}
+
+impl SymbolOptions {
+    pub(crate) fn build(&self) -> Result<symbol::SymbolOptions> {
+        Ok(symbol::SymbolOptions {
+            weight: self.weight.unwrap_or(symbol::DEFAULT_WEIGHT),
+        })
+    }
+}
+
+/// How a store is set up, every option is off when left out.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct StoreOptions {
+    /// The threads the queries run on.
+    pub pool: Option<PoolOptions>,
+    /// How the embeddings of the posts are indexed.
+    pub vectors: Option<VectorIndexOptions>,
+    /// When given, `findSimilarPosts()` only scores the candidates retrieved by its first
+    /// stage, and reports the time and the candidates of each stage.
+    pub pipeline: Option<PipelineOptions>,
+    /// When given, the contents are scored by their chunks, and each match reports its best
+    /// matching pair of chunks.
+    pub chunking: Option<ChunkOptions>,
+    /// How the posts are scored without a pipeline, `levenshtein` for the whole texts,
+    /// `partial` for the best part of the longer text, or `alignment` for the sentences of the
+    /// texts in any order, defaults to `levenshtein`.
+    pub metric: Option<String>,
+    /// When given, the facets of the posts are extracted into the metadata the queries see under
+    /// `facets`, where they can be filtered on, and each match reports the ones it shares with
+    /// the source.
+    pub facets: Option<FacetOptions>,
+    /// When given, the stack traces of the posts are fingerprinted into the metadata the queries
+    /// see under `trace`, and compared with the one of the source.
+    pub traces: Option<TraceOptions>,
+    /// When given, the symbols the posts mention are extracted into the metadata the queries see
+    /// under `symbols`, and each match reports the ones it shares with the source.
+    pub symbols: Option<SymbolOptions>,
+}
 
 #[napi]
+#[derive(Default)]
 pub struct PostStore {
-    posts: Arc<RwLock<Vec<PostData>>>,
+    inner: post::PostStore,
+    /// The handle keeping the shared store alive, if this instance is attached to one.
+    shared: Option<Shared<post::PostStore>>,
+}
+
+impl From<post::PostStore> for PostStore {
+    fn from(inner: post::PostStore) -> Self {
+        PostStore {
+            inner,
+            shared: None,
+        }
+    }
 }
 
 #[napi]
 impl PostStore {
+    /// Creates an empty store set up by `options`.
     #[napi(constructor)]
-    pub fn new() -> Self {
+    pub fn new(options: Option<StoreOptions>) -> Result<Self> {
+        let StoreOptions {
+            pool,
+            vectors,
+            pipeline,
+            chunking,
+            metric,
+            facets,
+            traces,
+            symbols,
+        } = options.unwrap_or_default();
+        let mut inner = match pool {
+            Some(pool) => post::PostStore::new().with_pool(pool.build()?),
+            None => post::PostStore::new(),
+        };
+        if let Some(vectors) = vectors {
+            inner = inner
+                .with_vector_index(vectors.build()?)
+                .map_err(to_napi_error)?;
+        }
+        if let Some(pipeline) = pipeline {
+            inner = inner
+                .with_pipeline(pipeline.build()?)
+                .map_err(to_napi_error)?;
+        }
+        if let Some(chunking) = chunking {
+            inner = inner
+                .with_chunking(chunking.build()?)
+                .map_err(to_napi_error)?;
+        }
+        if let Some(metric) = metric {
+            inner = inner.with_metric(metric::Metric::parse(&metric).map_err(to_napi_error)?);
+        }
+        if let Some(facets) = facets {
+            inner = inner.with_facets(facets.build()?).map_err(to_napi_error)?;
+        }
+        if let Some(traces) = traces {
+            inner = inner.with_traces(traces.build()?).map_err(to_napi_error)?;
+        }
+        if let Some(symbols) = symbols {
+            inner = inner
+                .with_symbols(symbols.build()?)
+                .map_err(to_napi_error)?;
+        }
+
+        Ok(inner.into())
+    }
+
+    /// Attaches to the process-global store named `name`, which is shared by all the worker
+    /// threads of the process. It's created empty on the first call, and dropped when the last
+    /// instance attached to it is disposed or garbage collected.
+    #[napi(factory)]
+    pub fn shared(name: String) -> Self {
+        let shared = post::PostStore::shared(&name);
+
         PostStore {
-            posts: Arc::new(RwLock::new(Vec::new())),
+            inner: (*shared).clone(),
+            shared: Some(shared),
         }
     }
 
+    /// The name of the shared store this instance is attached to.
+    #[napi(getter)]
+    pub fn shared_name(&self) -> Option<String> {
+        self.shared.as_ref().map(|shared| shared.name().to_string())
+    }
+
+    /// Releases the posts held by this instance, which becomes an empty, unshared store. The
+    /// posts of a shared store are dropped once every instance attached to it is disposed.
+    #[napi]
+    pub fn dispose(&mut self) {
+        self.shared = None;
+        self.inner = post::PostStore::default();
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    #[napi(getter)]
+    pub fn generation(&self) -> i64 {
+        self.inner.generation() as i64
+    }
+
+    /// Replaces all the posts in the store, including the ones of an opened snapshot file.
     #[napi]
     pub fn preload(&self, posts: Vec<PostData>) -> Result<()> {
-        match self.posts.write() {
-            Ok(mut _posts) => {
-                *_posts = posts;
-                Ok(())
-            }
-            Err(e) => Err(Error::from_reason(format!(
-                "Failed to preload posts: {}",
-                e
-            ))),
-        }
+        self.inner.preload(into_post_data(posts));
+        Ok(())
+    }
+
+    /// Adds posts to the store, keeping the existing ones.
+    #[napi]
+    pub fn append(&self, posts: Vec<PostData>) -> Result<()> {
+        self.inner.append(into_post_data(posts));
+        Ok(())
+    }
+
+    /// Learns the lines or the runs of words shared by many contents of the posts in the current
+    /// snapshot, such as the headers of a template. It isn't updated by later writes.
+    #[napi(ts_return_type = "Promise<Boilerplate>")]
+    pub fn learn_boilerplate(
+        &self,
+        env: Env,
+        options: Option<BoilerplateOptions>,
+    ) -> Result<JsObject> {
+        let task = AsyncLearnBoilerplate {
+            store: self.inner.clone(),
+            options: options.unwrap_or_default(),
+        };
+        spawn_task(env, self.inner.pool(), task, None)
     }
 
+    /// The boilerplate removed from the contents before they are scored by
+    /// `findSimilarPosts()`, if any.
+    #[napi(getter)]
+    pub fn boilerplate(&self) -> Option<Boilerplate> {
+        self.inner.boilerplate().map(Boilerplate::from)
+    }
+
+    /// Removes `boilerplate` from the contents of the posts and the source before they are
+    /// scored by `findSimilarPosts()` of this instance, or stops removing it if `null`. When
+    /// scoring by chunks, the chunks made of boilerplate are skipped instead.
+    #[napi]
+    pub fn set_boilerplate(&mut self, boilerplate: Option<Boilerplate>) -> Result<()> {
+        let inner = self.inner.clone();
+        self.inner = match boilerplate {
+            Some(boilerplate) => inner.with_boilerplate(boilerplate.build()?),
+            None => inner.without_boilerplate(),
+        };
+        Ok(())
+    }
+
+    /// Finds the `top_n` posts most similar to `source`. When `filter` is given, only the posts
+    /// whose metadata satisfy it are scored, see [Filter] for the syntax. When `hybrid` is
+    /// given, the lexical matches are fused with the posts whose embeddings are the nearest to
+    /// the one of `source`, and each match reports its rank in both.
     #[napi(ts_return_type = "Promise<FindTopNResult>")]
     pub fn find_similar_posts(
         &self,
+        env: Env,
         source: PostData,
         top_n: u32,
-    ) -> AsyncTask<AsyncFindSimilarPosts> {
-        AsyncTask::new(AsyncFindSimilarPosts {
-            source,
-            posts: self.posts.clone(),
+        filter: Option<Value>,
+        hybrid: Option<HybridOptions>,
+    ) -> Result<JsObject> {
+        let task = AsyncFindSimilarPosts {
+            source: source.into(),
+            store: self.inner.clone(),
             top_n,
-        })
+            filter,
+            hybrid,
+        };
+        spawn_task(env, self.inner.pool(), task, None)
+    }
+
+    /// Like `findSimilarPosts()`, but returns the positions of the posts in the snapshot along
+    /// with the fields picked by `options` instead of the whole posts.
+    #[napi(ts_return_type = "Promise<RankResult>")]
+    pub fn rank_similar_posts(
+        &self,
+        env: Env,
+        source: PostData,
+        top_n: u32,
+        options: Option<RankOptions>,
+    ) -> Result<JsObject> {
+        let task = AsyncRankSimilarPosts {
+            source: source.into(),
+            candidates: Candidates::Store(self.inner.clone()),
+            top_n,
+            options: options.unwrap_or_default(),
+        };
+        spawn_task(env, self.inner.pool(), task, None)
+    }
+
+    /// Finds the `top_n` posts whose embeddings are the nearest to `embedding`, the score of a
+    /// match is the cosine similarity, the dot product or `1 / (1 + distance)` for `l2`. The
+    /// embeddings are indexed by the first query after a write, except `append()`, which adds
+    /// the new ones to the existing index. There is no text to compare, so the metric and the
+    /// chunking of the store don't apply, but `filter` sees the facets, the stack traces and the
+    /// symbols it extracts.
+    #[napi(ts_return_type = "Promise<FindTopNResult>")]
+    pub fn find_nearest_posts(
+        &self,
+        env: Env,
+        embedding: Embedding,
+        top_n: u32,
+        filter: Option<Value>,
+    ) -> Result<JsObject> {
+        let task = AsyncFindNearestPosts {
+            embedding: embedding.0,
+            store: self.inner.clone(),
+            top_n,
+            filter,
+        };
+        spawn_task(env, self.inner.pool(), task, None)
     }
 }
 
 pub struct AsyncFindSimilarPosts {
-    source: PostData,
-    posts: Arc<RwLock<Vec<PostData>>>,
+    source: post::PostData,
+    store: post::PostStore,
     top_n: u32,
+    filter: Option<Value>,
+    hybrid: Option<HybridOptions>,
 }
 
 #[napi]
@@ -58,10 +686,82 @@ impl Task for AsyncFindSimilarPosts {
     type JsValue = FindTopNResult;
 
     fn compute(&mut self) -> Result<Self::Output> {
-        match self.posts.read() {
-            Ok(posts) => do_find_similar_posts_native_parallel(&self.source, &posts, self.top_n),
-            Err(e) => Err(Error::from_reason(format!("Failed to read posts: {}", e))),
-        }
+        let filter = self
+            .filter
+            .as_ref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(to_napi_error)?;
+
+        let result = match &self.hybrid {
+            Some(hybrid) => self.store.find_similar_posts_hybrid(
+                &self.source,
+                self.top_n as usize,
+                filter.as_ref(),
+                &hybrid.build()?,
+            ),
+            None => {
+                self.store
+                    .find_similar_posts(&self.source, self.top_n as usize, filter.as_ref())
+            }
+        };
+
+        result
+            .map(|result| FindTopNResult::from_query(result, &self.source.content))
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncLearnBoilerplate {
+    store: post::PostStore,
+    options: BoilerplateOptions,
+}
+
+#[napi]
+impl Task for AsyncLearnBoilerplate {
+    type Output = Boilerplate;
+    type JsValue = Boilerplate;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.store
+            .learn_boilerplate(&self.options.build()?)
+            .map(|boilerplate| Boilerplate::from(&boilerplate))
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncFindNearestPosts {
+    embedding: Vec<f32>,
+    store: post::PostStore,
+    top_n: u32,
+    filter: Option<Value>,
+}
+
+#[napi]
+impl Task for AsyncFindNearestPosts {
+    type Output = FindTopNResult;
+    type JsValue = FindTopNResult;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let filter = self
+            .filter
+            .as_ref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(to_napi_error)?;
+
+        self.store
+            .find_nearest_posts(&self.embedding, self.top_n as usize, filter.as_ref())
+            .map(FindTopNResult::from)
+            .map_err(to_napi_error)
     }
 
     fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
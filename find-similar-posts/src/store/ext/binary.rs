@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use napi::{bindgen_prelude::AsyncTask, Env, Result, Task};
+use similar_core::post::{self, PostsSnapshot};
+
+use crate::{store::PostStore, to_napi_error};
+
+#[napi]
+impl PostStore {
+    /// Saves the posts to a binary snapshot file, which can be restored with `PostStore.load()`.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn save(&self, path: String) -> AsyncTask<AsyncSaver> {
+        AsyncTask::new(AsyncSaver {
+            path,
+            posts: self.inner.snapshot(),
+        })
+    }
+
+    /// Loads a store from a binary snapshot file created by `PostStore#save()`.
+    #[napi(ts_return_type = "Promise<PostStore>")]
+    pub fn load(path: String) -> AsyncTask<AsyncLoader> {
+        AsyncTask::new(AsyncLoader { path })
+    }
+}
+
+pub struct AsyncSaver {
+    pub path: String,
+    pub posts: Arc<PostsSnapshot>,
+}
+
+#[napi]
+impl Task for AsyncSaver {
+    type Output = ();
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.posts.save(&self.path).map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
+        Ok(())
+    }
+}
+
+pub struct AsyncLoader {
+    pub path: String,
+}
+
+#[napi]
+impl Task for AsyncLoader {
+    type Output = PostStore;
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        post::PostStore::load(&self.path)
+            .map(PostStore::from)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use napi::{bindgen_prelude::AsyncTask, Env, Error, Result, Task};
+use similar_core::post::{self, PostsSnapshot};
+
+use crate::{
+    store::{ext::LoadReport, PostStore},
+    to_napi_error,
+};
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// The column holding the title of the post, defaults to `title`.
+    pub title_column: Option<String>,
+    /// The column holding the content of the post, defaults to `content`.
+    pub content_column: Option<String>,
+    /// The column holding the ID of the post, defaults to `id`. Unlike the title and content
+    /// columns, the default one may be absent from the file.
+    pub id_column: Option<String>,
+    /// The field delimiter, which must be a single ASCII character, defaults to `,`.
+    pub delimiter: Option<String>,
+}
+
+impl TryFrom<CsvOptions> for post::CsvOptions {
+    type Error = Error;
+
+    fn try_from(options: CsvOptions) -> Result<Self> {
+        let delimiter = match options.delimiter.as_deref() {
+            None => None,
+            Some(delimiter) if delimiter.len() == 1 && delimiter.is_ascii() => {
+                Some(delimiter.as_bytes()[0])
+            }
+            Some(delimiter) => {
+                return Err(Error::from_reason(format!(
+                    "Invalid CSV delimiter '{}', it must be a single ASCII character",
+                    delimiter
+                )));
+            }
+        };
+
+        Ok(post::CsvOptions {
+            title_column: options.title_column,
+            content_column: options.content_column,
+            id_column: options.id_column,
+            delimiter,
+        })
+    }
+}
+
+#[napi]
+impl PostStore {
+    /// Replaces the posts in the store with the ones in a CSV file, which is parsed on a worker
+    /// thread. Columns other than the title, content and ID ones are kept as string metadata.
+    /// Malformed records are skipped and reported with their line numbers.
+    #[napi(ts_return_type = "Promise<LoadReport>")]
+    pub fn load_csv(&self, path: String, options: Option<CsvOptions>) -> AsyncTask<AsyncCsvLoader> {
+        AsyncTask::new(AsyncCsvLoader {
+            path,
+            options: options.unwrap_or_default(),
+            store: self.inner.clone(),
+        })
+    }
+
+    /// Writes the posts in the store to a CSV file, using the same column options as
+    /// `loadCsv()`. Metadata values which are not strings are written as JSON.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn dump_csv(&self, path: String, options: Option<CsvOptions>) -> AsyncTask<AsyncCsvDumper> {
+        AsyncTask::new(AsyncCsvDumper {
+            path,
+            options: options.unwrap_or_default(),
+            posts: self.inner.snapshot(),
+        })
+    }
+}
+
+pub struct AsyncCsvLoader {
+    pub path: String,
+    pub options: CsvOptions,
+    pub store: post::PostStore,
+}
+
+#[napi]
+impl Task for AsyncCsvLoader {
+    type Output = LoadReport;
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let options = post::CsvOptions::try_from(self.options.clone())?;
+
+        self.store
+            .load_csv(&self.path, &options)
+            .map(LoadReport::from)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncCsvDumper {
+    pub path: String,
+    pub options: CsvOptions,
+    pub posts: Arc<PostsSnapshot>,
+}
+
+#[napi]
+impl Task for AsyncCsvDumper {
+    type Output = ();
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let options = post::CsvOptions::try_from(self.options.clone())?;
+
+        self.posts
+            .dump_csv(&self.path, &options)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_options_delimiter() {
+        let options = |delimiter: &str| CsvOptions {
+            delimiter: Some(delimiter.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            post::CsvOptions::try_from(options(";")).unwrap().delimiter,
+            Some(b';')
+        );
+        assert!(post::CsvOptions::try_from(options(";;")).is_err());
+        assert!(post::CsvOptions::try_from(options("；")).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use similar_core::post;
+
+use crate::{store::PostStore, to_napi_error};
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct DbColumns {
+    /// The column holding the ID of the post, defaults to `id`, which may be absent.
+    pub id: Option<String>,
+    /// The column holding the title of the post, defaults to `title`.
+    pub title: Option<String>,
+    /// The column holding the content of the post, defaults to `content`.
+    pub content: Option<String>,
+    /// The columns copied into the metadata of the post, defaults to all the other columns.
+    pub metadata: Option<Vec<String>>,
+}
+
+#[napi(object)]
+pub struct DbOptions {
+    /// The database URL, its scheme (`mysql:`, `postgres:` or `sqlite:`) selects the driver.
+    pub url: String,
+    /// The table to read the posts from, either `table` or `query` must be given.
+    /// It must be a plain or a dotted identifier, such as `posts` or `public.posts`.
+    pub table: Option<String>,
+    /// A `select` statement to read the posts with, takes precedence over `table`.
+    pub query: Option<String>,
+    pub columns: Option<DbColumns>,
+}
+
+impl From<DbOptions> for post::DbOptions {
+    fn from(options: DbOptions) -> Self {
+        post::DbOptions {
+            url: options.url,
+            table: options.table,
+            query: options.query,
+            columns: options.columns.map(|columns| post::DbColumns {
+                id: columns.id,
+                title: columns.title,
+                content: columns.content,
+                metadata: columns.metadata,
+            }),
+        }
+    }
+}
+
+#[napi]
+impl PostStore {
+    /// Loads a store from a MySQL, PostgreSQL or SQLite database, mapping the columns of `table`
+    /// or the rows returned by `query` to posts.
+    #[napi]
+    pub async fn from_db(options: DbOptions) -> napi::Result<Self> {
+        post::PostStore::from_db(&options.into())
+            .await
+            .map(PostStore::from)
+            .map_err(to_napi_error)
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use napi::{bindgen_prelude::AsyncTask, Env, Result, Task};
+use similar_core::post::{self, PostsSnapshot};
+
+use crate::{
+    store::{ext::LoadReport, PostStore},
+    to_napi_error,
+};
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct JsonlOptions {
+    /// The field holding the ID of the post, defaults to `id`.
+    pub id_field: Option<String>,
+    /// The field holding the title of the post, defaults to `title`.
+    pub title_field: Option<String>,
+    /// The field holding the content of the post, defaults to `content`.
+    pub content_field: Option<String>,
+    /// The field holding the metadata object of the post, defaults to `metadata`. Other unknown
+    /// fields are merged into the metadata as well, except `embedding`, which holds the
+    /// embedding of the post as an array of numbers.
+    pub metadata_field: Option<String>,
+    /// Whether the input is gzip-compressed, detected from the content when omitted. Only used by
+    /// `loadJsonl()`.
+    pub gzip: Option<bool>,
+}
+
+impl From<JsonlOptions> for post::JsonlOptions {
+    fn from(options: JsonlOptions) -> Self {
+        post::JsonlOptions {
+            id_field: options.id_field,
+            title_field: options.title_field,
+            content_field: options.content_field,
+            metadata_field: options.metadata_field,
+            gzip: options.gzip,
+        }
+    }
+}
+
+#[napi]
+impl PostStore {
+    /// Replaces the posts in the store with the ones in a JSON Lines file, which is streamed line
+    /// by line on a worker thread. Malformed lines are skipped and reported with their line
+    /// numbers.
+    #[napi(ts_return_type = "Promise<LoadReport>")]
+    pub fn load_jsonl(
+        &self,
+        path: String,
+        options: Option<JsonlOptions>,
+    ) -> AsyncTask<AsyncJsonlLoader> {
+        AsyncTask::new(AsyncJsonlLoader {
+            path,
+            options: options.unwrap_or_default().into(),
+            store: self.inner.clone(),
+        })
+    }
+
+    /// Writes the posts in the store to a JSON Lines file, using the same field options as
+    /// `loadJsonl()`.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn dump_jsonl(
+        &self,
+        path: String,
+        options: Option<JsonlOptions>,
+    ) -> AsyncTask<AsyncJsonlDumper> {
+        AsyncTask::new(AsyncJsonlDumper {
+            path,
+            options: options.unwrap_or_default().into(),
+            posts: self.inner.snapshot(),
+        })
+    }
+}
+
+pub struct AsyncJsonlLoader {
+    pub path: String,
+    pub options: post::JsonlOptions,
+    pub store: post::PostStore,
+}
+
+#[napi]
+impl Task for AsyncJsonlLoader {
+    type Output = LoadReport;
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.store
+            .load_jsonl(&self.path, &self.options)
+            .map(LoadReport::from)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncJsonlDumper {
+    pub path: String,
+    pub options: post::JsonlOptions,
+    pub posts: Arc<PostsSnapshot>,
+}
+
+#[napi]
+impl Task for AsyncJsonlDumper {
+    type Output = ();
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.posts
+            .dump_jsonl(&self.path, &self.options)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use napi::{bindgen_prelude::AsyncTask, Env, Result, Task};
+use similar_core::post;
+
+use crate::{store::PostStore, to_napi_error};
+
+#[napi]
+impl PostStore {
+    /// Opens a binary snapshot file created by `PostStore#save()` in read-only mode by memory
+    /// mapping it. Posts added afterwards with `append()` are kept in memory on top of the mapped
+    /// ones, while `preload()` replaces both.
+    #[napi(ts_return_type = "Promise<PostStore>")]
+    pub fn open(path: String) -> AsyncTask<AsyncOpener> {
+        AsyncTask::new(AsyncOpener { path })
+    }
+}
+
+pub struct AsyncOpener {
+    pub path: String,
+}
+
+#[napi]
+impl Task for AsyncOpener {
+    type Output = PostStore;
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        post::PostStore::open(&self.path)
+            .map(PostStore::from)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
@@ -0,0 +1,40 @@
+use similar_core::load;
+
+pub mod binary;
+pub mod csv;
+pub mod db;
+pub mod jsonl;
+pub mod mapped;
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    /// The 1-based line number where the malformed record starts.
+    pub line: u32,
+    pub message: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    /// How many posts have been loaded into the store.
+    pub loaded: u32,
+    /// The records which have been skipped because they are malformed.
+    pub errors: Vec<LoadError>,
+}
+
+impl From<load::LoadReport> for LoadReport {
+    fn from(report: load::LoadReport) -> Self {
+        LoadReport {
+            loaded: report.loaded as u32,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|e| LoadError {
+                    line: e.line as u32,
+                    message: e.message,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, ValueEnum};
+use similar_core::{
+    issue::{self, IssueFeatureStore},
+    load::LoadReport,
+    post::{self, CsvOptions, JsonlOptions, PostStore},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Sqlite,
+    Snapshot,
+}
+
+impl Format {
+    /// Guesses the format from the extension of the file, ignoring a trailing `.gz`.
+    pub fn detect(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let name = name.strip_suffix(".gz").unwrap_or(&name);
+
+        match Path::new(name).extension()?.to_str()? {
+            "csv" => Some(Format::Csv),
+            "jsonl" | "ndjson" => Some(Format::Jsonl),
+            "db" | "sqlite" | "sqlite3" => Some(Format::Sqlite),
+            "bin" | "snapshot" => Some(Format::Snapshot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct CorpusArgs {
+    /// The corpus to search in, a CSV, JSON Lines (optionally gzipped), SQLite or snapshot file.
+    #[arg(short, long)]
+    pub corpus: PathBuf,
+
+    /// The format of the corpus, detected from the file extension when omitted.
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+
+    /// The table to read from a SQLite corpus, defaults to `posts`, or `issue_features` with
+    /// `--issues`.
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// A `select` statement to read the posts from a SQLite corpus with, takes precedence over
+    /// `--table`.
+    #[arg(long)]
+    pub sql: Option<String>,
+
+    /// Treats the corpus as issue features instead of posts.
+    #[arg(long)]
+    pub issues: bool,
+}
+
+pub enum Corpus {
+    Posts(PostStore),
+    Issues(IssueFeatureStore),
+}
+
+impl Corpus {
+    pub fn len(&self) -> usize {
+        match self {
+            Corpus::Posts(store) => store.snapshot().len(),
+            Corpus::Issues(store) => store.snapshot().map.len(),
+        }
+    }
+}
+
+impl CorpusArgs {
+    fn format(&self) -> Result<Format> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => Format::detect(&self.corpus).ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Cannot detect the format of '{}', specify it with --format",
+                    self.corpus.display()
+                ))
+            }),
+        }
+    }
+
+    fn sqlite_url(&self) -> String {
+        format!("sqlite:{}", self.corpus.display())
+    }
+
+    /// Loads the corpus, the malformed records skipped by the loader are reported to stderr.
+    pub fn load(&self) -> Result<Corpus> {
+        if self.issues {
+            self.load_issues().map(Corpus::Issues)
+        } else {
+            self.load_posts().map(Corpus::Posts)
+        }
+    }
+
+    fn load_posts(&self) -> Result<PostStore> {
+        match self.format()? {
+            Format::Csv => {
+                let store = PostStore::new();
+                let report = store.load_csv(&self.corpus, &CsvOptions::default())?;
+
+                warn_skipped(&report);
+                Ok(store)
+            }
+            Format::Jsonl => {
+                let store = PostStore::new();
+                let report = store.load_jsonl(&self.corpus, &JsonlOptions::default())?;
+
+                warn_skipped(&report);
+                Ok(store)
+            }
+            Format::Sqlite => block_on(PostStore::from_db(&post::DbOptions {
+                url: self.sqlite_url(),
+                table: Some(self.table.clone().unwrap_or_else(|| "posts".to_string())),
+                query: self.sql.clone(),
+                columns: None,
+            })),
+            Format::Snapshot => PostStore::open(&self.corpus),
+        }
+    }
+
+    fn load_issues(&self) -> Result<IssueFeatureStore> {
+        match self.format()? {
+            Format::Csv => IssueFeatureStore::load_csv(&self.corpus),
+            Format::Jsonl => {
+                let store = IssueFeatureStore::default();
+                let report = store.load_jsonl(&self.corpus, &issue::JsonlOptions::default())?;
+
+                warn_skipped(&report);
+                Ok(store)
+            }
+            Format::Sqlite if self.sql.is_some() => Err(Error::InvalidArgument(
+                "--sql is only supported for posts".to_string(),
+            )),
+            Format::Sqlite => block_on(IssueFeatureStore::from_db(&issue::DbOptions {
+                url: self.sqlite_url(),
+                table: self
+                    .table
+                    .clone()
+                    .unwrap_or_else(|| "issue_features".to_string()),
+            })),
+            Format::Snapshot => Err(Error::InvalidArgument(
+                "Snapshots are only supported for posts".to_string(),
+            )),
+        }
+    }
+}
+
+fn warn_skipped(report: &LoadReport) {
+    for error in &report.errors {
+        eprintln!(
+            "find-similar: skipped line {}: {}",
+            error.line, error.message
+        );
+    }
+}
+
+fn block_on<T>(future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::Io("Cannot start the async runtime", e))?
+        .block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_detect() {
+        let detect = |path: &str| Format::detect(Path::new(path));
+
+        assert_eq!(detect("posts.csv"), Some(Format::Csv));
+        assert_eq!(detect("posts.jsonl"), Some(Format::Jsonl));
+        assert_eq!(detect("dumps/Posts.JSONL.gz"), Some(Format::Jsonl));
+        assert_eq!(detect("posts.ndjson"), Some(Format::Jsonl));
+        assert_eq!(detect("blog.sqlite3"), Some(Format::Sqlite));
+        assert_eq!(detect("posts.snapshot"), Some(Format::Snapshot));
+        assert_eq!(detect("posts.txt"), None);
+        assert_eq!(detect("posts"), None);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use csv::Writer;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use similar_core::{
+    issue::{find_similar_records_in_parallel, IssueFeatureStore, ScoringOptions},
+    post::{find_similar_posts_parallel, PostRef, PostStore},
+    Error, Result,
+};
+
+use crate::{
+    corpus::{Corpus, CorpusArgs},
+    query::summarize_issue,
+};
+
+#[derive(Debug, Args)]
+pub struct DedupeArgs {
+    #[command(flatten)]
+    pub corpus: CorpusArgs,
+
+    /// The minimum score for two entries to be considered duplicates, must be above 0.5, which is
+    /// the threshold of a match.
+    #[arg(short, long, default_value_t = 0.8)]
+    pub threshold: f64,
+
+    /// The CSV file to write the clusters to, defaults to stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// An entry of the corpus which belongs to a cluster of duplicates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    /// The 1-based position of the entry in the corpus.
+    pub row: usize,
+    pub id: String,
+    pub title: String,
+    /// The highest score between the entry and another member of the cluster.
+    pub score: f64,
+}
+
+/// A pair of duplicates, given as the positions of the entries in the corpus and their score.
+type Pair = (usize, usize, f64);
+
+pub fn run(args: &DedupeArgs) -> Result<()> {
+    if !(args.threshold > 0.5 && args.threshold <= 1.0) {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid threshold {}, it must be above 0.5 and at most 1",
+            args.threshold
+        )));
+    }
+
+    let clusters = match args.corpus.load()? {
+        Corpus::Posts(store) => dedupe_posts(&store, args.threshold)?,
+        Corpus::Issues(store) => dedupe_issues(&store, args.threshold)?,
+    };
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            Box::new(File::create(path).map_err(|e| Error::Io("Cannot create the output file", e))?)
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+
+    write_clusters(output, &clusters)
+}
+
+/// Finds the clusters of duplicate posts. Each post is scored against the ones after it with the
+/// regular scoring, so the earlier post of a pair is taken as the source.
+pub fn dedupe_posts(store: &PostStore, threshold: f64) -> Result<Vec<Vec<Member>>> {
+    let snapshot = store.snapshot();
+    let posts: Vec<PostRef> = snapshot.iter().collect();
+    // the candidates are tagged with their positions, so the matches can be traced back
+    let keys: Vec<String> = (0..posts.len()).map(|i| i.to_string()).collect();
+    let mut pairs: Vec<Pair> = vec![];
+
+    for (i, post) in posts.iter().enumerate() {
+        if post.title.is_empty() && post.content.is_empty() {
+            continue;
+        }
+
+        let candidates =
+            posts[i + 1..]
+                .par_iter()
+                .zip(keys[i + 1..].par_iter())
+                .map(|(candidate, key)| PostRef {
+                    id: Some(key),
+                    ..*candidate
+                });
+        let result =
+            find_similar_posts_parallel(&post.to_post_data(), candidates, usize::MAX, None, None)?;
+
+        for m in result.matches {
+            if m.score >= threshold {
+                if let Some(j) = m.target.id.and_then(|key| key.parse().ok()) {
+                    pairs.push((i, j, m.score));
+                }
+            }
+        }
+    }
+
+    Ok(cluster(posts.len(), &pairs)
+        .into_iter()
+        .map(|members| {
+            members
+                .into_iter()
+                .map(|(i, score)| Member {
+                    row: i + 1,
+                    id: posts[i].id.unwrap_or_default().to_string(),
+                    title: posts[i].title.to_string(),
+                    score,
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Finds the clusters of duplicate issues, ordered by issue ID. Like [dedupe_posts], the issue
+/// ordered first in a pair is taken as the source.
+pub fn dedupe_issues(store: &IssueFeatureStore, threshold: f64) -> Result<Vec<Vec<Member>>> {
+    let snapshot = store.snapshot();
+    let mut issue_ids: Vec<&String> = snapshot.map.keys().collect();
+    issue_ids.sort();
+
+    let positions: HashMap<&str, usize> = issue_ids
+        .iter()
+        .enumerate()
+        .map(|(i, issue_id)| (issue_id.as_str(), i))
+        .collect();
+    let mut pairs: Vec<Pair> = vec![];
+
+    for (i, issue_id) in issue_ids.iter().enumerate() {
+        let features = &snapshot.map[*issue_id].features;
+        let matches = find_similar_records_in_parallel(
+            features,
+            &snapshot,
+            usize::MAX,
+            None,
+            &ScoringOptions::default(),
+        )?;
+
+        for m in matches {
+            match positions.get(m.issue_id.as_str()) {
+                Some(&j) if j > i && m.score >= threshold => pairs.push((i, j, m.score)),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(cluster(issue_ids.len(), &pairs)
+        .into_iter()
+        .map(|members| {
+            members
+                .into_iter()
+                .map(|(i, score)| Member {
+                    row: i + 1,
+                    id: issue_ids[i].clone(),
+                    title: summarize_issue(&snapshot.map[issue_ids[i]].features).to_string(),
+                    score,
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Groups `len` entries into clusters connected by the pairs, returning the clusters with more
+/// than one member, each as the positions of the members and their highest scores.
+fn cluster(len: usize, pairs: &[Pair]) -> Vec<Vec<(usize, f64)>> {
+    let mut parents: Vec<usize> = (0..len).collect();
+    let mut scores = vec![0.0; len];
+
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    for &(i, j, score) in pairs {
+        let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+
+        if root_i != root_j {
+            parents[root_i.max(root_j)] = root_i.min(root_j);
+        }
+
+        scores[i] = f64::max(scores[i], score);
+        scores[j] = f64::max(scores[j], score);
+    }
+
+    let mut clusters: Vec<Vec<(usize, f64)>> = vec![];
+    let mut cluster_of_root: HashMap<usize, usize> = HashMap::new();
+
+    for (i, &score) in scores.iter().enumerate() {
+        if score == 0.0 {
+            continue;
+        }
+
+        let root = find(&mut parents, i);
+        let k = *cluster_of_root.entry(root).or_insert_with(|| {
+            clusters.push(vec![]);
+            clusters.len() - 1
+        });
+        clusters[k].push((i, score));
+    }
+
+    clusters
+}
+
+fn write_clusters(output: impl Write, clusters: &[Vec<Member>]) -> Result<()> {
+    let mut wtr = Writer::from_writer(output);
+
+    wtr.write_record(["cluster", "row", "id", "title", "score"])
+        .map_err(|e| Error::Csv("Cannot write CSV record", e))?;
+
+    for (k, members) in clusters.iter().enumerate() {
+        for member in members {
+            wtr.write_record([
+                &(k + 1).to_string(),
+                &member.row.to_string(),
+                &member.id,
+                &member.title,
+                &format!("{:.4}", member.score),
+            ])
+            .map_err(|e| Error::Csv("Cannot write CSV record", e))?;
+        }
+    }
+
+    wtr.flush()
+        .map_err(|e| Error::Io("Cannot flush CSV writer", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_core::post::PostData;
+
+    use super::*;
+
+    fn post(id: &str, title: &str, content: &str) -> PostData {
+        PostData {
+            id: Some(id.to_string()),
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: None,
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_cluster() {
+        let clusters = cluster(6, &[(0, 3, 0.9), (3, 5, 0.8), (1, 2, 0.95)]);
+
+        assert_eq!(
+            clusters,
+            vec![
+                vec![(0, 0.9), (3, 0.9), (5, 0.8)],
+                vec![(1, 0.95), (2, 0.95)]
+            ]
+        );
+        assert!(cluster(3, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_dedupe_posts() {
+        let store = PostStore::new();
+        store.preload(vec![
+            post(
+                "1",
+                "Deno.kill on windows",
+                "SIGINT is not supported on Windows",
+            ),
+            post("2", "How to embed V8", "Embedding V8 into a Rust program"),
+            post(
+                "3",
+                "Deno.kill on Windows",
+                "SIGINT is not supported on Windows.",
+            ),
+            post("4", "", ""),
+        ]);
+
+        let clusters = dedupe_posts(&store, 0.8).unwrap();
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].len(), 2);
+        assert_eq!(clusters[0][0].row, 1);
+        assert_eq!(clusters[0][0].id, "1");
+        assert_eq!(clusters[0][1].row, 3);
+        assert_eq!(clusters[0][1].title, "Deno.kill on Windows");
+        assert!(clusters[0][1].score > 0.9);
+
+        let mut output = vec![];
+        write_clusters(&mut output, &clusters).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "cluster,row,id,title,score");
+        assert!(lines[1].starts_with("1,1,1,Deno.kill on windows,"));
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use similar_core::{Error, Result};
+
+use crate::corpus::{Corpus, CorpusArgs};
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommand {
+    /// Writes the corpus to a snapshot file, which loads much faster than the source data and
+    /// can be opened with `PostStore.open()` or passed back as `--corpus`.
+    Build(BuildArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub corpus: CorpusArgs,
+
+    /// The snapshot file to write.
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+pub fn run(command: &IndexCommand) -> Result<()> {
+    match command {
+        IndexCommand::Build(args) => build(args),
+    }
+}
+
+fn build(args: &BuildArgs) -> Result<()> {
+    let corpus = args.corpus.load()?;
+    let store = match &corpus {
+        Corpus::Posts(store) => store,
+        Corpus::Issues(_) => {
+            return Err(Error::InvalidArgument(
+                "Snapshots are only supported for posts".to_string(),
+            ))
+        }
+    };
+
+    store.save(&args.output)?;
+    eprintln!(
+        "find-similar: wrote {} posts to {}",
+        corpus.len(),
+        args.output.display()
+    );
+    Ok(())
+}
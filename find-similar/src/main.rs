@@ -0,0 +1,46 @@
+//! `find-similar`, a command-line tool to search a corpus of posts or issue features for similar
+//! entries and report duplicates, without writing a script against the Node.js bindings.
+
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+mod corpus;
+mod dedupe;
+mod index;
+mod query;
+
+#[derive(Debug, Parser)]
+#[command(name = "find-similar", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Finds the entries most similar to the one given in the arguments or stdin.
+    Query(query::QueryArgs),
+    /// Reports the clusters of duplicate entries in the corpus as CSV.
+    Dedupe(dedupe::DedupeArgs),
+    /// Manages persistent snapshots of the corpus.
+    #[command(subcommand)]
+    Index(index::IndexCommand),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Query(args) => query::run(args),
+        Command::Dedupe(args) => dedupe::run(args),
+        Command::Index(command) => index::run(command),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("find-similar: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::io::{self, Read, Write};
+
+use clap::{Args, ValueEnum};
+use serde_json::{json, Map, Value};
+use similar_core::{
+    filter::Filter,
+    issue::{IssueFeatures, SimilarIssueFeaturesRecord},
+    post::{FindTopNResult, PostData},
+    Error, Result,
+};
+
+use crate::corpus::{Corpus, CorpusArgs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Table,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    #[command(flatten)]
+    pub corpus: CorpusArgs,
+
+    /// The title of the post to search with. When neither the title nor the content is given,
+    /// the post is read from stdin, either as a JSON object or as plain text whose first line is
+    /// the title.
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// The content of the post to search with.
+    #[arg(long)]
+    pub content: Option<String>,
+
+    /// The operation feature of the issue to search with, used with `--issues`. When no feature
+    /// is given, the issue features are read from stdin as a JSON object.
+    #[arg(long)]
+    pub operation: Option<String>,
+
+    /// The phenomenon feature of the issue to search with.
+    #[arg(long)]
+    pub phenomenon: Option<String>,
+
+    /// The expected behavior feature of the issue to search with.
+    #[arg(long)]
+    pub expected_behavior: Option<String>,
+
+    /// The actual behavior feature of the issue to search with.
+    #[arg(long)]
+    pub actual_behavior: Option<String>,
+
+    /// How many matches to print at most.
+    #[arg(short = 'n', long, default_value_t = 5)]
+    pub top_n: usize,
+
+    /// A metadata filter in JSON, only the candidates satisfying it are scored.
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    pub output: OutputFormat,
+}
+
+pub fn run(args: &QueryArgs) -> Result<()> {
+    let filter = match &args.filter {
+        Some(filter) => {
+            let filter = serde_json::from_str::<Value>(filter)
+                .map_err(|e| Error::Json("Cannot parse the filter", e))?;
+            Some(Filter::parse(&filter)?)
+        }
+        None => None,
+    };
+    let output = match args.corpus.load()? {
+        Corpus::Posts(store) => {
+            let source = read_post(args)?;
+            let result = store.find_similar_posts(&source, args.top_n, filter.as_ref())?;
+
+            match args.output {
+                OutputFormat::Json => posts_to_json(&result),
+                OutputFormat::Table => posts_to_table(&result),
+            }
+        }
+        Corpus::Issues(store) => {
+            let features = read_issue(args)?;
+            let matches = store.find_similar_records(&features, args.top_n, filter.as_ref())?;
+
+            match args.output {
+                OutputFormat::Json => issues_to_json(&matches),
+                OutputFormat::Table => issues_to_table(&matches),
+            }
+        }
+    };
+
+    io::stdout()
+        .lock()
+        .write_all(output.as_bytes())
+        .map_err(|e| Error::Io("Cannot write the output", e))
+}
+
+fn read_stdin() -> Result<String> {
+    let mut input = String::new();
+
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| Error::Io("Cannot read stdin", e))?;
+    Ok(input)
+}
+
+fn read_post(args: &QueryArgs) -> Result<PostData> {
+    if args.title.is_some() || args.content.is_some() {
+        return Ok(PostData {
+            title: args.title.clone().unwrap_or_default(),
+            content: args.content.clone().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+
+    Ok(parse_post(&read_stdin()?))
+}
+
+/// Parses a post given as a JSON object with `title` and `content` fields, or as plain text
+/// whose first line is the title and the rest is the content.
+fn parse_post(input: &str) -> PostData {
+    if let Ok(Value::Object(mut obj)) = serde_json::from_str::<Value>(input) {
+        let mut take = |field: &str| match obj.remove(field) {
+            Some(Value::String(value)) => value,
+            _ => String::new(),
+        };
+
+        return PostData {
+            title: take("title"),
+            content: take("content"),
+            ..Default::default()
+        };
+    }
+
+    let input = input.trim();
+    let (title, content) = input.split_once('\n').unwrap_or((input, ""));
+
+    PostData {
+        title: title.trim().to_string(),
+        content: content.trim().to_string(),
+        ..Default::default()
+    }
+}
+
+fn read_issue(args: &QueryArgs) -> Result<IssueFeatures> {
+    let features = IssueFeatures {
+        operation: args.operation.clone(),
+        phenomenon: args.phenomenon.clone(),
+        expected_behavior: args.expected_behavior.clone(),
+        actual_behavior: args.actual_behavior.clone(),
+        embedding: None,
+    };
+
+    if !features.is_empty() {
+        return Ok(features);
+    }
+
+    let mut obj = match serde_json::from_str::<Value>(&read_stdin()?) {
+        Ok(Value::Object(obj)) => obj,
+        Ok(_) => {
+            return Err(Error::InvalidArgument(
+                "The issue features must be a JSON object".to_string(),
+            ))
+        }
+        Err(e) => return Err(Error::Json("Cannot parse the issue features", e)),
+    };
+    let mut take = |field: &str| match obj.remove(field) {
+        Some(Value::String(value)) => Some(value),
+        _ => None,
+    };
+
+    Ok(IssueFeatures {
+        operation: take("operation"),
+        phenomenon: take("phenomenon"),
+        expected_behavior: take("expected_behavior"),
+        actual_behavior: take("actual_behavior"),
+        embedding: None,
+    })
+}
+
+fn metadata_to_json(metadata: &Option<Map<String, Value>>) -> Value {
+    metadata.clone().map_or(Value::Null, Value::Object)
+}
+
+/// Prints the result in the same shape as `FindTopNResult` of the Node.js binding.
+fn posts_to_json(result: &FindTopNResult) -> String {
+    let matches: Vec<Value> = result
+        .matches
+        .iter()
+        .map(|m| {
+            json!({
+                "target": {
+                    "id": m.target.id,
+                    "title": m.target.title,
+                    "content": m.target.content,
+                    "metadata": metadata_to_json(&m.target.metadata),
+                },
+                "score": m.score,
+            })
+        })
+        .collect();
+    let value = json!({
+        "matches": matches,
+        "processTime": result.process_time.as_secs_f64() * 1000.0,
+        "generation": result.generation,
+    });
+
+    format!("{:#}\n", value)
+}
+
+/// Prints the matches in the same shape as `SimilarIssueFeaturesRecord` of the Node.js binding.
+fn issues_to_json(matches: &[SimilarIssueFeaturesRecord]) -> String {
+    let matches: Vec<Value> = matches
+        .iter()
+        .map(|m| {
+            json!({
+                "issueId": m.issue_id,
+                "features": {
+                    "operation": m.features.operation,
+                    "phenomenon": m.features.phenomenon,
+                    "expectedBehavior": m.features.expected_behavior,
+                    "actualBehavior": m.features.actual_behavior,
+                },
+                "metadata": metadata_to_json(&m.metadata),
+                "score": m.score,
+                "generation": m.generation,
+            })
+        })
+        .collect();
+
+    format!("{:#}\n", Value::Array(matches))
+}
+
+fn posts_to_table(result: &FindTopNResult) -> String {
+    let rows = result
+        .matches
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            vec![
+                (i + 1).to_string(),
+                format!("{:.4}", m.score),
+                m.target.id.clone().unwrap_or_default(),
+                m.target.title.clone(),
+            ]
+        })
+        .collect();
+
+    render_table(&["RANK", "SCORE", "ID", "TITLE"], rows)
+}
+
+fn issues_to_table(matches: &[SimilarIssueFeaturesRecord]) -> String {
+    let rows = matches
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            vec![
+                (i + 1).to_string(),
+                format!("{:.4}", m.score),
+                m.issue_id.clone(),
+                summarize_issue(&m.features).to_string(),
+            ]
+        })
+        .collect();
+
+    render_table(&["RANK", "SCORE", "ISSUE ID", "SUMMARY"], rows)
+}
+
+/// Returns the first present feature of the issue, which is used in place of a title.
+pub fn summarize_issue(features: &IssueFeatures) -> &str {
+    [
+        &features.operation,
+        &features.phenomenon,
+        &features.expected_behavior,
+        &features.actual_behavior,
+    ]
+    .into_iter()
+    .find_map(|feature| feature.as_deref())
+    .unwrap_or_default()
+}
+
+/// Renders the rows as left-aligned columns, the last column is truncated to keep the lines
+/// short.
+fn render_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    const MAX_LAST_WIDTH: usize = 60;
+
+    let rows: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|mut row| {
+            if let Some(last) = row.last_mut() {
+                let last_line = last.lines().next().unwrap_or_default();
+
+                if last_line.chars().count() > MAX_LAST_WIDTH || last_line.len() < last.len() {
+                    let truncated: String = last_line.chars().take(MAX_LAST_WIDTH - 1).collect();
+                    *last = format!("{}…", truncated);
+                }
+            }
+            row
+        })
+        .collect();
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let render_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+    let mut output = render_row(headers.to_vec());
+
+    for row in &rows {
+        output.push_str(&render_row(row.iter().map(String::as_str).collect()));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_post() {
+        let post = parse_post(r#"{"title": "Deno.kill on windows", "content": "SIGINT"}"#);
+        assert_eq!(post.title, "Deno.kill on windows");
+        assert_eq!(post.content, "SIGINT");
+
+        let post = parse_post("Deno.kill on windows\n\nSIGINT is not supported\non Windows\n");
+        assert_eq!(post.title, "Deno.kill on windows");
+        assert_eq!(post.content, "SIGINT is not supported\non Windows");
+
+        let post = parse_post("Deno.kill on windows");
+        assert_eq!(post.title, "Deno.kill on windows");
+        assert_eq!(post.content, "");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render_table(
+            &["RANK", "SCORE", "TITLE"],
+            vec![
+                vec!["1".to_string(), "0.9000".to_string(), "a".repeat(80)],
+                vec!["2".to_string(), "0.6000".to_string(), "b\nc".to_string()],
+            ],
+        );
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines[0], "RANK  SCORE   TITLE");
+        assert_eq!(lines[1], format!("1     0.9000  {}…", "a".repeat(59)));
+        assert_eq!(lines[2], "2     0.6000  b…");
+    }
+}
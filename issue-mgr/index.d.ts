@@ -3,33 +3,347 @@
 
 /* auto-generated by NAPI-RS */
 
+export interface JsonlOptions {
+  /** The field holding the issue ID, defaults to `issue_id`. */
+  issueIdField?: string
+  /** The field holding the operation feature, defaults to `operation`. */
+  operationField?: string
+  /** The field holding the phenomenon feature, defaults to `phenomenon`. */
+  phenomenonField?: string
+  /** The field holding the expected behavior feature, defaults to `expected_behavior`. */
+  expectedBehaviorField?: string
+  /** The field holding the actual behavior feature, defaults to `actual_behavior`. */
+  actualBehaviorField?: string
+  /**
+   * The field holding the metadata object of the record, defaults to `metadata`. Other unknown
+   * fields are merged into the metadata as well, except `embedding`, which holds the
+   * embedding of the record as an array of numbers.
+   */
+  metadataField?: string
+  /**
+   * Whether the input is gzip-compressed, detected from the content when omitted. Only used by
+   * `loadJsonl()`.
+   */
+  gzip?: boolean
+}
 export interface DbOptions {
   url: string
   table: string
 }
+export interface LoadError {
+  /** The 1-based line number where the malformed record starts. */
+  line: number
+  message: string
+}
+export interface LoadReport {
+  /** How many records have been loaded into the store. */
+  loaded: number
+  /** The records which have been skipped because they are malformed. */
+  errors: Array<LoadError>
+}
 export interface IssueFeatures {
   operation?: string
   phenomenon?: string
   expectedBehavior?: string
   actualBehavior?: string
+  /**
+   * A vector computed upstream from the issue, such as a sentence embedding, which is indexed
+   * for `IssueFeatureStore#findNearestRecords()`.
+   */
+  embedding?: Float32Array
 }
 export interface IssueFeaturesRecord {
   issueId: string
   features: IssueFeatures
+  /**
+   * Structured attributes of the issue, such as `component`, `labels` and `state`, which can be
+   * used to filter the candidates before scoring.
+   */
+  metadata?: Record<string, any>
 }
 export interface SimilarIssueFeaturesRecord {
   issueId: string
   features: IssueFeatures
-  /** Similarity score `0 - 1`, higher is more similar. */
+  metadata?: Record<string, any>
+  /** Similarity score `0 - 1`, higher is more similar. The fused score in a hybrid query. */
   score: number
+  /** The generation of the store snapshot the query was run against. */
+  generation: number
+  /**
+   * The rank of the record among the lexical matches, counting from 1. Only set by hybrid
+   * queries.
+   */
+  lexicalRank?: number
+  /**
+   * The rank of the record among the nearest embeddings, counting from 1. Only set by hybrid
+   * queries.
+   */
+  vectorRank?: number
+  /**
+   * The names of the facets the record shares with the source, only set when the store
+   * extracts facets.
+   */
+  sharedFacets?: Array<string>
+  /**
+   * The fingerprint of the stack trace of the record, only set when the store compares
+   * traces.
+   */
+  fingerprint?: string
+  /**
+   * The symbols the record shares with the source, only set when the store compares
+   * symbols.
+   */
+  sharedSymbols?: Array<string>
+}
+/**
+ * The threads the queries of a store run on, the rayon global pool shared by the whole process
+ * is used by default.
+ */
+export interface PoolOptions {
+  /** How many threads score the candidates, the rayon global pool is used when omitted. */
+  threads?: number
+  /**
+   * The prefix of the names of the threads, followed by their indices, defaults to
+   * `similar-`.
+   */
+  threadNamePrefix?: string
+  /** How many queries may run at the same time, unlimited when omitted. */
+  maxConcurrentQueries?: number
+  /**
+   * How many queries may wait for a running one to finish, the excess ones are rejected.
+   * Unlimited when omitted, `0` rejects the queries which can't run right away. A waiting
+   * query doesn't hold any thread, and a running one runs on the threads of the store
+   * instead of the libuv pool.
+   */
+  maxQueuedQueries?: number
+  /** Candidate sets smaller than this are scanned by a single thread, defaults to 256. */
+  sequentialThreshold?: number
+}
+/** How `findSimilarRecords()` fuses the lexical matches with the nearest embeddings. */
+export interface HybridOptions {
+  /**
+   * `rrf` for reciprocal rank fusion, or `weighted` for a weighted sum of the scores
+   * normalized to `0 - 1`, defaults to `rrf`.
+   */
+  fusion?: string
+  /**
+   * The `k` of the reciprocal rank fusion, a match scores `1 / (k + rank)` for each
+   * retriever, defaults to 60.
+   */
+  k?: number
+  /** The weight of the lexical score in the weighted fusion, defaults to 0.5. */
+  lexicalWeight?: number
+  /** The weight of the vector score in the weighted fusion, defaults to 0.5. */
+  vectorWeight?: number
+  /** How many matches each retriever returns before they are fused, defaults to 50. */
+  candidates?: number
+}
+/**
+ * How `findSimilarRecords()` compares each feature, `levenshtein` for the whole texts,
+ * `partial` for the best part of the longer text, or `alignment` for the sentences of the
+ * texts in any order. Each defaults to `levenshtein`.
+ */
+export interface FeatureMetrics {
+  operation?: string
+  phenomenon?: string
+  expectedBehavior?: string
+  actualBehavior?: string
+}
+/** How `learnBoilerplate()` finds the lines or the runs of words shared by many documents. */
+export interface BoilerplateOptions {
+  /** `line` for whole lines, or `ngram` for runs of words, defaults to `line`. */
+  unit?: string
+  /** How many words a run is made of, defaults to 4. */
+  n?: number
+  /** The share of the documents a line or a run of words must appear in, defaults to 0.3. */
+  minShare?: number
+  /** How many documents a line or a run of words must appear in, defaults to 3. */
+  minDocuments?: number
+}
+export interface BoilerplateEntry {
+  /** The line or the run of words, with its whitespace collapsed and lowercased. */
+  text: string
+  /** The share of the documents it was found in. */
+  share: number
+}
+/**
+ * The boilerplate learned by `learnBoilerplate()`, which can be edited before it's given to
+ * `setBoilerplate()`.
+ */
+export interface Boilerplate {
+  /** `line` or `ngram`. */
+  unit: string
+  /** How many words a run is made of, only set for `ngram`. */
+  n?: number
+  entries: Array<BoilerplateEntry>
+}
+export interface FacetRule {
+  /** The name of the facet, such as `os`. */
+  name: string
+  /**
+   * A regular expression, the value of the facet is its `value` group, or the whole match if
+   * it has none.
+   */
+  pattern: string
+}
+/**
+ * How the facets of the records, such as their version and OS, are extracted when they are
+ * written to the store.
+ */
+export interface FacetOptions {
+  /** Rules taking precedence over the built-in ones. */
+  rules?: Array<FacetRule>
+  /**
+   * Whether the built-in rules for the `version`, `os`, `arch` and `runtime` facets are
+   * used, defaults to `true`.
+   */
+  builtin?: boolean
+  /**
+   * Added to the score of a match sharing every facet of the source, a share of it for a
+   * match sharing some of them, from 0 to 1, defaults to 0.
+   */
+  boost?: number
+}
+/** How the stack traces of the records are compared with the one of the source. */
+export interface TraceOptions {
+  /** How many frames from the top of a trace are fingerprinted and compared, defaults to 5. */
+  depth?: number
+  /**
+   * `signal` to combine the similarity of the traces with the one of the texts, or `bucket`
+   * to score the records with the fingerprint of the source as exact matches, defaults to
+   * `signal`.
+   */
+  matching?: string
+  /**
+   * The share of the score made up by the similarity of the traces for `signal`, from 0 to
+   * 1, defaults to 0.3.
+   */
+  weight?: number
+}
+/**
+ * How the symbols mentioned by the records, such as `Deno.kill`, `ERR_INVALID_ARG_TYPE` or
+ * `SIGTERM`, are compared with the ones of the source.
+ */
+export interface SymbolOptions {
+  /**
+   * The share of the score made up by the overlap of the symbols, from 0 to 1, defaults to
+   * 0.25.
+   */
+  weight?: number
+}
+/** How a store is set up, every option is off when left out. */
+export interface StoreOptions {
+  /** The threads the queries run on. */
+  pool?: PoolOptions
+  /** How the embeddings of the records are indexed. */
+  vectors?: VectorIndexOptions
+  /** How `findSimilarRecords()` compares each feature. */
+  metrics?: FeatureMetrics
+  /**
+   * When given, the facets of the features of the records are extracted into the metadata the
+   * queries see under `facets`, where they can be filtered on, and each match reports the ones
+   * it shares with the source.
+   */
+  facets?: FacetOptions
+  /**
+   * When given, the stack traces of the features are fingerprinted into the metadata the
+   * queries see under `trace`, and compared with the one of the source.
+   */
+  traces?: TraceOptions
+  /**
+   * When given, the symbols the features mention are extracted into the metadata the queries
+   * see under `symbols`, and each match reports the ones it shares with the source.
+   */
+  symbols?: SymbolOptions
+}
+/** How `findSimilarRecords()` picks and scores the records. */
+export interface FindOptions {
+  /**
+   * When given, only the records whose metadata satisfy it are scored, see [Filter] for the
+   * syntax.
+   */
+  filter?: any
+  /**
+   * When given, the lexical matches are fused with the records whose embeddings are the
+   * nearest to the one of the features, and each match reports its rank in both.
+   */
+  hybrid?: HybridOptions
+}
+/** How the embeddings of the records are indexed for `findNearestRecords()`. */
+export interface VectorIndexOptions {
+  /** `cosine`, `dot` or `l2`, defaults to `cosine`. */
+  distance?: string
+  /** How many neighbors a node links to on each layer of the graph, defaults to 16. */
+  m?: number
+  /** How many candidates are considered when indexing an embedding, defaults to 200. */
+  efConstruction?: number
+  /** How many candidates are considered when querying, defaults to 64. */
+  efSearch?: number
 }
 export declare class IssueFeatureStore {
   static loadCsv(path: string): Promise<IssueFeatureStore>
   dumpCsv(path: string): Promise<void>
   static fromDb(options: DbOptions): Promise<IssueFeatureStore>
-  constructor(records?: Array<IssueFeaturesRecord> | undefined | null)
+  /**
+   * Adds the records in a JSON Lines file to the store, which is streamed line by line on a
+   * worker thread. Records with an existing issue ID replace the stored ones, malformed lines
+   * are skipped and reported with their line numbers.
+   */
+  loadJsonl(path: string, options?: JsonlOptions | undefined | null): Promise<LoadReport>
+  /**
+   * Writes the records in the store to a JSON Lines file, using the same field options as
+   * `loadJsonl()`.
+   */
+  dumpJsonl(path: string, options?: JsonlOptions | undefined | null): Promise<void>
+  /** Creates a store holding the records, set up by `options`. */
+  constructor(records?: Array<IssueFeaturesRecord> | undefined | null, options?: StoreOptions | undefined | null)
+  /**
+   * Attaches to the process-global store named `name`, which is shared by all the worker
+   * threads of the process. It's created empty on the first call, and dropped when the last
+   * instance attached to it is disposed or garbage collected.
+   */
+  static shared(name: string): IssueFeatureStore
+  /** The name of the shared store this instance is attached to. */
+  get sharedName(): string | null
+  /**
+   * Releases the records held by this instance, which becomes an empty, unshared store. The
+   * records of a shared store are dropped once every instance attached to it is disposed.
+   */
+  dispose(): void
+  /** The generation of the current snapshot, increased by every write. */
+  get generation(): number
   setRecord(record: IssueFeaturesRecord): void
+  /**
+   * Inserts or replaces the records in a single write, none of them is written if any is
+   * invalid.
+   */
+  setRecords(records: Array<IssueFeaturesRecord>): void
   getRecord(issueId: string): IssueFeaturesRecord | null
   removeRecord(issueId: string): boolean
-  findSimilarRecords(features: IssueFeatures, topN?: number | undefined | null, signal?: AbortSignal | undefined | null): Promise<Array<SimilarIssueFeaturesRecord>>
+  /**
+   * Learns the lines or the runs of words shared by many records in the current snapshot,
+   * such as the headers of a template. It isn't updated by later writes.
+   */
+  learnBoilerplate(options?: BoilerplateOptions | undefined | null): Promise<Boilerplate>
+  /**
+   * The boilerplate removed from the features before they are compared by
+   * `findSimilarRecords()`, if any.
+   */
+  get boilerplate(): Boilerplate | null
+  /**
+   * Removes `boilerplate` from the features of the records and the source before they are
+   * compared by `findSimilarRecords()` of this instance, or stops removing it if `null`.
+   */
+  setBoilerplate(boilerplate?: Boilerplate | undefined | null): void
+  /**
+   * Finds the `top_n` records most similar to the given `features`, picked and scored as set
+   * up by `options`.
+   */
+  findSimilarRecords(features: IssueFeatures, topN?: number | undefined | null, signal?: AbortSignal | undefined | null, options?: FindOptions | undefined | null): Promise<Array<SimilarIssueFeaturesRecord>>
+  /**
+   * Finds the `top_n` records whose embeddings are the nearest to `embedding`, the score of a
+   * match is the cosine similarity, the dot product or `1 / (1 + distance)` for `l2`. The
+   * embeddings are indexed by the first query after a write.
+   */
+  findNearestRecords(embedding: Float32Array, topN?: number | undefined | null, signal?: AbortSignal | undefined | null, filter?: any | undefined | null): Promise<Array<SimilarIssueFeaturesRecord>>
 }
@@ -1,14 +1,35 @@
 import { abortWith } from "@ayonli/jsext/async"
 // @deno-types="./index.d.ts"
 import {
+    type Boilerplate,
+    type BoilerplateEntry,
+    type BoilerplateOptions,
     type DbOptions,
+    type HybridOptions,
     type IssueFeatures,
     type IssueFeaturesRecord,
     IssueFeatureStore as IssueFeatureStoreNative,
+    type JsonlOptions,
+    type LoadError,
+    type LoadReport,
     type SimilarIssueFeaturesRecord,
+    type StoreOptions,
 } from "./index.js"
 
-export type { DbOptions, IssueFeatures, IssueFeaturesRecord, SimilarIssueFeaturesRecord }
+export type {
+    Boilerplate,
+    BoilerplateEntry,
+    BoilerplateOptions,
+    DbOptions,
+    HybridOptions,
+    IssueFeatures,
+    IssueFeaturesRecord,
+    JsonlOptions,
+    LoadError,
+    LoadReport,
+    SimilarIssueFeaturesRecord,
+    StoreOptions,
+}
 
 export class IssueFeatureStore {
     #impl: IssueFeatureStoreNative
@@ -31,8 +52,28 @@ export class IssueFeatureStore {
         return await this.#impl.dumpCsv(path)
     }
 
-    constructor(records?: IssueFeaturesRecord[] | null | undefined) {
-        this.#impl = new IssueFeatureStoreNative(records)
+    /**
+     * Adds the records in a JSON Lines file (optionally gzip-compressed) to the store, malformed
+     * lines are skipped and reported with their line numbers.
+     */
+    async loadJSONL(path: string, options?: JsonlOptions | null): Promise<LoadReport> {
+        return await this.#impl.loadJsonl(path, options)
+    }
+
+    async dumpJSONL(path: string, options?: JsonlOptions | null): Promise<void> {
+        return await this.#impl.dumpJsonl(path, options)
+    }
+
+    constructor(
+        records?: IssueFeaturesRecord[] | null | undefined,
+        options?: StoreOptions | null,
+    ) {
+        this.#impl = new IssueFeatureStoreNative(records, options)
+    }
+
+    /** The generation of the current snapshot, increased by every write. */
+    get generation(): number {
+        return this.#impl.generation
     }
 
     setRecord(record: IssueFeaturesRecord): void {
@@ -47,11 +88,39 @@ export class IssueFeatureStore {
         return this.#impl.removeRecord(issueId)
     }
 
+    /**
+     * Learns the lines or the runs of words shared by many records, such as the headers of a
+     * template, e.g. `{ unit: "ngram", minShare: 0.5 }`.
+     */
+    async learnBoilerplate(options?: BoilerplateOptions | null): Promise<Boilerplate> {
+        return await this.#impl.learnBoilerplate(options)
+    }
+
+    /** The boilerplate removed from the features before they are compared, if any. */
+    get boilerplate(): Boilerplate | null {
+        return this.#impl.boilerplate
+    }
+
+    /** Removes `boilerplate` from the features before they are compared, or stops if `null`. */
+    setBoilerplate(boilerplate: Boilerplate | null): void {
+        this.#impl.setBoilerplate(boilerplate)
+    }
+
     async findSimilarRecords(
         features: IssueFeatures,
         options: {
             topN?: number
             signal?: AbortSignal | null
+            /**
+             * A metadata filter evaluated before scoring, e.g.
+             * `{ component: "cli", labels: { $in: ["bug"] }, state: { $ne: "closed" } }`.
+             */
+            filter?: Record<string, unknown> | null
+            /**
+             * Fuses the lexical matches with the records whose embeddings are the nearest to
+             * `features.embedding`, e.g. `{ fusion: "rrf" }`.
+             */
+            hybrid?: HybridOptions | null
         } = {},
     ): Promise<SimilarIssueFeaturesRecord[]> {
         // NAPI-RS has a bug when reusing the same AbortSignal, so we derive a
@@ -61,6 +130,7 @@ export class IssueFeatureStore {
             features,
             options.topN,
             signal,
+            { filter: options.filter, hybrid: options.hybrid ?? undefined },
         )
     }
 }
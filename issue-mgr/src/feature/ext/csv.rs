@@ -1,15 +1,9 @@
-use std::{
-    collections::HashMap,
-    fs::File,
-    sync::{Arc, RwLock},
-};
+use std::sync::Arc;
 
-use csv::{Reader, Writer};
-use napi::{Env, Error, Result, Task, bindgen_prelude::AsyncTask};
+use napi::{Env, Result, Task, bindgen_prelude::AsyncTask};
+use similar_core::issue::{self, IssueFeaturesSnapshot};
 
-use crate::feature::{
-    IssueFeatureStore, IssueFeatures, IssueFeaturesRecord, ext::RawIssueFeaturesRecord,
-};
+use crate::feature::{IssueFeatureStore, to_napi_error};
 
 #[napi]
 impl IssueFeatureStore {
@@ -22,7 +16,7 @@ impl IssueFeatureStore {
     pub fn dump_csv(&self, path: String) -> AsyncTask<AsyncDumper> {
         AsyncTask::new(AsyncDumper {
             path,
-            issue_features_map: self.issue_features_map.clone(),
+            snapshot: self.inner.snapshot(),
         })
     }
 }
@@ -37,42 +31,9 @@ impl Task for AsyncLoader {
     type JsValue = Self::Output;
 
     fn compute(&mut self) -> Result<Self::Output> {
-        let file = match File::open(&self.path) {
-            Ok(file) => file,
-            Err(e) => return Err(Error::from_reason(format!("Cannot open CSV file: {}", e))),
-        };
-        let mut rdr = Reader::from_reader(file);
-        let mut records: Vec<IssueFeaturesRecord> = Vec::new();
-
-        for record in rdr.deserialize() {
-            let RawIssueFeaturesRecord {
-                issue_id,
-                operation,
-                phenomenon,
-                expected_behavior,
-                actual_behavior,
-            } = match record {
-                Ok(record) => record,
-                Err(e) => {
-                    return Err(Error::from_reason(format!(
-                        "Cannot parse CSV record: {}",
-                        e
-                    )));
-                }
-            };
-
-            records.push(IssueFeaturesRecord {
-                issue_id,
-                features: IssueFeatures {
-                    operation,
-                    phenomenon,
-                    expected_behavior,
-                    actual_behavior,
-                },
-            });
-        }
-
-        Ok(IssueFeatureStore::new(Some(records)))
+        issue::IssueFeatureStore::load_csv(&self.path)
+            .map(IssueFeatureStore::from)
+            .map_err(to_napi_error)
     }
 
     fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
@@ -82,7 +43,7 @@ impl Task for AsyncLoader {
 
 pub struct AsyncDumper {
     pub path: String,
-    pub issue_features_map: Arc<RwLock<HashMap<String, IssueFeatures>>>,
+    pub snapshot: Arc<IssueFeaturesSnapshot>,
 }
 
 #[napi]
@@ -91,41 +52,7 @@ impl Task for AsyncDumper {
     type JsValue = Self::Output;
 
     fn compute(&mut self) -> Result<Self::Output> {
-        let file = match File::create(&self.path) {
-            Ok(file) => file,
-            Err(e) => return Err(Error::from_reason(format!("Cannot create CSV file: {}", e))),
-        };
-        let mut wtr = Writer::from_writer(file);
-        let map = match self.issue_features_map.read() {
-            Ok(lock) => lock,
-            Err(e) => {
-                return Err(Error::from_reason(format!(
-                    "Cannot acquire read lock on issue features map: {}",
-                    e
-                )));
-            }
-        };
-
-        for (issue_id, features) in map.iter() {
-            let raw_record = RawIssueFeaturesRecord {
-                issue_id: issue_id.clone(),
-                operation: features.operation.clone(),
-                phenomenon: features.phenomenon.clone(),
-                expected_behavior: features.expected_behavior.clone(),
-                actual_behavior: features.actual_behavior.clone(),
-            };
-
-            if let Err(e) = wtr.serialize(raw_record) {
-                return Err(Error::from_reason(format!(
-                    "Cannot serialize CSV record: {}",
-                    e
-                )));
-            }
-        }
-
-        wtr.flush()
-            .map_err(|e| Error::from_reason(format!("Cannot flush CSV writer: {}", e)))?;
-        Ok(())
+        self.snapshot.dump_csv(&self.path).map_err(to_napi_error)
     }
 
     fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
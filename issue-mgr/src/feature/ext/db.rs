@@ -1,9 +1,6 @@
-use napi::Error;
-use sqlx::{Connection, MySqlConnection, PgConnection, SqliteConnection};
+use similar_core::issue;
 
-use crate::feature::{
-    IssueFeatureStore, IssueFeatures, IssueFeaturesRecord, ext::RawIssueFeaturesRecord,
-};
+use crate::feature::{IssueFeatureStore, to_napi_error};
 
 #[napi(object)]
 pub struct DbOptions {
@@ -16,67 +13,11 @@ impl IssueFeatureStore {
     #[napi]
     pub async fn from_db(options: DbOptions) -> napi::Result<Self> {
         let DbOptions { url, table } = options;
-        let sql = format!(
-            "select issue_id, operation, phenomenon, expected_behavior, actual_behavior from {}",
-            &table
-        );
-
-        let result = async {
-            if url.starts_with("mysql:") {
-                let mut db = MySqlConnection::connect(&url).await?;
-                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
-                prepare.fetch_all(&mut db).await
-            } else if url.starts_with("postgres:") {
-                let mut db = PgConnection::connect(&url).await?;
-                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
-                prepare.fetch_all(&mut db).await
-            } else if url.starts_with("sqlite:") {
-                let mut db = SqliteConnection::connect(&url).await?;
-                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
-                prepare.fetch_all(&mut db).await
-            } else {
-                let i = url.find(':').unwrap_or(0);
-                let scheme = &url[..i];
-                return Err(sqlx::Error::InvalidArgument(format!(
-                    "Unsupported database scheme '{}'",
-                    scheme
-                )));
-            }
-        }
-        .await;
-        let rows = match result {
-            Ok(rows) => rows,
-            Err(e) => {
-                return Err(Error::from_reason(format!(
-                    "Cannot fetch issue features from database: {}",
-                    e
-                )));
-            }
-        };
-        let records = rows
-            .into_iter()
-            .map(
-                |RawIssueFeaturesRecord {
-                     issue_id,
-                     phenomenon,
-                     operation,
-                     expected_behavior,
-                     actual_behavior,
-                 }| {
-                    IssueFeaturesRecord {
-                        issue_id: issue_id.clone(),
-                        features: IssueFeatures {
-                            phenomenon,
-                            operation,
-                            expected_behavior,
-                            actual_behavior,
-                        },
-                    }
-                },
-            )
-            .collect();
 
-        Ok(IssueFeatureStore::new(Some(records)))
+        issue::IssueFeatureStore::from_db(&issue::DbOptions { url, table })
+            .await
+            .map(IssueFeatureStore::from)
+            .map_err(to_napi_error)
     }
 }
 
@@ -110,7 +51,9 @@ mod tests {
                     phenomenon: None,
                     expected_behavior: Some("The device is turned on".to_string()),
                     actual_behavior: Some("The device is not turned on".to_string()),
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(
@@ -124,7 +67,9 @@ mod tests {
                     ),
                     expected_behavior: None,
                     actual_behavior: None,
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(store.get_record("3".to_string()).unwrap(), None);
@@ -149,7 +94,9 @@ mod tests {
                     phenomenon: None,
                     expected_behavior: Some("The device is turned on".to_string()),
                     actual_behavior: Some("The device is not turned on".to_string()),
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(
@@ -163,7 +110,9 @@ mod tests {
                     ),
                     expected_behavior: None,
                     actual_behavior: None,
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(store.get_record("3".to_string()).unwrap(), None);
@@ -187,7 +136,9 @@ mod tests {
                     phenomenon: None,
                     expected_behavior: Some("The device is turned on".to_string()),
                     actual_behavior: Some("The device is not turned on".to_string()),
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(
@@ -201,7 +152,9 @@ mod tests {
                     ),
                     expected_behavior: None,
                     actual_behavior: None,
+                    embedding: None,
                 },
+                metadata: None,
             })
         );
         assert_eq!(store.get_record("3".to_string()).unwrap(), None);
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use napi::{Env, Result, Task, bindgen_prelude::AsyncTask};
+use similar_core::issue::{self, IssueFeaturesSnapshot};
+
+use crate::feature::{IssueFeatureStore, ext::LoadReport, to_napi_error};
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct JsonlOptions {
+    /// The field holding the issue ID, defaults to `issue_id`.
+    pub issue_id_field: Option<String>,
+    /// The field holding the operation feature, defaults to `operation`.
+    pub operation_field: Option<String>,
+    /// The field holding the phenomenon feature, defaults to `phenomenon`.
+    pub phenomenon_field: Option<String>,
+    /// The field holding the expected behavior feature, defaults to `expected_behavior`.
+    pub expected_behavior_field: Option<String>,
+    /// The field holding the actual behavior feature, defaults to `actual_behavior`.
+    pub actual_behavior_field: Option<String>,
+    /// The field holding the metadata object of the record, defaults to `metadata`. Other unknown
+    /// fields are merged into the metadata as well, except `embedding`, which holds the
+    /// embedding of the record as an array of numbers.
+    pub metadata_field: Option<String>,
+    /// Whether the input is gzip-compressed, detected from the content when omitted. Only used by
+    /// `loadJsonl()`.
+    pub gzip: Option<bool>,
+}
+
+impl From<JsonlOptions> for issue::JsonlOptions {
+    fn from(options: JsonlOptions) -> Self {
+        issue::JsonlOptions {
+            issue_id_field: options.issue_id_field,
+            operation_field: options.operation_field,
+            phenomenon_field: options.phenomenon_field,
+            expected_behavior_field: options.expected_behavior_field,
+            actual_behavior_field: options.actual_behavior_field,
+            metadata_field: options.metadata_field,
+            gzip: options.gzip,
+        }
+    }
+}
+
+#[napi]
+impl IssueFeatureStore {
+    /// Adds the records in a JSON Lines file to the store, which is streamed line by line on a
+    /// worker thread. Records with an existing issue ID replace the stored ones, malformed lines
+    /// are skipped and reported with their line numbers.
+    #[napi(ts_return_type = "Promise<LoadReport>")]
+    pub fn load_jsonl(
+        &self,
+        path: String,
+        options: Option<JsonlOptions>,
+    ) -> AsyncTask<AsyncJsonlLoader> {
+        AsyncTask::new(AsyncJsonlLoader {
+            path,
+            options: options.unwrap_or_default().into(),
+            store: self.inner.clone(),
+        })
+    }
+
+    /// Writes the records in the store to a JSON Lines file, using the same field options as
+    /// `loadJsonl()`.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn dump_jsonl(
+        &self,
+        path: String,
+        options: Option<JsonlOptions>,
+    ) -> AsyncTask<AsyncJsonlDumper> {
+        AsyncTask::new(AsyncJsonlDumper {
+            path,
+            options: options.unwrap_or_default().into(),
+            snapshot: self.inner.snapshot(),
+        })
+    }
+}
+
+pub struct AsyncJsonlLoader {
+    pub path: String,
+    pub options: issue::JsonlOptions,
+    pub store: issue::IssueFeatureStore,
+}
+
+#[napi]
+impl Task for AsyncJsonlLoader {
+    type Output = LoadReport;
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.store
+            .load_jsonl(&self.path, &self.options)
+            .map(LoadReport::from)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncJsonlDumper {
+    pub path: String,
+    pub options: issue::JsonlOptions,
+    pub snapshot: Arc<IssueFeaturesSnapshot>,
+}
+
+#[napi]
+impl Task for AsyncJsonlDumper {
+    type Output = ();
+    type JsValue = Self::Output;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.snapshot
+            .dump_jsonl(&self.path, &self.options)
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: Self::Output) -> Result<Self::JsValue> {
+        Ok(())
+    }
+}
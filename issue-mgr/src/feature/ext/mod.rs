@@ -1,14 +1,38 @@
-use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use similar_core::load;
 
 mod csv;
 mod db;
+mod jsonl;
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
-pub struct RawIssueFeaturesRecord {
-    pub issue_id: String,
-    pub operation: Option<String>,
-    pub phenomenon: Option<String>,
-    pub expected_behavior: Option<String>,
-    pub actual_behavior: Option<String>,
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    /// The 1-based line number where the malformed record starts.
+    pub line: u32,
+    pub message: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    /// How many records have been loaded into the store.
+    pub loaded: u32,
+    /// The records which have been skipped because they are malformed.
+    pub errors: Vec<LoadError>,
+}
+
+impl From<load::LoadReport> for LoadReport {
+    fn from(report: load::LoadReport) -> Self {
+        LoadReport {
+            loaded: report.loaded as u32,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|e| LoadError {
+                    line: e.line as u32,
+                    message: e.message,
+                })
+                .collect(),
+        }
+    }
 }
@@ -1,34 +1,114 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-};
-
 use napi::{
-    bindgen_prelude::{AbortSignal, AsyncTask},
-    Env, Error, Result, Task,
+    Env, JsObject, Result, Task, ValueType,
+    bindgen_prelude::{Float32Array, FromNapiValue, ToNapiValue, TypeName, ValidateNapiValue},
+    sys,
+};
+use serde_json::{Map, Value};
+use similar_core::{
+    boilerplate, facet, filter::Filter, hybrid, issue, metric, pool, shared::Shared, symbol, trace,
+    vector,
 };
-use rapidfuzz::distance::levenshtein::normalized_similarity;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-
-use crate::feature::util::get_feature_weights;
 
 mod ext;
-mod util;
+
+/// A vector computed by the caller, passed as a `Float32Array`. It's copied out of the
+/// JavaScript heap, so it can be moved to the threads scoring the records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding(pub Vec<f32>);
+
+impl TypeName for Embedding {
+    fn type_name() -> &'static str {
+        Float32Array::type_name()
+    }
+
+    fn value_type() -> ValueType {
+        Float32Array::value_type()
+    }
+}
+
+impl ValidateNapiValue for Embedding {
+    unsafe fn validate(env: sys::napi_env, napi_val: sys::napi_value) -> Result<sys::napi_value> {
+        unsafe { Float32Array::validate(env, napi_val) }
+    }
+}
+
+impl FromNapiValue for Embedding {
+    unsafe fn from_napi_value(env: sys::napi_env, napi_val: sys::napi_value) -> Result<Self> {
+        let array = unsafe { Float32Array::from_napi_value(env, napi_val)? };
+        Ok(Embedding(array.to_vec()))
+    }
+}
+
+impl ToNapiValue for Embedding {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> Result<sys::napi_value> {
+        unsafe { Float32Array::to_napi_value(env, Float32Array::new(val.0)) }
+    }
+}
 
 #[napi(object)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IssueFeatures {
     pub operation: Option<String>,
     pub phenomenon: Option<String>,
     pub expected_behavior: Option<String>,
     pub actual_behavior: Option<String>,
+    /// A vector computed upstream from the issue, such as a sentence embedding, which is indexed
+    /// for `IssueFeatureStore#findNearestRecords()`.
+    pub embedding: Option<Embedding>,
+}
+
+impl From<IssueFeatures> for issue::IssueFeatures {
+    fn from(features: IssueFeatures) -> Self {
+        issue::IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: features.embedding.map(|embedding| embedding.0),
+        }
+    }
+}
+
+impl From<issue::IssueFeatures> for IssueFeatures {
+    fn from(features: issue::IssueFeatures) -> Self {
+        IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: features.embedding.map(Embedding),
+        }
+    }
 }
 
 #[napi(object)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IssueFeaturesRecord {
     pub issue_id: String,
     pub features: IssueFeatures,
+    /// Structured attributes of the issue, such as `component`, `labels` and `state`, which can be
+    /// used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+}
+
+impl From<IssueFeaturesRecord> for issue::IssueFeaturesRecord {
+    fn from(record: IssueFeaturesRecord) -> Self {
+        issue::IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+impl From<issue::IssueFeaturesRecord> for IssueFeaturesRecord {
+    fn from(record: issue::IssueFeaturesRecord) -> Self {
+        IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
 }
 
 #[napi(object)]
@@ -36,124 +116,622 @@ pub struct IssueFeaturesRecord {
 pub struct SimilarIssueFeaturesRecord {
     pub issue_id: String,
     pub features: IssueFeatures,
-    /// Similarity score `0 - 1`, higher is more similar.
+    pub metadata: Option<Map<String, Value>>,
+    /// Similarity score `0 - 1`, higher is more similar. The fused score in a hybrid query.
     pub score: f64,
+    /// The generation of the store snapshot the query was run against.
+    pub generation: i64,
+    /// The rank of the record among the lexical matches, counting from 1. Only set by hybrid
+    /// queries.
+    pub lexical_rank: Option<u32>,
+    /// The rank of the record among the nearest embeddings, counting from 1. Only set by hybrid
+    /// queries.
+    pub vector_rank: Option<u32>,
+    /// The names of the facets the record shares with the source, only set when the store
+    /// extracts facets.
+    pub shared_facets: Option<Vec<String>>,
+    /// The fingerprint of the stack trace of the record, only set when the store compares
+    /// traces.
+    pub fingerprint: Option<String>,
+    /// The symbols the record shares with the source, only set when the store compares
+    /// symbols.
+    pub shared_symbols: Option<Vec<String>>,
+}
+
+impl From<issue::SimilarIssueFeaturesRecord> for SimilarIssueFeaturesRecord {
+    fn from(record: issue::SimilarIssueFeaturesRecord) -> Self {
+        SimilarIssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+            score: record.score,
+            generation: record.generation as i64,
+            lexical_rank: record.lexical_rank.map(|rank| rank as u32),
+            vector_rank: record.vector_rank.map(|rank| rank as u32),
+            shared_facets: (!record.shared_facets.is_empty()).then_some(record.shared_facets),
+            fingerprint: record.fingerprint,
+            shared_symbols: (!record.shared_symbols.is_empty()).then_some(record.shared_symbols),
+        }
+    }
+}
+
+pub(crate) use similar_napi::to_napi_error;
+
+/// The threads the queries of a store run on, the rayon global pool shared by the whole process
+/// is used by default.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct PoolOptions {
+    /// How many threads score the candidates, the rayon global pool is used when omitted.
+    pub threads: Option<u32>,
+    /// The prefix of the names of the threads, followed by their indices, defaults to
+    /// `similar-`.
+    pub thread_name_prefix: Option<String>,
+    /// How many queries may run at the same time, unlimited when omitted.
+    pub max_concurrent_queries: Option<u32>,
+    /// How many queries may wait for a running one to finish, the excess ones are rejected.
+    /// Unlimited when omitted, `0` rejects the queries which can't run right away. A waiting
+    /// query doesn't hold any thread, and a running one runs on the threads of the store
+    /// instead of the libuv pool.
+    pub max_queued_queries: Option<u32>,
+    /// Candidate sets smaller than this are scanned by a single thread, defaults to 256.
+    pub sequential_threshold: Option<u32>,
+}
+
+impl PoolOptions {
+    pub(crate) fn build(&self) -> Result<pool::QueryPool> {
+        pool::QueryPool::new(&pool::PoolOptions {
+            threads: self.threads.map(|threads| threads as usize),
+            thread_name_prefix: self.thread_name_prefix.clone(),
+            max_concurrent_queries: self.max_concurrent_queries.map(|max| max as usize),
+            max_queued_queries: self.max_queued_queries.map(|max| max as usize),
+            sequential_threshold: self
+                .sequential_threshold
+                .map(|threshold| threshold as usize),
+        })
+        .map_err(to_napi_error)
+    }
+}
+
+/// How the embeddings of the records are indexed for `findNearestRecords()`.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct VectorIndexOptions {
+    /// `cosine`, `dot` or `l2`, defaults to `cosine`.
+    pub distance: Option<String>,
+    /// How many neighbors a node links to on each layer of the graph, defaults to 16.
+    pub m: Option<u32>,
+    /// How many candidates are considered when indexing an embedding, defaults to 200.
+    pub ef_construction: Option<u32>,
+    /// How many candidates are considered when querying, defaults to 64.
+    pub ef_search: Option<u32>,
+}
+
+impl VectorIndexOptions {
+    pub(crate) fn build(&self) -> Result<vector::HnswOptions> {
+        let defaults = vector::HnswOptions::default();
+
+        Ok(vector::HnswOptions {
+            distance: match &self.distance {
+                Some(distance) => vector::Distance::parse(distance).map_err(to_napi_error)?,
+                None => defaults.distance,
+            },
+            m: self.m.map_or(defaults.m, |m| m as usize),
+            ef_construction: self
+                .ef_construction
+                .map_or(defaults.ef_construction, |ef| ef as usize),
+            ef_search: self.ef_search.map_or(defaults.ef_search, |ef| ef as usize),
+        })
+    }
+}
+
+/// How `findSimilarRecords()` fuses the lexical matches with the nearest embeddings.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct HybridOptions {
+    /// `rrf` for reciprocal rank fusion, or `weighted` for a weighted sum of the scores
+    /// normalized to `0 - 1`, defaults to `rrf`.
+    pub fusion: Option<String>,
+    /// The `k` of the reciprocal rank fusion, a match scores `1 / (k + rank)` for each
+    /// retriever, defaults to 60.
+    pub k: Option<f64>,
+    /// The weight of the lexical score in the weighted fusion, defaults to 0.5.
+    pub lexical_weight: Option<f64>,
+    /// The weight of the vector score in the weighted fusion, defaults to 0.5.
+    pub vector_weight: Option<f64>,
+    /// How many matches each retriever returns before they are fused, defaults to 50.
+    pub candidates: Option<u32>,
+}
+
+impl HybridOptions {
+    pub(crate) fn build(&self) -> Result<hybrid::HybridOptions> {
+        let fusion = match hybrid::Fusion::parse(self.fusion.as_deref().unwrap_or("rrf"))
+            .map_err(to_napi_error)?
+        {
+            hybrid::Fusion::Rrf { k } => hybrid::Fusion::Rrf {
+                k: self.k.unwrap_or(k),
+            },
+            hybrid::Fusion::Weighted { lexical, vector } => hybrid::Fusion::Weighted {
+                lexical: self.lexical_weight.unwrap_or(lexical),
+                vector: self.vector_weight.unwrap_or(vector),
+            },
+        };
+
+        Ok(hybrid::HybridOptions {
+            fusion,
+            candidates: self.candidates.map(|candidates| candidates as usize),
+        })
+    }
+}
+
+/// How `findSimilarRecords()` compares each feature, `levenshtein` for the whole texts,
+/// `partial` for the best part of the longer text, or `alignment` for the sentences of the
+/// texts in any order. Each defaults to `levenshtein`.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct FeatureMetrics {
+    pub operation: Option<String>,
+    pub phenomenon: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub actual_behavior: Option<String>,
+}
+
+impl FeatureMetrics {
+    pub(crate) fn build(&self) -> Result<issue::FeatureMetrics> {
+        let parse = |metric: &Option<String>| match metric {
+            Some(metric) => metric::Metric::parse(metric).map_err(to_napi_error),
+            None => Ok(metric::Metric::default()),
+        };
+
+        Ok(issue::FeatureMetrics {
+            operation: parse(&self.operation)?,
+            phenomenon: parse(&self.phenomenon)?,
+            expected_behavior: parse(&self.expected_behavior)?,
+            actual_behavior: parse(&self.actual_behavior)?,
+        })
+    }
+}
+
+/// How `learnBoilerplate()` finds the lines or the runs of words shared by many documents.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct BoilerplateOptions {
+    /// `line` for whole lines, or `ngram` for runs of words, defaults to `line`.
+    pub unit: Option<String>,
+    /// How many words a run is made of, defaults to 4.
+    pub n: Option<u32>,
+    /// The share of the documents a line or a run of words must appear in, defaults to 0.3.
+    pub min_share: Option<f64>,
+    /// How many documents a line or a run of words must appear in, defaults to 3.
+    pub min_documents: Option<u32>,
+}
+
+impl BoilerplateOptions {
+    pub(crate) fn build(&self) -> Result<boilerplate::BoilerplateOptions> {
+        let defaults = boilerplate::BoilerplateOptions::default();
+
+        Ok(boilerplate::BoilerplateOptions {
+            unit: build_unit(self.unit.as_deref(), self.n)?,
+            min_share: self.min_share.unwrap_or(defaults.min_share),
+            min_documents: self
+                .min_documents
+                .map_or(defaults.min_documents, |min| min as usize),
+        })
+    }
+}
+
+fn build_unit(unit: Option<&str>, n: Option<u32>) -> Result<boilerplate::Unit> {
+    match boilerplate::Unit::parse(unit.unwrap_or("line")).map_err(to_napi_error)? {
+        boilerplate::Unit::Line => Ok(boilerplate::Unit::Line),
+        boilerplate::Unit::NGram { n: default } => Ok(boilerplate::Unit::NGram {
+            n: n.map_or(default, |n| n as usize),
+        }),
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoilerplateEntry {
+    /// The line or the run of words, with its whitespace collapsed and lowercased.
+    pub text: String,
+    /// The share of the documents it was found in.
+    pub share: f64,
+}
+
+/// The boilerplate learned by `learnBoilerplate()`, which can be edited before it's given to
+/// `setBoilerplate()`.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boilerplate {
+    /// `line` or `ngram`.
+    pub unit: String,
+    /// How many words a run is made of, only set for `ngram`.
+    pub n: Option<u32>,
+    pub entries: Vec<BoilerplateEntry>,
+}
+
+impl From<&boilerplate::Boilerplate> for Boilerplate {
+    fn from(boilerplate: &boilerplate::Boilerplate) -> Self {
+        let (unit, n) = match boilerplate.unit() {
+            boilerplate::Unit::Line => ("line", None),
+            boilerplate::Unit::NGram { n } => ("ngram", Some(n as u32)),
+        };
+
+        Boilerplate {
+            unit: unit.to_string(),
+            n,
+            entries: boilerplate
+                .entries()
+                .iter()
+                .map(|entry| BoilerplateEntry {
+                    text: entry.text.clone(),
+                    share: entry.share,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Boilerplate {
+    pub(crate) fn build(&self) -> Result<boilerplate::Boilerplate> {
+        let unit = build_unit(Some(&self.unit), self.n)?;
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| boilerplate::BoilerplateEntry {
+                text: entry.text.clone(),
+                share: entry.share,
+            })
+            .collect();
+
+        Ok(boilerplate::Boilerplate::new(unit, entries))
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct FacetRule {
+    /// The name of the facet, such as `os`.
+    pub name: String,
+    /// A regular expression, the value of the facet is its `value` group, or the whole match if
+    /// it has none.
+    pub pattern: String,
+}
+
+/// How the facets of the records, such as their version and OS, are extracted when they are
+/// written to the store.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct FacetOptions {
+    /// Rules taking precedence over the built-in ones.
+    pub rules: Option<Vec<FacetRule>>,
+    /// Whether the built-in rules for the `version`, `os`, `arch` and `runtime` facets are
+    /// used, defaults to `true`.
+    pub builtin: Option<bool>,
+    /// Added to the score of a match sharing every facet of the source, a share of it for a
+    /// match sharing some of them, from 0 to 1, defaults to 0.
+    pub boost: Option<f64>,
+}
+
+impl FacetOptions {
+    pub(crate) fn build(&self) -> Result<facet::FacetOptions> {
+        let rules = self
+            .rules
+            .iter()
+            .flatten()
+            .map(|rule| facet::FacetRule::new(&rule.name, &rule.pattern).map_err(to_napi_error))
+            .collect::<Result<Vec<_>>>()?;
+        let extractor = if self.builtin.unwrap_or(true) {
+            facet::FacetExtractor::builtin().with_rules(rules)
+        } else {
+            facet::FacetExtractor::new(rules)
+        };
+
+        Ok(facet::FacetOptions {
+            extractor,
+            boost: self.boost.unwrap_or(facet::DEFAULT_BOOST),
+        })
+    }
+}
+
+/// How the stack traces of the records are compared with the one of the source.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// How many frames from the top of a trace are fingerprinted and compared, defaults to 5.
+    pub depth: Option<u32>,
+    /// `signal` to combine the similarity of the traces with the one of the texts, or `bucket`
+    /// to score the records with the fingerprint of the source as exact matches, defaults to
+    /// `signal`.
+    pub matching: Option<String>,
+    /// The share of the score made up by the similarity of the traces for `signal`, from 0 to
+    /// 1, defaults to 0.3.
+    pub weight: Option<f64>,
+}
+
+impl TraceOptions {
+    pub(crate) fn build(&self) -> Result<trace::TraceOptions> {
+        let matching =
+            match trace::TraceMatching::parse(self.matching.as_deref().unwrap_or("signal"))
+                .map_err(to_napi_error)?
+            {
+                trace::TraceMatching::Signal { weight } => trace::TraceMatching::Signal {
+                    weight: self.weight.unwrap_or(weight),
+                },
+                trace::TraceMatching::Bucket => trace::TraceMatching::Bucket,
+            };
+
+        Ok(trace::TraceOptions {
+            depth: self
+                .depth
+                .map_or(trace::DEFAULT_DEPTH, |depth| depth as usize),
+            matching,
+        })
+    }
+}
+
+/// How the symbols mentioned by the records, such as `Deno.kill`, `ERR_INVALID_ARG_TYPE` or
+/// `SIGTERM`, are compared with the ones of the source.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct SymbolOptions {
+    /// The share of the score made up by the overlap of the symbols, from 0 to 1, defaults to
+    /// 0.25.
+    pub weight: Option<f64>,
+}
+
+impl SymbolOptions {
+    pub(crate) fn build(&self) -> Result<symbol::SymbolOptions> {
+        Ok(symbol::SymbolOptions {
+            weight: self.weight.unwrap_or(symbol::DEFAULT_WEIGHT),
+        })
+    }
+}
+
+/// How a store is set up, every option is off when left out.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct StoreOptions {
+    /// The threads the queries run on.
+    pub pool: Option<PoolOptions>,
+    /// How the embeddings of the records are indexed.
+    pub vectors: Option<VectorIndexOptions>,
+    /// How `findSimilarRecords()` compares each feature.
+    pub metrics: Option<FeatureMetrics>,
+    /// When given, the facets of the features of the records are extracted into the metadata the
+    /// queries see under `facets`, where they can be filtered on, and each match reports the ones
+    /// it shares with the source.
+    pub facets: Option<FacetOptions>,
+    /// When given, the stack traces of the features are fingerprinted into the metadata the
+    /// queries see under `trace`, and compared with the one of the source.
+    pub traces: Option<TraceOptions>,
+    /// When given, the symbols the features mention are extracted into the metadata the queries
+    /// see under `symbols`, and each match reports the ones it shares with the source.
+    pub symbols: Option<SymbolOptions>,
+}
+
+/// How `findSimilarRecords()` picks and scores the records.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct FindOptions {
+    /// When given, only the records whose metadata satisfy it are scored, see [Filter] for the
+    /// syntax.
+    pub filter: Option<Value>,
+    /// When given, the lexical matches are fused with the records whose embeddings are the
+    /// nearest to the one of the features, and each match reports its rank in both.
+    pub hybrid: Option<HybridOptions>,
 }
 
 #[napi]
+#[derive(Default)]
 pub struct IssueFeatureStore {
-    issue_features_map: Arc<RwLock<HashMap<String, IssueFeatures>>>,
+    inner: issue::IssueFeatureStore,
+    /// The handle keeping the shared store alive, if this instance is attached to one.
+    shared: Option<Shared<issue::IssueFeatureStore>>,
+}
+
+impl From<issue::IssueFeatureStore> for IssueFeatureStore {
+    fn from(inner: issue::IssueFeatureStore) -> Self {
+        IssueFeatureStore {
+            inner,
+            shared: None,
+        }
+    }
 }
 
 #[napi]
 impl IssueFeatureStore {
+    /// Creates a store holding the records, set up by `options`.
     #[napi(constructor)]
-    pub fn new(records: Option<Vec<IssueFeaturesRecord>>) -> Self {
-        let map: HashMap<String, IssueFeatures> = records.map_or_else(
-            || HashMap::new(),
-            |records| {
-                records
-                    .into_iter()
-                    .filter(|item| {
-                        !item.issue_id.is_empty()
-                            && (item.features.operation.is_some()
-                                || item.features.phenomenon.is_some()
-                                || item.features.expected_behavior.is_some()
-                                || item.features.actual_behavior.is_some())
-                    })
-                    .map(|map| (map.issue_id, map.features))
-                    .collect()
-            },
-        );
+    pub fn new(
+        records: Option<Vec<IssueFeaturesRecord>>,
+        options: Option<StoreOptions>,
+    ) -> Result<Self> {
+        let StoreOptions {
+            pool,
+            vectors,
+            metrics,
+            facets,
+            traces,
+            symbols,
+        } = options.unwrap_or_default();
+        let records = records
+            .unwrap_or_default()
+            .into_iter()
+            .map(issue::IssueFeaturesRecord::from)
+            .collect();
+        let mut inner = match pool {
+            Some(pool) => issue::IssueFeatureStore::new(records).with_pool(pool.build()?),
+            None => issue::IssueFeatureStore::new(records),
+        };
+        if let Some(vectors) = vectors {
+            inner = inner
+                .with_vector_index(vectors.build()?)
+                .map_err(to_napi_error)?;
+        }
+        if let Some(metrics) = metrics {
+            inner = inner.with_metrics(metrics.build()?);
+        }
+        if let Some(facets) = facets {
+            inner = inner.with_facets(facets.build()?).map_err(to_napi_error)?;
+        }
+        if let Some(traces) = traces {
+            inner = inner.with_traces(traces.build()?).map_err(to_napi_error)?;
+        }
+        if let Some(symbols) = symbols {
+            inner = inner
+                .with_symbols(symbols.build()?)
+                .map_err(to_napi_error)?;
+        }
+
+        Ok(inner.into())
+    }
+
+    /// Attaches to the process-global store named `name`, which is shared by all the worker
+    /// threads of the process. It's created empty on the first call, and dropped when the last
+    /// instance attached to it is disposed or garbage collected.
+    #[napi(factory)]
+    pub fn shared(name: String) -> Self {
+        let shared = issue::IssueFeatureStore::shared(&name);
 
         IssueFeatureStore {
-            issue_features_map: Arc::new(RwLock::new(map)),
+            inner: (*shared).clone(),
+            shared: Some(shared),
         }
     }
 
+    /// The name of the shared store this instance is attached to.
+    #[napi(getter)]
+    pub fn shared_name(&self) -> Option<String> {
+        self.shared.as_ref().map(|shared| shared.name().to_string())
+    }
+
+    /// Releases the records held by this instance, which becomes an empty, unshared store. The
+    /// records of a shared store are dropped once every instance attached to it is disposed.
+    #[napi]
+    pub fn dispose(&mut self) {
+        self.shared = None;
+        self.inner = issue::IssueFeatureStore::default();
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    #[napi(getter)]
+    pub fn generation(&self) -> i64 {
+        self.inner.generation() as i64
+    }
+
     #[napi]
     pub fn set_record(&self, record: IssueFeaturesRecord) -> Result<()> {
-        if record.issue_id.is_empty() {
-            return Err(Error::from_reason("issue_id must not be empty"));
-        } else if record.features.operation.is_none()
-            && record.features.phenomenon.is_none()
-            && record.features.expected_behavior.is_none()
-            && record.features.actual_behavior.is_none()
-        {
-            return Err(Error::from_reason("features must not be empty"));
-        }
+        self.inner.set_record(record.into()).map_err(to_napi_error)
+    }
 
-        match self.issue_features_map.write() {
-            Ok(mut map) => {
-                map.insert(record.issue_id, record.features);
-                Ok(())
-            }
-            Err(e) => Err(Error::from_reason(format!(
-                "Failed to add issue feature record: {}",
-                e
-            ))),
-        }
+    /// Inserts or replaces the records in a single write, none of them is written if any is
+    /// invalid.
+    #[napi]
+    pub fn set_records(&self, records: Vec<IssueFeaturesRecord>) -> Result<()> {
+        self.inner
+            .set_records(records.into_iter().map(Into::into).collect())
+            .map_err(to_napi_error)
     }
 
     #[napi]
     pub fn get_record(&self, issue_id: String) -> Result<Option<IssueFeaturesRecord>> {
-        match self.issue_features_map.read() {
-            Ok(map) => {
-                if let Some(feature) = map.get(&issue_id) {
-                    Ok(Some(IssueFeaturesRecord {
-                        issue_id,
-                        features: feature.clone(),
-                    }))
-                } else {
-                    Ok(None)
-                }
-            }
-            Err(e) => Err(Error::from_reason(format!(
-                "Failed to get issue feature record: {}",
-                e
-            ))),
-        }
+        Ok(self
+            .inner
+            .get_record(&issue_id)
+            .map(IssueFeaturesRecord::from))
     }
 
     #[napi]
     pub fn remove_record(&self, issue_id: String) -> Result<bool> {
-        match self.issue_features_map.write() {
-            Ok(mut map) => {
-                if map.remove(&issue_id).is_some() {
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            }
-            Err(e) => Err(Error::from_reason(format!(
-                "Failed to remove issue feature record: {}",
-                e
-            ))),
-        }
+        Ok(self.inner.remove_record(&issue_id))
+    }
+
+    /// Learns the lines or the runs of words shared by many records in the current snapshot,
+    /// such as the headers of a template. It isn't updated by later writes.
+    #[napi(ts_return_type = "Promise<Boilerplate>")]
+    pub fn learn_boilerplate(
+        &self,
+        env: Env,
+        options: Option<BoilerplateOptions>,
+    ) -> Result<JsObject> {
+        let task = AsyncLearnBoilerplate {
+            store: self.inner.clone(),
+            options: options.unwrap_or_default(),
+        };
+        similar_napi::spawn_task(env, self.inner.pool(), task, None)
     }
 
+    /// The boilerplate removed from the features before they are compared by
+    /// `findSimilarRecords()`, if any.
+    #[napi(getter)]
+    pub fn boilerplate(&self) -> Option<Boilerplate> {
+        self.inner.boilerplate().map(Boilerplate::from)
+    }
+
+    /// Removes `boilerplate` from the features of the records and the source before they are
+    /// compared by `findSimilarRecords()` of this instance, or stops removing it if `null`.
+    #[napi]
+    pub fn set_boilerplate(&mut self, boilerplate: Option<Boilerplate>) -> Result<()> {
+        let inner = self.inner.clone();
+        self.inner = match boilerplate {
+            Some(boilerplate) => inner.with_boilerplate(boilerplate.build()?),
+            None => inner.without_boilerplate(),
+        };
+        Ok(())
+    }
+
+    /// Finds the `top_n` records most similar to the given `features`, picked and scored as set
+    /// up by `options`.
     #[napi(ts_return_type = "Promise<Array<SimilarIssueFeaturesRecord>>")]
     pub fn find_similar_records(
         &self,
+        env: Env,
         features: IssueFeatures,
         top_n: Option<u32>,
-        signal: Option<AbortSignal>,
-    ) -> AsyncTask<AsyncFindSimilarRecords> {
-        AsyncTask::with_optional_signal(
-            AsyncFindSimilarRecords {
-                features,
-                issue_feature_map: self.issue_features_map.clone(),
-                top_n: top_n.unwrap_or(5),
-            },
-            signal,
-        )
+        #[napi(ts_arg_type = "AbortSignal | undefined | null")] signal: Option<JsObject>,
+        options: Option<FindOptions>,
+    ) -> Result<JsObject> {
+        let FindOptions { filter, hybrid } = options.unwrap_or_default();
+        let task = AsyncFindSimilarRecords {
+            features: features.into(),
+            store: self.inner.clone(),
+            top_n: top_n.unwrap_or(5),
+            // a `null` field of an object isn't omitted like a `null` argument
+            filter: filter.filter(|filter| !filter.is_null()),
+            hybrid,
+        };
+        similar_napi::spawn_task(env, self.inner.pool(), task, signal)
+    }
+
+    /// Finds the `top_n` records whose embeddings are the nearest to `embedding`, the score of a
+    /// match is the cosine similarity, the dot product or `1 / (1 + distance)` for `l2`. The
+    /// embeddings are indexed by the first query after a write.
+    #[napi(ts_return_type = "Promise<Array<SimilarIssueFeaturesRecord>>")]
+    pub fn find_nearest_records(
+        &self,
+        env: Env,
+        embedding: Embedding,
+        top_n: Option<u32>,
+        #[napi(ts_arg_type = "AbortSignal | undefined | null")] signal: Option<JsObject>,
+        filter: Option<Value>,
+    ) -> Result<JsObject> {
+        let task = AsyncFindNearestRecords {
+            embedding: embedding.0,
+            store: self.inner.clone(),
+            top_n: top_n.unwrap_or(5),
+            filter,
+        };
+        similar_napi::spawn_task(env, self.inner.pool(), task, signal)
     }
 }
 
 pub struct AsyncFindSimilarRecords {
-    features: IssueFeatures,
-    issue_feature_map: Arc<RwLock<HashMap<String, IssueFeatures>>>,
+    features: issue::IssueFeatures,
+    store: issue::IssueFeatureStore,
     top_n: u32,
+    filter: Option<Value>,
+    hybrid: Option<HybridOptions>,
 }
 
 #[napi]
@@ -162,10 +740,35 @@ impl Task for AsyncFindSimilarRecords {
     type JsValue = Vec<SimilarIssueFeaturesRecord>;
 
     fn compute(&mut self) -> Result<Self::Output> {
-        match self.issue_feature_map.read() {
-            Ok(map) => find_similar_records_in_parallel(&self.features, &map, self.top_n),
-            Err(e) => Err(Error::from_reason(format!("Failed to read posts: {}", e))),
-        }
+        let filter = self
+            .filter
+            .as_ref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(to_napi_error)?;
+
+        let matches = match &self.hybrid {
+            Some(hybrid) => self.store.find_similar_records_hybrid(
+                &self.features,
+                self.top_n as usize,
+                filter.as_ref(),
+                &hybrid.build()?,
+            ),
+            None => self.store.find_similar_records(
+                &self.features,
+                self.top_n as usize,
+                filter.as_ref(),
+            ),
+        };
+
+        matches
+            .map(|matches| {
+                matches
+                    .into_iter()
+                    .map(SimilarIssueFeaturesRecord::from)
+                    .collect()
+            })
+            .map_err(to_napi_error)
     }
 
     fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
@@ -173,83 +776,62 @@ impl Task for AsyncFindSimilarRecords {
     }
 }
 
-fn find_similar_records_in_parallel(
-    source: &IssueFeatures,
-    candidates: &HashMap<String, IssueFeatures>,
+pub struct AsyncLearnBoilerplate {
+    store: issue::IssueFeatureStore,
+    options: BoilerplateOptions,
+}
+
+#[napi]
+impl Task for AsyncLearnBoilerplate {
+    type Output = Boilerplate;
+    type JsValue = Boilerplate;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.store
+            .learn_boilerplate(&self.options.build()?)
+            .map(|boilerplate| Boilerplate::from(&boilerplate))
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct AsyncFindNearestRecords {
+    embedding: Vec<f32>,
+    store: issue::IssueFeatureStore,
     top_n: u32,
-) -> Result<Vec<SimilarIssueFeaturesRecord>> {
-    let weights = get_feature_weights(source)?;
-    let mut matches: Vec<SimilarIssueFeaturesRecord> = candidates
-        .par_iter()
-        .filter_map(|(issue_id, features)| {
-            let operation_score = {
-                if source.operation.is_some() && features.operation.is_some() {
-                    let operand1 = source.operation.as_ref().unwrap();
-                    let operand2 = features.operation.as_ref().unwrap();
-                    normalized_similarity(operand1.chars(), operand2.chars()) * weights.operation
-                } else {
-                    0.0
-                }
-            };
-            let phenomenon_score = {
-                if source.phenomenon.is_some() && features.phenomenon.is_some() {
-                    let operand1 = source.phenomenon.as_ref().unwrap();
-                    let operand2 = features.phenomenon.as_ref().unwrap();
-                    normalized_similarity(operand1.chars(), operand2.chars()) * weights.phenomenon
-                } else {
-                    0.0
-                }
-            };
-            let expected_behavior_score = {
-                if source.expected_behavior.is_some() && features.expected_behavior.is_some() {
-                    let operand1 = source.expected_behavior.as_ref().unwrap();
-                    let operand2 = features.expected_behavior.as_ref().unwrap();
-                    normalized_similarity(operand1.chars(), operand2.chars())
-                        * weights.expected_behavior
-                } else {
-                    0.0
-                }
-            };
-            let actual_behavior_score = {
-                if source.actual_behavior.is_some() && features.actual_behavior.is_some() {
-                    let operand1 = source.actual_behavior.as_ref().unwrap();
-                    let operand2 = features.actual_behavior.as_ref().unwrap();
-                    normalized_similarity(operand1.chars(), operand2.chars())
-                        * weights.actual_behavior
-                } else {
-                    0.0
-                }
-            };
-            let mut score = operation_score
-                + phenomenon_score
-                + expected_behavior_score
-                + actual_behavior_score;
-            score = score.clamp(0.0, 1.0);
-
-            if score > 0.5 {
-                Some(SimilarIssueFeaturesRecord {
-                    issue_id: issue_id.clone(),
-                    features: features.clone(),
-                    score,
-                })
-            } else {
-                None
-            }
-        })
-        .collect();
+    filter: Option<Value>,
+}
 
-    matches.sort_by(|a, b| {
-        let order = b.score.partial_cmp(&a.score);
-        order.unwrap_or(std::cmp::Ordering::Equal)
-    });
+#[napi]
+impl Task for AsyncFindNearestRecords {
+    type Output = Vec<SimilarIssueFeaturesRecord>;
+    type JsValue = Vec<SimilarIssueFeaturesRecord>;
 
-    let top_n_matches = if matches.len() <= top_n as usize {
-        matches
-    } else {
-        matches.into_iter().take(top_n as usize).collect()
-    };
+    fn compute(&mut self) -> Result<Self::Output> {
+        let filter = self
+            .filter
+            .as_ref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(to_napi_error)?;
 
-    Ok(top_n_matches)
+        self.store
+            .find_nearest_records(&self.embedding, self.top_n as usize, filter.as_ref())
+            .map(|matches| {
+                matches
+                    .into_iter()
+                    .map(SimilarIssueFeaturesRecord::from)
+                    .collect()
+            })
+            .map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
 }
 
 #[cfg(test)]
@@ -265,7 +847,9 @@ mod tests {
                 phenomenon: None,
                 expected_behavior: Some("The device is turned on".to_string()),
                 actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
             },
+            metadata: None,
         };
         let record2 = IssueFeaturesRecord {
             issue_id: "2".to_string(),
@@ -276,7 +860,9 @@ mod tests {
                 ),
                 expected_behavior: None,
                 actual_behavior: None,
+                embedding: None,
             },
+            metadata: None,
         };
         let records = vec![
             record1.clone(),
@@ -289,11 +875,13 @@ mod tests {
                     phenomenon: None,
                     expected_behavior: None,
                     actual_behavior: None,
+                    embedding: None,
                 },
+                metadata: None,
             },
         ];
 
-        let store = IssueFeatureStore::new(Some(records));
+        let store = IssueFeatureStore::new(Some(records), None).unwrap();
 
         assert_eq!(store.get_record("1".to_string()).unwrap(), Some(record1));
         assert_eq!(store.get_record("2".to_string()).unwrap(), Some(record2));
@@ -303,7 +891,7 @@ mod tests {
 
     #[test]
     fn test_issue_feature_store_set_record() {
-        let store = IssueFeatureStore::new(None);
+        let store = IssueFeatureStore::new(None, None).unwrap();
 
         let record = IssueFeaturesRecord {
             issue_id: "1".to_string(),
@@ -312,7 +900,9 @@ mod tests {
                 phenomenon: None,
                 expected_behavior: Some("The device is turned on".to_string()),
                 actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
             },
+            metadata: None,
         };
 
         store.set_record(record.clone()).unwrap();
@@ -329,7 +919,9 @@ mod tests {
                 phenomenon: None,
                 expected_behavior: Some("The device is turned on".to_string()),
                 actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
             },
+            metadata: None,
         };
         let record2 = IssueFeaturesRecord {
             issue_id: "2".to_string(),
@@ -340,62 +932,372 @@ mod tests {
                 ),
                 expected_behavior: None,
                 actual_behavior: None,
+                embedding: None,
             },
+            metadata: None,
         };
 
         let records = vec![record1.clone(), record2.clone()];
-        let store = IssueFeatureStore::new(Some(records));
+        let store = IssueFeatureStore::new(Some(records), None).unwrap();
 
         assert_eq!(store.get_record("1".to_string()).unwrap(), Some(record1));
         assert_eq!(store.get_record("2".to_string()).unwrap(), Some(record2));
 
-        assert_eq!(store.remove_record("1".to_string()).unwrap(), true);
+        assert!(store.remove_record("1".to_string()).unwrap());
         assert_eq!(store.get_record("1".to_string()).unwrap(), None);
-        assert_eq!(store.remove_record("3".to_string()).unwrap(), false);
+        assert!(!store.remove_record("3".to_string()).unwrap());
     }
 
     #[test]
-    fn test_find_similar_records_in_parallel() {
-        let record1 = IssueFeaturesRecord {
+    fn test_issue_feature_store_shared() {
+        let record = IssueFeaturesRecord {
             issue_id: "1".to_string(),
             features: IssueFeatures {
                 operation: Some("Turn on the switch".to_string()),
                 phenomenon: None,
-                expected_behavior: Some("The device is turned on".to_string()),
-                actual_behavior: Some("The device is not turned on".to_string()),
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: None,
             },
+            metadata: None,
         };
-        let record2 = IssueFeaturesRecord {
-            issue_id: "2".to_string(),
+
+        let mut store1 = IssueFeatureStore::shared("test_issue_feature_store_shared".to_string());
+        let store2 = IssueFeatureStore::shared("test_issue_feature_store_shared".to_string());
+        store1.set_record(record.clone()).unwrap();
+        assert_eq!(store2.get_record("1".to_string()).unwrap(), Some(record));
+        assert_eq!(
+            store2.shared_name().as_deref(),
+            Some("test_issue_feature_store_shared")
+        );
+
+        store1.dispose();
+        assert_eq!(store1.get_record("1".to_string()).unwrap(), None);
+        assert_eq!(store1.shared_name(), None);
+        assert_eq!(store2.shared.as_ref().unwrap().refs(), 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_pool() {
+        let store = IssueFeatureStore::new(
+            None,
+            Some(StoreOptions {
+                pool: Some(PoolOptions {
+                    threads: Some(0),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(
+            store.err().unwrap().reason,
+            "threads must be greater than 0"
+        );
+
+        let store = IssueFeatureStore::new(
+            None,
+            Some(StoreOptions {
+                pool: Some(PoolOptions {
+                    threads: Some(1),
+                    max_concurrent_queries: Some(1),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        store
+            .set_record(IssueFeaturesRecord {
+                issue_id: "1".to_string(),
+                features: IssueFeatures {
+                    operation: Some("Turn on the switch".to_string()),
+                    phenomenon: None,
+                    expected_behavior: None,
+                    actual_behavior: None,
+                    embedding: None,
+                },
+                metadata: None,
+            })
+            .unwrap();
+
+        let features = issue::IssueFeatures {
+            operation: Some("Turn on the switch".to_string()),
+            ..Default::default()
+        };
+        let matches = store
+            .inner
+            .find_similar_records(&features, 5, None)
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_metrics() {
+        let store = IssueFeatureStore::new(
+            None,
+            Some(StoreOptions {
+                metrics: Some(FeatureMetrics {
+                    phenomenon: Some("jaro".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(
+            store.err().unwrap().reason,
+            "Unknown metric 'jaro', it must be one of levenshtein, partial and alignment"
+        );
+
+        let metrics = FeatureMetrics {
+            phenomenon: Some("alignment".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            metrics.build().unwrap(),
+            issue::FeatureMetrics {
+                phenomenon: metric::Metric::Alignment,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_facets() {
+        let facets = FacetOptions {
+            rules: Some(vec![FacetRule {
+                name: "shell".to_string(),
+                pattern: r"(?i)\b(?:powershell|bash|zsh)\b".to_string(),
+            }]),
+            builtin: None,
+            boost: Some(0.1),
+        };
+        let store = IssueFeatureStore::new(
+            None,
+            Some(StoreOptions {
+                facets: Some(facets),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+
+        let record = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
             features: IssueFeatures {
-                operation: Some("Turn off the switch".to_string()),
-                phenomenon: Some(
-                    "The device remains turned on instead if being turned on".to_string(),
-                ),
+                operation: Some("Run the script in PowerShell on Windows 11".to_string()),
+                phenomenon: None,
+                expected_behavior: None,
+                actual_behavior: Some("It panics".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+        store.set_record(record.clone()).unwrap();
+        let metadata = store.get_record("1".to_string()).unwrap().unwrap().metadata;
+        assert_eq!(metadata, None);
+        let matches = store
+            .inner
+            .find_similar_records(&record.features.into(), 1, None)
+            .unwrap();
+        assert_eq!(
+            matches[0].metadata.as_ref().unwrap()["facets"],
+            serde_json::json!({ "os": "windows 11", "shell": "powershell" })
+        );
+
+        let facets = FacetOptions {
+            rules: Some(vec![FacetRule {
+                name: "shell".to_string(),
+                pattern: "(".to_string(),
+            }]),
+            ..Default::default()
+        };
+        assert!(facets.build().is_err());
+        let facets = FacetOptions {
+            boost: Some(1.5),
+            ..Default::default()
+        };
+        assert!(
+            IssueFeatureStore::new(
+                None,
+                Some(StoreOptions {
+                    facets: Some(facets),
+                    ..Default::default()
+                })
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_traces() {
+        let traces = TraceOptions {
+            depth: Some(3),
+            matching: Some("bucket".to_string()),
+            weight: None,
+        };
+        assert_eq!(
+            traces.build().unwrap(),
+            trace::TraceOptions {
+                depth: 3,
+                matching: trace::TraceMatching::Bucket,
+            }
+        );
+        let traces = TraceOptions {
+            weight: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(
+            traces.build().unwrap().matching,
+            trace::TraceMatching::Signal { weight: 0.5 }
+        );
+
+        let traces = TraceOptions {
+            matching: Some("exact".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            IssueFeatureStore::new(
+                None,
+                Some(StoreOptions {
+                    traces: Some(traces),
+                    ..Default::default()
+                })
+            )
+            .err()
+            .unwrap()
+            .reason,
+            "Unknown matching 'exact', it must be one of signal and bucket"
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_symbols() {
+        assert_eq!(
+            SymbolOptions::default().build().unwrap(),
+            symbol::SymbolOptions {
+                weight: symbol::DEFAULT_WEIGHT,
+            }
+        );
+
+        let symbols = SymbolOptions { weight: Some(1.5) };
+        assert_eq!(
+            IssueFeatureStore::new(
+                None,
+                Some(StoreOptions {
+                    symbols: Some(symbols),
+                    ..Default::default()
+                })
+            )
+            .err()
+            .unwrap()
+            .reason,
+            "weight must be a number from 0 to 1"
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_boilerplate() {
+        let record = |issue_id: &str, phenomenon: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: None,
+                phenomenon: Some(format!("Describe the problem below.\n{}", phenomenon)),
                 expected_behavior: None,
                 actual_behavior: None,
+                embedding: None,
             },
+            metadata: None,
         };
+        let mut store = IssueFeatureStore::new(
+            Some(vec![
+                record("1", "It panics"),
+                record("2", "The output is empty"),
+                record("3", "It hangs"),
+            ]),
+            None,
+        )
+        .unwrap();
+        let boilerplate = store
+            .inner
+            .learn_boilerplate(&BoilerplateOptions::default().build().unwrap())
+            .unwrap();
+        let boilerplate = Boilerplate::from(&boilerplate);
+        assert_eq!(
+            boilerplate,
+            Boilerplate {
+                unit: "line".to_string(),
+                n: None,
+                entries: vec![BoilerplateEntry {
+                    text: "describe the problem below.".to_string(),
+                    share: 1.0,
+                }],
+            }
+        );
 
-        let records = vec![record1.clone(), record2.clone()];
-        let store = IssueFeatureStore::new(Some(records));
+        store.set_boilerplate(Some(boilerplate.clone())).unwrap();
+        assert_eq!(store.boilerplate(), Some(boilerplate));
+        store.set_boilerplate(None).unwrap();
+        assert_eq!(store.boilerplate(), None);
 
-        let features = IssueFeatures {
-            operation: Some("Turn on the switch".to_string()),
-            phenomenon: None,
-            expected_behavior: Some("The device turns on".to_string()),
-            actual_behavior: Some("The device does not turn on".to_string()),
+        let options = BoilerplateOptions {
+            unit: Some("paragraph".to_string()),
+            ..Default::default()
         };
-        let matches = find_similar_records_in_parallel(
-            &features,
-            &store.issue_features_map.read().unwrap(),
-            5,
+        assert_eq!(
+            options.build().err().unwrap().reason,
+            "Unknown unit 'paragraph', it must be one of line and ngram"
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_vectors() {
+        let store = IssueFeatureStore::new(
+            None,
+            Some(StoreOptions {
+                vectors: Some(VectorIndexOptions {
+                    distance: Some("hamming".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(
+            store.err().unwrap().reason,
+            "Unknown distance 'hamming', it must be one of cosine, dot and l2"
+        );
+
+        let record = |issue_id: &str, embedding: Vec<f32>| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: None,
+                phenomenon: None,
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: Some(Embedding(embedding)),
+            },
+            metadata: None,
+        };
+        let records = vec![record("1", vec![1.0, 0.0]), record("2", vec![0.0, 1.0])];
+        let store = IssueFeatureStore::new(
+            Some(records),
+            Some(StoreOptions {
+                vectors: Some(VectorIndexOptions {
+                    distance: Some("l2".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
         )
         .unwrap();
 
+        let matches = store
+            .inner
+            .find_nearest_records(&[0.1, 0.9], 1, None)
+            .unwrap();
         assert_eq!(matches.len(), 1);
-        assert_eq!(matches[0].issue_id, "1");
-        assert_eq!(matches[0].features, record1.features);
-        assert!(matches[0].score > 0.8);
+        assert_eq!(matches[0].issue_id, "2");
+        assert_eq!(
+            store.get_record("2".to_string()).unwrap(),
+            Some(record("2", vec![0.0, 1.0]))
+        );
     }
 }
@@ -108,6 +108,35 @@ Deno.test("IssueFeatureStore#findSimilarRecords", async () => {
     ok(similarRecords[0].score > 0.8)
 })
 
+Deno.test("IssueFeatureStore#findSimilarRecords_filter", async () => {
+    const features = {
+        operation: "Turn on the switch",
+        expectedBehavior: "The device is turned on",
+        actualBehavior: "The device is not turned on",
+    }
+    const store = new IssueFeatureStore([
+        { issueId: "1", features, metadata: { component: "switch", state: "closed" } },
+        {
+            issueId: "2",
+            features,
+            metadata: { component: "switch", labels: ["bug"], state: "open" },
+        },
+        { issueId: "3", features },
+    ])
+
+    const similarRecords = await store.findSimilarRecords(features, {
+        filter: { component: "switch", state: { $ne: "closed" } },
+    })
+
+    deepStrictEqual(similarRecords.length, 1)
+    deepStrictEqual(similarRecords[0].issueId, "2")
+    deepStrictEqual(similarRecords[0].metadata, {
+        component: "switch",
+        labels: ["bug"],
+        state: "open",
+    })
+})
+
 Deno.test("IssueFeatureStore.fromDB_mysql", async () => {
     const store = await IssueFeatureStore.fromDB({
         url: Deno.env.get("MYSQL_URL") ?? "",
@@ -240,3 +269,20 @@ Deno.test(
         deepStrictEqual(store2.getRecord("3"), null)
     }),
 )
+
+Deno.test(
+    "IssueFeatureStore#loadJSONL",
+    func(async (defer) => {
+        const output = "./assets/issue_features_copy.jsonl"
+        const store1 = await IssueFeatureStore.loadCSV("./assets/issue_features.csv")
+        await store1.dumpJSONL(output)
+        defer(() => Deno.remove(output))
+
+        const store2 = new IssueFeatureStore()
+        const report = await store2.loadJSONL(output)
+        deepStrictEqual(report, { loaded: 2, errors: [] })
+        deepStrictEqual(store2.getRecord("1"), store1.getRecord("1"))
+        deepStrictEqual(store2.getRecord("2"), store1.getRecord("2"))
+        deepStrictEqual(store2.getRecord("3"), null)
+    }),
+)
@@ -0,0 +1,365 @@
+//! Finding the boilerplate shared by many documents of a corpus, such as the headers of an issue
+//! template.
+//!
+//! Two reports filed with the same template share its headers, e.g. `Version:` and `OS:`, which
+//! makes them look similar whatever they are about. The lines or the runs of words found in a
+//! large share of the documents are learned from the corpus, and removed from the texts before
+//! they are scored. The learned [Boilerplate] can be inspected and edited before it's used.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use rayon::iter::ParallelIterator;
+
+use crate::{Error, Result};
+
+/// The share of the documents a line or a run of words must appear in by default.
+pub const DEFAULT_MIN_SHARE: f64 = 0.3;
+
+/// How many documents a line or a run of words must appear in by default, so a small corpus
+/// doesn't turn everything into boilerplate.
+pub const DEFAULT_MIN_DOCUMENTS: usize = 3;
+
+/// What the boilerplate is made of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Unit {
+    /// Whole lines, such as the headers of a template.
+    #[default]
+    Line,
+    /// Runs of `n` words, which also catch the fixed part of a line followed by a value, such
+    /// as `Please provide the version`.
+    NGram { n: usize },
+}
+
+impl Unit {
+    /// Returns the unit named `name` with its default parameters, runs of 4 words for `ngram`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "line" => Ok(Unit::Line),
+            "ngram" => Ok(Unit::NGram { n: 4 }),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown unit '{}', it must be one of line and ngram",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoilerplateOptions {
+    pub unit: Unit,
+    /// The share of the documents a line or a run of words must appear in, defaults to
+    /// [DEFAULT_MIN_SHARE].
+    pub min_share: f64,
+    /// How many documents a line or a run of words must appear in, defaults to
+    /// [DEFAULT_MIN_DOCUMENTS].
+    pub min_documents: usize,
+}
+
+impl Default for BoilerplateOptions {
+    fn default() -> Self {
+        BoilerplateOptions {
+            unit: Unit::default(),
+            min_share: DEFAULT_MIN_SHARE,
+            min_documents: DEFAULT_MIN_DOCUMENTS,
+        }
+    }
+}
+
+impl BoilerplateOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.unit == (Unit::NGram { n: 0 }) {
+            Err(Error::InvalidArgument(
+                "n must be greater than 0".to_string(),
+            ))
+        } else if !(self.min_share > 0.0 && self.min_share <= 1.0) {
+            Err(Error::InvalidArgument(
+                "min_share must be greater than 0 and at most 1".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A line or a run of words of the boilerplate, with its whitespace collapsed and lowercased.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoilerplateEntry {
+    pub text: String,
+    /// The share of the documents it was found in, or 1 if it was added by hand.
+    pub share: f64,
+}
+
+/// The boilerplate of a corpus, removed from the texts before they are scored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Boilerplate {
+    unit: Unit,
+    entries: Vec<BoilerplateEntry>,
+    keys: HashSet<String>,
+}
+
+/// Collapses the whitespace of `text` and lowercases it, so the lines and the words differing
+/// in their spacing or case match.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the byte ranges of the words of `text`.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(begin)) => {
+                ranges.push(begin..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        ranges.push(begin..text.len());
+    }
+
+    ranges
+}
+
+/// Returns the runs of `n` words of `text`, each with the range of its words.
+fn ngrams(text: &str, n: usize) -> impl Iterator<Item = (String, Range<usize>)> + '_ {
+    let words = words(text);
+    let count = (words.len() + 1).saturating_sub(n);
+
+    (0..count).map(move |i| {
+        let key = words[i..i + n]
+            .iter()
+            .map(|range| text[range.clone()].to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (key, i..i + n)
+    })
+}
+
+/// Returns the distinct lines or runs of words of a document.
+fn keys(text: &str, unit: &Unit) -> HashSet<String> {
+    match *unit {
+        Unit::Line => text
+            .lines()
+            .map(normalize)
+            .filter(|line| !line.is_empty())
+            .collect(),
+        Unit::NGram { n } => ngrams(text, n).map(|(key, _)| key).collect(),
+    }
+}
+
+impl Boilerplate {
+    /// Creates the boilerplate from its entries, such as the ones of a learned boilerplate
+    /// after editing them. The texts are normalized, and the duplicates are dropped.
+    pub fn new(unit: Unit, entries: Vec<BoilerplateEntry>) -> Self {
+        let mut keys = HashSet::new();
+        let entries = entries
+            .into_iter()
+            .map(|entry| BoilerplateEntry {
+                text: normalize(&entry.text),
+                share: entry.share,
+            })
+            .filter(|entry| !entry.text.is_empty() && keys.insert(entry.text.clone()))
+            .collect();
+
+        Boilerplate {
+            unit,
+            entries,
+            keys,
+        }
+    }
+
+    /// Finds the lines or the runs of words appearing in at least `min_share` and
+    /// `min_documents` of the `documents`. The entries are ordered from the most frequent one.
+    pub fn learn<'a>(
+        options: &BoilerplateOptions,
+        documents: impl ParallelIterator<Item = &'a str>,
+    ) -> Result<Self> {
+        options.validate()?;
+
+        let (total, counts) = documents
+            .fold(
+                || (0, HashMap::<String, usize>::new()),
+                |(total, mut counts), document| {
+                    for key in keys(document, &options.unit) {
+                        *counts.entry(key).or_default() += 1;
+                    }
+                    (total + 1, counts)
+                },
+            )
+            .reduce(
+                || (0, HashMap::new()),
+                |(total, mut counts), (other_total, other_counts)| {
+                    for (key, count) in other_counts {
+                        *counts.entry(key).or_default() += count;
+                    }
+                    (total + other_total, counts)
+                },
+            );
+
+        let mut entries: Vec<BoilerplateEntry> = counts
+            .into_iter()
+            .filter(|(_, count)| {
+                *count >= options.min_documents && *count as f64 >= options.min_share * total as f64
+            })
+            .map(|(text, count)| BoilerplateEntry {
+                text,
+                share: count as f64 / total as f64,
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.share
+                .total_cmp(&a.share)
+                .then_with(|| a.text.cmp(&b.text))
+        });
+
+        Ok(Boilerplate::new(options.unit, entries))
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    pub fn entries(&self) -> &[BoilerplateEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes the boilerplate from `text`, the lines of the boilerplate or the words covered by
+    /// its runs. The text is borrowed as is if it has no boilerplate.
+    pub fn strip<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if self.is_empty() {
+            return Cow::Borrowed(text);
+        }
+
+        match self.unit {
+            Unit::Line => {
+                let boilerplate = |line: &str| self.keys.contains(&normalize(line));
+
+                if !text.lines().any(boilerplate) {
+                    return Cow::Borrowed(text);
+                }
+                Cow::Owned(
+                    text.split_inclusive('\n')
+                        .filter(|line| !boilerplate(line))
+                        .collect(),
+                )
+            }
+            Unit::NGram { n } => {
+                let words = words(text);
+                let mut covered = vec![false; words.len()];
+
+                for (key, range) in ngrams(text, n) {
+                    if self.keys.contains(&key) {
+                        covered[range].fill(true);
+                    }
+                }
+                if !covered.contains(&true) {
+                    return Cow::Borrowed(text);
+                }
+
+                // each kept word keeps the whitespace following it
+                let mut stripped = String::with_capacity(text.len());
+                for (i, range) in words.iter().enumerate() {
+                    if !covered[i] {
+                        let end = words.get(i + 1).map_or(text.len(), |next| next.start);
+                        stripped.push_str(&text[range.start..end]);
+                    }
+                }
+                Cow::Owned(stripped)
+            }
+        }
+    }
+
+    /// Whether `text` is made of boilerplate only, such as a chunk holding the headers of a
+    /// template.
+    pub fn covers(&self, text: &str) -> bool {
+        !text.trim().is_empty() && self.strip(text).trim().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::iter::IntoParallelIterator;
+
+    use super::*;
+
+    const REPORTS: [&str; 4] = [
+        "Version: Deno 2.3.3\nOS: Windows 11\n\nDeno.kill fails on windows.",
+        "Version: Deno 2.3.3\nOS:  windows 11\n\nThe fmt command hangs.",
+        "Version: Deno 2.3.2\nOS: Windows 11\n\nThe LSP crashes on startup.",
+        "Deno.serve leaks memory.",
+    ];
+
+    #[test]
+    fn test_boilerplate_lines() {
+        let boilerplate =
+            Boilerplate::learn(&BoilerplateOptions::default(), REPORTS.into_par_iter()).unwrap();
+        let entries: Vec<(&str, f64)> = boilerplate
+            .entries()
+            .iter()
+            .map(|entry| (entry.text.as_str(), entry.share))
+            .collect();
+
+        // the version differs in one report, which is below the minimum of documents
+        assert_eq!(entries, vec![("os: windows 11", 0.75)]);
+        assert_eq!(
+            boilerplate.strip(REPORTS[1]),
+            "Version: Deno 2.3.3\n\nThe fmt command hangs."
+        );
+        assert!(matches!(boilerplate.strip(REPORTS[3]), Cow::Borrowed(_)));
+
+        let mut entries = boilerplate.entries().to_vec();
+        entries.push(BoilerplateEntry {
+            text: "Version:  Deno 2.3.3".to_string(),
+            share: 1.0,
+        });
+        let edited = Boilerplate::new(Unit::Line, entries);
+        assert_eq!(edited.strip(REPORTS[0]), "\nDeno.kill fails on windows.");
+        assert!(edited.covers("Version: Deno 2.3.3\nOS: Windows 11"));
+        assert!(!edited.covers(REPORTS[0]));
+    }
+
+    #[test]
+    fn test_boilerplate_ngrams() {
+        let options = BoilerplateOptions {
+            unit: Unit::NGram { n: 2 },
+            ..Default::default()
+        };
+        let boilerplate = Boilerplate::learn(&options, REPORTS.into_par_iter()).unwrap();
+        let texts: Vec<&str> = boilerplate
+            .entries()
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect();
+
+        assert_eq!(texts, vec!["os: windows", "version: deno", "windows 11"]);
+        // each kept word keeps the whitespace following it
+        assert_eq!(
+            boilerplate.strip(REPORTS[2]),
+            "2.3.2\nThe LSP crashes on startup."
+        );
+
+        assert!(BoilerplateOptions {
+            min_share: 0.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(Unit::parse("paragraph").is_err());
+    }
+}
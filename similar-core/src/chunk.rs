@@ -0,0 +1,433 @@
+//! Scoring long texts by their chunks instead of as a whole.
+//!
+//! The edit distance of two long texts sharing a single paragraph is dominated by the rest of
+//! them, so the shared paragraph barely changes the score. Splitting the texts into chunks and
+//! comparing the chunks pairwise keeps it visible, and the best matching pair of chunks tells
+//! where the texts agree.
+
+use std::ops::Range;
+
+use rapidfuzz::distance::levenshtein::{normalized_similarity, BatchComparator};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use crate::{Error, Result};
+
+/// How a text is split into chunks, the chunks of the same text may overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// Runs of lines separated by blank lines, each chunk spans `overlap + 1` paragraphs and
+    /// shares `overlap` of them with the next one.
+    Paragraph { overlap: usize },
+    /// Sentences ended by a punctuation mark or a line break, each chunk spans `overlap + 1`
+    /// sentences and shares `overlap` of them with the next one.
+    Sentence { overlap: usize },
+    /// Windows of `size` characters, each sharing `overlap` characters with the next one.
+    Window { size: usize, overlap: usize },
+}
+
+impl Default for Split {
+    fn default() -> Self {
+        Split::Paragraph { overlap: 0 }
+    }
+}
+
+impl Split {
+    /// Returns the split named `name` with its default parameters, no overlap for `paragraph`,
+    /// one sentence for `sentence`, and 500 characters with an overlap of 100 for `window`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "paragraph" => Ok(Split::default()),
+            "sentence" => Ok(Split::Sentence { overlap: 1 }),
+            "window" => Ok(Split::Window {
+                size: 500,
+                overlap: 100,
+            }),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown split '{}', it must be one of paragraph, sentence and window",
+                name
+            ))),
+        }
+    }
+
+    /// Returns the byte ranges of the chunks of `text`, which are trimmed, a blank text has no
+    /// chunks.
+    pub fn split(&self, text: &str) -> Vec<Range<usize>> {
+        match *self {
+            Split::Paragraph { overlap } => group(paragraphs(text), overlap),
+            Split::Sentence { overlap } => group(sentences(text), overlap),
+            Split::Window { size, overlap } => windows(text, size, overlap),
+        }
+    }
+}
+
+/// Returns the range of `part` within `text` with the surrounding whitespace trimmed, `None` if
+/// it's blank.
+fn trimmed(text: &str, part: Range<usize>) -> Option<Range<usize>> {
+    let slice = &text[part.clone()];
+    let start = part.start + (slice.len() - slice.trim_start().len());
+    let end = part.start + slice.trim_end().len();
+
+    (start < end).then_some(start..end)
+}
+
+fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut offset = 0;
+    let mut blank = false;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            blank = true;
+        } else if blank {
+            ranges.extend(trimmed(text, start..offset));
+            start = offset;
+            blank = false;
+        }
+        offset += line.len();
+    }
+
+    ranges.extend(trimmed(text, start..text.len()));
+    ranges
+}
+
+fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = i + c.len_utf8();
+        let ends_sentence = match c {
+            '\n' | '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+
+        if ends_sentence {
+            ranges.extend(trimmed(text, start..end));
+            start = end;
+        }
+    }
+
+    ranges.extend(trimmed(text, start..text.len()));
+    ranges
+}
+
+/// Joins every `overlap + 1` consecutive units into a chunk, fewer units are joined into one.
+fn group(units: Vec<Range<usize>>, overlap: usize) -> Vec<Range<usize>> {
+    let size = (overlap + 1).min(units.len());
+
+    if size == 0 {
+        return vec![];
+    }
+
+    units
+        .windows(size)
+        .map(|window| window[0].start..window[size - 1].end)
+        .collect()
+}
+
+fn windows(text: &str, size: usize, overlap: usize) -> Vec<Range<usize>> {
+    let offsets: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([text.len()])
+        .collect();
+    let chars = offsets.len() - 1;
+    let mut ranges = vec![];
+    let mut start = 0;
+
+    loop {
+        let end = (start + size).min(chars);
+        ranges.extend(trimmed(text, offsets[start]..offsets[end]));
+
+        if end == chars {
+            break;
+        }
+        start += size - overlap;
+    }
+    ranges
+}
+
+/// How the similarities of the chunks of the source to their best matching chunks of a
+/// candidate are combined into the score of the candidate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Aggregation {
+    /// The similarity of the best matching pair of chunks.
+    #[default]
+    Max,
+    /// The mean of the `k` highest similarities.
+    MeanTopK { k: usize },
+    /// The share of the source, by the length of its chunks, whose chunks match a chunk of the
+    /// candidate with a similarity of at least `threshold`.
+    Coverage { threshold: f64 },
+}
+
+impl Aggregation {
+    /// Returns the aggregation named `name` with its default parameters, the top 3 for `mean`,
+    /// and a threshold of 0.8 for `coverage`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "max" => Ok(Aggregation::Max),
+            "mean" => Ok(Aggregation::MeanTopK { k: 3 }),
+            "coverage" => Ok(Aggregation::Coverage { threshold: 0.8 }),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown aggregation '{}', it must be one of max, mean and coverage",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChunkOptions {
+    pub split: Split,
+    pub aggregation: Aggregation,
+}
+
+impl ChunkOptions {
+    pub fn validate(&self) -> Result<()> {
+        match (self.split, self.aggregation) {
+            (Split::Window { size, overlap }, _) if overlap >= size => Err(Error::InvalidArgument(
+                "the size of a window must be greater than its overlap".to_string(),
+            )),
+            (_, Aggregation::MeanTopK { k: 0 }) => Err(Error::InvalidArgument(
+                "k must be a positive integer".to_string(),
+            )),
+            (_, Aggregation::Coverage { threshold }) if !(0.0..=1.0).contains(&threshold) => Err(
+                Error::InvalidArgument("the threshold must be between 0 and 1".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The chunks of a set of texts, split once for all the queries with the same [Split].
+#[derive(Debug, Clone)]
+pub struct ChunkIndex {
+    split: Split,
+    ranges: Vec<Vec<Range<usize>>>,
+}
+
+impl ChunkIndex {
+    pub fn build<'a>(split: Split, texts: impl IndexedParallelIterator<Item = &'a str>) -> Self {
+        ChunkIndex {
+            split,
+            ranges: texts.map(|text| split.split(text)).collect(),
+        }
+    }
+
+    pub fn split(&self) -> &Split {
+        &self.split
+    }
+
+    /// Returns a copy of the index with the chunks of `texts` added after the existing ones.
+    pub fn extend<'a>(&self, texts: impl Iterator<Item = &'a str>) -> Self {
+        let mut ranges = self.ranges.clone();
+        ranges.extend(texts.map(|text| self.split.split(text)));
+
+        ChunkIndex {
+            split: self.split,
+            ranges,
+        }
+    }
+
+    /// Returns the byte ranges of the chunks of the text at position `i`.
+    pub fn get(&self, i: usize) -> &[Range<usize>] {
+        self.ranges.get(i).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The best matching pair of chunks of a source and a candidate, given by their byte ranges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkMatch {
+    pub source: Range<usize>,
+    pub target: Range<usize>,
+    pub score: f64,
+}
+
+/// The chunks of a source, prepared to be compared with the chunks of many candidates.
+pub struct ChunkedText<'a> {
+    text: &'a str,
+    chunks: Vec<(Range<usize>, BatchComparator<char>)>,
+}
+
+impl<'a> ChunkedText<'a> {
+    pub fn new(text: &'a str, split: &Split) -> Self {
+        let chunks = split
+            .split(text)
+            .into_iter()
+            .map(|range| {
+                let comparator = BatchComparator::new(text[range.clone()].chars());
+                (range, comparator)
+            })
+            .collect();
+
+        ChunkedText { text, chunks }
+    }
+
+    /// Drops the chunks of the text not satisfying `keep`, such as the ones made of
+    /// boilerplate.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        let text = self.text;
+        self.chunks.retain(|(range, _)| keep(&text[range.clone()]));
+    }
+
+    /// Scores `target` by its chunks given by `ranges`, returning the score along with the best
+    /// matching pair of chunks. If either text has no chunks, the whole texts are compared and
+    /// there's no pair.
+    pub fn compare(
+        &self,
+        target: &str,
+        ranges: &[Range<usize>],
+        aggregation: &Aggregation,
+    ) -> (f64, Option<ChunkMatch>) {
+        if self.chunks.is_empty() || ranges.is_empty() {
+            return (
+                normalized_similarity(self.text.chars(), target.chars()),
+                None,
+            );
+        }
+
+        let mut best: Option<ChunkMatch> = None;
+        let mut scores: Vec<f64> = Vec::with_capacity(self.chunks.len());
+
+        for (source, comparator) in &self.chunks {
+            let mut chunk_best = 0.0;
+
+            for range in ranges {
+                let score = comparator.normalized_similarity(target[range.clone()].chars());
+
+                if score > chunk_best {
+                    chunk_best = score;
+                }
+                if best.as_ref().is_none_or(|best| score > best.score) {
+                    best = Some(ChunkMatch {
+                        source: source.clone(),
+                        target: range.clone(),
+                        score,
+                    });
+                }
+            }
+            scores.push(chunk_best);
+        }
+
+        let score = match *aggregation {
+            Aggregation::Max => scores.iter().copied().fold(0.0, f64::max),
+            Aggregation::MeanTopK { k } => {
+                scores.sort_by(|a, b| b.total_cmp(a));
+                let top = &scores[..k.min(scores.len())];
+                top.iter().sum::<f64>() / top.len() as f64
+            }
+            Aggregation::Coverage { threshold } => {
+                let (covered, total) = self.chunks.iter().zip(&scores).fold(
+                    (0, 0),
+                    |(covered, total), ((range, _), score)| {
+                        let len = self.text[range.clone()].chars().count();
+                        (
+                            covered + if *score >= threshold { len } else { 0 },
+                            total + len,
+                        )
+                    },
+                );
+                covered as f64 / total as f64
+            }
+        };
+
+        (score, best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Version: Deno 2.3.3. OS: Windows 11\n\n\
+        Sending a SIGINT on windows fails!  It works on linux.\n\n\n\
+        Same goes for SIGBREAK";
+
+    fn chunks(text: &str, split: Split) -> Vec<&str> {
+        split
+            .split(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            chunks(TEXT, Split::Paragraph { overlap: 0 }),
+            vec![
+                "Version: Deno 2.3.3. OS: Windows 11",
+                "Sending a SIGINT on windows fails!  It works on linux.",
+                "Same goes for SIGBREAK",
+            ]
+        );
+        assert_eq!(
+            chunks(TEXT, Split::Sentence { overlap: 1 }),
+            vec![
+                "Version: Deno 2.3.3. OS: Windows 11",
+                "OS: Windows 11\n\nSending a SIGINT on windows fails!",
+                "Sending a SIGINT on windows fails!  It works on linux.",
+                "It works on linux.\n\n\nSame goes for SIGBREAK",
+            ]
+        );
+        assert_eq!(
+            chunks(
+                "Deno.kill on windows",
+                Split::Window {
+                    size: 8,
+                    overlap: 2
+                }
+            ),
+            vec!["Deno.kil", "ill on w", "windows"]
+        );
+        assert_eq!(
+            chunks(
+                "Termux上的Deno",
+                Split::Window {
+                    size: 8,
+                    overlap: 0
+                }
+            ),
+            vec!["Termux上的", "Deno"]
+        );
+        assert!(Split::default().split(" \n\n ").is_empty());
+    }
+
+    #[test]
+    fn test_chunked_text_compare() {
+        let source = "Sending a SIGINT on windows fails!";
+        let chunked = ChunkedText::new(source, &Split::Sentence { overlap: 0 });
+        let ranges = Split::Paragraph { overlap: 0 }.split(TEXT);
+
+        let (score, best) = chunked.compare(TEXT, &ranges, &Aggregation::Max);
+        let best = best.unwrap();
+        assert_eq!(
+            &TEXT[best.target],
+            "Sending a SIGINT on windows fails!  It works on linux."
+        );
+        assert_eq!(score, best.score);
+        // the shared paragraph stands out from the rest of the text
+        assert!(score > normalized_similarity(source.chars(), TEXT.chars()));
+
+        let (coverage, _) =
+            chunked.compare(TEXT, &ranges, &Aggregation::Coverage { threshold: 0.9 });
+        assert_eq!(coverage, 0.0);
+
+        let (score, best) = chunked.compare("", &[], &Aggregation::MeanTopK { k: 3 });
+        assert_eq!((score, best), (0.0, None));
+
+        assert!(ChunkOptions {
+            split: Split::Window {
+                size: 10,
+                overlap: 10
+            },
+            aggregation: Aggregation::Max,
+        }
+        .validate()
+        .is_err());
+    }
+}
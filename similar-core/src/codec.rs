@@ -0,0 +1,122 @@
+//! The little-endian encoding of the indexes saved along with the posts in a snapshot file, see
+//! [crate::post::store::ext::binary].
+
+use crate::{Error, Result};
+
+/// Appends the values to a buffer.
+#[derive(Debug, Default)]
+pub(crate) struct Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn length(&mut self, len: usize) {
+        self.u64(len as u64);
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.length(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+}
+
+/// Reads the values written by a [Writer], failing with [Error::InvalidSnapshot] instead of
+/// reading past the end of the bytes.
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    /// What is being read, to tell which index of a snapshot is corrupted.
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8], what: &'static str) -> Self {
+        Reader { bytes, what }
+    }
+
+    pub fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidSnapshot(format!(
+            "Invalid snapshot file: bad {}: {}",
+            self.what, reason
+        ))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            return Err(self.invalid("truncated value"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a length, checking that at least `len * item_len` bytes follow it, so a corrupted
+    /// length never makes the caller allocate more than the size of the snapshot.
+    pub fn length(&mut self, item_len: usize) -> Result<usize> {
+        let len = usize::try_from(self.u64()?).map_err(|_| self.invalid("bad length"))?;
+
+        match len.checked_mul(item_len) {
+            Some(size) if size <= self.bytes.len() => Ok(len),
+            _ => Err(self.invalid("truncated value")),
+        }
+    }
+
+    pub fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn f32s(&mut self, len: usize) -> Result<Vec<f32>> {
+        let bytes = self.take(
+            len.checked_mul(4)
+                .ok_or_else(|| self.invalid("bad length"))?,
+        )?;
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    pub fn str(&mut self) -> Result<&'a str> {
+        let len = self.length(1)?;
+        let bytes = self.take(len)?;
+
+        std::str::from_utf8(bytes).map_err(|e| self.invalid(&e.to_string()))
+    }
+
+    /// Fails unless everything has been read.
+    pub fn finish(self) -> Result<()> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid("trailing bytes"))
+        }
+    }
+}
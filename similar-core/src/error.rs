@@ -0,0 +1,55 @@
+use std::fmt;
+
+/// The errors returned by this crate.
+///
+/// The variants wrapping another error carry a short context, such as `Cannot open CSV file`,
+/// which is printed in front of the cause.
+#[derive(Debug)]
+pub enum Error {
+    /// An argument is invalid, such as a source without text or an empty issue ID.
+    InvalidArgument(String),
+    /// A metadata filter expression is malformed.
+    InvalidFilter(String),
+    /// A snapshot file is corrupted or written by a newer version.
+    InvalidSnapshot(String),
+    /// A query is rejected because the store is running and queueing as many queries as it
+    /// allows, see [PoolOptions](crate::pool::PoolOptions).
+    Overloaded(String),
+    /// A query spawned with [QueryPool::spawn](crate::pool::QueryPool::spawn) panicked, with the
+    /// message of the panic.
+    Panicked(String),
+    Io(&'static str, std::io::Error),
+    Csv(&'static str, csv::Error),
+    Json(&'static str, serde_json::Error),
+    Database(&'static str, sqlx::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument(reason) => write!(f, "{}", reason),
+            Error::InvalidFilter(reason) => write!(f, "Invalid filter: {}", reason),
+            Error::InvalidSnapshot(reason) => write!(f, "{}", reason),
+            Error::Overloaded(reason) => write!(f, "{}", reason),
+            Error::Panicked(reason) => write!(f, "Panicked: {}", reason),
+            Error::Io(context, e) => write!(f, "{}: {}", context, e),
+            Error::Csv(context, e) => write!(f, "{}: {}", context, e),
+            Error::Json(context, e) => write!(f, "{}: {}", context, e),
+            Error::Database(context, e) => write!(f, "{}: {}", context, e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            Error::Csv(_, e) => Some(e),
+            Error::Json(_, e) => Some(e),
+            Error::Database(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
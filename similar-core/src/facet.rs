@@ -0,0 +1,408 @@
+//! Extracting the environment of a report, such as its version and OS, into facets.
+//!
+//! Bug reports carry structured facts like `Version: Deno 2.3.3` or `OS: Windows 11`. A
+//! [FacetExtractor] parses them with regex rules into named facets, which a store configured
+//! with [FacetOptions] writes into the metadata of the records or the posts its queries see
+//! under [FACETS_KEY], next to the facets supplied by the caller. They can then be filtered on,
+//! e.g. `{ "facets.os": "windows 11" }`, or `{ "facets.version": { "$gte": "deno 2.10" } }` as
+//! the filters compare the version facet by its numbers, see [compare_versions], and the matches
+//! sharing facets with the source report them and may get a boost.
+
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{Error, Result};
+
+/// The metadata key the facets are written to.
+pub const FACETS_KEY: &str = "facets";
+
+/// The field of the version facet, which the filters compare with [compare_versions].
+pub const VERSION_FIELD: &str = "facets.version";
+
+/// The facets of a text by name, the values are lowercased with their whitespace collapsed.
+pub type Facets = BTreeMap<String, String>;
+
+/// The built-in rules, a line like `OS: Windows 11` takes precedence over a mention found
+/// anywhere in the text.
+const BUILTIN_RULES: [(&str, &str); 7] = [
+    (
+        "version",
+        r"(?im)^[\s*>#-]*version\s*[:=]\s*(?P<value>[^\r\n]+)",
+    ),
+    (
+        "os",
+        r"(?im)^[\s*>#-]*(?:os|operating system|platform)\s*[:=]\s*(?P<value>[^\r\n]+)",
+    ),
+    (
+        "arch",
+        r"(?im)^[\s*>#-]*(?:arch|architecture)\s*[:=]\s*(?P<value>[^\r\n]+)",
+    ),
+    (
+        "runtime",
+        r"(?im)^[\s*>#-]*runtime\s*[:=]\s*(?P<value>[^\r\n]+)",
+    ),
+    (
+        "runtime",
+        r"(?i)\b(?P<value>(?:deno|node(?:\.js)?|bun|python|rustc|go)\s+v?\d+(?:\.\d+)+)",
+    ),
+    (
+        "os",
+        r"(?i)\b(?P<value>windows\s+(?:10|11)|macos(?:\s+\d+(?:\.\d+)*)?|ubuntu(?:\s+\d+\.\d+)?|debian|fedora|android|linux)\b",
+    ),
+    (
+        "arch",
+        r"(?i)\b(?P<value>x86_64|x86-64|amd64|aarch64|arm64|i686)\b",
+    ),
+];
+
+/// The boost of a match sharing every facet of the source by default.
+pub const DEFAULT_BOOST: f64 = 0.0;
+
+/// A named pattern, the value of the facet is its `value` group, or the whole match if it has
+/// none.
+#[derive(Debug, Clone)]
+pub struct FacetRule {
+    name: String,
+    pattern: Regex,
+}
+
+impl FacetRule {
+    pub fn new(name: &str, pattern: &str) -> Result<Self> {
+        if name.is_empty() {
+            return Err(Error::InvalidArgument(
+                "the name of a facet must not be empty".to_string(),
+            ));
+        }
+        let pattern = Regex::new(pattern).map_err(|e| {
+            Error::InvalidArgument(format!("invalid pattern of facet '{}': {}", name, e))
+        })?;
+
+        Ok(FacetRule {
+            name: name.to_string(),
+            pattern,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// Returns the normalized value of the first match in `text`, if any.
+    fn find(&self, text: &str) -> Option<String> {
+        let captures = self.pattern.captures(text)?;
+        let value = captures.name("value").or_else(|| captures.get(0))?.as_str();
+        let value = value
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches(['.', ',', ';'])
+            .to_lowercase();
+
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+impl PartialEq for FacetRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.pattern() == other.pattern()
+    }
+}
+
+/// Parses the facets of a text with its rules, the first rule matching a name sets its facet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FacetExtractor {
+    rules: Vec<FacetRule>,
+}
+
+impl Default for FacetExtractor {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl FacetExtractor {
+    /// Creates an extractor with the given rules only.
+    pub fn new(rules: Vec<FacetRule>) -> Self {
+        FacetExtractor { rules }
+    }
+
+    /// Creates an extractor with the built-in rules for the `version`, `os`, `arch` and
+    /// `runtime` facets.
+    pub fn builtin() -> Self {
+        let rules = BUILTIN_RULES
+            .iter()
+            .map(|(name, pattern)| FacetRule::new(name, pattern).expect("a valid built-in rule"))
+            .collect();
+
+        FacetExtractor { rules }
+    }
+
+    /// Adds `rules`, which take precedence over the existing ones.
+    pub fn with_rules(mut self, rules: Vec<FacetRule>) -> Self {
+        self.rules.splice(0..0, rules);
+        self
+    }
+
+    pub fn rules(&self) -> &[FacetRule] {
+        &self.rules
+    }
+
+    pub fn extract(&self, text: &str) -> Facets {
+        let mut facets = Facets::new();
+
+        for rule in &self.rules {
+            if facets.contains_key(&rule.name) {
+                continue;
+            }
+            if let Some(value) = rule.find(text) {
+                facets.insert(rule.name.clone(), value);
+            }
+        }
+        facets
+    }
+
+    /// Writes the facets of `text` into `metadata` under [FACETS_KEY], merged into the ones
+    /// already there, which take precedence as they are supplied by the caller. The metadata is
+    /// left as is if the text has no facets, or if [FACETS_KEY] holds something else than an
+    /// object.
+    pub fn annotate(&self, metadata: &mut Option<Map<String, Value>>, text: &str) {
+        let facets = self.extract(text);
+
+        if facets.is_empty() {
+            return;
+        }
+
+        let existing = metadata
+            .get_or_insert_with(Map::new)
+            .entry(FACETS_KEY)
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if let Value::Object(existing) = existing {
+            for (name, value) in facets {
+                existing.entry(name).or_insert(Value::String(value));
+            }
+        }
+    }
+}
+
+/// Reads the facets written by [FacetExtractor::annotate] back from `metadata`, the numbers and
+/// booleans supplied by the caller are read as their text.
+pub fn facets_of(metadata: Option<&Map<String, Value>>) -> Facets {
+    match metadata.and_then(|metadata| metadata.get(FACETS_KEY)) {
+        Some(Value::Object(facets)) => facets
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    Value::Number(_) | Value::Bool(_) => value.to_string(),
+                    _ => return None,
+                };
+                Some((name.clone(), value))
+            })
+            .collect(),
+        _ => Facets::new(),
+    }
+}
+
+/// Returns the names of the facets with the same value in both.
+pub fn shared_facets(source: &Facets, target: &Facets) -> Vec<String> {
+    source
+        .iter()
+        .filter(|(name, value)| target.get(*name) == Some(value))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct FacetOptions {
+    pub extractor: FacetExtractor,
+    /// Added to the score of a match sharing every facet of the source, a share of it for a
+    /// match sharing some of them. Defaults to [DEFAULT_BOOST], the facets are only reported.
+    pub boost: f64,
+}
+
+impl Default for FacetOptions {
+    fn default() -> Self {
+        FacetOptions {
+            extractor: FacetExtractor::default(),
+            boost: DEFAULT_BOOST,
+        }
+    }
+}
+
+impl FacetOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.boost.is_finite() && (0.0..=1.0).contains(&self.boost)) {
+            Err(Error::InvalidArgument(
+                "boost must be a number from 0 to 1".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns `score` with the boost of the facets `target` shares with `source`, capped to
+    /// 1, along with the names of the shared facets.
+    pub fn boost(&self, score: f64, source: &Facets, target: &Facets) -> (f64, Vec<String>) {
+        let shared = shared_facets(source, target);
+
+        if shared.is_empty() {
+            return (score, shared);
+        }
+        let boost = self.boost * shared.len() as f64 / source.len() as f64;
+        ((score + boost).min(1.0), shared)
+    }
+}
+
+/// Compares two versions, such as `deno 2.10.0` and `deno 2.9.1`, lexicographically but their
+/// runs of digits by value, falling back to the plain order when they only differ by leading
+/// zeros.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a, b);
+
+    loop {
+        let (Some(c), Some(d)) = (x.chars().next(), y.chars().next()) else {
+            return x.len().cmp(&y.len()).then_with(|| a.cmp(b));
+        };
+
+        if c.is_ascii_digit() && d.is_ascii_digit() {
+            let (m, rest_x) = split_digits(x);
+            let (n, rest_y) = split_digits(y);
+            let (m, n) = (m.trim_start_matches('0'), n.trim_start_matches('0'));
+            let order = m.len().cmp(&n.len()).then_with(|| m.cmp(n));
+
+            if order != Ordering::Equal {
+                return order;
+            }
+            (x, y) = (rest_x, rest_y);
+        } else if c != d {
+            return c.cmp(&d);
+        } else {
+            (x, y) = (&x[c.len_utf8()..], &y[d.len_utf8()..]);
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "
+Version: Deno 2.3.3
+OS: Windows 11
+
+Sending a SIGINT OS signal on windows like: Deno.kill(Deno.pid, 'SIGINT');
+";
+
+    #[test]
+    fn test_facet_extractor_builtin() {
+        let facets = FacetExtractor::builtin().extract(REPORT);
+        let expected: Facets = [
+            ("os", "windows 11"),
+            ("runtime", "deno 2.3.3"),
+            ("version", "deno 2.3.3"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        assert_eq!(facets, expected);
+
+        // the mentions are found when there is no header
+        let facets =
+            FacetExtractor::builtin().extract("Fails on macOS 14.2 (arm64) with node v20.1.0.");
+        assert_eq!(facets["os"], "macos 14.2");
+        assert_eq!(facets["arch"], "arm64");
+        assert_eq!(facets["runtime"], "node v20.1.0");
+        assert!(!facets.contains_key("version"));
+    }
+
+    #[test]
+    fn test_facet_extractor_rules() {
+        let extractor = FacetExtractor::builtin().with_rules(vec![
+            FacetRule::new("os", r"(?i)\bwin(?:dows)?\s*(?P<value>\d+)").unwrap(),
+            FacetRule::new("shell", r"(?i)\b(?:powershell|bash|zsh)\b").unwrap(),
+        ]);
+        let facets = extractor.extract("Using PowerShell on Win 10");
+        assert_eq!(facets["os"], "10");
+        assert_eq!(facets["shell"], "powershell");
+
+        let mut metadata = None;
+        extractor.annotate(&mut metadata, REPORT);
+        let facets = facets_of(metadata.as_ref());
+        assert_eq!(facets["os"], "11");
+        assert_eq!(facets_of(None), Facets::new());
+
+        let other = FacetExtractor::builtin().extract("Version: Deno 2.3.3\nOS: macOS");
+        let options = FacetOptions {
+            boost: 0.2,
+            ..Default::default()
+        };
+        let source = FacetExtractor::builtin().extract(REPORT);
+        let (score, shared) = options.boost(0.45, &source, &other);
+        assert_eq!(shared, vec!["runtime", "version"]);
+        assert!((score - (0.45 + 0.2 * 2.0 / 3.0)).abs() < 1e-9);
+
+        assert!(FacetRule::new("os", "(").is_err());
+        assert!(FacetOptions {
+            boost: 2.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_facet_extractor_annotate() {
+        let extractor = FacetExtractor::builtin();
+
+        // the facets supplied by the caller are kept and take precedence
+        let mut metadata = match serde_json::json!({ "facets": { "os": "wsl", "build": 1024 } }) {
+            Value::Object(map) => Some(map),
+            _ => unreachable!(),
+        };
+        extractor.annotate(&mut metadata, REPORT);
+        let facets = facets_of(metadata.as_ref());
+        assert_eq!(facets["os"], "wsl");
+        assert_eq!(facets["build"], "1024");
+        assert_eq!(facets["version"], "deno 2.3.3");
+
+        // something else than an object is left as is
+        let mut metadata = match serde_json::json!({ "facets": "none" }) {
+            Value::Object(map) => Some(map),
+            _ => unreachable!(),
+        };
+        extractor.annotate(&mut metadata, REPORT);
+        assert_eq!(metadata.unwrap()[FACETS_KEY], "none");
+
+        // the versions compare by their numbers
+        let mut metadata = None;
+        extractor.annotate(&mut metadata, "Version: 10.0.1");
+        let matches = |expr: Value| {
+            crate::filter::Filter::parse(&expr)
+                .unwrap()
+                .matches(metadata.as_ref())
+        };
+        assert!(matches(
+            serde_json::json!({ "facets.version": { "$gt": "9.0" } })
+        ));
+        assert!(!matches(
+            serde_json::json!({ "facets.version": { "$lt": "9.10" } })
+        ));
+        assert_eq!(
+            compare_versions("deno 2.10.0", "deno 2.9.1"),
+            Ordering::Greater
+        );
+        assert_eq!(compare_versions("1.02", "1.2"), Ordering::Less);
+    }
+}
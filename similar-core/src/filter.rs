@@ -0,0 +1,373 @@
+use std::cmp::Ordering;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    facet::{compare_versions, VERSION_FIELD},
+    Error, Result,
+};
+
+/// A metadata filter expression, compiled from its JSON form.
+///
+/// The JSON form follows a small MongoDB-like syntax:
+///
+/// - `{ "category": "rust" }` matches posts (or issue records) whose `category` equals `"rust"`,
+///   if the stored value is an array (e.g. `tags` or `labels`), it matches when any of the
+///   elements equals the given value.
+/// - `{ "tags": { "$in": ["deno", "node"] } }` matches set membership, `$nin` is the opposite.
+/// - `{ "createdAt": { "$gte": 1700000000000, "$lt": 1710000000000 } }` matches ranges, numbers
+///   are compared numerically and strings (such as ISO 8601 dates) lexicographically. The
+///   version facet is compared by its numbers instead, see [compare_versions], so
+///   `{ "facets.version": { "$gte": "10.0" } }` doesn't match `"9.1"`.
+/// - `{ "author": { "$ne": "bot" } }`, `{ "language": { "$exists": true } }`.
+/// - `{ "$and": [...] }`, `{ "$or": [...] }` and `{ "$not": {...} }` combine expressions.
+///
+/// Multiple keys in the same object are combined with `and`. A key with dots which isn't a field
+/// of the metadata is looked up in the nested objects, e.g. `{ "facets.os": "linux" }`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Field(String, Condition),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Eq(Value),
+    Ne(Value),
+    In(Vec<Value>),
+    Nin(Vec<Value>),
+    Gt(Value),
+    Gte(Value),
+    Lt(Value),
+    Lte(Value),
+    Exists(bool),
+}
+
+impl Filter {
+    pub fn parse(expr: &Value) -> Result<Filter> {
+        match expr {
+            Value::Object(obj) => parse_object(obj),
+            _ => Err(invalid("filter must be an object")),
+        }
+    }
+
+    pub fn matches(&self, metadata: Option<&Map<String, Value>>) -> bool {
+        match self {
+            Filter::And(filters) => filters.iter().all(|f| f.matches(metadata)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(metadata)),
+            Filter::Not(filter) => !filter.matches(metadata),
+            Filter::Field(field, condition) => {
+                let compare_strings = if field == VERSION_FIELD {
+                    compare_versions
+                } else {
+                    str::cmp
+                };
+                condition.matches(metadata.and_then(|map| lookup(map, field)), compare_strings)
+            }
+        }
+    }
+}
+
+impl Condition {
+    /// Tests the condition against the value, the strings of a range are compared with
+    /// `compare_strings`.
+    fn matches(&self, value: Option<&Value>, compare_strings: fn(&str, &str) -> Ordering) -> bool {
+        match self {
+            Condition::Exists(exists) => value.is_some_and(|v| !v.is_null()) == *exists,
+            Condition::Ne(expected) => !any_element(value, |v| values_equal(v, expected)),
+            Condition::Nin(list) => {
+                !any_element(value, |v| list.iter().any(|item| values_equal(v, item)))
+            }
+            Condition::Eq(expected) => any_element(value, |v| values_equal(v, expected)),
+            Condition::In(list) => {
+                any_element(value, |v| list.iter().any(|item| values_equal(v, item)))
+            }
+            Condition::Gt(bound) => any_element(value, |v| {
+                compare_values(v, bound, compare_strings).is_some_and(|o| o == Ordering::Greater)
+            }),
+            Condition::Gte(bound) => any_element(value, |v| {
+                compare_values(v, bound, compare_strings).is_some_and(|o| o != Ordering::Less)
+            }),
+            Condition::Lt(bound) => any_element(value, |v| {
+                compare_values(v, bound, compare_strings).is_some_and(|o| o == Ordering::Less)
+            }),
+            Condition::Lte(bound) => any_element(value, |v| {
+                compare_values(v, bound, compare_strings).is_some_and(|o| o != Ordering::Greater)
+            }),
+        }
+    }
+}
+
+/// Returns the value of `field` in `metadata`, or of its path of nested objects if it has dots
+/// and isn't a field itself.
+fn lookup<'a>(metadata: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+    if let Some(value) = metadata.get(field) {
+        return Some(value);
+    }
+
+    let mut path = field.split('.');
+    let mut value = metadata.get(path.next()?)?;
+    for key in path {
+        value = value.as_object()?.get(key)?;
+    }
+    Some(value)
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidFilter(reason.to_string())
+}
+
+fn parse_object(obj: &Map<String, Value>) -> Result<Filter> {
+    let mut filters = Vec::with_capacity(obj.len());
+
+    for (key, value) in obj.iter() {
+        let filter = match key.as_str() {
+            "$and" | "$or" => {
+                let items = match value {
+                    Value::Array(items) => items
+                        .iter()
+                        .map(Filter::parse)
+                        .collect::<Result<Vec<_>>>()?,
+                    _ => return Err(invalid(&format!("'{}' expects an array", key))),
+                };
+
+                if key == "$and" {
+                    Filter::And(items)
+                } else {
+                    Filter::Or(items)
+                }
+            }
+            "$not" => Filter::Not(Box::new(Filter::parse(value)?)),
+            _ if key.starts_with('$') => {
+                return Err(invalid(&format!("unknown operator '{}'", key)));
+            }
+            _ => parse_field(key, value)?,
+        };
+
+        filters.push(filter);
+    }
+
+    if filters.len() == 1 {
+        Ok(filters.pop().unwrap())
+    } else {
+        Ok(Filter::And(filters))
+    }
+}
+
+fn parse_field(field: &str, value: &Value) -> Result<Filter> {
+    let ops = match value {
+        Value::Object(ops) if !ops.is_empty() && ops.keys().all(|k| k.starts_with('$')) => ops,
+        _ => {
+            return Ok(Filter::Field(
+                field.to_string(),
+                Condition::Eq(value.clone()),
+            ))
+        }
+    };
+    let mut filters = Vec::with_capacity(ops.len());
+
+    for (op, operand) in ops.iter() {
+        let condition = match op.as_str() {
+            "$eq" => Condition::Eq(operand.clone()),
+            "$ne" => Condition::Ne(operand.clone()),
+            "$in" | "$nin" => {
+                let list = match operand {
+                    Value::Array(list) => list.clone(),
+                    _ => return Err(invalid(&format!("'{}' expects an array", op))),
+                };
+
+                if op == "$in" {
+                    Condition::In(list)
+                } else {
+                    Condition::Nin(list)
+                }
+            }
+            "$gt" | "$gte" | "$lt" | "$lte" => {
+                if !operand.is_number() && !operand.is_string() {
+                    return Err(invalid(&format!("'{}' expects a number or a string", op)));
+                }
+
+                match op.as_str() {
+                    "$gt" => Condition::Gt(operand.clone()),
+                    "$gte" => Condition::Gte(operand.clone()),
+                    "$lt" => Condition::Lt(operand.clone()),
+                    _ => Condition::Lte(operand.clone()),
+                }
+            }
+            "$exists" => match operand {
+                Value::Bool(exists) => Condition::Exists(*exists),
+                _ => return Err(invalid("'$exists' expects a boolean")),
+            },
+            _ => return Err(invalid(&format!("unknown operator '{}'", op))),
+        };
+
+        filters.push(Filter::Field(field.to_string(), condition));
+    }
+
+    if filters.len() == 1 {
+        Ok(filters.pop().unwrap())
+    } else {
+        Ok(Filter::And(filters))
+    }
+}
+
+/// Tests the predicate against the value, or against each element if the value is an array.
+fn any_element(value: Option<&Value>, predicate: impl Fn(&Value) -> bool) -> bool {
+    match value {
+        None => false,
+        Some(Value::Array(items)) => items.iter().any(predicate),
+        Some(value) => predicate(value),
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn compare_values(
+    a: &Value,
+    b: &Value,
+    compare_strings: fn(&str, &str) -> Ordering,
+) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(compare_strings(a, b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn metadata() -> Map<String, Value> {
+        match json!({
+            "category": "runtime",
+            "tags": ["deno", "windows", "signal"],
+            "author": "alice",
+            "language": "en",
+            "createdAt": 1748736000000i64,
+            "publishedAt": "2025-06-01T00:00:00Z",
+            "updatedAt": "2025-06-01T00:00:00.5Z",
+            "version": "10.0.0",
+        }) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    fn check(expr: Value) -> bool {
+        let meta = metadata();
+        Filter::parse(&expr).unwrap().matches(Some(&meta))
+    }
+
+    #[test]
+    fn test_filter_equality_and_membership() {
+        assert!(check(json!({ "category": "runtime" })));
+        assert!(!check(json!({ "category": "web" })));
+        assert!(check(json!({ "tags": "windows" })));
+        assert!(check(json!({ "tags": { "$in": ["linux", "signal"] } })));
+        assert!(!check(json!({ "tags": { "$nin": ["linux", "signal"] } })));
+        assert!(check(json!({ "author": { "$ne": "bob" } })));
+        assert!(check(json!({ "language": { "$exists": true } })));
+        assert!(check(json!({ "component": { "$exists": false } })));
+    }
+
+    #[test]
+    fn test_filter_ranges() {
+        assert!(check(
+            json!({ "createdAt": { "$gte": 1748736000000i64, "$lt": 1748736000001i64 } })
+        ));
+        assert!(!check(json!({ "createdAt": { "$gt": 1748736000000i64 } })));
+        assert!(check(
+            json!({ "publishedAt": { "$gte": "2025-01-01", "$lte": "2025-12-31" } })
+        ));
+        // the strings are compared lexicographically, digits included
+        assert!(check(
+            json!({ "updatedAt": { "$gt": "2025-06-01T00:00:00.25Z" } })
+        ));
+        assert!(check(json!({ "version": { "$lt": "9.0" } })));
+        // mismatched types never satisfy a range
+        assert!(!check(json!({ "publishedAt": { "$gte": 0 } })));
+    }
+
+    #[test]
+    fn test_filter_logical() {
+        assert!(check(
+            json!({ "$or": [{ "category": "web" }, { "author": "alice" }] })
+        ));
+        assert!(!check(
+            json!({ "$and": [{ "category": "web" }, { "author": "alice" }] })
+        ));
+        assert!(check(json!({ "$not": { "language": "zh" } })));
+        assert!(!check(json!({ "category": "runtime", "author": "bob" })));
+    }
+
+    #[test]
+    fn test_filter_nested_fields() {
+        let metadata = match json!({
+            "facets": { "os": "windows 11", "runtime": "deno 2.3.3", "version": "deno 2.10.0" },
+            "a.b": 1,
+        }) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        };
+        let check = |expr: Value| Filter::parse(&expr).unwrap().matches(Some(&metadata));
+
+        assert!(check(json!({ "facets.os": "windows 11" })));
+        assert!(!check(json!({ "facets.os": "linux" })));
+        assert!(check(json!({ "facets.arch": { "$exists": false } })));
+        assert!(check(json!({ "a.b": 1 })));
+        assert!(!check(json!({ "facets.os.name": { "$exists": true } })));
+        // the version facet is compared by its numbers, the other ones lexicographically
+        assert!(check(json!({ "facets.version": { "$gt": "deno 2.9" } })));
+        assert!(check(json!({ "facets.runtime": { "$gt": "deno 2.10" } })));
+    }
+
+    #[test]
+    fn test_filter_without_metadata() {
+        let filter = Filter::parse(&json!({ "category": "runtime" })).unwrap();
+        assert!(!filter.matches(None));
+
+        let filter = Filter::parse(&json!({ "$not": { "category": "runtime" } })).unwrap();
+        assert!(filter.matches(None));
+    }
+
+    #[test]
+    fn test_filter_invalid() {
+        assert!(Filter::parse(&json!("category")).is_err());
+        assert!(Filter::parse(&json!({ "$xor": [] })).is_err());
+        assert!(Filter::parse(&json!({ "tags": { "$in": "deno" } })).is_err());
+        assert!(Filter::parse(&json!({ "createdAt": { "$gt": true } })).is_err());
+        assert!(Filter::parse(&json!({ "tags": { "$contains": "deno" } })).is_err());
+    }
+
+    #[test]
+    fn test_filter_issue_metadata() {
+        let metadata = match json!({
+            "component": "runtime",
+            "labels": ["bug", "windows"],
+            "state": "open",
+        }) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        };
+        let check = |expr: Value| Filter::parse(&expr).unwrap().matches(Some(&metadata));
+
+        assert!(check(json!({ "component": "runtime", "state": "open" })));
+        assert!(check(json!({ "labels": "bug" })));
+        assert!(!check(json!({ "labels": { "$nin": ["windows"] } })));
+        assert!(check(
+            json!({ "$or": [{ "state": "closed" }, { "labels": { "$in": ["bug"] } }] })
+        ));
+        assert!(!check(json!({ "$not": { "state": "open" } })));
+        assert!(Filter::parse(&json!({ "state": { "$like": "open" } })).is_err());
+    }
+}
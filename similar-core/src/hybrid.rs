@@ -0,0 +1,229 @@
+//! Merging the matches of the lexical and the vector retrievers into one ranking.
+//!
+//! A hybrid query ranks the candidates twice, by the edit distance of their text and by the
+//! distance of their embeddings, then fuses the two rankings. A candidate found by only one of
+//! the retrievers is still ranked, it just misses the contribution of the other one.
+
+use std::{cmp::Ordering, collections::HashMap, hash::Hash};
+
+use crate::{Error, Result};
+
+/// The `k` of [Fusion::Rrf] used by default, as suggested by the paper introducing it.
+pub const DEFAULT_RRF_K: f64 = 60.0;
+
+/// How many matches each retriever returns before they are fused by default.
+pub const DEFAULT_CANDIDATES: usize = 50;
+
+/// How the rankings of the two retrievers are merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fusion {
+    /// Reciprocal rank fusion, a match scores `1 / (k + rank)` for each ranking it's in. Only
+    /// the ranks matter, so the scales of the scores don't need to agree.
+    Rrf { k: f64 },
+    /// A weighted sum of the scores, each normalized to `0 - 1` over its ranking.
+    Weighted { lexical: f64, vector: f64 },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Rrf { k: DEFAULT_RRF_K }
+    }
+}
+
+impl Fusion {
+    /// Returns the fusion named `name` with its default parameters, an equal weight for each
+    /// retriever in the case of `weighted`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "rrf" => Ok(Fusion::default()),
+            "weighted" => Ok(Fusion::Weighted {
+                lexical: 0.5,
+                vector: 0.5,
+            }),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown fusion '{}', it must be one of rrf and weighted",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HybridOptions {
+    pub fusion: Fusion,
+    /// How many matches each retriever returns before they are fused, defaults to
+    /// [DEFAULT_CANDIDATES]. Raised to the number of requested matches if lower.
+    pub candidates: Option<usize>,
+}
+
+impl HybridOptions {
+    pub fn validate(&self) -> Result<()> {
+        match self.fusion {
+            Fusion::Rrf { k } if !(k.is_finite() && k >= 0.0) => Err(Error::InvalidArgument(
+                "k must be a non-negative number".to_string(),
+            )),
+            Fusion::Weighted { lexical, vector }
+                if !(lexical.is_finite()
+                    && lexical >= 0.0
+                    && vector.is_finite()
+                    && vector >= 0.0)
+                    || lexical + vector == 0.0 =>
+            {
+                Err(Error::InvalidArgument(
+                    "the weights must be non-negative numbers and not both 0".to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How many matches each retriever returns for a query of `top_n` matches.
+    pub fn candidates(&self, top_n: usize) -> usize {
+        self.candidates.unwrap_or(DEFAULT_CANDIDATES).max(top_n)
+    }
+}
+
+/// A match of the fused ranking, the ranks count from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fused<K> {
+    pub key: K,
+    pub score: f64,
+    pub lexical_rank: Option<usize>,
+    pub vector_rank: Option<usize>,
+}
+
+/// Scales the scores of a ranking to `0 - 1`, a ranking of equal scores is scaled to 1.
+fn normalize<K>(ranking: &[(K, f64)]) -> Vec<f64> {
+    let min = ranking
+        .iter()
+        .map(|(_, score)| *score)
+        .fold(f64::INFINITY, f64::min);
+    let max = ranking
+        .iter()
+        .map(|(_, score)| *score)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    ranking
+        .iter()
+        .map(|(_, score)| {
+            if max > min {
+                (score - min) / (max - min)
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+/// Fuses two rankings of keys, each ordered from the best match, into the `top_n` best ones.
+/// Matches with the same fused score keep the lexical order, followed by the ones only found
+/// by the vector retriever.
+pub fn fuse<K: Clone + Eq + Hash>(
+    lexical: &[(K, f64)],
+    vector: &[(K, f64)],
+    fusion: &Fusion,
+    top_n: usize,
+) -> Vec<Fused<K>> {
+    let mut fused: Vec<Fused<K>> = Vec::with_capacity(lexical.len() + vector.len());
+    let mut positions: HashMap<&K, usize> = HashMap::new();
+    let lexical_scores = normalize(lexical);
+    let vector_scores = normalize(vector);
+    let contribution = |rank: usize, normalized: f64, weight: f64| match fusion {
+        Fusion::Rrf { k } => 1.0 / (k + rank as f64),
+        Fusion::Weighted { .. } => normalized * weight,
+    };
+    let (lexical_weight, vector_weight) = match fusion {
+        Fusion::Rrf { .. } => (1.0, 1.0),
+        Fusion::Weighted { lexical, vector } => (*lexical, *vector),
+    };
+
+    for (i, (key, _)) in lexical.iter().enumerate() {
+        positions.insert(key, fused.len());
+        fused.push(Fused {
+            key: key.clone(),
+            score: contribution(i + 1, lexical_scores[i], lexical_weight),
+            lexical_rank: Some(i + 1),
+            vector_rank: None,
+        });
+    }
+
+    for (i, (key, _)) in vector.iter().enumerate() {
+        let score = contribution(i + 1, vector_scores[i], vector_weight);
+
+        match positions.get(key) {
+            Some(&position) => {
+                fused[position].score += score;
+                fused[position].vector_rank = Some(i + 1);
+            }
+            None => {
+                positions.insert(key, fused.len());
+                fused.push(Fused {
+                    key: key.clone(),
+                    score,
+                    lexical_rank: None,
+                    vector_rank: Some(i + 1),
+                });
+            }
+        }
+    }
+
+    fused.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    fused.truncate(top_n);
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuse_rrf() {
+        let lexical = [("a", 0.9), ("b", 0.8), ("c", 0.6)];
+        let vector = [("c", 0.95), ("d", 0.9), ("a", 0.2)];
+        let fused = fuse(&lexical, &vector, &Fusion::Rrf { k: 0.0 }, 3);
+
+        // a: 1/1 + 1/3, c: 1/3 + 1/1, b: 1/2, d: 1/2
+        let keys: Vec<&str> = fused.iter().map(|m| m.key).collect();
+        assert_eq!(keys, vec!["a", "c", "b"]);
+        assert_eq!(fused[0].score, 1.0 + 1.0 / 3.0);
+        assert_eq!(
+            (fused[0].lexical_rank, fused[0].vector_rank),
+            (Some(1), Some(3))
+        );
+        assert_eq!(
+            (fused[1].lexical_rank, fused[1].vector_rank),
+            (Some(3), Some(1))
+        );
+        assert_eq!(
+            (fused[2].lexical_rank, fused[2].vector_rank),
+            (Some(2), None)
+        );
+    }
+
+    #[test]
+    fn test_fuse_weighted() {
+        let lexical = [("a", 0.9), ("b", 0.7)];
+        let vector = [("b", 0.5), ("c", 0.25), ("a", 0.0)];
+        let fusion = Fusion::Weighted {
+            lexical: 0.25,
+            vector: 0.75,
+        };
+        let fused = fuse(&lexical, &vector, &fusion, 5);
+
+        // the scores are normalized to 0 - 1 before being weighted
+        let scores: Vec<(&str, f64)> = fused.iter().map(|m| (m.key, m.score)).collect();
+        assert_eq!(scores, vec![("b", 0.75), ("c", 0.375), ("a", 0.25)]);
+        assert_eq!(fused[1].lexical_rank, None);
+
+        assert!(HybridOptions {
+            fusion: Fusion::Weighted {
+                lexical: 0.0,
+                vector: 0.0
+            },
+            candidates: None,
+        }
+        .validate()
+        .is_err());
+        assert!(Fusion::parse("max").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use std::{fs::File, path::Path};
+
+use csv::{Reader, Writer};
+
+use crate::{
+    issue::{
+        ext::RawIssueFeaturesRecord, IssueFeatureStore, IssueFeatures, IssueFeaturesEntry,
+        IssueFeaturesRecord, IssueFeaturesSnapshot,
+    },
+    Error, Result,
+};
+
+impl IssueFeatureStore {
+    /// Loads a store from a CSV file with the `issue_id`, `operation`, `phenomenon`,
+    /// `expected_behavior` and `actual_behavior` columns.
+    pub fn load_csv(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::Io("Cannot open CSV file", e))?;
+        let mut rdr = Reader::from_reader(file);
+        let mut records: Vec<IssueFeaturesRecord> = Vec::new();
+
+        for record in rdr.deserialize() {
+            let RawIssueFeaturesRecord {
+                issue_id,
+                operation,
+                phenomenon,
+                expected_behavior,
+                actual_behavior,
+            } = record.map_err(|e| Error::Csv("Cannot parse CSV record", e))?;
+
+            records.push(IssueFeaturesRecord {
+                issue_id,
+                features: IssueFeatures {
+                    operation,
+                    phenomenon,
+                    expected_behavior,
+                    actual_behavior,
+                    embedding: None,
+                },
+                metadata: None,
+            });
+        }
+
+        Ok(IssueFeatureStore::new(records))
+    }
+
+    /// Writes the records of the current snapshot to a CSV file, see
+    /// [IssueFeaturesSnapshot::dump_csv].
+    pub fn dump_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.snapshot().dump_csv(path)
+    }
+}
+
+impl IssueFeaturesSnapshot {
+    /// Writes the records to a CSV file which can be loaded with [IssueFeatureStore::load_csv],
+    /// the metadata is not written.
+    pub fn dump_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::create(path).map_err(|e| Error::Io("Cannot create CSV file", e))?;
+        let mut wtr = Writer::from_writer(file);
+
+        for (issue_id, entry) in self.map.iter() {
+            let IssueFeaturesEntry { features, .. } = entry.as_ref();
+            let raw_record = RawIssueFeaturesRecord {
+                issue_id: issue_id.clone(),
+                operation: features.operation.clone(),
+                phenomenon: features.phenomenon.clone(),
+                expected_behavior: features.expected_behavior.clone(),
+                actual_behavior: features.actual_behavior.clone(),
+            };
+
+            wtr.serialize(raw_record)
+                .map_err(|e| Error::Csv("Cannot serialize CSV record", e))?;
+        }
+
+        wtr.flush()
+            .map_err(|e| Error::Io("Cannot flush CSV writer", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use sqlx::{Connection, MySqlConnection, PgConnection, SqliteConnection};
+
+use crate::{
+    issue::{ext::RawIssueFeaturesRecord, IssueFeatureStore, IssueFeatures, IssueFeaturesRecord},
+    load::quote_table,
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct DbOptions {
+    /// The database URL, its scheme (`mysql:`, `postgres:` or `sqlite:`) selects the driver.
+    pub url: String,
+    /// The table to read the issues from, a plain or a dotted identifier, such as `issues` or
+    /// `public.issues`.
+    pub table: String,
+}
+
+impl IssueFeatureStore {
+    /// Loads a store from the `issue_id`, `operation`, `phenomenon`, `expected_behavior` and
+    /// `actual_behavior` columns of a table in a MySQL, PostgreSQL or SQLite database.
+    pub async fn from_db(options: &DbOptions) -> Result<Self> {
+        let DbOptions { url, table } = options;
+        let sql = format!(
+            "select issue_id, operation, phenomenon, expected_behavior, actual_behavior from {}",
+            quote_table(url, table)?
+        );
+
+        let result = async {
+            if url.starts_with("mysql:") {
+                let mut db = MySqlConnection::connect(url).await?;
+                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
+                prepare.fetch_all(&mut db).await
+            } else if url.starts_with("postgres:") {
+                let mut db = PgConnection::connect(url).await?;
+                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
+                prepare.fetch_all(&mut db).await
+            } else if url.starts_with("sqlite:") {
+                let mut db = SqliteConnection::connect(url).await?;
+                let prepare = sqlx::query_as::<_, RawIssueFeaturesRecord>(&sql);
+                prepare.fetch_all(&mut db).await
+            } else {
+                let i = url.find(':').unwrap_or(0);
+                let scheme = &url[..i];
+                Err(sqlx::Error::InvalidArgument(format!(
+                    "Unsupported database scheme '{}'",
+                    scheme
+                )))
+            }
+        }
+        .await;
+        let rows =
+            result.map_err(|e| Error::Database("Cannot fetch issue features from database", e))?;
+        let records = rows
+            .into_iter()
+            .map(
+                |RawIssueFeaturesRecord {
+                     issue_id,
+                     phenomenon,
+                     operation,
+                     expected_behavior,
+                     actual_behavior,
+                 }| IssueFeaturesRecord {
+                    issue_id,
+                    features: IssueFeatures {
+                        phenomenon,
+                        operation,
+                        expected_behavior,
+                        actual_behavior,
+                        embedding: None,
+                    },
+                    metadata: None,
+                },
+            )
+            .collect();
+
+        Ok(IssueFeatureStore::new(records))
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    issue::{
+        IssueFeatureStore, IssueFeatures, IssueFeaturesEntry, IssueFeaturesRecord,
+        IssueFeaturesSnapshot,
+    },
+    load::{
+        open_lines, parse_object, read_lines, take_embedding, take_metadata, take_string,
+        LoadReport,
+    },
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonlOptions {
+    /// The field holding the issue ID, defaults to `issue_id`.
+    pub issue_id_field: Option<String>,
+    /// The field holding the operation feature, defaults to `operation`.
+    pub operation_field: Option<String>,
+    /// The field holding the phenomenon feature, defaults to `phenomenon`.
+    pub phenomenon_field: Option<String>,
+    /// The field holding the expected behavior feature, defaults to `expected_behavior`.
+    pub expected_behavior_field: Option<String>,
+    /// The field holding the actual behavior feature, defaults to `actual_behavior`.
+    pub actual_behavior_field: Option<String>,
+    /// The field holding the metadata object of the record, defaults to `metadata`. Other unknown
+    /// fields are merged into the metadata as well, except `embedding`, which holds the
+    /// embedding of the record as an array of numbers.
+    pub metadata_field: Option<String>,
+    /// Whether the input is gzip-compressed, detected from the content when omitted. Only used
+    /// when loading.
+    pub gzip: Option<bool>,
+}
+
+const EMBEDDING_FIELD: &str = "embedding";
+
+struct Fields<'a> {
+    issue_id: &'a str,
+    operation: &'a str,
+    phenomenon: &'a str,
+    expected_behavior: &'a str,
+    actual_behavior: &'a str,
+    metadata: &'a str,
+}
+
+impl<'a> From<&'a JsonlOptions> for Fields<'a> {
+    fn from(options: &'a JsonlOptions) -> Self {
+        Fields {
+            issue_id: options.issue_id_field.as_deref().unwrap_or("issue_id"),
+            operation: options.operation_field.as_deref().unwrap_or("operation"),
+            phenomenon: options.phenomenon_field.as_deref().unwrap_or("phenomenon"),
+            expected_behavior: options
+                .expected_behavior_field
+                .as_deref()
+                .unwrap_or("expected_behavior"),
+            actual_behavior: options
+                .actual_behavior_field
+                .as_deref()
+                .unwrap_or("actual_behavior"),
+            metadata: options.metadata_field.as_deref().unwrap_or("metadata"),
+        }
+    }
+}
+
+fn parse_record(line: &str, fields: &Fields) -> std::result::Result<IssueFeaturesRecord, String> {
+    let mut obj = parse_object(line)?;
+    let issue_id = take_string(&mut obj, fields.issue_id)?
+        .filter(|issue_id| !issue_id.is_empty())
+        .ok_or_else(|| format!("field '{}' is missing", fields.issue_id))?;
+    let features = IssueFeatures {
+        operation: take_string(&mut obj, fields.operation)?,
+        phenomenon: take_string(&mut obj, fields.phenomenon)?,
+        expected_behavior: take_string(&mut obj, fields.expected_behavior)?,
+        actual_behavior: take_string(&mut obj, fields.actual_behavior)?,
+        embedding: take_embedding(&mut obj, EMBEDDING_FIELD)?,
+    };
+
+    if features.is_empty() {
+        return Err("features must not be empty".to_string());
+    }
+
+    Ok(IssueFeaturesRecord {
+        issue_id,
+        features,
+        metadata: take_metadata(obj, fields.metadata)?,
+    })
+}
+
+impl IssueFeatureStore {
+    /// Adds the records in a JSON Lines file to the store, which is streamed line by line.
+    /// Records with an existing issue ID replace the stored ones, malformed lines are skipped and
+    /// reported with their line numbers.
+    pub fn load_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<LoadReport> {
+        let fields = Fields::from(options);
+        let mut reader = open_lines(path.as_ref(), options.gzip)?;
+        let (entries, errors) = read_lines(&mut reader, |line| {
+            let record = parse_record(line, &fields)?;
+            let entry = IssueFeaturesEntry {
+                features: record.features,
+                metadata: record.metadata,
+            };
+
+            Ok((record.issue_id, Arc::new(entry)))
+        })?;
+        let loaded = entries.len();
+
+        if !entries.is_empty() {
+            self.issue_features_map.rcu(|current| {
+                let mut map = current.map.clone();
+                map.extend(entries.iter().cloned());
+                IssueFeaturesSnapshot {
+                    generation: current.generation + 1,
+                    map,
+                    ..Default::default()
+                }
+            });
+        }
+
+        Ok(LoadReport { loaded, errors })
+    }
+
+    /// Writes the records of the current snapshot to a JSON Lines file, see
+    /// [IssueFeaturesSnapshot::dump_jsonl].
+    pub fn dump_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<()> {
+        self.snapshot().dump_jsonl(path, options)
+    }
+}
+
+impl IssueFeaturesSnapshot {
+    /// Writes the records to a JSON Lines file, using the same field options as
+    /// [IssueFeatureStore::load_jsonl].
+    pub fn dump_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<()> {
+        let fields = Fields::from(options);
+        let file = File::create(path).map_err(|e| Error::Io("Cannot create JSONL file", e))?;
+        let mut wtr = BufWriter::new(file);
+        let write_error = |e: std::io::Error| Error::Io("Cannot write JSONL record", e);
+
+        for (issue_id, entry) in self.map.iter() {
+            let IssueFeaturesEntry { features, metadata } = entry.as_ref();
+            let mut obj = Map::new();
+            let mut insert = |field: &str, value: &Option<String>| {
+                if let Some(value) = value {
+                    obj.insert(field.to_string(), Value::String(value.clone()));
+                }
+            };
+
+            insert(fields.issue_id, &Some(issue_id.clone()));
+            insert(fields.operation, &features.operation);
+            insert(fields.phenomenon, &features.phenomenon);
+            insert(fields.expected_behavior, &features.expected_behavior);
+            insert(fields.actual_behavior, &features.actual_behavior);
+            if let Some(embedding) = &features.embedding {
+                obj.insert(EMBEDDING_FIELD.to_string(), Value::from(embedding.clone()));
+            }
+            if let Some(metadata) = metadata {
+                obj.insert(fields.metadata.to_string(), Value::Object(metadata.clone()));
+            }
+
+            serde_json::to_writer(&mut wtr, &obj)
+                .map_err(|e| Error::Json("Cannot serialize JSONL record", e))?;
+            wtr.write_all(b"\n").map_err(write_error)?;
+        }
+
+        wtr.flush().map_err(write_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+
+    use super::*;
+
+    const INPUT: &str = r#"{"id":"1","operation":"run deno","phenomenon":"crash","metadata":{"labels":["bug"]},"state":"open"}
+{"id":"2"}
+
+{"id":"3","operation":"install deno","actual_behavior":"hangs"}
+{"id":"4","operation":3}
+"#;
+
+    fn options() -> JsonlOptions {
+        JsonlOptions {
+            issue_id_field: Some("id".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_issue_feature_store_load_jsonl_with_traces() {
+        use crate::trace::{StackTrace, TraceMatching, TraceOptions};
+
+        let trace = |home: &str| {
+            format!(
+                "Traceback (most recent call last):\n  File \"{}/app/sync.py\", line 6, in pull\n    \
+                 int(value)\nValueError: invalid literal for int() with base 10: 'abc'",
+                home
+            )
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-issues-load-traces.jsonl");
+        let line = json!({ "issue_id": "1", "operation": "Sync the calendar", "actual_behavior": trace("/srv") });
+        fs::write(&path, format!("{}\n", line)).unwrap();
+
+        // the loaded records are annotated in the queries like the ones written afterwards
+        let store = IssueFeatureStore::new(vec![])
+            .with_traces(TraceOptions {
+                matching: TraceMatching::Bucket,
+                ..Default::default()
+            })
+            .unwrap();
+        let report = store.load_jsonl(&path, &JsonlOptions::default()).unwrap();
+        assert_eq!(report.loaded, 1);
+
+        assert_eq!(store.get_record("1").unwrap().metadata, None);
+        let features = IssueFeatures {
+            operation: Some("Import a CSV file".to_string()),
+            actual_behavior: Some(trace("/home/bob")),
+            ..Default::default()
+        };
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].score, 1.0);
+        let (_, fingerprint) = StackTrace::from_metadata(matches[0].metadata.as_ref()).unwrap();
+        assert_eq!(matches[0].fingerprint, Some(fingerprint));
+    }
+
+    fn check_loaded(store: &IssueFeatureStore, report: &LoadReport) {
+        assert_eq!(report.loaded, 2);
+        assert_eq!(
+            report.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![2, 5]
+        );
+
+        let record = store.get_record("1").unwrap();
+        assert_eq!(record.features.operation.as_deref(), Some("run deno"));
+        assert_eq!(
+            record.metadata,
+            json!({ "labels": ["bug"], "state": "open" })
+                .as_object()
+                .cloned()
+        );
+
+        let record = store.get_record("3").unwrap();
+        assert_eq!(record.features.actual_behavior.as_deref(), Some("hangs"));
+        assert_eq!(record.metadata, None);
+    }
+
+    #[test]
+    fn test_issue_feature_store_load_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("issue-mgr-load.jsonl");
+        fs::write(&path, INPUT).unwrap();
+
+        let store = IssueFeatureStore::new(vec![]);
+        let report = store.load_jsonl(&path, &options()).unwrap();
+
+        check_loaded(&store, &report);
+        assert_eq!(store.generation(), 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_load_jsonl_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("issue-mgr-load.jsonl.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let store = IssueFeatureStore::new(vec![]);
+        let report = store.load_jsonl(&path, &options()).unwrap();
+
+        check_loaded(&store, &report);
+    }
+
+    #[test]
+    fn test_issue_feature_store_dump_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("issue-mgr-dump-input.jsonl");
+        let output = dir.path().join("issue-mgr-dump-output.jsonl");
+        fs::write(&input, INPUT).unwrap();
+
+        let store1 = IssueFeatureStore::new(vec![]);
+        store1.load_jsonl(&input, &options()).unwrap();
+        store1
+            .dump_jsonl(&output, &JsonlOptions::default())
+            .unwrap();
+
+        let store2 = IssueFeatureStore::new(vec![]);
+        let report = store2
+            .load_jsonl(&output, &JsonlOptions::default())
+            .unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert!(report.errors.is_empty());
+        assert_eq!(store1.snapshot().map, store2.snapshot().map);
+    }
+}
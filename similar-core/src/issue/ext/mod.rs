@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+pub mod csv;
+pub mod db;
+pub mod jsonl;
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct RawIssueFeaturesRecord {
+    pub issue_id: String,
+    pub operation: Option<String>,
+    pub phenomenon: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub actual_behavior: Option<String>,
+}
@@ -0,0 +1,1752 @@
+//! Finding similar issues by the edit distance of their features.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+use arc_swap::ArcSwap;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde_json::{Map, Value};
+
+use crate::{
+    boilerplate::{Boilerplate, BoilerplateOptions},
+    facet::{facets_of, FacetExtractor, FacetOptions, Facets},
+    filter::Filter,
+    hybrid::{fuse, Fusion, HybridOptions},
+    pool::QueryPool,
+    shared::{Registry, Shared},
+    symbol::{extract, write_symbols, SymbolIndex, SymbolOptions, Symbols},
+    trace::{StackTrace, TraceOptions},
+    vector::{Hnsw, HnswOptions},
+    Error, Result,
+};
+
+mod ext;
+pub mod util;
+
+pub use ext::{db::DbOptions, jsonl::JsonlOptions};
+pub use util::{get_feature_weights, FeatureMetrics, FeatureWeights};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IssueFeatures {
+    pub operation: Option<String>,
+    pub phenomenon: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub actual_behavior: Option<String>,
+    /// A vector computed upstream from the issue, such as a sentence embedding, which is indexed
+    /// for [IssueFeatureStore::find_nearest_records].
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl IssueFeatures {
+    pub fn is_empty(&self) -> bool {
+        self.operation.is_none()
+            && self.phenomenon.is_none()
+            && self.expected_behavior.is_none()
+            && self.actual_behavior.is_none()
+            && self.embedding.is_none()
+    }
+
+    /// Joins the features into a single text, one feature per line.
+    fn text(&self) -> String {
+        [
+            &self.operation,
+            &self.phenomenon,
+            &self.expected_behavior,
+            &self.actual_behavior,
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueFeaturesRecord {
+    pub issue_id: String,
+    pub features: IssueFeatures,
+    /// Structured attributes of the issue, such as `component`, `labels` and `state`, which can be
+    /// used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarIssueFeaturesRecord {
+    pub issue_id: String,
+    pub features: IssueFeatures,
+    pub metadata: Option<Map<String, Value>>,
+    /// Similarity score `0 - 1`, higher is more similar. The fused score in a hybrid query.
+    pub score: f64,
+    /// The generation of the store snapshot the query was run against.
+    pub generation: u64,
+    /// The rank of the record among the lexical matches, counting from 1. Only set by hybrid
+    /// queries, see [crate::hybrid].
+    pub lexical_rank: Option<usize>,
+    /// The rank of the record among the nearest embeddings, counting from 1. Only set by hybrid
+    /// queries.
+    pub vector_rank: Option<usize>,
+    /// The names of the facets the record shares with the source, only set when the store
+    /// extracts facets, see [IssueFeatureStore::with_facets].
+    pub shared_facets: Vec<String>,
+    /// The fingerprint of the stack trace of the record, only set when the store compares
+    /// traces, see [IssueFeatureStore::with_traces].
+    pub fingerprint: Option<String>,
+    /// The symbols the record shares with the source, only set when the store compares
+    /// symbols, see [IssueFeatureStore::with_symbols].
+    pub shared_symbols: Vec<String>,
+}
+
+/// The value stored in the issue features map, keyed by issue ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueFeaturesEntry {
+    pub features: IssueFeatures,
+    pub metadata: Option<Map<String, Value>>,
+}
+
+/// An immutable generation of the records held by an [IssueFeatureStore].
+///
+/// Readers grab the current snapshot without blocking, writers copy the map, apply their change
+/// and publish the new snapshot atomically. Entries are shared between generations, so a copy
+/// only duplicates the keys and pointers.
+#[derive(Debug, Default)]
+pub struct IssueFeaturesSnapshot {
+    pub generation: u64,
+    pub map: HashMap<String, Arc<IssueFeaturesEntry>>,
+    /// The index over the embeddings of the records, built by the first nearest neighbor query.
+    vectors: OnceLock<Arc<VectorIndex>>,
+    /// The symbols mentioned by the records, extracted by the first query comparing symbols.
+    symbols: OnceLock<Arc<RecordSymbols>>,
+    /// The metadata of the records with their facets, stack traces and symbols, written by the
+    /// first query of a handle extracting any.
+    annotations: OnceLock<Arc<Annotations>>,
+}
+
+/// The symbols mentioned by the records of a snapshot by issue ID, extracted from their
+/// features, and how many records mention each of them.
+#[derive(Debug, Default)]
+pub struct RecordSymbols {
+    symbols: HashMap<String, Symbols>,
+    index: SymbolIndex,
+}
+
+impl RecordSymbols {
+    /// The symbols mentioned by the record `issue_id`.
+    pub fn get(&self, issue_id: &str) -> Option<&Symbols> {
+        self.symbols.get(issue_id)
+    }
+
+    /// How many records mention each symbol.
+    pub fn index(&self) -> &SymbolIndex {
+        &self.index
+    }
+}
+
+/// What the records are annotated with, given by the [ScoringOptions] of a handle.
+#[derive(Debug, Clone, PartialEq)]
+struct AnnotationKey {
+    facets: Option<FacetExtractor>,
+    /// The depth of the traces.
+    traces: Option<usize>,
+    symbols: bool,
+}
+
+impl AnnotationKey {
+    /// Returns `None` if `scoring` annotates nothing.
+    fn new(scoring: &ScoringOptions) -> Option<Self> {
+        let key = AnnotationKey {
+            facets: scoring.facets.map(|facets| facets.extractor.clone()),
+            traces: scoring.traces.map(|traces| traces.depth),
+            symbols: scoring.symbols.is_some(),
+        };
+
+        (key.facets.is_some() || key.traces.is_some() || key.symbols).then_some(key)
+    }
+}
+
+/// The metadata of the records of a snapshot by issue ID, with their facets, stack traces and
+/// symbols written into it, see [IssueFeaturesSnapshot::annotations]. The metadata of the
+/// records is merged into rather than replaced, so the stored records are never modified.
+#[derive(Debug)]
+pub struct Annotations {
+    key: AnnotationKey,
+    /// Only the records something has been written for, the metadata of the others is used as
+    /// is.
+    metadata: HashMap<String, Map<String, Value>>,
+}
+
+impl Annotations {
+    /// Annotates `entry`, returns `None` if nothing has been written.
+    fn annotate(
+        key: &AnnotationKey,
+        entry: &IssueFeaturesEntry,
+        symbols: Option<&Symbols>,
+    ) -> Option<Map<String, Value>> {
+        let text = entry.features.text();
+        let mut metadata = entry.metadata.clone();
+
+        if let Some(extractor) = &key.facets {
+            extractor.annotate(&mut metadata, &text);
+        }
+        if let Some(depth) = key.traces {
+            if let Some(trace) = StackTrace::parse(&text) {
+                trace.annotate(&mut metadata, depth);
+            }
+        }
+        if let Some(symbols) = symbols {
+            write_symbols(&mut metadata, symbols);
+        }
+
+        metadata.filter(|metadata| Some(metadata) != entry.metadata.as_ref())
+    }
+
+    /// The metadata of the record `issue_id` with its annotations, or `metadata` if it has none.
+    pub fn metadata<'a>(
+        &'a self,
+        issue_id: &str,
+        metadata: Option<&'a Map<String, Value>>,
+    ) -> Option<&'a Map<String, Value>> {
+        self.metadata.get(issue_id).or(metadata)
+    }
+}
+
+/// An index over the embeddings of a snapshot, keyed by the positions of the issue IDs.
+#[derive(Debug)]
+struct VectorIndex {
+    issue_ids: Vec<String>,
+    index: Hnsw,
+}
+
+impl IssueFeaturesSnapshot {
+    /// Returns the symbols mentioned by the records, extracted from their features like the
+    /// ones of the source, so the symbols supplied by the caller in the metadata of a record
+    /// are ignored. They're extracted on the first call and kept along with the snapshot.
+    pub fn symbols(&self) -> Arc<RecordSymbols> {
+        self.symbols
+            .get_or_init(|| {
+                let symbols: HashMap<String, Symbols> = self
+                    .map
+                    .par_iter()
+                    .map(|(issue_id, entry)| (issue_id.clone(), extract(&entry.features.text())))
+                    .collect();
+                let index = SymbolIndex::build(symbols.values());
+                Arc::new(RecordSymbols { symbols, index })
+            })
+            .clone()
+    }
+
+    /// Returns the metadata of the records with the facets, the stack traces and the symbols
+    /// extracted by `scoring` written into it, or `None` if it extracts none. It's written on
+    /// the first call and kept along with the snapshot, later calls with other options write
+    /// throwaway ones.
+    pub fn annotations(&self, scoring: &ScoringOptions) -> Option<Arc<Annotations>> {
+        let key = AnnotationKey::new(scoring)?;
+        if let Some(annotations) = self
+            .annotations
+            .get()
+            .filter(|annotations| annotations.key == key)
+        {
+            return Some(annotations.clone());
+        }
+
+        let symbols = key.symbols.then(|| self.symbols());
+        let metadata = self
+            .map
+            .par_iter()
+            .filter_map(|(issue_id, entry)| {
+                let symbols = symbols.as_ref().and_then(|symbols| symbols.get(issue_id));
+                Some((
+                    issue_id.clone(),
+                    Annotations::annotate(&key, entry, symbols)?,
+                ))
+            })
+            .collect();
+        let annotations = Arc::new(Annotations { key, metadata });
+        let _ = self.annotations.set(annotations.clone());
+        Some(annotations)
+    }
+
+    fn vector_index(&self, options: &HnswOptions) -> Result<Arc<VectorIndex>> {
+        if let Some(vectors) = self
+            .vectors
+            .get()
+            .filter(|vectors| vectors.index.options() == options)
+        {
+            return Ok(vectors.clone());
+        }
+
+        // sorted so the same records always build the same graph
+        let mut entries: Vec<(&String, &[f32])> = self
+            .map
+            .iter()
+            .filter_map(|(issue_id, entry)| Some((issue_id, entry.features.embedding.as_deref()?)))
+            .collect();
+        entries.sort_by_key(|(issue_id, _)| *issue_id);
+
+        let mut index = Hnsw::new(*options);
+        for (i, (_, embedding)) in entries.iter().enumerate() {
+            index.insert(i, embedding)?;
+        }
+
+        let vectors = Arc::new(VectorIndex {
+            issue_ids: entries
+                .into_iter()
+                .map(|(issue_id, _)| issue_id.clone())
+                .collect(),
+            index,
+        });
+        let _ = self.vectors.set(vectors.clone());
+        Ok(vectors)
+    }
+
+    /// Finds the `top_n` records whose embeddings are the nearest to `embedding`, the score of a
+    /// match is derived from the distance, see [crate::vector::Distance::score]. The index is
+    /// built on the first call and kept along with the snapshot, later calls with other options
+    /// build a throwaway one.
+    pub fn find_nearest_records(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+        options: &HnswOptions,
+    ) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+        self.find_nearest_records_in(embedding, top_n, filter, options, None)
+    }
+
+    fn find_nearest_records_in(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+        options: &HnswOptions,
+        annotations: Option<&Annotations>,
+    ) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+        let vectors = self.vector_index(options)?;
+        let entry = |i: usize| {
+            let issue_id = &vectors.issue_ids[i];
+            let entry = self.map.get(issue_id)?;
+            let metadata = match annotations {
+                Some(annotations) => annotations.metadata(issue_id, entry.metadata.as_ref()),
+                None => entry.metadata.as_ref(),
+            };
+            Some((issue_id, entry, metadata))
+        };
+        let neighbors = match filter {
+            Some(filter) => vectors.index.search_filtered(embedding, top_n, |i| {
+                entry(i).is_some_and(|(_, _, metadata)| filter.matches(metadata))
+            })?,
+            None => vectors.index.search(embedding, top_n)?,
+        };
+
+        Ok(neighbors
+            .into_iter()
+            .filter_map(|neighbor| {
+                let (issue_id, entry, metadata) = entry(neighbor.key)?;
+
+                Some(SimilarIssueFeaturesRecord {
+                    issue_id: issue_id.clone(),
+                    features: entry.features.clone(),
+                    metadata: metadata.cloned(),
+                    score: options.distance.score(neighbor.distance),
+                    generation: self.generation,
+                    lexical_rank: None,
+                    vector_rank: None,
+                    shared_facets: Vec::new(),
+                    fingerprint: None,
+                    shared_symbols: Vec::new(),
+                })
+            })
+            .collect())
+    }
+
+    /// Learns the boilerplate of the features of the records, each record is a document made
+    /// of its features, see [Boilerplate::learn].
+    pub fn learn_boilerplate(&self, options: &BoilerplateOptions) -> Result<Boilerplate> {
+        let documents: Vec<String> = self
+            .map
+            .values()
+            .map(|entry| entry.features.text())
+            .collect();
+
+        Boilerplate::learn(options, documents.par_iter().map(String::as_str))
+    }
+}
+
+/// A store of issue features keyed by issue ID, cloning it is cheap and the clones share the
+/// records.
+#[derive(Debug, Clone, Default)]
+pub struct IssueFeatureStore {
+    issue_features_map: Arc<ArcSwap<IssueFeaturesSnapshot>>,
+    pool: QueryPool,
+    vector_options: HnswOptions,
+    metrics: FeatureMetrics,
+    boilerplate: Option<Arc<Boilerplate>>,
+    facets: Option<Arc<FacetOptions>>,
+    traces: Option<Arc<TraceOptions>>,
+    symbols: Option<Arc<SymbolOptions>>,
+}
+
+static SHARED_STORES: Registry<IssueFeatureStore> = Registry::new();
+
+impl IssueFeatureStore {
+    /// Returns a handle of the process-global store named `name`, which is created empty on the
+    /// first call and dropped along with its last handle.
+    pub fn shared(name: &str) -> Shared<IssueFeatureStore> {
+        SHARED_STORES.attach(name)
+    }
+
+    /// Creates a store holding the records, the ones without an issue ID or features are
+    /// ignored.
+    pub fn new(records: Vec<IssueFeaturesRecord>) -> Self {
+        let map: HashMap<String, Arc<IssueFeaturesEntry>> = records
+            .into_iter()
+            .filter(|item| !item.issue_id.is_empty() && !item.features.is_empty())
+            .map(|record| {
+                let entry = IssueFeaturesEntry {
+                    features: record.features,
+                    metadata: record.metadata,
+                };
+                (record.issue_id, Arc::new(entry))
+            })
+            .collect();
+
+        IssueFeatureStore {
+            issue_features_map: Arc::new(ArcSwap::from_pointee(IssueFeaturesSnapshot {
+                generation: 0,
+                map,
+                ..Default::default()
+            })),
+            pool: QueryPool::default(),
+            vector_options: HnswOptions::default(),
+            metrics: FeatureMetrics::default(),
+            boilerplate: None,
+            facets: None,
+            traces: None,
+            symbols: None,
+        }
+    }
+
+    /// Runs the queries of this handle on `pool`, the clones made afterwards share it.
+    pub fn with_pool(mut self, pool: QueryPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// The pool the queries of this handle run on, see [QueryPool::spawn] to run them without
+    /// blocking the calling thread.
+    pub fn pool(&self) -> &QueryPool {
+        &self.pool
+    }
+
+    /// Indexes the embeddings of the records with `options` for the queries of this handle, the
+    /// clones made afterwards share them.
+    pub fn with_vector_index(mut self, options: HnswOptions) -> Result<Self> {
+        options.validate()?;
+        self.vector_options = options;
+        Ok(self)
+    }
+
+    /// Compares the features with `metrics` in the queries of this handle, the clones made
+    /// afterwards share them.
+    pub fn with_metrics(mut self, metrics: FeatureMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Removes `boilerplate` from the features of the records and the source before they are
+    /// compared in the queries of this handle, the clones made afterwards share it.
+    pub fn with_boilerplate(mut self, boilerplate: Boilerplate) -> Self {
+        self.boilerplate = Some(Arc::new(boilerplate));
+        self
+    }
+
+    /// Stops removing the boilerplate in the queries of this handle.
+    pub fn without_boilerplate(mut self) -> Self {
+        self.boilerplate = None;
+        self
+    }
+
+    /// The boilerplate ignored by the queries of this handle, if any.
+    pub fn boilerplate(&self) -> Option<&Boilerplate> {
+        self.boilerplate.as_deref()
+    }
+
+    /// Extracts the facets of the features of the records in the queries of this handle, the
+    /// clones made afterwards share it. They're written into the metadata the filters of the
+    /// queries see, once per snapshot, see [IssueFeaturesSnapshot::annotations]. The matches
+    /// report the facets they share with the source, and are boosted by them.
+    pub fn with_facets(mut self, facets: FacetOptions) -> Result<Self> {
+        facets.validate()?;
+        self.facets = Some(Arc::new(facets));
+        Ok(self)
+    }
+
+    /// The facets extracted by this handle, if any.
+    pub fn facets(&self) -> Option<&FacetOptions> {
+        self.facets.as_deref()
+    }
+
+    /// Parses the stack traces of the features of the records in the queries of this handle,
+    /// the clones made afterwards share it. They're fingerprinted into the metadata the filters
+    /// of the queries see, once per snapshot, see [IssueFeaturesSnapshot::annotations]. The
+    /// queries compare the traces of the matches with the one of the source, as given by
+    /// `traces`, and each match reports its fingerprint.
+    pub fn with_traces(mut self, traces: TraceOptions) -> Result<Self> {
+        traces.validate()?;
+        self.traces = Some(Arc::new(traces));
+        Ok(self)
+    }
+
+    /// How the stack traces are compared by this handle, if they are.
+    pub fn traces(&self) -> Option<&TraceOptions> {
+        self.traces.as_deref()
+    }
+
+    /// Extracts the symbols mentioned by the features of the records in the queries of this
+    /// handle, the clones made afterwards share it. They're written into the metadata the
+    /// filters of the queries see, once per snapshot, see [IssueFeaturesSnapshot::annotations].
+    /// The queries combine the overlap of the symbols of the matches with the ones of the
+    /// source with their scores, as given by `symbols`, the rarer symbols weighing more, and
+    /// each match reports the symbols it shares.
+    pub fn with_symbols(mut self, symbols: SymbolOptions) -> Result<Self> {
+        symbols.validate()?;
+        self.symbols = Some(Arc::new(symbols));
+        Ok(self)
+    }
+
+    /// How the symbols are compared by this handle, if they are.
+    pub fn symbols(&self) -> Option<&SymbolOptions> {
+        self.symbols.as_deref()
+    }
+
+    /// How the queries of this handle compare the records with the source.
+    fn scoring(&self) -> ScoringOptions<'_> {
+        ScoringOptions {
+            metrics: self.metrics,
+            boilerplate: self.boilerplate.as_deref(),
+            facets: self.facets.as_deref(),
+            traces: self.traces.as_deref(),
+            symbols: self.symbols.as_deref(),
+        }
+    }
+
+    /// Learns the boilerplate of the features of the records in the current snapshot, which
+    /// can be edited and given to [IssueFeatureStore::with_boilerplate]. It isn't updated by
+    /// later writes.
+    pub fn learn_boilerplate(&self, options: &BoilerplateOptions) -> Result<Boilerplate> {
+        let snapshot = self.snapshot();
+
+        self.pool
+            .run(snapshot.map.len(), |_| snapshot.learn_boilerplate(options))
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    pub fn generation(&self) -> u64 {
+        self.issue_features_map.load().generation
+    }
+
+    /// Returns the current snapshot, which isn't affected by later writes.
+    pub fn snapshot(&self) -> Arc<IssueFeaturesSnapshot> {
+        self.issue_features_map.load_full()
+    }
+
+    /// Replaces all the records with the ones of `other`, such as a store loaded with
+    /// [IssueFeatureStore::load_csv]. The entries are shared rather than copied.
+    pub fn replace_with(&self, other: &IssueFeatureStore) {
+        let map = other.snapshot().map.clone();
+
+        self.issue_features_map
+            .rcu(|current| IssueFeaturesSnapshot {
+                generation: current.generation + 1,
+                map: map.clone(),
+                ..Default::default()
+            });
+    }
+
+    pub fn set_record(&self, record: IssueFeaturesRecord) -> Result<()> {
+        self.set_records(vec![record])
+    }
+
+    /// Inserts or replaces the records in a single write, which copies the map of the current
+    /// snapshot once rather than once per record. None of them is written if any is invalid.
+    pub fn set_records(&self, records: Vec<IssueFeaturesRecord>) -> Result<()> {
+        for record in &records {
+            if record.issue_id.is_empty() {
+                return Err(Error::InvalidArgument(
+                    "issue_id must not be empty".to_string(),
+                ));
+            } else if record.features.is_empty() {
+                return Err(Error::InvalidArgument(
+                    "features must not be empty".to_string(),
+                ));
+            }
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let entries: Vec<(String, Arc<IssueFeaturesEntry>)> = records
+            .into_par_iter()
+            .map(|record| {
+                let entry = IssueFeaturesEntry {
+                    features: record.features,
+                    metadata: record.metadata,
+                };
+                (record.issue_id, Arc::new(entry))
+            })
+            .collect();
+
+        self.issue_features_map.rcu(|current| {
+            let mut map = current.map.clone();
+            map.extend(entries.iter().cloned());
+            IssueFeaturesSnapshot {
+                generation: current.generation + 1,
+                map,
+                ..Default::default()
+            }
+        });
+        Ok(())
+    }
+
+    pub fn get_record(&self, issue_id: &str) -> Option<IssueFeaturesRecord> {
+        let snapshot = self.issue_features_map.load();
+
+        snapshot.map.get(issue_id).map(|entry| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: entry.features.clone(),
+            metadata: entry.metadata.clone(),
+        })
+    }
+
+    /// Removes the record, returns whether it existed. Removing a missing record publishes no
+    /// new generation.
+    pub fn remove_record(&self, issue_id: &str) -> bool {
+        let mut removed = false;
+
+        self.issue_features_map.rcu(|current| {
+            removed = current.map.contains_key(issue_id);
+
+            if removed {
+                let mut map = current.map.clone();
+                map.remove(issue_id);
+                Arc::new(IssueFeaturesSnapshot {
+                    generation: current.generation + 1,
+                    map,
+                    ..Default::default()
+                })
+            } else {
+                current.clone()
+            }
+        });
+        removed
+    }
+
+    /// Finds the `top_n` records most similar to the given `features` in the current snapshot.
+    /// When `filter` is given, only the records whose metadata satisfy it are scored.
+    pub fn find_similar_records(
+        &self,
+        features: &IssueFeatures,
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+        let snapshot = self.snapshot();
+
+        self.pool.run(snapshot.map.len(), |parallel| {
+            if parallel {
+                find_similar_records_in_parallel(
+                    features,
+                    &snapshot,
+                    top_n,
+                    filter,
+                    &self.scoring(),
+                )
+            } else {
+                find_similar_records_sequential(features, &snapshot, top_n, filter, &self.scoring())
+            }
+        })
+    }
+
+    /// Finds the `top_n` records most similar to the given `features` by both their text and
+    /// their embedding, fusing the lexical matches with the nearest embeddings as given by
+    /// `hybrid`. Each match reports its rank in both retrievers, see [crate::hybrid::fuse].
+    pub fn find_similar_records_hybrid(
+        &self,
+        features: &IssueFeatures,
+        top_n: usize,
+        filter: Option<&Filter>,
+        hybrid: &HybridOptions,
+    ) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+        hybrid.validate()?;
+        let embedding = features.embedding.as_deref().ok_or_else(|| {
+            Error::InvalidArgument("features must have an embedding in a hybrid query".to_string())
+        })?;
+        let snapshot = self.snapshot();
+        let candidates = hybrid.candidates(top_n);
+
+        self.pool.run(snapshot.map.len(), |parallel| {
+            let lexical = if parallel {
+                find_similar_records_in_parallel(
+                    features,
+                    &snapshot,
+                    candidates,
+                    filter,
+                    &self.scoring(),
+                )?
+            } else {
+                find_similar_records_sequential(
+                    features,
+                    &snapshot,
+                    candidates,
+                    filter,
+                    &self.scoring(),
+                )?
+            };
+            let annotations = snapshot.annotations(&self.scoring());
+            let vector = snapshot.find_nearest_records_in(
+                embedding,
+                candidates,
+                filter,
+                &self.vector_options,
+                annotations.as_deref(),
+            )?;
+
+            Ok(fuse_records(lexical, vector, &hybrid.fusion, top_n))
+        })
+    }
+
+    /// Finds the `top_n` records whose embeddings are the nearest to `embedding` in the current
+    /// snapshot, see [IssueFeaturesSnapshot::find_nearest_records].
+    pub fn find_nearest_records(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+        let snapshot = self.snapshot();
+
+        self.pool.run(0, |_| {
+            let annotations = snapshot.annotations(&self.scoring());
+            snapshot.find_nearest_records_in(
+                embedding,
+                top_n,
+                filter,
+                &self.vector_options,
+                annotations.as_deref(),
+            )
+        })
+    }
+}
+
+fn strip<'t>(text: &'t str, boilerplate: Option<&Boilerplate>) -> Cow<'t, str> {
+    boilerplate.map_or(Cow::Borrowed(text), |boilerplate| boilerplate.strip(text))
+}
+
+/// Removes the boilerplate from the features of `source`. If nothing but boilerplate is left,
+/// the source is returned as is without the boilerplate, so the records are compared as is too.
+fn strip_source<'a, 'b>(
+    source: &'a IssueFeatures,
+    boilerplate: Option<&'b Boilerplate>,
+) -> (Cow<'a, IssueFeatures>, Option<&'b Boilerplate>) {
+    let Some(boilerplate) = boilerplate else {
+        return (Cow::Borrowed(source), None);
+    };
+    let strip_feature = |feature: &Option<String>| {
+        feature
+            .as_deref()
+            .map(|text| boilerplate.strip(text).into_owned())
+    };
+    let stripped = IssueFeatures {
+        operation: strip_feature(&source.operation),
+        phenomenon: strip_feature(&source.phenomenon),
+        expected_behavior: strip_feature(&source.expected_behavior),
+        actual_behavior: strip_feature(&source.actual_behavior),
+        embedding: None,
+    };
+
+    if get_feature_weights(&stripped).is_ok() {
+        (Cow::Owned(stripped), Some(boilerplate))
+    } else {
+        (Cow::Borrowed(source), None)
+    }
+}
+
+/// How the records are compared with the source by [find_similar_records_in_parallel] and
+/// [find_similar_records_sequential].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScoringOptions<'a> {
+    pub metrics: FeatureMetrics,
+    /// Removed from the features before they are compared.
+    pub boilerplate: Option<&'a Boilerplate>,
+    /// Boosts the records sharing facets with the source.
+    pub facets: Option<&'a FacetOptions>,
+    /// Compares the stack traces of the records with the one of the source.
+    pub traces: Option<&'a TraceOptions>,
+    /// Compares the symbols mentioned by the records with the ones of the source, weighted by
+    /// how many records of the snapshot mention them.
+    pub symbols: Option<&'a SymbolOptions>,
+}
+
+/// Scores the records against a source, see [Scoring::score].
+struct Scoring<'a> {
+    source: Cow<'a, IssueFeatures>,
+    weights: FeatureWeights,
+    metrics: FeatureMetrics,
+    boilerplate: Option<&'a Boilerplate>,
+    /// The facets of the source, extracted from its features before the boilerplate is removed.
+    facets: Option<(&'a FacetOptions, Facets)>,
+    /// The stack trace of the source with its fingerprint, if it has one.
+    traces: Option<(&'a TraceOptions, Option<(StackTrace, String)>)>,
+    /// The symbols of the source, and the ones of the records.
+    symbols: Option<(&'a SymbolOptions, Symbols, Arc<RecordSymbols>)>,
+    /// The metadata of the records with their facets, stack traces and symbols.
+    annotations: Option<Arc<Annotations>>,
+}
+
+impl<'a> Scoring<'a> {
+    fn new(
+        source: &'a IssueFeatures,
+        candidates: &IssueFeaturesSnapshot,
+        options: &ScoringOptions<'a>,
+    ) -> Result<Self> {
+        let text = source.text();
+        let facets = options
+            .facets
+            .map(|facets| (facets, facets.extractor.extract(&text)));
+        let traces = options.traces.map(|traces| (traces, traces.source(&text)));
+        let symbols = options
+            .symbols
+            .map(|symbols| (symbols, extract(&text), candidates.symbols()));
+        let (source, boilerplate) = strip_source(source, options.boilerplate);
+        let weights = get_feature_weights(&source)?;
+
+        Ok(Scoring {
+            source,
+            weights,
+            metrics: options.metrics,
+            boilerplate,
+            facets,
+            traces,
+            symbols,
+            annotations: candidates.annotations(options),
+        })
+    }
+
+    /// Scores a record against the source with the weights returned by [get_feature_weights],
+    /// comparing each feature with its metric, without the boilerplate if given, combined with
+    /// the similarity of the stack traces and the overlap of the symbols, and boosted by the
+    /// shared facets. Returns `None` when
+    /// it doesn't satisfy the filter or isn't a match.
+    fn score(
+        &self,
+        (issue_id, entry): (&String, &IssueFeaturesEntry),
+        generation: u64,
+        filter: Option<&Filter>,
+    ) -> Option<SimilarIssueFeaturesRecord> {
+        let IssueFeaturesEntry { features, metadata } = entry;
+        let metadata = match &self.annotations {
+            Some(annotations) => annotations.metadata(issue_id, metadata.as_ref()),
+            None => metadata.as_ref(),
+        };
+        let Scoring {
+            source,
+            weights,
+            metrics,
+            boilerplate,
+            ..
+        } = self;
+        let boilerplate = *boilerplate;
+
+        if filter.is_some_and(|filter| !filter.matches(metadata)) {
+            return None;
+        }
+
+        let operation_score = match (&source.operation, &features.operation) {
+            (Some(operand1), Some(operand2)) => {
+                metrics
+                    .operation
+                    .similarity(operand1, &strip(operand2, boilerplate))
+                    * weights.operation
+            }
+            _ => 0.0,
+        };
+        let phenomenon_score = match (&source.phenomenon, &features.phenomenon) {
+            (Some(operand1), Some(operand2)) => {
+                metrics
+                    .phenomenon
+                    .similarity(operand1, &strip(operand2, boilerplate))
+                    * weights.phenomenon
+            }
+            _ => 0.0,
+        };
+        let expected_behavior_score = match (&source.expected_behavior, &features.expected_behavior)
+        {
+            (Some(operand1), Some(operand2)) => {
+                metrics
+                    .expected_behavior
+                    .similarity(operand1, &strip(operand2, boilerplate))
+                    * weights.expected_behavior
+            }
+            _ => 0.0,
+        };
+        let actual_behavior_score = match (&source.actual_behavior, &features.actual_behavior) {
+            (Some(operand1), Some(operand2)) => {
+                metrics
+                    .actual_behavior
+                    .similarity(operand1, &strip(operand2, boilerplate))
+                    * weights.actual_behavior
+            }
+            _ => 0.0,
+        };
+        let mut score =
+            operation_score + phenomenon_score + expected_behavior_score + actual_behavior_score;
+        score = score.clamp(0.0, 1.0);
+        let (score, fingerprint) = match &self.traces {
+            Some((options, source)) => {
+                let target = StackTrace::from_metadata(metadata);
+                let score = options.score(score, source.as_ref(), target.as_ref());
+                (score, target.map(|(_, fingerprint)| fingerprint))
+            }
+            None => (score, None),
+        };
+        let (score, shared_symbols) = match &self.symbols {
+            Some((options, symbols, records)) => options.score(
+                score,
+                symbols,
+                records.get(issue_id).unwrap_or(&Symbols::new()),
+                records.index(),
+            ),
+            None => (score, Vec::new()),
+        };
+        let (score, shared_facets) = match &self.facets {
+            Some((options, facets)) => options.boost(score, facets, &facets_of(metadata)),
+            None => (score, Vec::new()),
+        };
+
+        if score > 0.5 {
+            Some(SimilarIssueFeaturesRecord {
+                issue_id: issue_id.clone(),
+                features: features.clone(),
+                metadata: metadata.cloned(),
+                score,
+                generation,
+                lexical_rank: None,
+                vector_rank: None,
+                shared_facets,
+                fingerprint,
+                shared_symbols,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Fuses the lexical matches with the nearest records, the fused score replaces the ones of
+/// the retrievers.
+fn fuse_records(
+    lexical: Vec<SimilarIssueFeaturesRecord>,
+    vector: Vec<SimilarIssueFeaturesRecord>,
+    fusion: &Fusion,
+    top_n: usize,
+) -> Vec<SimilarIssueFeaturesRecord> {
+    let ranking = |records: &[SimilarIssueFeaturesRecord]| -> Vec<(String, f64)> {
+        records
+            .iter()
+            .map(|record| (record.issue_id.clone(), record.score))
+            .collect()
+    };
+    let fused = fuse(&ranking(&lexical), &ranking(&vector), fusion, top_n);
+    let mut records: HashMap<String, SimilarIssueFeaturesRecord> = lexical
+        .into_iter()
+        .chain(vector)
+        .map(|record| (record.issue_id.clone(), record))
+        .collect();
+
+    fused
+        .into_iter()
+        .filter_map(|fused| {
+            let mut record = records.remove(&fused.key)?;
+            record.score = fused.score;
+            record.lexical_rank = fused.lexical_rank;
+            record.vector_rank = fused.vector_rank;
+            Some(record)
+        })
+        .collect()
+}
+
+fn sort_and_truncate(
+    mut matches: Vec<SimilarIssueFeaturesRecord>,
+    top_n: usize,
+) -> Vec<SimilarIssueFeaturesRecord> {
+    matches.sort_by(|a, b| {
+        let order = b.score.partial_cmp(&a.score);
+        order.unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    matches.truncate(top_n);
+    matches
+}
+
+/// Scores the records of the snapshot against `source` in parallel, each feature contributes
+/// to the score by its weight in the source, see [get_feature_weights], and is compared by its
+/// metric in the `metrics` of `scoring`. Its boilerplate, if any, is removed from the features
+/// first, its facets boost the records sharing them with the source, and its traces and its
+/// symbols are compared with the ones of the source.
+pub fn find_similar_records_in_parallel(
+    source: &IssueFeatures,
+    candidates: &IssueFeaturesSnapshot,
+    top_n: usize,
+    filter: Option<&Filter>,
+    scoring: &ScoringOptions,
+) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+    let scoring = Scoring::new(source, candidates, scoring)?;
+    let matches: Vec<SimilarIssueFeaturesRecord> = candidates
+        .map
+        .par_iter()
+        .filter_map(|(issue_id, entry)| {
+            scoring.score((issue_id, entry), candidates.generation, filter)
+        })
+        .collect();
+
+    Ok(sort_and_truncate(matches, top_n))
+}
+
+/// Like [find_similar_records_in_parallel], but scores the records on the current thread,
+/// which is faster for small snapshots.
+pub fn find_similar_records_sequential(
+    source: &IssueFeatures,
+    candidates: &IssueFeaturesSnapshot,
+    top_n: usize,
+    filter: Option<&Filter>,
+    scoring: &ScoringOptions,
+) -> Result<Vec<SimilarIssueFeaturesRecord>> {
+    let scoring = Scoring::new(source, candidates, scoring)?;
+    let matches: Vec<SimilarIssueFeaturesRecord> = candidates
+        .map
+        .iter()
+        .filter_map(|(issue_id, entry)| {
+            scoring.score((issue_id, entry), candidates.generation, filter)
+        })
+        .collect();
+
+    Ok(sort_and_truncate(matches, top_n))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::metric::Metric;
+
+    #[test]
+    fn test_issue_feature_store_new() {
+        let record1 = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                phenomenon: None,
+                expected_behavior: Some("The device is turned on".to_string()),
+                actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+        let record2 = IssueFeaturesRecord {
+            issue_id: "2".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn off the switch".to_string()),
+                phenomenon: Some(
+                    "The device remains turned on instead if being turned on".to_string(),
+                ),
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: None,
+            },
+            metadata: None,
+        };
+        let records = vec![
+            record1.clone(),
+            record2.clone(),
+            IssueFeaturesRecord {
+                // This one is invalid will be ignored
+                issue_id: "3".to_string(),
+                features: IssueFeatures {
+                    operation: None,
+                    phenomenon: None,
+                    expected_behavior: None,
+                    actual_behavior: None,
+                    embedding: None,
+                },
+                metadata: None,
+            },
+        ];
+
+        let store = IssueFeatureStore::new(records);
+
+        assert_eq!(store.get_record("1"), Some(record1));
+        assert_eq!(store.get_record("2"), Some(record2));
+        assert_eq!(store.get_record("3"), None);
+        assert_eq!(store.get_record("4"), None);
+    }
+
+    #[test]
+    fn test_issue_feature_store_set_record() {
+        let store = IssueFeatureStore::new(vec![]);
+
+        let record = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                phenomenon: None,
+                expected_behavior: Some("The device is turned on".to_string()),
+                actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+
+        store.set_record(record.clone()).unwrap();
+        assert_eq!(store.get_record("1"), Some(record));
+        assert_eq!(store.get_record("2"), None);
+    }
+
+    #[test]
+    fn test_issue_feature_store_set_records() {
+        let store = IssueFeatureStore::new(vec![]);
+        let record = |issue_id: &str, operation: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some(operation.to_string()),
+                phenomenon: None,
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: None,
+            },
+            metadata: None,
+        };
+
+        store
+            .set_records(vec![
+                record("1", "Turn on the switch"),
+                record("2", "Turn off the switch"),
+            ])
+            .unwrap();
+        assert_eq!(store.generation(), 1);
+        assert_eq!(
+            store.get_record("2"),
+            Some(record("2", "Turn off the switch"))
+        );
+
+        // an invalid record rejects the whole batch
+        let mut invalid = record("3", "Reset the device");
+        invalid.features.operation = None;
+        assert!(store
+            .set_records(vec![record("1", "Reset the switch"), invalid])
+            .is_err());
+        assert_eq!(
+            store.get_record("1"),
+            Some(record("1", "Turn on the switch"))
+        );
+        assert_eq!(store.generation(), 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_remove_record() {
+        let record1 = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                phenomenon: None,
+                expected_behavior: Some("The device is turned on".to_string()),
+                actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+        let record2 = IssueFeaturesRecord {
+            issue_id: "2".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn off the switch".to_string()),
+                phenomenon: Some(
+                    "The device remains turned on instead if being turned on".to_string(),
+                ),
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: None,
+            },
+            metadata: None,
+        };
+
+        let records = vec![record1.clone(), record2.clone()];
+        let store = IssueFeatureStore::new(records);
+
+        assert_eq!(store.get_record("1"), Some(record1));
+        assert_eq!(store.get_record("2"), Some(record2));
+
+        assert!(store.remove_record("1"));
+        assert_eq!(store.get_record("1"), None);
+        assert!(!store.remove_record("3"));
+    }
+
+    #[test]
+    fn test_find_similar_records_in_parallel() {
+        let record1 = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                phenomenon: None,
+                expected_behavior: Some("The device is turned on".to_string()),
+                actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+        let record2 = IssueFeaturesRecord {
+            issue_id: "2".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn off the switch".to_string()),
+                phenomenon: Some(
+                    "The device remains turned on instead if being turned on".to_string(),
+                ),
+                expected_behavior: None,
+                actual_behavior: None,
+                embedding: None,
+            },
+            metadata: None,
+        };
+
+        let records = vec![record1.clone(), record2.clone()];
+        let store = IssueFeatureStore::new(records);
+
+        let features = IssueFeatures {
+            operation: Some("Turn on the switch".to_string()),
+            phenomenon: None,
+            expected_behavior: Some("The device turns on".to_string()),
+            actual_behavior: Some("The device does not turn on".to_string()),
+            embedding: None,
+        };
+        let matches = find_similar_records_in_parallel(
+            &features,
+            &store.snapshot(),
+            5,
+            None,
+            &ScoringOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].issue_id, "1");
+        assert_eq!(matches[0].features, record1.features);
+        assert!(matches[0].score > 0.8);
+    }
+
+    #[test]
+    fn test_find_similar_records_with_filter() {
+        let metadata = |value: Value| match value {
+            Value::Object(map) => Some(map),
+            _ => None,
+        };
+        let features = IssueFeatures {
+            operation: Some("Turn on the switch".to_string()),
+            phenomenon: None,
+            expected_behavior: Some("The device is turned on".to_string()),
+            actual_behavior: Some("The device is not turned on".to_string()),
+            embedding: None,
+        };
+        let store = IssueFeatureStore::new(vec![
+            IssueFeaturesRecord {
+                issue_id: "1".to_string(),
+                features: features.clone(),
+                metadata: metadata(json!({ "component": "switch", "state": "closed" })),
+            },
+            IssueFeaturesRecord {
+                issue_id: "2".to_string(),
+                features: features.clone(),
+                metadata: metadata(
+                    json!({ "component": "switch", "labels": ["bug"], "state": "open" }),
+                ),
+            },
+            IssueFeaturesRecord {
+                issue_id: "3".to_string(),
+                features: features.clone(),
+                metadata: None,
+            },
+        ]);
+
+        let filter = Filter::parse(&json!({ "component": "switch", "state": "open" })).unwrap();
+        let matches = find_similar_records_in_parallel(
+            &features,
+            &store.snapshot(),
+            5,
+            Some(&filter),
+            &ScoringOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].issue_id, "2");
+        assert_eq!(
+            matches[0].metadata,
+            metadata(json!({ "component": "switch", "labels": ["bug"], "state": "open" }))
+        );
+
+        let filter = Filter::parse(&json!({ "$not": { "labels": { "$in": ["bug"] } } })).unwrap();
+        let mut matches = find_similar_records_in_parallel(
+            &features,
+            &store.snapshot(),
+            5,
+            Some(&filter),
+            &ScoringOptions::default(),
+        )
+        .unwrap();
+        matches.sort_by(|a, b| a.issue_id.cmp(&b.issue_id));
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].issue_id, "1");
+        assert_eq!(matches[1].issue_id, "3");
+    }
+
+    #[test]
+    fn test_issue_feature_store_snapshot_generation() {
+        let record = IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                phenomenon: None,
+                expected_behavior: Some("The device is turned on".to_string()),
+                actual_behavior: Some("The device is not turned on".to_string()),
+                embedding: None,
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![]);
+        assert_eq!(store.generation(), 0);
+
+        store.set_record(record.clone()).unwrap();
+        assert_eq!(store.generation(), 1);
+
+        // a reader holding the old snapshot is unaffected by later writes
+        let snapshot = store.snapshot();
+        assert!(store.remove_record("1"));
+        assert_eq!(store.generation(), 2);
+        assert_eq!(snapshot.generation, 1);
+        assert!(snapshot.map.contains_key("1"));
+
+        // removing a missing record publishes no new generation
+        assert!(!store.remove_record("1"));
+        assert_eq!(store.generation(), 2);
+
+        let matches = find_similar_records_in_parallel(
+            &record.features,
+            &snapshot,
+            5,
+            None,
+            &ScoringOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].generation, 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_replace_with() {
+        let record = |issue_id: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some("Turn on the switch".to_string()),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![record("1")]);
+        let other = IssueFeatureStore::new(vec![record("2"), record("3")]);
+
+        store.replace_with(&other);
+        assert_eq!(store.generation(), 1);
+        assert_eq!(store.get_record("1"), None);
+        assert_eq!(store.get_record("2"), Some(record("2")));
+        assert_eq!(store.snapshot().map.len(), 2);
+
+        // the stores stay independent afterwards
+        other.remove_record("2");
+        assert_eq!(store.get_record("2"), Some(record("2")));
+    }
+
+    #[test]
+    fn test_issue_feature_store_find_nearest_records() {
+        let record = |issue_id: &str, embedding: Vec<f32>, state: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                embedding: Some(embedding),
+                ..Default::default()
+            },
+            metadata: json!({ "state": state }).as_object().cloned(),
+        };
+        let store = IssueFeatureStore::new(vec![
+            record("1", vec![1.0, 0.0, 0.0], "open"),
+            record("2", vec![0.0, 1.0, 0.0], "closed"),
+            record("3", vec![0.7, 0.7, 0.0], "open"),
+        ]);
+
+        let matches = store
+            .find_nearest_records(&[1.0, 0.1, 0.0], 2, None)
+            .unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+        assert!(matches[0].score > 0.99);
+
+        let filter = Filter::parse(&json!({ "state": "closed" })).unwrap();
+        let matches = store
+            .find_nearest_records(&[1.0, 0.1, 0.0], 2, Some(&filter))
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].issue_id, "2");
+
+        // the index is rebuilt for the new generation
+        store.remove_record("1");
+        let matches = store
+            .find_nearest_records(&[1.0, 0.1, 0.0], 1, None)
+            .unwrap();
+        assert_eq!(matches[0].issue_id, "3");
+        assert_eq!(matches[0].generation, 1);
+    }
+
+    #[test]
+    fn test_issue_feature_store_find_similar_records_hybrid() {
+        let record = |issue_id: &str, operation: &str, embedding: Vec<f32>| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some(operation.to_string()),
+                embedding: Some(embedding),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![
+            record("1", "Turn on the switch", vec![0.0, 1.0]),
+            record("2", "Unplug the device", vec![1.0, 0.0]),
+        ]);
+        let features = IssueFeatures {
+            operation: Some("Turn on the switch".to_string()),
+            embedding: Some(vec![1.0, 0.1]),
+            ..Default::default()
+        };
+        let hybrid = HybridOptions {
+            fusion: Fusion::Weighted {
+                lexical: 0.6,
+                vector: 0.4,
+            },
+            candidates: Some(1),
+        };
+
+        let matches = store
+            .find_similar_records_hybrid(&features, 5, None, &hybrid)
+            .unwrap();
+        let ranks: Vec<(&str, f64, Option<usize>, Option<usize>)> = matches
+            .iter()
+            .map(|m| (m.issue_id.as_str(), m.score, m.lexical_rank, m.vector_rank))
+            .collect();
+        assert_eq!(
+            ranks,
+            vec![("1", 0.6, Some(1), Some(2)), ("2", 0.4, None, Some(1))]
+        );
+
+        let features = IssueFeatures {
+            embedding: None,
+            ..features
+        };
+        assert!(store
+            .find_similar_records_hybrid(&features, 5, None, &hybrid)
+            .is_err());
+    }
+
+    #[test]
+    fn test_issue_feature_store_with_metrics() {
+        let phenomenon = |text: &str| IssueFeatures {
+            phenomenon: Some(text.to_string()),
+            ..Default::default()
+        };
+        let store = IssueFeatureStore::new(vec![IssueFeaturesRecord {
+            issue_id: "1".to_string(),
+            features: phenomenon(
+                "The screen flickers. The fan gets loud. The battery drains in an hour.",
+            ),
+            metadata: None,
+        }]);
+        let features =
+            phenomenon("The battery drains in an hour. The fan gets loud. The screen flickers.");
+
+        assert!(store
+            .find_similar_records(&features, 5, None)
+            .unwrap()
+            .is_empty());
+
+        let store = store.with_metrics(FeatureMetrics {
+            phenomenon: Metric::Alignment,
+            ..Default::default()
+        });
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].score, 1.0);
+    }
+
+    #[test]
+    fn test_issue_feature_store_with_boilerplate() {
+        let record = |issue_id: &str, phenomenon: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some("Run the script".to_string()),
+                phenomenon: Some(format!(
+                    "Please describe the problem in detail below.\n{}",
+                    phenomenon
+                )),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![
+            record("1", "It panics"),
+            record("2", "The output is empty"),
+            record("3", "It takes forever to finish"),
+        ]);
+        let features = record("4", "It panics!").features;
+
+        // the template and the operation make every record a match
+        assert_eq!(
+            store
+                .find_similar_records(&features, 5, None)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let boilerplate = store
+            .learn_boilerplate(&BoilerplateOptions::default())
+            .unwrap();
+        let texts: Vec<&str> = boilerplate
+            .entries()
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "please describe the problem in detail below.",
+                "run the script"
+            ]
+        );
+
+        // only the phenomena are left to compare
+        let store = store.with_boilerplate(boilerplate);
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+        assert_eq!(matches[0].features, record("1", "It panics").features);
+
+        // a source made of boilerplate only is compared as is, and so are the records
+        let features = record("5", "").features;
+        assert_eq!(
+            store
+                .find_similar_records(&features, 5, None)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_issue_feature_store_with_facets() {
+        use crate::facet::{facets_of, FacetOptions};
+
+        let record = |issue_id: &str, os: &str, actual_behavior: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some(format!("Run deno 2.3.3 on {}", os)),
+                actual_behavior: Some(actual_behavior.to_string()),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![
+            record("1", "Windows 11", "The process exits with code 1"),
+            record("2", "Ubuntu 22.04", "The process exits with code 1"),
+        ]);
+        let features = record("4", "Windows 11", "The process crashes").features;
+        let plain = store.find_similar_records(&features, 5, None).unwrap();
+        assert!(plain.iter().all(|m| m.shared_facets.is_empty()));
+
+        let store = store
+            .with_facets(FacetOptions {
+                boost: 0.1,
+                ..Default::default()
+            })
+            .unwrap();
+        store
+            .set_record(record("3", "macOS 14.2", "The process exits with code 1"))
+            .unwrap();
+        // the facets are written into the metadata of the snapshot, not of the records
+        assert_eq!(store.generation(), 1);
+        assert_eq!(store.get_record("1").unwrap().metadata, None);
+        let snapshot = store.snapshot();
+        let annotations = snapshot.annotations(&store.scoring()).unwrap();
+        let os = |issue_id: &str| facets_of(annotations.metadata(issue_id, None))["os"].clone();
+        assert_eq!(os("1"), "windows 11");
+        assert_eq!(os("3"), "macos 14.2");
+
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        let shared: Vec<(&str, Vec<String>)> = matches
+            .iter()
+            .map(|m| (m.issue_id.as_str(), m.shared_facets.clone()))
+            .collect();
+        assert_eq!(
+            shared,
+            vec![
+                ("1", vec!["os".to_string(), "runtime".to_string()]),
+                ("3", vec!["runtime".to_string()]),
+                ("2", vec!["runtime".to_string()]),
+            ]
+        );
+        // the boost is proportional to the share of the facets of the source
+        assert!((matches[0].score - (plain[0].score + 0.1)).abs() < 1e-9);
+        assert!((matches[2].score - (plain[1].score + 0.05)).abs() < 1e-9);
+
+        let filter = Filter::parse(&json!({ "facets.os": { "$ne": "windows 11" } })).unwrap();
+        let matches = store
+            .find_similar_records(&features, 5, Some(&filter))
+            .unwrap();
+        let mut ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[test]
+    fn test_issue_feature_store_with_traces() {
+        use crate::trace::{TraceMatching, TraceOptions};
+
+        let record = |issue_id: &str, operation: &str, home: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some(operation.to_string()),
+                actual_behavior: Some(format!(
+                    r#"Traceback (most recent call last):
+  File "{home}/app/main.py", line 10, in <module>
+    main()
+  File "{home}/app/sync.py", line 6, in pull
+    int(value)
+ValueError: invalid literal for int() with base 10: 'abc'"#
+                )),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![record(
+            "1",
+            "Sync the calendar with a shared account",
+            "/srv",
+        )])
+        .with_traces(TraceOptions {
+            matching: TraceMatching::Bucket,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(store.generation(), 0);
+        store
+            .set_record(IssueFeaturesRecord {
+                issue_id: "2".to_string(),
+                features: IssueFeatures {
+                    operation: Some("Sync the contacts".to_string()),
+                    actual_behavior: Some("It hangs forever".to_string()),
+                    ..Default::default()
+                },
+                metadata: None,
+            })
+            .unwrap();
+
+        let features = record(
+            "3",
+            "Import a CSV file exported from another app",
+            "/home/bob",
+        )
+        .features;
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+        assert_eq!(matches[0].score, 1.0);
+
+        let (trace, fingerprint) = StackTrace::from_metadata(matches[0].metadata.as_ref()).unwrap();
+        assert_eq!(trace.frames, vec!["pull", "main.py"]);
+        assert_eq!(matches[0].fingerprint, Some(fingerprint));
+        assert_eq!(store.get_record("1").unwrap().metadata, None);
+        assert_eq!(store.get_record("2").unwrap().metadata, None);
+    }
+
+    #[test]
+    fn test_issue_feature_store_with_symbols() {
+        use crate::symbol::SymbolOptions;
+
+        let record = |issue_id: &str, operation: &str, actual_behavior: &str| IssueFeaturesRecord {
+            issue_id: issue_id.to_string(),
+            features: IssueFeatures {
+                operation: Some(operation.to_string()),
+                actual_behavior: Some(actual_behavior.to_string()),
+                ..Default::default()
+            },
+            metadata: None,
+        };
+        let store = IssueFeatureStore::new(vec![
+            record(
+                "1",
+                "Stop a child process with `Deno.kill(child.pid, \"SIGTERM\")`",
+                "The process keeps running",
+            ),
+            record(
+                "2",
+                "Listen to `Deno.addSignalListener(\"SIGINT\", onExit)`",
+                "The listener is never called",
+            ),
+        ]);
+        let features = record(
+            "3",
+            "Call `Deno.kill(child.pid, \"SIGTERM\")` in a test",
+            "It throws ERR_INVALID_ARG_TYPE",
+        )
+        .features;
+        assert!(store
+            .find_similar_records(&features, 5, None)
+            .unwrap()
+            .is_empty());
+
+        // the issue calling `Deno.kill` too matches, the one about another API doesn't
+        let store = store.with_symbols(SymbolOptions { weight: 0.5 }).unwrap();
+        assert_eq!(store.generation(), 0);
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+        assert!(matches[0].score > 0.5 && matches[0].score < 1.0);
+        assert!(matches[0]
+            .shared_symbols
+            .iter()
+            .any(|symbol| symbol == "Deno.kill"));
+        assert!(!matches[0]
+            .shared_symbols
+            .iter()
+            .any(|symbol| symbol == "ERR_INVALID_ARG_TYPE"));
+
+        // the symbols are extracted from the features, the ones in the metadata are ignored
+        store
+            .set_record(IssueFeaturesRecord {
+                metadata: json!({ "symbols": extract(&features.text()) })
+                    .as_object()
+                    .cloned(),
+                ..record("2", "Stop a child process in a test", "It throws an error")
+            })
+            .unwrap();
+        let matches = store.find_similar_records(&features, 5, None).unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.issue_id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{issue::IssueFeatures, metric::Metric, Error, Result};
+
+fn count_chars(text: Option<&str>) -> usize {
+    text.map_or(0, |s| s.chars().count())
+}
+
+pub struct FeatureWeights {
+    pub operation: f64,
+    pub phenomenon: f64,
+    pub expected_behavior: f64,
+    pub actual_behavior: f64,
+}
+
+/// The metric comparing each feature, the edit distance of the whole texts by default. Long
+/// features listing the steps of the issue, such as the phenomenon, are compared better by
+/// [Metric::Alignment], which ignores the order of their sentences.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeatureMetrics {
+    pub operation: Metric,
+    pub phenomenon: Metric,
+    pub expected_behavior: Metric,
+    pub actual_behavior: Metric,
+}
+
+/// Returns the weights of the features, proportional to their lengths in the source, so the
+/// longer features count more.
+pub fn get_feature_weights(source: &IssueFeatures) -> Result<FeatureWeights> {
+    let operation_chars = count_chars(source.operation.as_deref());
+    let phenomenon_chars = count_chars(source.phenomenon.as_deref());
+    let expected_behavior_chars = count_chars(source.expected_behavior.as_deref());
+    let actual_behavior_chars = count_chars(source.actual_behavior.as_deref());
+    let total_chars =
+        operation_chars + phenomenon_chars + expected_behavior_chars + actual_behavior_chars;
+
+    if total_chars == 0 {
+        Err(Error::InvalidArgument("source is invalid".to_string()))
+    } else {
+        Ok(FeatureWeights {
+            operation: operation_chars as f64 / total_chars as f64,
+            phenomenon: phenomenon_chars as f64 / total_chars as f64,
+            expected_behavior: expected_behavior_chars as f64 / total_chars as f64,
+            actual_behavior: actual_behavior_chars as f64 / total_chars as f64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_feature_weights() {
+        let weights = get_feature_weights(&IssueFeatures {
+            operation: Some("Turn on".to_string()),
+            phenomenon: None,
+            expected_behavior: Some("It's on".to_string()),
+            actual_behavior: Some("它没开".to_string()),
+            embedding: None,
+        })
+        .unwrap();
+        assert_eq!(weights.operation, 7.0 / 17.0);
+        assert_eq!(weights.phenomenon, 0.0);
+        assert_eq!(weights.expected_behavior, 7.0 / 17.0);
+        assert_eq!(weights.actual_behavior, 3.0 / 17.0);
+
+        assert!(get_feature_weights(&IssueFeatures::default()).is_err());
+    }
+}
@@ -0,0 +1,37 @@
+#![deny(clippy::all)]
+//! The similarity search shared by the Node.js bindings and our Rust services.
+//!
+//! [post] scores blog posts by their title and content, [issue] scores issues by their
+//! features. Both come with a copy-on-write store, metadata [filter]s, and loaders for CSV,
+//! JSON Lines and SQL databases. The stores can be [shared] by name across the threads of a
+//! process, and run their queries on a dedicated [pool]. Embeddings supplied by the caller are
+//! indexed for [vector] queries, whose matches can be fused with the lexical ones by a
+//! [hybrid] query. Large post stores can narrow down the candidates with a cheap [pipeline]
+//! stage before scoring them, score long posts by their [chunk]s, and use a [metric] other
+//! than the edit distance of the whole texts. The [boilerplate] shared by many documents, such
+//! as the headers of an issue template, can be learned from a store and ignored by its queries.
+//! The environment a report was filed in, such as its version and OS, is extracted into
+//! [facet]s, which can be filtered on and boost the matches sharing them. The stack [trace] of a
+//! crash report is fingerprinted too, and compared with the one of the
+//! source. The code [symbol]s a post or an issue mentions, such as `Deno.kill`, are compared
+//! with the ones of the source as well.
+
+pub mod boilerplate;
+pub mod chunk;
+mod codec;
+pub mod error;
+pub mod facet;
+pub mod filter;
+pub mod hybrid;
+pub mod issue;
+pub mod load;
+pub mod metric;
+pub mod pipeline;
+pub mod pool;
+pub mod post;
+pub mod shared;
+pub mod symbol;
+pub mod trace;
+pub mod vector;
+
+pub use error::{Error, Result};
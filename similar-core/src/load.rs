@@ -0,0 +1,172 @@
+//! Shared pieces of the file and database loaders of both stores.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use flate2::bufread::MultiGzDecoder;
+use serde_json::{Map, Value};
+
+use crate::{Error, Result};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A malformed record skipped while loading a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    /// The 1-based line number where the malformed record starts.
+    pub line: u64,
+    pub message: String,
+}
+
+/// The outcome of loading a file into a store.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// How many entries have been loaded into the store.
+    pub loaded: usize,
+    /// The records which have been skipped because they are malformed.
+    pub errors: Vec<LoadError>,
+}
+
+/// Opens a JSON Lines file for reading, decompressing it on the fly if it's gzip-compressed.
+/// When `gzip` is `None`, the compression is detected from the content.
+pub fn open_lines(path: &Path, gzip: Option<bool>) -> Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path).map_err(|e| Error::Io("Cannot open JSONL file", e))?;
+    let mut reader = BufReader::new(file);
+    let gzip = match gzip {
+        Some(gzip) => gzip,
+        None => reader
+            .fill_buf()
+            .map_err(|e| Error::Io("Cannot read JSONL file", e))?
+            .starts_with(&GZIP_MAGIC),
+    };
+
+    if gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads the lines of a JSON Lines file, calling `parse` with each non-blank one and collecting
+/// the messages it returns into the report, along with the lines which aren't valid UTF-8.
+/// Returns the parsed entries and the errors.
+pub fn read_lines<T>(
+    reader: &mut dyn BufRead,
+    mut parse: impl FnMut(&str) -> std::result::Result<T, String>,
+) -> Result<(Vec<T>, Vec<LoadError>)> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut line_number: u64 = 0;
+    let mut entries: Vec<T> = Vec::new();
+    let mut errors: Vec<LoadError> = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) => line_number += 1,
+            Err(e) => return Err(Error::Io("Cannot read JSONL file", e)),
+        }
+
+        let result = match std::str::from_utf8(&buf) {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => parse(line),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(message) => errors.push(LoadError {
+                line: line_number,
+                message,
+            }),
+        }
+    }
+
+    Ok((entries, errors))
+}
+
+/// Parses a line of a JSON Lines file, which must hold an object.
+pub fn parse_object(line: &str) -> std::result::Result<Map<String, Value>, String> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(obj)) => Ok(obj),
+        Ok(_) => Err("record must be an object".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Removes a string field from the object, `null` counts as missing.
+pub fn take_string(
+    obj: &mut Map<String, Value>,
+    field: &str,
+) -> std::result::Result<Option<String>, String> {
+    match obj.remove(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("field '{}' must be a string", field)),
+    }
+}
+
+/// Removes the embedding from the record, which must be an array of numbers.
+pub fn take_embedding(
+    obj: &mut Map<String, Value>,
+    field: &str,
+) -> std::result::Result<Option<Vec<f32>>, String> {
+    let error = || format!("field '{}' must be an array of numbers", field);
+
+    match obj.remove(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_f64().map(|x| x as f32).ok_or_else(error))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map(Some),
+        Some(_) => Err(error()),
+    }
+}
+
+/// Removes the metadata object from the record and merges the remaining unknown fields into
+/// it, so they are kept rather than dropped.
+pub fn take_metadata(
+    mut obj: Map<String, Value>,
+    field: &str,
+) -> std::result::Result<Option<Map<String, Value>>, String> {
+    let mut metadata = match obj.remove(field) {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::Object(metadata)) => metadata,
+        Some(_) => return Err(format!("field '{}' must be an object", field)),
+    };
+
+    metadata.extend(obj);
+
+    if metadata.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(metadata))
+    }
+}
+
+/// Quotes `table`, a plain or a dotted identifier such as `posts` or `public.posts`, for the
+/// database of `url`, so it can be written into a query. Anything else is rejected rather than
+/// written into the query as is. The quoted name is case-sensitive on PostgreSQL.
+pub fn quote_table(url: &str, table: &str) -> Result<String> {
+    let is_identifier = |part: &str| {
+        part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !table.split('.').all(is_identifier) {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid table name '{}', it must be an identifier such as posts or public.posts",
+            table
+        )));
+    }
+
+    let quote = if url.starts_with("mysql:") { '`' } else { '"' };
+    Ok(table
+        .split('.')
+        .map(|part| format!("{}{}{}", quote, part, quote))
+        .collect::<Vec<_>>()
+        .join("."))
+}
@@ -0,0 +1,184 @@
+//! Measures of the similarity of two texts, from 0 to 1.
+//!
+//! The normalized edit distance of the whole texts is used by default. The other metrics are
+//! slower, but hold up better when the texts only partly agree, or say the same things in
+//! another order.
+
+use std::iter;
+
+use rapidfuzz::distance::levenshtein::{normalized_similarity, BatchComparator};
+
+use crate::{chunk::Split, Error, Result};
+
+/// Sentences less similar than this aren't aligned by [alignment_similarity].
+pub const MIN_ALIGNED_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// The normalized edit distance of the whole texts.
+    #[default]
+    Levenshtein,
+    /// The best normalized edit distance of the shorter text against the parts of the longer
+    /// one, see [partial_similarity].
+    Partial,
+    /// The share of the texts covered by sentences aligned with a similar sentence of the
+    /// other text, in any order, see [alignment_similarity].
+    Alignment,
+}
+
+impl Metric {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "levenshtein" => Ok(Metric::Levenshtein),
+            "partial" => Ok(Metric::Partial),
+            "alignment" => Ok(Metric::Alignment),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown metric '{}', it must be one of levenshtein, partial and alignment",
+                name
+            ))),
+        }
+    }
+
+    /// The similarity of two texts from 0 to 1.
+    pub fn similarity(&self, a: &str, b: &str) -> f64 {
+        match self {
+            Metric::Levenshtein => normalized_similarity(a.chars(), b.chars()),
+            Metric::Partial => partial_similarity(a, b),
+            Metric::Alignment => alignment_similarity(a, b),
+        }
+    }
+}
+
+/// The best normalized similarity of the shorter text against the windows of the longer one with
+/// the same length, so a text quoted in a longer one scores close to 1. The windows start at
+/// every quarter of the length of the shorter text, then the best one is moved by halving steps
+/// while it improves, which keeps the cost at a few full comparisons.
+pub fn partial_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+
+    if short.is_empty() || short.len() == long.len() {
+        return normalized_similarity(short, long);
+    }
+
+    let scorer = BatchComparator::new(short.iter().copied());
+    let last = long.len() - short.len();
+    let score = |start: usize| {
+        scorer.normalized_similarity(long[start..start + short.len()].iter().copied())
+    };
+    let mut step = (short.len() / 4).max(1);
+    let (mut best_start, mut best_score) = (0..=last)
+        .step_by(step)
+        .chain(iter::once(last))
+        .map(|start| (start, score(start)))
+        .fold((0, f64::NEG_INFINITY), |best, window| {
+            if window.1 > best.1 {
+                window
+            } else {
+                best
+            }
+        });
+
+    while step > 1 {
+        step /= 2;
+
+        for start in [
+            best_start.saturating_sub(step),
+            (best_start + step).min(last),
+        ] {
+            let window_score = score(start);
+            if window_score > best_score {
+                (best_start, best_score) = (start, window_score);
+            }
+        }
+    }
+
+    best_score
+}
+
+/// Aligns the sentences of the texts one to one, the most similar pairs first, and returns the
+/// share of the characters of both texts in aligned sentences, each pair weighted by its
+/// similarity. Sentences without a counterpart of at least [MIN_ALIGNED_SIMILARITY] count as
+/// unmatched, so the filler of either text lowers the score while the order of the sentences
+/// doesn't matter. Texts without a sentence are compared as a whole.
+pub fn alignment_similarity(a: &str, b: &str) -> f64 {
+    let split = Split::Sentence { overlap: 0 };
+    let sentences = |text: &str| -> Vec<Vec<char>> {
+        split
+            .split(text)
+            .into_iter()
+            .map(|range| text[range].chars().collect())
+            .collect()
+    };
+    let (a_sentences, b_sentences) = (sentences(a), sentences(b));
+
+    if a_sentences.is_empty() || b_sentences.is_empty() {
+        return normalized_similarity(a.chars(), b.chars());
+    }
+
+    let mut pairs: Vec<(usize, usize, f64)> = vec![];
+    for (i, sentence) in a_sentences.iter().enumerate() {
+        let scorer = BatchComparator::new(sentence.iter().copied());
+
+        for (j, other) in b_sentences.iter().enumerate() {
+            let score = scorer.normalized_similarity(other.iter().copied());
+            if score >= MIN_ALIGNED_SIMILARITY {
+                pairs.push((i, j, score));
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.2.total_cmp(&x.2));
+
+    let mut a_aligned = vec![false; a_sentences.len()];
+    let mut b_aligned = vec![false; b_sentences.len()];
+    let mut covered = 0.0;
+
+    for (i, j, score) in pairs {
+        if !a_aligned[i] && !b_aligned[j] {
+            a_aligned[i] = true;
+            b_aligned[j] = true;
+            covered += score * (a_sentences[i].len() + b_sentences[j].len()) as f64;
+        }
+    }
+
+    let total: usize = a_sentences.iter().chain(&b_sentences).map(Vec::len).sum();
+    covered / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_similarity() {
+        let text = "Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK)";
+        let quoted = "only supports ctrl-c";
+
+        assert_eq!(partial_similarity(quoted, text), 1.0);
+        assert!(Metric::Levenshtein.similarity(quoted, text) < 0.5);
+        assert_eq!(partial_similarity("", ""), 1.0);
+        assert_eq!(partial_similarity(text, text), 1.0);
+    }
+
+    #[test]
+    fn test_alignment_similarity() {
+        let report = "Deno.kill fails on windows. It throws a TypeError. SIGBREAK fails too.";
+        let reordered = "SIGBREAK fails too. It throws a TypeError. Deno.kill fails on windows.";
+
+        assert_eq!(alignment_similarity(report, reordered), 1.0);
+        assert!(Metric::Levenshtein.similarity(report, reordered) < 0.5);
+
+        // the filler only counts by its length
+        let padded = format!("{} Thanks for the great work!", reordered);
+        let score = alignment_similarity(report, &padded);
+        let aligned = 2.0
+            * "Deno.kill fails on windows.It throws a TypeError.SIGBREAK fails too.".len() as f64;
+        let filler = "Thanks for the great work!".len() as f64;
+        assert!((score - aligned / (aligned + filler)).abs() < 1e-9);
+
+        assert_eq!(alignment_similarity("", ""), 1.0);
+        assert_eq!(alignment_similarity(report, "Unrelated."), 0.0);
+        assert!(Metric::parse("jaro").is_err());
+    }
+}
@@ -0,0 +1,547 @@
+//! Retrieving the candidates with a cheap measure before scoring them with the edit distance.
+//!
+//! Scoring every post by the edit distance of its text is quadratic in the length of the text,
+//! which gets slow for large stores of long posts. A pipeline first ranks the posts by a measure
+//! which is linear in the length, keeps the top `candidates` of them, and only re-scores those
+//! with the edit distance. The threshold and the number of matches are applied last, so with
+//! the default [Metric] the scores are the same as the ones of a full scan, only the matches
+//! the first stage misses are lost.
+
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    codec::{Reader, Writer},
+    metric::Metric,
+    Error, Result,
+};
+
+/// How many candidates the first stage keeps by default.
+pub const DEFAULT_CANDIDATES: usize = 100;
+
+/// The cheap measure ranking the candidates in the first stage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retriever {
+    /// The Dice coefficient of the sets of the character n-grams of the texts, case-insensitive.
+    NGram { n: usize },
+    /// The Okapi BM25 relevance of the candidates to the words of the source.
+    Bm25 { k1: f64, b: f64 },
+    /// The share of the bits of the 64-bit SimHash fingerprints of the words which are equal,
+    /// the cheapest one, suited to finding near-duplicates.
+    SimHash,
+}
+
+impl Default for Retriever {
+    fn default() -> Self {
+        Retriever::NGram { n: 3 }
+    }
+}
+
+impl Retriever {
+    /// Returns the retriever named `name` with its default parameters, trigrams for `ngram`,
+    /// and `k1 = 1.2`, `b = 0.75` for `bm25`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "ngram" => Ok(Retriever::default()),
+            "bm25" => Ok(Retriever::Bm25 { k1: 1.2, b: 0.75 }),
+            "simhash" => Ok(Retriever::SimHash),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown retriever '{}', it must be one of ngram, bm25 and simhash",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineOptions {
+    pub retriever: Retriever,
+    /// How many candidates the first stage keeps, defaults to [DEFAULT_CANDIDATES]. Raised to
+    /// the number of requested matches if lower.
+    pub candidates: Option<usize>,
+    /// The metric scoring the retrieved candidates.
+    pub rescorer: Metric,
+}
+
+impl PipelineOptions {
+    pub fn validate(&self) -> Result<()> {
+        match self.retriever {
+            Retriever::NGram { n: 0 } => Err(Error::InvalidArgument(
+                "n must be a positive integer".to_string(),
+            )),
+            Retriever::Bm25 { k1, b }
+                if !(k1.is_finite() && k1 >= 0.0 && (0.0..=1.0).contains(&b)) =>
+            {
+                Err(Error::InvalidArgument(
+                    "k1 must be a non-negative number and b must be between 0 and 1".to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How many candidates the first stage keeps for a query of `top_n` matches.
+    pub fn candidates(&self, top_n: usize) -> usize {
+        self.candidates.unwrap_or(DEFAULT_CANDIDATES).max(top_n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Ranking all the candidates with the [Retriever].
+    Retrieve,
+    /// Scoring the retrieved candidates with the rescorer [Metric].
+    Rerank,
+    /// Applying the threshold and the number of matches.
+    Select,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Retrieve => "retrieve",
+            Stage::Rerank => "rerank",
+            Stage::Select => "select",
+        }
+    }
+}
+
+/// What a stage of a pipeline query did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StageStats {
+    pub stage: Stage,
+    pub time: Duration,
+    /// How many candidates the stage received, the candidates which don't satisfy the filter
+    /// are counted by the first stage.
+    pub input: usize,
+    /// How many candidates the stage passed on.
+    pub output: usize,
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// The sorted, distinct hashes of the character n-grams of `text`, a text shorter than `n` is
+/// taken as a whole.
+fn ngrams(text: &str, n: usize) -> Vec<u64> {
+    let chars: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut grams: Vec<u64> = if chars.len() <= n {
+        vec![hash(&chars)]
+    } else {
+        chars.windows(n).map(hash).collect()
+    };
+
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+fn dice(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+fn simhash(text: &str) -> u64 {
+    let mut weights = [0i64; 64];
+
+    for word in words(text) {
+        let hash = hash(&word);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |fingerprint, (bit, _)| fingerprint | 1 << bit)
+}
+
+#[derive(Debug)]
+enum Sketches {
+    NGram {
+        n: usize,
+        documents: Vec<Vec<u64>>,
+    },
+    Bm25 {
+        k1: f64,
+        b: f64,
+        /// The documents containing each word, along with the number of occurrences.
+        postings: HashMap<String, Vec<(usize, u32)>>,
+        lengths: Vec<u32>,
+        average_length: f64,
+    },
+    SimHash(Vec<u64>),
+}
+
+/// The sketches of a set of documents the first stage of a pipeline ranks, built once for all
+/// the queries with the same [Retriever].
+#[derive(Debug)]
+pub struct TextIndex {
+    retriever: Retriever,
+    sketches: Sketches,
+}
+
+impl TextIndex {
+    /// Builds the index of `len` documents, the text of each is given by `text`.
+    pub fn build(retriever: Retriever, len: usize, text: impl Fn(usize) -> String + Sync) -> Self {
+        let sketches = match retriever {
+            Retriever::NGram { n } => Sketches::NGram {
+                n,
+                documents: (0..len)
+                    .into_par_iter()
+                    .map(|i| ngrams(&text(i), n))
+                    .collect(),
+            },
+            Retriever::Bm25 { k1, b } => {
+                let documents: Vec<HashMap<String, u32>> = (0..len)
+                    .into_par_iter()
+                    .map(|i| {
+                        let mut counts = HashMap::new();
+                        for word in words(&text(i)) {
+                            *counts.entry(word).or_insert(0) += 1;
+                        }
+                        counts
+                    })
+                    .collect();
+                let lengths: Vec<u32> = documents.iter().map(|doc| doc.values().sum()).collect();
+                let mut postings: HashMap<String, Vec<(usize, u32)>> = HashMap::new();
+
+                for (i, doc) in documents.into_iter().enumerate() {
+                    for (word, count) in doc {
+                        postings.entry(word).or_default().push((i, count));
+                    }
+                }
+
+                Sketches::Bm25 {
+                    k1,
+                    b,
+                    postings,
+                    average_length: lengths.iter().map(|&len| len as f64).sum::<f64>()
+                        / len.max(1) as f64,
+                    lengths,
+                }
+            }
+            Retriever::SimHash => Sketches::SimHash(
+                (0..len)
+                    .into_par_iter()
+                    .map(|i| simhash(&text(i)))
+                    .collect(),
+            ),
+        };
+
+        TextIndex {
+            retriever,
+            sketches,
+        }
+    }
+
+    pub fn retriever(&self) -> &Retriever {
+        &self.retriever
+    }
+
+    /// Returns the positions and the scores of the `k` documents ranked the highest for
+    /// `query`, leaving out the ones `keep` rejects. Documents with the same score keep their
+    /// order, BM25 only ranks the documents sharing a word with the query.
+    pub fn top_k(
+        &self,
+        query: &str,
+        k: usize,
+        keep: impl Fn(usize) -> bool + Sync,
+        parallel: bool,
+    ) -> Vec<(usize, f64)> {
+        let mut ranked: Vec<(usize, f64)> = match &self.sketches {
+            Sketches::NGram { n, documents } => {
+                let query = ngrams(query, *n);
+                let score = |i: usize| keep(i).then(|| (i, dice(&query, &documents[i])));
+
+                if parallel {
+                    (0..documents.len())
+                        .into_par_iter()
+                        .filter_map(score)
+                        .collect()
+                } else {
+                    (0..documents.len()).filter_map(score).collect()
+                }
+            }
+            Sketches::SimHash(documents) => {
+                let query = simhash(query);
+                let score = |i: usize| {
+                    let distance = (query ^ documents[i]).count_ones();
+                    keep(i).then(|| (i, 1.0 - distance as f64 / 64.0))
+                };
+
+                if parallel {
+                    (0..documents.len())
+                        .into_par_iter()
+                        .filter_map(score)
+                        .collect()
+                } else {
+                    (0..documents.len()).filter_map(score).collect()
+                }
+            }
+            Sketches::Bm25 {
+                k1,
+                b,
+                postings,
+                lengths,
+                average_length,
+            } => {
+                let mut query: Vec<String> = words(query).collect();
+                query.sort_unstable();
+                query.dedup();
+
+                let total = lengths.len() as f64;
+                let mut scores: HashMap<usize, f64> = HashMap::new();
+
+                for word in &query {
+                    let Some(documents) = postings.get(word) else {
+                        continue;
+                    };
+                    let found = documents.len() as f64;
+                    let idf = (1.0 + (total - found + 0.5) / (found + 0.5)).ln();
+
+                    for &(i, count) in documents {
+                        let count = count as f64;
+                        let norm = 1.0 - b + b * lengths[i] as f64 / average_length;
+                        *scores.entry(i).or_insert(0.0) +=
+                            idf * count * (k1 + 1.0) / (count + k1 * norm);
+                    }
+                }
+
+                let mut ranked: Vec<(usize, f64)> =
+                    scores.into_iter().filter(|(i, _)| keep(*i)).collect();
+                ranked.sort_unstable_by_key(|(i, _)| *i);
+                ranked
+            }
+        };
+
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        ranked.truncate(k);
+        ranked
+    }
+
+    /// Encodes the index to be saved in a snapshot file, see [TextIndex::decode].
+    pub(crate) fn encode(&self, writer: &mut Writer) {
+        match &self.sketches {
+            Sketches::NGram { n, documents } => {
+                writer.u32(0);
+                writer.length(*n);
+                writer.length(documents.len());
+                for document in documents {
+                    writer.length(document.len());
+                    for &ngram in document {
+                        writer.u64(ngram);
+                    }
+                }
+            }
+            Sketches::Bm25 {
+                k1,
+                b,
+                postings,
+                lengths,
+                average_length,
+            } => {
+                writer.u32(1);
+                writer.f64(*k1);
+                writer.f64(*b);
+                writer.length(lengths.len());
+                for &len in lengths {
+                    writer.u32(len);
+                }
+                writer.f64(*average_length);
+                writer.length(postings.len());
+                for (word, documents) in postings {
+                    writer.str(word);
+                    writer.length(documents.len());
+                    for &(i, count) in documents {
+                        writer.length(i);
+                        writer.u32(count);
+                    }
+                }
+            }
+            Sketches::SimHash(documents) => {
+                writer.u32(2);
+                writer.length(documents.len());
+                for &document in documents {
+                    writer.u64(document);
+                }
+            }
+        }
+    }
+
+    /// Restores an index of `len` documents encoded by [TextIndex::encode].
+    pub(crate) fn decode(bytes: &[u8], len: usize) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "text index");
+        let document_count = |reader: &mut Reader, item_len: usize| -> Result<usize> {
+            match reader.length(item_len)? {
+                count if count == len => Ok(count),
+                _ => Err(reader.invalid("document count mismatch")),
+            }
+        };
+
+        let (retriever, sketches) = match reader.u32()? {
+            0 => {
+                let n = reader.length(0)?;
+                let count = document_count(&mut reader, 8)?;
+                let documents = (0..count)
+                    .map(|_| {
+                        let len = reader.length(8)?;
+                        (0..len).map(|_| reader.u64()).collect()
+                    })
+                    .collect::<Result<_>>()?;
+
+                (Retriever::NGram { n }, Sketches::NGram { n, documents })
+            }
+            1 => {
+                let k1 = reader.f64()?;
+                let b = reader.f64()?;
+                let count = document_count(&mut reader, 4)?;
+                let lengths = (0..count).map(|_| reader.u32()).collect::<Result<_>>()?;
+                let average_length = reader.f64()?;
+                let words = reader.length(16)?;
+                let mut postings = HashMap::with_capacity(words);
+
+                for _ in 0..words {
+                    let word = reader.str()?.to_string();
+                    let found = reader.length(12)?;
+                    let documents = (0..found)
+                        .map(|_| match (reader.length(0)?, reader.u32()?) {
+                            (i, count) if i < len => Ok((i, count)),
+                            _ => Err(reader.invalid("document out of bounds")),
+                        })
+                        .collect::<Result<_>>()?;
+                    postings.insert(word, documents);
+                }
+
+                (
+                    Retriever::Bm25 { k1, b },
+                    Sketches::Bm25 {
+                        k1,
+                        b,
+                        postings,
+                        lengths,
+                        average_length,
+                    },
+                )
+            }
+            2 => {
+                let count = document_count(&mut reader, 8)?;
+                let documents = (0..count).map(|_| reader.u64()).collect::<Result<_>>()?;
+
+                (Retriever::SimHash, Sketches::SimHash(documents))
+            }
+            _ => return Err(reader.invalid("unknown retriever")),
+        };
+        reader.finish()?;
+
+        Ok(TextIndex {
+            retriever,
+            sketches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENTS: [&str; 4] = [
+        "Deno.kill on windows only supports SIGINT and SIGBREAK",
+        "denojs on termux like nodejs",
+        "A smooth download for Deno.js like Node.js on Termux",
+        "Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK) in Deno.kill",
+    ];
+
+    fn index(retriever: Retriever) -> TextIndex {
+        TextIndex::build(retriever, DOCUMENTS.len(), |i| DOCUMENTS[i].to_string())
+    }
+
+    #[test]
+    fn test_text_index_top_k() {
+        let query = "Deno.kill not working on windows, SIGINT is not supported";
+
+        for retriever in ["ngram", "bm25", "simhash"] {
+            let index = index(Retriever::parse(retriever).unwrap());
+            let top: Vec<usize> = index
+                .top_k(query, 2, |_| true, false)
+                .into_iter()
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(top.len(), 2, "{}", retriever);
+            assert!(top.contains(&0), "{}", retriever);
+
+            let parallel = index.top_k(query, 4, |_| true, true);
+            assert_eq!(parallel, index.top_k(query, 4, |_| true, false));
+        }
+
+        let index = index(Retriever::Bm25 { k1: 1.2, b: 0.75 });
+        let top = index.top_k("termux", 4, |i| i != 2, false);
+        assert_eq!(top.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_text_index_encode_and_decode() {
+        let query = "Deno.kill not working on windows, SIGINT is not supported";
+
+        for retriever in ["ngram", "bm25", "simhash"] {
+            let index = index(Retriever::parse(retriever).unwrap());
+            let mut writer = Writer::default();
+            index.encode(&mut writer);
+
+            let decoded = TextIndex::decode(&writer.bytes, DOCUMENTS.len()).unwrap();
+            assert_eq!(decoded.retriever(), index.retriever());
+            assert_eq!(
+                decoded.top_k(query, 4, |_| true, false),
+                index.top_k(query, 4, |_| true, false),
+                "{}",
+                retriever
+            );
+
+            assert!(TextIndex::decode(&writer.bytes, DOCUMENTS.len() - 1).is_err());
+            assert!(TextIndex::decode(&writer.bytes[1..], DOCUMENTS.len()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_pipeline_options_validate() {
+        assert!(Retriever::parse("tfidf").is_err());
+        assert!(PipelineOptions {
+            retriever: Retriever::NGram { n: 0 },
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
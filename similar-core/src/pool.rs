@@ -0,0 +1,509 @@
+//! The threads the queries of a store run on.
+//!
+//! By default a store scores its candidates on the rayon global pool, which is shared with
+//! everything else in the process. A [QueryPool] gives the store its own threads, bounds how
+//! many queries run at the same time, and scans small candidate sets on the calling thread,
+//! where splitting the work costs more than it saves. [QueryPool::spawn] runs a query on the
+//! threads of the pool instead, and queues it without holding any thread while it waits.
+
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::{Error, Result};
+
+/// Candidate sets smaller than this are scanned on the calling thread by default.
+pub const DEFAULT_SEQUENTIAL_THRESHOLD: usize = 256;
+
+#[derive(Debug, Clone, Default)]
+pub struct PoolOptions {
+    /// How many threads score the candidates, the rayon global pool is used when omitted.
+    pub threads: Option<usize>,
+    /// The prefix of the names of the threads, followed by their indices, defaults to
+    /// `similar-`. Only used along with `threads`.
+    pub thread_name_prefix: Option<String>,
+    /// How many queries may run at the same time, unlimited when omitted.
+    pub max_concurrent_queries: Option<usize>,
+    /// How many queries may wait for one of the `max_concurrent_queries` to finish, the excess
+    /// ones are rejected with [Error::Overloaded]. Unlimited when omitted, `0` rejects every
+    /// query which can't run right away.
+    pub max_queued_queries: Option<usize>,
+    /// Candidate sets smaller than this are scanned on the calling thread, defaults to
+    /// [DEFAULT_SEQUENTIAL_THRESHOLD].
+    pub sequential_threshold: Option<usize>,
+}
+
+/// A query spawned by [QueryPool::spawn], started once it gets a slot, or with the error it's
+/// rejected with.
+type Job = Box<dyn FnOnce(Result<()>) + Send>;
+
+#[derive(Default)]
+struct Slots {
+    running: usize,
+    queued: usize,
+    /// The spawned queries waiting for a slot, which they take before the blocked callers of
+    /// [QueryPool::run].
+    jobs: VecDeque<Job>,
+}
+
+impl fmt::Debug for Slots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slots")
+            .field("running", &self.running)
+            .field("queued", &self.queued)
+            .field("jobs", &self.jobs.len())
+            .finish()
+    }
+}
+
+thread_local! {
+    /// The limiter whose slot is held by the spawned query running on this thread.
+    static ADMITTED: Cell<*const Limiter> = const { Cell::new(ptr::null()) };
+}
+
+/// Bounds the queries running at the same time, queueing or rejecting the excess ones.
+#[derive(Debug)]
+struct Limiter {
+    max_running: usize,
+    max_queued: Option<usize>,
+    slots: Mutex<Slots>,
+    released: Condvar,
+}
+
+impl Limiter {
+    fn slots(&self) -> MutexGuard<'_, Slots> {
+        // the counters are consistent after every statement, so a panic can't corrupt them
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn overloaded(&self, slots: &Slots) -> Option<Error> {
+        self.max_queued
+            .is_some_and(|max| slots.queued >= max)
+            .then(|| {
+                Error::Overloaded(format!(
+                    "Too many queries, {} running and {} queued",
+                    slots.running, slots.queued
+                ))
+            })
+    }
+
+    fn acquire(&self) -> Result<Permit<'_>> {
+        let mut slots = self.slots();
+
+        if slots.running >= self.max_running {
+            if let Some(err) = self.overloaded(&slots) {
+                return Err(err);
+            }
+
+            slots.queued += 1;
+            slots = self
+                .released
+                .wait_while(slots, |slots| slots.running >= self.max_running)
+                .unwrap_or_else(PoisonError::into_inner);
+            slots.queued -= 1;
+        }
+
+        slots.running += 1;
+        Ok(Permit { limiter: self })
+    }
+
+    /// Starts `job` if a slot is free, queues it until one is released, or rejects it, without
+    /// blocking the calling thread.
+    fn admit(&self, job: Job) {
+        let mut slots = self.slots();
+
+        if slots.running < self.max_running {
+            slots.running += 1;
+            drop(slots);
+            job(Ok(()));
+        } else if let Some(err) = self.overloaded(&slots) {
+            drop(slots);
+            job(Err(err));
+        } else {
+            slots.queued += 1;
+            slots.jobs.push_back(job);
+        }
+    }
+
+    /// Hands the slot over to the first queued job, or wakes up a blocked caller.
+    fn release(&self) {
+        let mut slots = self.slots();
+
+        match slots.jobs.pop_front() {
+            Some(job) => {
+                slots.queued -= 1;
+                drop(slots);
+                job(Ok(()));
+            }
+            None => {
+                slots.running -= 1;
+                drop(slots);
+                self.released.notify_one();
+            }
+        }
+    }
+
+    /// Whether the spawned query running on this thread holds a slot of this limiter.
+    fn admitted(&self) -> bool {
+        ptr::eq(ADMITTED.get(), self)
+    }
+}
+
+struct Permit<'a> {
+    limiter: &'a Limiter,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// The slot held by a spawned query while it runs, released even if it panics.
+struct Admission {
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl Admission {
+    fn enter(limiter: Option<Arc<Limiter>>) -> Self {
+        if let Some(limiter) = &limiter {
+            ADMITTED.set(Arc::as_ptr(limiter));
+        }
+        Admission { limiter }
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Some(limiter) = &self.limiter {
+            ADMITTED.set(ptr::null());
+            limiter.release();
+        }
+    }
+}
+
+/// Where and how many queries of a store run, cloning it is cheap and the clones share the
+/// threads and the limit.
+#[derive(Clone)]
+pub struct QueryPool {
+    threads: Option<Arc<ThreadPool>>,
+    limiter: Option<Arc<Limiter>>,
+    sequential_threshold: usize,
+}
+
+impl Default for QueryPool {
+    fn default() -> Self {
+        QueryPool {
+            threads: None,
+            limiter: None,
+            sequential_threshold: DEFAULT_SEQUENTIAL_THRESHOLD,
+        }
+    }
+}
+
+impl fmt::Debug for QueryPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryPool")
+            .field(
+                "threads",
+                &self.threads.as_ref().map(|pool| pool.current_num_threads()),
+            )
+            .field("limiter", &self.limiter)
+            .field("sequential_threshold", &self.sequential_threshold)
+            .finish()
+    }
+}
+
+impl QueryPool {
+    pub fn new(options: &PoolOptions) -> Result<Self> {
+        let threads = match options.threads {
+            Some(0) => {
+                return Err(Error::InvalidArgument(
+                    "threads must be greater than 0".to_string(),
+                ))
+            }
+            Some(threads) => {
+                let prefix = options
+                    .thread_name_prefix
+                    .clone()
+                    .unwrap_or_else(|| "similar-".to_string());
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(move |i| format!("{}{}", prefix, i))
+                    .build()
+                    .map_err(|e| {
+                        Error::InvalidArgument(format!("Cannot start the threads: {}", e))
+                    })?;
+
+                Some(Arc::new(pool))
+            }
+            None => None,
+        };
+        let limiter = match options.max_concurrent_queries {
+            Some(0) => {
+                return Err(Error::InvalidArgument(
+                    "max_concurrent_queries must be greater than 0".to_string(),
+                ))
+            }
+            Some(max_running) => Some(Arc::new(Limiter {
+                max_running,
+                max_queued: options.max_queued_queries,
+                slots: Mutex::default(),
+                released: Condvar::new(),
+            })),
+            None => None,
+        };
+
+        Ok(QueryPool {
+            threads,
+            limiter,
+            sequential_threshold: options
+                .sequential_threshold
+                .unwrap_or(DEFAULT_SEQUENTIAL_THRESHOLD),
+        })
+    }
+
+    /// Runs a query over `len` candidates, `query` is told whether to scan them in parallel.
+    ///
+    /// The calling thread is blocked while the query waits for a slot and runs, a query which
+    /// can't be queued is rejected with [Error::Overloaded] without running it. Called by a query
+    /// spawned with [QueryPool::spawn], it runs in the slot of that query.
+    pub fn run<R: Send>(
+        &self,
+        len: usize,
+        query: impl FnOnce(bool) -> Result<R> + Send,
+    ) -> Result<R> {
+        let _permit = self
+            .limiter
+            .as_ref()
+            .filter(|limiter| !limiter.admitted())
+            .map(|limiter| limiter.acquire())
+            .transpose()?;
+        let parallel = len >= self.sequential_threshold;
+
+        match &self.threads {
+            Some(threads) if parallel => threads.install(|| query(true)),
+            _ => query(parallel),
+        }
+    }
+
+    /// Runs `query` on the threads of the pool, or the rayon global pool, once a query may run,
+    /// and calls `done` with its result on the same thread. Unlike [QueryPool::run], no thread
+    /// is held while it waits for a slot, and the calling thread isn't blocked while it runs, so
+    /// the queries of the store `query` makes run in its slot. `done` is called with
+    /// [Error::Overloaded] on the calling thread instead if it can't be queued, and with
+    /// [Error::Panicked] if `query` panics.
+    pub fn spawn<R>(
+        &self,
+        query: impl FnOnce() -> Result<R> + Send + 'static,
+        done: impl FnOnce(Result<R>) + Send + 'static,
+    ) {
+        let threads = self.threads.clone();
+        let limiter = self.limiter.clone();
+        let start = move |admitted: Result<()>| {
+            if let Err(err) = admitted {
+                return done(Err(err));
+            }
+            let run = move || {
+                let _admission = Admission::enter(limiter);
+                let result = panic::catch_unwind(AssertUnwindSafe(query))
+                    .unwrap_or_else(|payload| Err(Error::Panicked(panic_message(payload))));
+                done(result);
+            };
+
+            match threads {
+                Some(threads) => threads.spawn(run),
+                None => rayon::spawn(run),
+            }
+        };
+
+        match &self.limiter {
+            Some(limiter) => limiter.admit(Box::new(start)),
+            None => start(Ok(())),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(reason) => *reason,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(reason) => reason.to_string(),
+            Err(_) => "unknown reason".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn test_query_pool_threads() {
+        let pool = QueryPool::new(&PoolOptions {
+            threads: Some(2),
+            thread_name_prefix: Some("test-pool-".to_string()),
+            sequential_threshold: Some(10),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let (parallel, name) = pool
+            .run(10, |parallel| {
+                Ok((parallel, thread::current().name().map(str::to_string)))
+            })
+            .unwrap();
+        assert!(parallel);
+        assert!(name.unwrap().starts_with("test-pool-"));
+
+        let (parallel, name) = pool
+            .run(9, |parallel| {
+                Ok((parallel, thread::current().name().map(str::to_string)))
+            })
+            .unwrap();
+        assert!(!parallel);
+        assert_eq!(name, thread::current().name().map(str::to_string));
+
+        assert!(matches!(
+            QueryPool::new(&PoolOptions {
+                threads: Some(0),
+                ..Default::default()
+            }),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_query_pool_limit() {
+        let pool = QueryPool::new(&PoolOptions {
+            max_concurrent_queries: Some(1),
+            max_queued_queries: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+
+        let running = thread::spawn({
+            let pool = pool.clone();
+            move || {
+                pool.run(0, move |_| {
+                    started_tx.send(()).unwrap();
+                    finish_rx.recv().unwrap();
+                    Ok(1)
+                })
+            }
+        });
+        started_rx.recv().unwrap();
+
+        let queued = thread::spawn({
+            let pool = pool.clone();
+            move || pool.run(0, |_| Ok(2))
+        });
+        while pool.limiter.as_ref().unwrap().slots().queued == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // both the slot and the queue are taken
+        assert!(matches!(pool.run(0, |_| Ok(3)), Err(Error::Overloaded(_))));
+
+        finish_tx.send(()).unwrap();
+        assert_eq!(running.join().unwrap().unwrap(), 1);
+        assert_eq!(queued.join().unwrap().unwrap(), 2);
+        assert_eq!(pool.run(0, |_| Ok(4)).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_query_pool_spawn() {
+        let pool = QueryPool::new(&PoolOptions {
+            threads: Some(2),
+            thread_name_prefix: Some("test-spawn-".to_string()),
+            max_concurrent_queries: Some(1),
+            max_queued_queries: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        pool.spawn(
+            {
+                let pool = pool.clone();
+                move || {
+                    finish_rx.recv().unwrap();
+                    // the query runs in the slot of the spawned one
+                    pool.run(0, |_| Ok(thread::current().name().map(str::to_string)))
+                }
+            },
+            {
+                let done_tx = done_tx.clone();
+                move |name| done_tx.send(name.unwrap().unwrap()).unwrap()
+            },
+        );
+
+        // the second one is queued without blocking this thread, the third one is rejected
+        pool.spawn(
+            || Ok("queued".to_string()),
+            move |queued| done_tx.send(queued.unwrap()).unwrap(),
+        );
+        assert_eq!(pool.limiter.as_ref().unwrap().slots().queued, 1);
+        let (rejected_tx, rejected_rx) = mpsc::channel();
+        pool.spawn(
+            || Ok(()),
+            move |admitted| rejected_tx.send(admitted).unwrap(),
+        );
+        assert!(matches!(
+            rejected_rx.try_recv().unwrap(),
+            Err(Error::Overloaded(_))
+        ));
+        assert!(matches!(pool.run(0, |_| Ok(1)), Err(Error::Overloaded(_))));
+
+        finish_tx.send(()).unwrap();
+        assert!(done_rx.recv().unwrap().starts_with("test-spawn-"));
+        assert_eq!(done_rx.recv().unwrap(), "queued");
+
+        // the slots are released once the spawned queries finish
+        while pool.limiter.as_ref().unwrap().slots().running > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(pool.run(0, |_| Ok(2)).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_query_pool_spawn_panic() {
+        let pool = QueryPool::new(&PoolOptions {
+            threads: Some(1),
+            max_concurrent_queries: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        pool.spawn(
+            || -> Result<()> { panic!("boom") },
+            move |result| done_tx.send(result).unwrap(),
+        );
+        assert!(matches!(
+            done_rx.recv().unwrap(),
+            Err(Error::Panicked(reason)) if reason == "boom"
+        ));
+
+        // the slot and the thread are still usable
+        while pool.limiter.as_ref().unwrap().slots().running > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (done_tx, done_rx) = mpsc::channel();
+        pool.spawn(|| Ok(1), move |result| done_tx.send(result).unwrap());
+        assert_eq!(done_rx.recv().unwrap().unwrap(), 1);
+    }
+}
@@ -0,0 +1,664 @@
+//! Finding similar blog posts by the edit distance of their titles and contents.
+
+use std::{
+    cmp::Ordering,
+    time::{Duration, Instant},
+};
+
+use rapidfuzz::distance::levenshtein::normalized_similarity;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::{Map, Value};
+
+use crate::{
+    chunk::ChunkMatch,
+    filter::Filter,
+    pipeline::StageStats,
+    symbol::{extract, SymbolIndex, SymbolOptions, Symbols},
+    Error, Result,
+};
+
+mod store;
+
+pub use store::{
+    ext::{
+        csv::CsvOptions,
+        db::{DbColumns, DbOptions},
+        jsonl::JsonlOptions,
+        mapped::MappedPosts,
+    },
+    ContentOptions, PostStore, PostsSnapshot,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PostData {
+    /// An optional identifier of the post, such as its primary key in the source data.
+    pub id: Option<String>,
+    pub title: String,
+    pub content: String,
+    /// Structured attributes of the post, such as `category`, `tags`, `author`, `language` and
+    /// `createdAt`, which can be used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+    /// A vector computed upstream from the post, such as a sentence embedding, which is indexed
+    /// for [PostStore::find_nearest_posts].
+    pub embedding: Option<Vec<f32>>,
+}
+
+/// A borrowed view of a candidate post, which may live in a `Vec<PostData>` or be read straight
+/// from a memory mapped snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct PostRef<'a> {
+    pub id: Option<&'a str>,
+    pub title: &'a str,
+    pub content: &'a str,
+    pub metadata: Option<&'a Map<String, Value>>,
+    pub embedding: Option<&'a [f32]>,
+}
+
+impl<'a> From<&'a PostData> for PostRef<'a> {
+    fn from(post: &'a PostData) -> Self {
+        PostRef {
+            id: post.id.as_deref(),
+            title: &post.title,
+            content: &post.content,
+            metadata: post.metadata.as_ref(),
+            embedding: post.embedding.as_deref(),
+        }
+    }
+}
+
+impl PostRef<'_> {
+    pub fn to_post_data(&self) -> PostData {
+        PostData {
+            id: self.id.map(|id| id.to_string()),
+            title: self.title.to_string(),
+            content: self.content.to_string(),
+            metadata: self.metadata.cloned(),
+            embedding: self.embedding.map(<[f32]>::to_vec),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub target: PostData,
+    pub score: f64,
+    /// The rank of the post among the lexical matches, counting from 1. Only set by hybrid
+    /// queries, see [crate::hybrid].
+    pub lexical_rank: Option<usize>,
+    /// The rank of the post among the nearest embeddings, counting from 1. Only set by hybrid
+    /// queries.
+    pub vector_rank: Option<usize>,
+    /// The best matching pair of chunks of the contents of the source and the post, only set
+    /// when scoring by chunks, see [PostStore::with_chunking].
+    pub chunks: Option<ChunkMatch>,
+    /// The names of the facets the post shares with the source, only set when the store
+    /// extracts facets, see [PostStore::with_facets].
+    pub shared_facets: Vec<String>,
+    /// The fingerprint of the stack trace of the post, only set when the store compares traces,
+    /// see [PostStore::with_traces].
+    pub fingerprint: Option<String>,
+    /// The symbols the post shares with the source, only set when the store compares symbols,
+    /// see [PostStore::with_symbols].
+    pub shared_symbols: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FindTopNResult {
+    pub matches: Vec<Match>,
+    /// How much time is used for processing.
+    pub process_time: Duration,
+    /// The generation of the store snapshot the query was run against, only set when querying a
+    /// [PostStore].
+    pub generation: Option<u64>,
+    /// What each stage did, only set when the query ran through a pipeline, see
+    /// [PostStore::with_pipeline].
+    pub stages: Vec<StageStats>,
+}
+
+/// Returns the weights of the title and the content, proportional to their lengths in the
+/// source, so a long content isn't outweighed by a short title.
+pub fn get_weights(source: &PostData) -> Result<(f64, f64)> {
+    let title_chars = source.title.chars().count();
+    let content_chars = source.content.chars().count();
+    let total_chars = title_chars + content_chars;
+
+    if total_chars == 0 {
+        Err(Error::InvalidArgument("source is invalid".to_string()))
+    } else {
+        Ok((
+            title_chars as f64 / total_chars as f64,
+            content_chars as f64 / total_chars as f64,
+        ))
+    }
+}
+
+/// The text of a post ranked by the first stage of a pipeline, and which its facets, its stack
+/// trace and its symbols are extracted from.
+pub(crate) fn post_text(post: &PostRef) -> String {
+    format!("{}\n{}", post.title, post.content)
+}
+
+/// The symbols mentioned by a post, the scans of [crate::post] and the stores extract them
+/// alike, so they score the same post the same.
+pub(crate) fn post_symbols(post: &PostRef) -> Symbols {
+    extract(&post_text(post))
+}
+
+fn sort_and_truncate(mut matches: Vec<Match>, top_n: usize) -> Vec<Match> {
+    matches.sort_by(|a, b| {
+        let order = b.score.partial_cmp(&a.score);
+        order.unwrap_or(Ordering::Equal)
+    });
+    matches.truncate(top_n);
+    matches
+}
+
+/// Finds the `top_n` candidates most similar to `source`, scoring them one by one on the
+/// current thread.
+pub fn find_similar_posts(
+    source: &PostData,
+    candidates: Vec<PostData>,
+    top_n: usize,
+) -> Result<FindTopNResult> {
+    let start = Instant::now();
+    let (title_weight, content_weight) = get_weights(source)?;
+    let mut matches = vec![];
+
+    for candidate in candidates.into_iter() {
+        let title_score =
+            normalized_similarity(source.title.chars(), candidate.title.chars()) * title_weight;
+        let content_score =
+            normalized_similarity(source.content.chars(), candidate.content.chars())
+                * content_weight;
+        let score = title_score + content_score;
+
+        // 0.5 is the threshold to consider a match
+        if score > 0.5 {
+            matches.push(Match {
+                target: candidate,
+                score,
+                lexical_rank: None,
+                vector_rank: None,
+                chunks: None,
+                shared_facets: Vec::new(),
+                fingerprint: None,
+                shared_symbols: Vec::new(),
+            });
+        }
+    }
+
+    Ok(FindTopNResult {
+        matches: sort_and_truncate(matches, top_n),
+        process_time: start.elapsed(),
+        generation: None,
+        stages: Vec::new(),
+    })
+}
+
+/// The edit distance similarity of the title and the content of a candidate to the ones of
+/// `source`, weighted by `weights`. The threshold isn't applied.
+fn text_score(
+    source: &PostData,
+    (title_weight, content_weight): (f64, f64),
+    candidate: &PostRef,
+) -> f64 {
+    let title_score =
+        normalized_similarity(source.title.chars(), candidate.title.chars()) * title_weight;
+    let content_score =
+        normalized_similarity(source.content.chars(), candidate.content.chars()) * content_weight;
+    title_score + content_score
+}
+
+/// The symbols of the source, and how many of the candidates mention each of them, for the
+/// scans comparing symbols. All the candidates are counted, whether the filter admits them or
+/// not, as the stores count all the posts of a snapshot, so a filter never changes the scores.
+struct SymbolScoring<'a> {
+    options: &'a SymbolOptions,
+    source: Symbols,
+    index: SymbolIndex,
+}
+
+impl<'a> SymbolScoring<'a> {
+    fn new(
+        options: &'a SymbolOptions,
+        source: &PostData,
+        candidates: &[(Symbols, PostRef)],
+    ) -> Self {
+        SymbolScoring {
+            options,
+            source: post_symbols(&source.into()),
+            index: SymbolIndex::build(candidates.iter().map(|(symbols, _)| symbols)),
+        }
+    }
+}
+
+/// What the parallel and the sequential scans share, so they only differ in how they iterate
+/// the candidates and score them alike.
+struct Scan<'a> {
+    source: &'a PostData,
+    weights: (f64, f64),
+    filter: Option<&'a Filter>,
+    start: Instant,
+}
+
+impl<'a> Scan<'a> {
+    fn new(
+        source: &'a PostData,
+        filter: Option<&'a Filter>,
+        symbols: Option<&SymbolOptions>,
+    ) -> Result<Self> {
+        let start = Instant::now();
+        let weights = get_weights(source)?;
+
+        if let Some(options) = symbols {
+            options.validate()?;
+        }
+
+        Ok(Scan {
+            source,
+            weights,
+            filter,
+            start,
+        })
+    }
+
+    /// Whether the candidate satisfies the filter, if any.
+    fn admits(&self, candidate: &PostRef) -> bool {
+        self.filter
+            .is_none_or(|filter| filter.matches(candidate.metadata))
+    }
+
+    /// Scores a candidate by the similarity of its texts, combined with the overlap of its
+    /// symbols when `symbols` is given, returning `None` when it isn't a match.
+    fn score(
+        &self,
+        candidate: &PostRef,
+        symbols: Option<(&SymbolScoring, &Symbols)>,
+    ) -> Option<(f64, Vec<String>)> {
+        let score = text_score(self.source, self.weights, candidate);
+        let (score, shared_symbols) = match symbols {
+            Some((scoring, symbols)) => {
+                scoring
+                    .options
+                    .score(score, &scoring.source, symbols, &scoring.index)
+            }
+            None => (score, Vec::new()),
+        };
+
+        // 0.5 is the threshold to consider a match
+        (score > 0.5).then_some((score, shared_symbols))
+    }
+
+    /// Like [Scan::score], but returns the match with a copy of the candidate.
+    fn score_candidate(
+        &self,
+        candidate: PostRef,
+        symbols: Option<(&SymbolScoring, &Symbols)>,
+    ) -> Option<Match> {
+        let (score, shared_symbols) = self.score(&candidate, symbols)?;
+
+        Some(Match {
+            target: candidate.to_post_data(),
+            score,
+            lexical_rank: None,
+            vector_rank: None,
+            chunks: None,
+            shared_facets: Vec::new(),
+            fingerprint: None,
+            shared_symbols,
+        })
+    }
+
+    /// Like [Scan::score], but returns the match by the position of the candidate.
+    fn rank_candidate(&self, (index, candidate): (usize, PostRef)) -> Option<RankedMatch> {
+        let (score, _) = self.score(&candidate, None)?;
+        Some(RankedMatch { index, score })
+    }
+
+    fn top_n(self, matches: Vec<Match>, top_n: usize) -> FindTopNResult {
+        FindTopNResult {
+            matches: sort_and_truncate(matches, top_n),
+            process_time: self.start.elapsed(),
+            generation: None,
+            stages: Vec::new(),
+        }
+    }
+
+    fn ranked(self, matches: Vec<RankedMatch>, top_n: usize) -> RankResult {
+        RankResult {
+            matches: sort_and_truncate_ranked(matches, top_n),
+            process_time: self.start.elapsed(),
+            generation: None,
+        }
+    }
+}
+
+/// Finds the `top_n` candidates most similar to `source`, scoring them in parallel. When
+/// `filter` is given, only the candidates whose metadata satisfy it are scored. When `symbols`
+/// is given, the overlap of the symbols mentioned by the candidates with the ones of the source
+/// is combined with their scores, the symbols mentioned by fewer of all the candidates weighing
+/// more, and each match reports the symbols it shares.
+pub fn find_similar_posts_parallel<'a>(
+    source: &PostData,
+    candidates: impl ParallelIterator<Item = PostRef<'a>>,
+    top_n: usize,
+    filter: Option<&Filter>,
+    symbols: Option<&SymbolOptions>,
+) -> Result<FindTopNResult> {
+    let scan = Scan::new(source, filter, symbols)?;
+
+    let matches: Vec<Match> = match symbols {
+        Some(options) => {
+            let candidates: Vec<(Symbols, PostRef)> = candidates
+                .map(|candidate| (post_symbols(&candidate), candidate))
+                .collect();
+            let scoring = SymbolScoring::new(options, source, &candidates);
+
+            candidates
+                .into_par_iter()
+                .filter(|(_, candidate)| scan.admits(candidate))
+                .filter_map(|(symbols, candidate)| {
+                    scan.score_candidate(candidate, Some((&scoring, &symbols)))
+                })
+                .collect()
+        }
+        None => candidates
+            .filter(|candidate| scan.admits(candidate))
+            .filter_map(|candidate| scan.score_candidate(candidate, None))
+            .collect(),
+    };
+
+    Ok(scan.top_n(matches, top_n))
+}
+
+/// Like [find_similar_posts_parallel], but scores the candidates on the current thread, which
+/// is faster for small candidate sets. The scores and the order of the matches are the same.
+pub fn find_similar_posts_sequential<'a>(
+    source: &PostData,
+    candidates: impl Iterator<Item = PostRef<'a>>,
+    top_n: usize,
+    filter: Option<&Filter>,
+    symbols: Option<&SymbolOptions>,
+) -> Result<FindTopNResult> {
+    let scan = Scan::new(source, filter, symbols)?;
+
+    let matches: Vec<Match> = match symbols {
+        Some(options) => {
+            let candidates: Vec<(Symbols, PostRef)> = candidates
+                .map(|candidate| (post_symbols(&candidate), candidate))
+                .collect();
+            let scoring = SymbolScoring::new(options, source, &candidates);
+
+            candidates
+                .into_iter()
+                .filter(|(_, candidate)| scan.admits(candidate))
+                .filter_map(|(symbols, candidate)| {
+                    scan.score_candidate(candidate, Some((&scoring, &symbols)))
+                })
+                .collect()
+        }
+        None => candidates
+            .filter(|candidate| scan.admits(candidate))
+            .filter_map(|candidate| scan.score_candidate(candidate, None))
+            .collect(),
+    };
+
+    Ok(scan.top_n(matches, top_n))
+}
+
+/// A match given by the position of the candidate, so the caller can pick the fields it needs
+/// instead of receiving a copy of the whole post.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankedMatch {
+    pub index: usize,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RankResult {
+    pub matches: Vec<RankedMatch>,
+    /// How much time is used for processing.
+    pub process_time: Duration,
+    /// The generation of the store snapshot the query was run against, only set when querying a
+    /// [PostStore].
+    pub generation: Option<u64>,
+}
+
+/// Like [find_similar_posts_parallel], but the candidates come with their positions and the
+/// matches refer to them instead of copying the posts. The scores and the order of the matches
+/// are the same.
+pub fn rank_similar_posts<'a>(
+    source: &PostData,
+    candidates: impl ParallelIterator<Item = (usize, PostRef<'a>)>,
+    top_n: usize,
+    filter: Option<&Filter>,
+) -> Result<RankResult> {
+    let scan = Scan::new(source, filter, None)?;
+    let matches: Vec<RankedMatch> = candidates
+        .filter(|(_, candidate)| scan.admits(candidate))
+        .filter_map(|candidate| scan.rank_candidate(candidate))
+        .collect();
+
+    Ok(scan.ranked(matches, top_n))
+}
+
+/// Like [rank_similar_posts], but scores the candidates on the current thread.
+pub fn rank_similar_posts_sequential<'a>(
+    source: &PostData,
+    candidates: impl Iterator<Item = (usize, PostRef<'a>)>,
+    top_n: usize,
+    filter: Option<&Filter>,
+) -> Result<RankResult> {
+    let scan = Scan::new(source, filter, None)?;
+    let matches: Vec<RankedMatch> = candidates
+        .filter(|(_, candidate)| scan.admits(candidate))
+        .filter_map(|candidate| scan.rank_candidate(candidate))
+        .collect();
+
+    Ok(scan.ranked(matches, top_n))
+}
+
+fn sort_and_truncate_ranked(mut matches: Vec<RankedMatch>, top_n: usize) -> Vec<RankedMatch> {
+    matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    matches.truncate(top_n);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator};
+    use serde_json::json;
+
+    use super::*;
+
+    #[allow(non_upper_case_globals)]
+    static source: LazyLock<PostData> = LazyLock::new(|| {
+        PostData {
+        id: None,
+        title: "Deno.kill not working on windows".to_string(),
+        content: r#"
+Version: Deno 2.3.3
+OS: Windows 11
+
+Sending a SIGINT OS signal on windows like: Deno.kill(Deno.pid, 'SIGINT');
+Results in: TypeError: Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK), but got SIGINT
+            "#
+        .to_string(),
+        metadata: None,
+        embedding: None,
+    }
+    });
+
+    #[allow(non_upper_case_globals)]
+    static candidates: LazyLock<Vec<PostData>> = LazyLock::new(|| {
+        vec![
+            PostData {
+                id: Some("1".to_string()),
+                title: "Deno.kill on windows".to_string(),
+                content: r#"
+Version: Deno 2.3.3
+OS: Windows 11
+
+Sending a SIGINT OS signal on windows like: Deno.kill(Deno.pid, 'SIGINT');
+Results in: TypeError: Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK), but got SIGINT
+
+Same goes for SIGBREAK
+
+Registering event listeners with: Deno.addSignalListener('SIGINT', doSomething); Works correctly
+            "#
+                .to_string(),
+                metadata: match json!({ "category": "bug", "tags": ["windows", "signal"] }) {
+                    Value::Object(map) => Some(map),
+                    _ => None,
+                },
+                embedding: None,
+            },
+            PostData {
+                id: Some("2".to_string()),
+                title: "denojs on termux like nodejs".to_string(),
+                content: r#"
+We want a smooth download for Deno.js like Node.js, Python, etc., instead of downloading extra
+libraries on Termux. We seek a streamlined download process for Deno.js similar to that of Node.js
+and Python, rather than having to download additional libraries on Termux.
+"#
+                .to_string(),
+                metadata: None,
+                embedding: None,
+            },
+        ]
+    });
+
+    #[test]
+    fn test_get_weights() {
+        let (title_weight, content_weight) = get_weights(&PostData {
+            title: "abc".to_string(),
+            content: "Termux上".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(title_weight, 0.3);
+        assert_eq!(content_weight, 0.7);
+
+        assert!(get_weights(&PostData::default()).is_err());
+    }
+
+    #[test]
+    fn test_find_similar_posts() {
+        let FindTopNResult { matches, .. } =
+            find_similar_posts(&source, candidates.clone(), 1).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].target.title, "Deno.kill on windows");
+    }
+
+    #[test]
+    fn test_find_similar_posts_parallel() {
+        let FindTopNResult { matches, .. } = find_similar_posts_parallel(
+            &source,
+            candidates.par_iter().map(PostRef::from),
+            1,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].target.title, "Deno.kill on windows");
+        assert_eq!(matches[0].target.id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_find_similar_posts_with_symbols() {
+        let options = SymbolOptions::default();
+        let FindTopNResult { matches, .. } = find_similar_posts_parallel(
+            &source,
+            candidates.par_iter().map(PostRef::from),
+            5,
+            None,
+            Some(&options),
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].target.title, "Deno.kill on windows");
+        assert!(matches[0]
+            .shared_symbols
+            .iter()
+            .any(|symbol| symbol == "Deno.kill"));
+
+        let FindTopNResult {
+            matches: sequential,
+            ..
+        } = find_similar_posts_sequential(
+            &source,
+            candidates.iter().map(PostRef::from),
+            5,
+            None,
+            Some(&options),
+        )
+        .unwrap();
+        assert_eq!(sequential.len(), 1);
+        assert_eq!(sequential[0].score, matches[0].score);
+        assert_eq!(sequential[0].shared_symbols, matches[0].shared_symbols);
+    }
+
+    #[test]
+    fn test_find_similar_posts_with_filter() {
+        let filter = Filter::parse(&json!({ "tags": { "$in": ["windows"] } })).unwrap();
+        let FindTopNResult { matches, .. } = find_similar_posts_parallel(
+            &source,
+            candidates.par_iter().map(PostRef::from),
+            1,
+            Some(&filter),
+            None,
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].target.title, "Deno.kill on windows");
+
+        let filter = Filter::parse(&json!({ "category": "feature" })).unwrap();
+        let FindTopNResult { matches, .. } = find_similar_posts_parallel(
+            &source,
+            candidates.par_iter().map(PostRef::from),
+            1,
+            Some(&filter),
+            None,
+        )
+        .unwrap();
+        assert_eq!(matches.len(), 0);
+    }
+
+    #[test]
+    fn test_rank_similar_posts() {
+        let mut posts = candidates.clone();
+        posts.insert(0, posts[0].clone());
+        posts[0].id = Some("0".to_string());
+
+        let expected = find_similar_posts_parallel(
+            &source,
+            posts.par_iter().map(PostRef::from),
+            5,
+            None,
+            None,
+        )
+        .unwrap();
+        let RankResult { matches, .. } = rank_similar_posts(
+            &source,
+            posts
+                .par_iter()
+                .enumerate()
+                .map(|(i, post)| (i, PostRef::from(post))),
+            5,
+            None,
+        )
+        .unwrap();
+
+        // ties keep the order of the candidates, like the matches with copies
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches.len(), expected.matches.len());
+        for (m, expected) in matches.iter().zip(&expected.matches) {
+            assert_eq!(posts[m.index], expected.target);
+            assert_eq!(m.score.to_bits(), expected.score.to_bits());
+        }
+        assert_eq!(matches[0].index, 0);
+    }
+}
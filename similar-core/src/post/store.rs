@@ -0,0 +1,2296 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use arc_swap::ArcSwap;
+use rayon::iter::{
+    IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    boilerplate::{Boilerplate, BoilerplateOptions},
+    chunk::{Aggregation, ChunkIndex, ChunkMatch, ChunkOptions, ChunkedText, Split},
+    facet::{facets_of, FacetExtractor, FacetOptions, Facets},
+    filter::Filter,
+    hybrid::{fuse, HybridOptions},
+    metric::Metric,
+    pipeline::{PipelineOptions, Retriever, Stage, StageStats, TextIndex},
+    pool::QueryPool,
+    post::{
+        find_similar_posts_parallel, find_similar_posts_sequential, get_weights, post_symbols,
+        post_text, rank_similar_posts, rank_similar_posts_sequential,
+        store::ext::mapped::MappedPosts, FindTopNResult, Match, PostData, PostRef, RankResult,
+        RankedMatch,
+    },
+    shared::{Registry, Shared},
+    symbol::{write_symbols, SymbolIndex, SymbolOptions, Symbols},
+    trace::{StackTrace, TraceOptions},
+    vector::{Hnsw, HnswOptions},
+    Error, Result,
+};
+
+pub mod ext;
+
+/// An immutable generation of the posts held by a [PostStore].
+///
+/// Readers grab the current snapshot without blocking, writers build a new snapshot and publish
+/// it atomically, so a long running query never blocks a `preload` and vice versa.
+#[derive(Debug, Default)]
+pub struct PostsSnapshot {
+    pub generation: u64,
+    /// The read-only posts of a memory mapped snapshot file, if the store was opened from one.
+    pub base: Option<Arc<MappedPosts>>,
+    /// The posts held in memory, layered on top of the `base`.
+    pub posts: Arc<Vec<PostData>>,
+    /// The positions of the posts of the `base` which have been replaced or removed, they are
+    /// skipped by the reads while the file stays mapped.
+    removed: Arc<Tombstones>,
+    /// The index over the embeddings of the posts, built by the first nearest neighbor query.
+    vectors: OnceLock<Arc<Hnsw>>,
+    /// The index ranking the candidates in the first stage of a pipeline, built by the first
+    /// pipeline query.
+    texts: OnceLock<Arc<TextIndex>>,
+    /// The chunks of the contents of the posts, split by the first query scoring by chunks.
+    chunks: OnceLock<Arc<ChunkIndex>>,
+    /// The symbols mentioned by the posts, extracted by the first query comparing symbols.
+    symbols: OnceLock<Arc<PostSymbols>>,
+    /// The metadata of the posts with their facets, stack traces and symbols, written by the
+    /// first query of a handle extracting any.
+    annotations: OnceLock<Arc<Annotations>>,
+}
+
+/// The symbols mentioned by the posts of a snapshot by position, extracted from their texts,
+/// and how many posts mention each of them.
+#[derive(Debug, Default)]
+pub struct PostSymbols {
+    symbols: Vec<Symbols>,
+    index: SymbolIndex,
+}
+
+impl PostSymbols {
+    /// The symbols mentioned by the post at position `i`.
+    pub fn get(&self, i: usize) -> Option<&Symbols> {
+        self.symbols.get(i)
+    }
+
+    /// How many posts mention each symbol.
+    pub fn index(&self) -> &SymbolIndex {
+        &self.index
+    }
+
+    /// Returns a copy with the symbols of `posts` added after the existing ones.
+    fn extend(&self, posts: &[PostData]) -> Self {
+        let mut extended = PostSymbols {
+            symbols: self.symbols.clone(),
+            index: self.index.clone(),
+        };
+
+        for post in posts {
+            let symbols = post_symbols(&post.into());
+            extended.index.add(&symbols);
+            extended.symbols.push(symbols);
+        }
+        extended
+    }
+}
+
+/// What the posts are annotated with, given by the [ContentOptions] of a handle.
+#[derive(Debug, Clone, PartialEq)]
+struct AnnotationKey {
+    facets: Option<FacetExtractor>,
+    /// The depth of the traces.
+    traces: Option<usize>,
+    symbols: bool,
+}
+
+impl AnnotationKey {
+    /// Returns `None` if `content` annotates nothing.
+    fn new(content: &ContentOptions) -> Option<Self> {
+        let key = AnnotationKey {
+            facets: content
+                .facets
+                .as_ref()
+                .map(|facets| facets.extractor.clone()),
+            traces: content.traces.as_ref().map(|traces| traces.depth),
+            symbols: content.symbols.is_some(),
+        };
+
+        (key.facets.is_some() || key.traces.is_some() || key.symbols).then_some(key)
+    }
+}
+
+/// The metadata of the posts of a snapshot by position, with their facets, stack traces and
+/// symbols written into it, see [PostsSnapshot::annotations]. The metadata of the posts is
+/// merged into rather than replaced, so the posts themselves, such as the ones of an opened
+/// snapshot file, are never modified.
+#[derive(Debug)]
+pub struct Annotations {
+    key: AnnotationKey,
+    /// `None` where nothing has been written, the metadata of the post is used as is.
+    metadata: Vec<Option<Map<String, Value>>>,
+}
+
+impl Annotations {
+    /// Annotates `post`, returns `None` if nothing has been written.
+    fn annotate(
+        key: &AnnotationKey,
+        post: &PostRef,
+        symbols: Option<&Symbols>,
+    ) -> Option<Map<String, Value>> {
+        let text = post_text(post);
+        let mut metadata = post.metadata.cloned();
+
+        if let Some(extractor) = &key.facets {
+            extractor.annotate(&mut metadata, &text);
+        }
+        if let Some(depth) = key.traces {
+            if let Some(trace) = StackTrace::parse(&text) {
+                trace.annotate(&mut metadata, depth);
+            }
+        }
+        if let Some(symbols) = symbols {
+            write_symbols(&mut metadata, symbols);
+        }
+
+        metadata.filter(|metadata| Some(metadata) != post.metadata)
+    }
+
+    /// Returns a copy with the annotations of `posts` added after the existing ones.
+    fn extend(&self, posts: &[PostData]) -> Self {
+        let mut metadata = self.metadata.clone();
+        metadata.par_extend(posts.par_iter().map(|post| {
+            let symbols = self.key.symbols.then(|| post_symbols(&post.into()));
+            Annotations::annotate(&self.key, &post.into(), symbols.as_ref())
+        }));
+
+        Annotations {
+            key: self.key.clone(),
+            metadata,
+        }
+    }
+
+    /// The metadata of the post at position `i` with its annotations, or `metadata` if it has
+    /// none.
+    pub fn metadata<'a>(
+        &'a self,
+        i: usize,
+        metadata: Option<&'a Map<String, Value>>,
+    ) -> Option<&'a Map<String, Value>> {
+        match self.metadata.get(i) {
+            Some(Some(annotated)) => Some(annotated),
+            _ => metadata,
+        }
+    }
+}
+
+/// A set of positions of mapped posts, one bit per post.
+#[derive(Debug, Clone, Default)]
+struct Tombstones {
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl Tombstones {
+    fn contains(&self, i: usize) -> bool {
+        self.words
+            .get(i / 64)
+            .is_some_and(|word| word & (1 << (i % 64)) != 0)
+    }
+
+    fn insert(&mut self, i: usize) {
+        if i / 64 >= self.words.len() {
+            self.words.resize(i / 64 + 1, 0);
+        }
+        if self.words[i / 64] & (1 << (i % 64)) == 0 {
+            self.words[i / 64] |= 1 << (i % 64);
+            self.count += 1;
+        }
+    }
+}
+
+/// How the contents of the posts are compared by [PostsSnapshot::find_similar_posts_by] and
+/// [PostsSnapshot::find_similar_posts_pipeline].
+#[derive(Debug, Clone, Default)]
+pub struct ContentOptions {
+    /// Scores the contents by their chunks instead of as a whole.
+    pub chunking: Option<ChunkOptions>,
+    /// Removed from the contents before they are scored, or the chunks made of it are skipped.
+    pub boilerplate: Option<Arc<Boilerplate>>,
+    /// Reports the facets the matches share with the source, and boosts their scores.
+    pub facets: Option<Arc<FacetOptions>>,
+    /// Compares the stack traces of the matches with the one of the source.
+    pub traces: Option<Arc<TraceOptions>>,
+    /// Compares the symbols the matches mention with the ones of the source.
+    pub symbols: Option<Arc<SymbolOptions>>,
+}
+
+/// Scores the candidates of a pipeline or a query with another metric, by chunks, without
+/// the boilerplate, with facets, with traces or with symbols, which can't use the plain scans of
+/// [crate::post].
+struct Scorer<'a> {
+    source: &'a PostData,
+    /// The content of the source without its boilerplate.
+    content: Cow<'a, str>,
+    weights: (f64, f64),
+    metric: Metric,
+    chunks: Option<(ChunkedText<'a>, Arc<ChunkIndex>, Aggregation)>,
+    boilerplate: Option<&'a Boilerplate>,
+    /// The facets of the source, extracted from its text.
+    facets: Option<(&'a FacetOptions, Facets)>,
+    /// The stack trace of the source with its fingerprint, if it has one.
+    traces: Option<(&'a TraceOptions, Option<(StackTrace, String)>)>,
+    /// The symbols of the source, and the ones of the posts.
+    symbols: Option<(&'a SymbolOptions, Symbols, Arc<PostSymbols>)>,
+}
+
+impl Scorer<'_> {
+    /// Scores the candidate at position `index`, the content is scored by its chunks if they are
+    /// given, otherwise by `metric` like the title. The threshold isn't applied.
+    fn score(&self, index: usize, candidate: &PostRef) -> Scored {
+        let (title_weight, content_weight) = self.weights;
+        let title_score = self.metric.similarity(&self.source.title, candidate.title);
+        let (content_score, chunks) = match (&self.chunks, self.boilerplate) {
+            (Some((chunked, chunk_index, aggregation)), Some(boilerplate)) => {
+                let ranges: Vec<_> = chunk_index
+                    .get(index)
+                    .iter()
+                    .filter(|range| !boilerplate.covers(&candidate.content[(*range).clone()]))
+                    .cloned()
+                    .collect();
+                chunked.compare(candidate.content, &ranges, aggregation)
+            }
+            (Some((chunked, chunk_index, aggregation)), None) => {
+                chunked.compare(candidate.content, chunk_index.get(index), aggregation)
+            }
+            (None, boilerplate) => {
+                let content = boilerplate.map_or(Cow::Borrowed(candidate.content), |boilerplate| {
+                    boilerplate.strip(candidate.content)
+                });
+                (self.metric.similarity(&self.content, &content), None)
+            }
+        };
+
+        let score = title_score * title_weight + content_score * content_weight;
+        let (score, fingerprint) = match &self.traces {
+            Some((options, source)) => {
+                let target = StackTrace::from_metadata(candidate.metadata);
+                let score = options.score(score, source.as_ref(), target.as_ref());
+                (score, target.map(|(_, fingerprint)| fingerprint))
+            }
+            None => (score, None),
+        };
+        let (score, shared_symbols) = match &self.symbols {
+            Some((options, symbols, posts)) => options.score(
+                score,
+                symbols,
+                posts.get(index).unwrap_or(&Symbols::new()),
+                posts.index(),
+            ),
+            None => (score, Vec::new()),
+        };
+        let (score, shared_facets) = match &self.facets {
+            Some((options, facets)) => options.boost(score, facets, &facets_of(candidate.metadata)),
+            None => (score, Vec::new()),
+        };
+
+        Scored {
+            index,
+            score,
+            chunks,
+            shared_facets,
+            fingerprint,
+            shared_symbols,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scored {
+    index: usize,
+    score: f64,
+    chunks: Option<ChunkMatch>,
+    shared_facets: Vec<String>,
+    fingerprint: Option<String>,
+    shared_symbols: Vec<String>,
+}
+
+impl Scored {
+    /// A match of the plain scans, which report nothing but the score.
+    fn ranked(ranked: RankedMatch) -> Self {
+        Scored {
+            index: ranked.index,
+            score: ranked.score,
+            ..Default::default()
+        }
+    }
+}
+
+impl PostsSnapshot {
+    pub fn len(&self) -> usize {
+        self.positions() - self.removed.count
+    }
+
+    /// The number of positions, including the ones of the replaced or removed mapped posts, for
+    /// which [PostsSnapshot::get] returns `None`.
+    fn positions(&self) -> usize {
+        self.base.as_ref().map_or(0, |base| base.len()) + self.posts.len()
+    }
+
+    /// Returns the mapped post at position `i` unless it has been replaced or removed.
+    fn base_post(&self, i: usize) -> Option<PostRef<'_>> {
+        if self.removed.contains(i) {
+            return None;
+        }
+        self.base.as_ref()?.get(i)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = PostRef<'_>> {
+        let base_len = self.base.as_ref().map_or(0, |base| base.len());
+
+        (0..base_len)
+            .filter_map(move |i| self.base_post(i))
+            .chain(self.posts.iter().map(PostRef::from))
+    }
+
+    /// Returns the post at position `i`, the mapped posts come before the ones in memory. The
+    /// positions of the mapped posts which have been replaced or removed are kept, and return
+    /// `None`.
+    pub fn get(&self, i: usize) -> Option<PostRef<'_>> {
+        let base_len = self.base.as_ref().map_or(0, |base| base.len());
+
+        if i < base_len {
+            self.base_post(i)
+        } else {
+            self.posts.get(i - base_len).map(PostRef::from)
+        }
+    }
+
+    /// Returns the posts satisfying `keep`. The mapped posts which don't are marked as removed
+    /// rather than copied, so the file stays mapped, the ones in memory are filtered.
+    fn retain(&self, keep: impl Fn(&PostRef) -> bool + Sync) -> (Arc<Tombstones>, Vec<PostData>) {
+        let posts = self
+            .posts
+            .iter()
+            .filter(|post| keep(&PostRef::from(*post)))
+            .cloned()
+            .collect();
+        let base_len = self.base.as_ref().map_or(0, |base| base.len());
+        let dropped: Vec<usize> = (0..base_len)
+            .into_par_iter()
+            .filter(|&i| self.base_post(i).is_some_and(|post| !keep(&post)))
+            .collect();
+
+        if dropped.is_empty() {
+            return (self.removed.clone(), posts);
+        }
+
+        let mut removed = Tombstones::clone(&self.removed);
+        for i in dropped {
+            removed.insert(i);
+        }
+        (Arc::new(removed), posts)
+    }
+
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = PostRef<'_>> {
+        let base_len = self.base.as_ref().map_or(0, |base| base.len());
+
+        (0..base_len)
+            .into_par_iter()
+            .filter_map(move |i| self.base_post(i))
+            .chain(self.posts.par_iter().map(PostRef::from))
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in this snapshot, see
+    /// [find_similar_posts_parallel].
+    pub fn find_similar_posts(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<FindTopNResult> {
+        self.find_similar_posts_in(source, top_n, filter, true)
+    }
+
+    fn find_similar_posts_in(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        parallel: bool,
+    ) -> Result<FindTopNResult> {
+        let mut result = if parallel {
+            find_similar_posts_parallel(source, self.par_iter(), top_n, filter, None)?
+        } else {
+            find_similar_posts_sequential(source, self.iter(), top_n, filter, None)?
+        };
+
+        result.generation = Some(self.generation);
+        Ok(result)
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in this snapshot, the matches refer to
+    /// the posts by their positions, see [PostsSnapshot::get] and [rank_similar_posts].
+    pub fn rank_similar_posts(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<RankResult> {
+        self.rank_similar_posts_in(source, top_n, filter, None, true)
+    }
+
+    fn rank_similar_posts_in(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        annotations: Option<&Annotations>,
+        parallel: bool,
+    ) -> Result<RankResult> {
+        let candidate = |i| Some((i, self.get_annotated(i, annotations)?));
+        let mut result = if parallel {
+            let candidates = (0..self.positions()).into_par_iter().filter_map(candidate);
+            rank_similar_posts(source, candidates, top_n, filter)?
+        } else {
+            let candidates = (0..self.positions()).filter_map(candidate);
+            rank_similar_posts_sequential(source, candidates, top_n, filter)?
+        };
+
+        result.generation = Some(self.generation);
+        Ok(result)
+    }
+
+    /// Returns the index ranking the posts in the first stage of a pipeline. It's built on the
+    /// first call and kept along with the snapshot, later calls with another retriever build a
+    /// throwaway one.
+    pub fn text_index(&self, retriever: &Retriever) -> Arc<TextIndex> {
+        if let Some(index) = self
+            .texts
+            .get()
+            .filter(|index| index.retriever() == retriever)
+        {
+            return index.clone();
+        }
+
+        let index = Arc::new(TextIndex::build(*retriever, self.positions(), |i| {
+            self.get(i)
+                .map_or_else(String::new, |post| post_text(&post))
+        }));
+        let _ = self.texts.set(index.clone());
+        index
+    }
+
+    /// Returns the chunks of the contents of the posts. They're split on the first call and
+    /// kept along with the snapshot, later calls with another split build a throwaway index.
+    pub fn chunk_index(&self, split: &Split) -> Arc<ChunkIndex> {
+        if let Some(index) = self.chunks.get().filter(|index| index.split() == split) {
+            return index.clone();
+        }
+
+        let contents = (0..self.positions())
+            .into_par_iter()
+            .map(|i| self.get(i).map_or("", |post| post.content));
+        let index = Arc::new(ChunkIndex::build(*split, contents));
+        let _ = self.chunks.set(index.clone());
+        index
+    }
+
+    /// Returns the symbols mentioned by the posts, extracted from their texts like the ones of
+    /// the source, so the posts of an opened snapshot file or written by a handle comparing no
+    /// symbols have theirs too. They're extracted on the first call and kept along with the
+    /// snapshot.
+    pub fn symbols(&self) -> Arc<PostSymbols> {
+        self.symbols
+            .get_or_init(|| {
+                let symbols: Vec<Symbols> = (0..self.positions())
+                    .into_par_iter()
+                    .map(|i| {
+                        self.get(i)
+                            .map(|post| post_symbols(&post))
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                let index = SymbolIndex::build(
+                    symbols
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| self.get(*i).is_some())
+                        .map(|(_, symbols)| symbols),
+                );
+                Arc::new(PostSymbols { symbols, index })
+            })
+            .clone()
+    }
+
+    /// Returns the metadata of the posts with the facets, the stack traces and the symbols
+    /// extracted by `content` written into it, or `None` if it extracts none. It's written on
+    /// the first call and kept along with the snapshot, later calls with other options write
+    /// throwaway ones.
+    pub fn annotations(&self, content: &ContentOptions) -> Option<Arc<Annotations>> {
+        let key = AnnotationKey::new(content)?;
+        if let Some(annotations) = self
+            .annotations
+            .get()
+            .filter(|annotations| annotations.key == key)
+        {
+            return Some(annotations.clone());
+        }
+
+        let symbols = key.symbols.then(|| self.symbols());
+        let metadata = (0..self.positions())
+            .into_par_iter()
+            .map(|i| {
+                let post = self.get(i)?;
+                let symbols = symbols.as_ref().and_then(|symbols| symbols.get(i));
+                Annotations::annotate(&key, &post, symbols)
+            })
+            .collect();
+        let annotations = Arc::new(Annotations { key, metadata });
+        let _ = self.annotations.set(annotations.clone());
+        Some(annotations)
+    }
+
+    /// Returns the post at position `i` with the metadata of `annotations`, if given.
+    pub fn get_annotated<'a>(
+        &'a self,
+        i: usize,
+        annotations: Option<&'a Annotations>,
+    ) -> Option<PostRef<'a>> {
+        let post = self.get(i)?;
+
+        Some(match annotations {
+            Some(annotations) => PostRef {
+                metadata: annotations.metadata(i, post.metadata),
+                ..post
+            },
+            None => post,
+        })
+    }
+
+    /// Learns the boilerplate of the contents of the posts, see [Boilerplate::learn].
+    pub fn learn_boilerplate(&self, options: &BoilerplateOptions) -> Result<Boilerplate> {
+        Boilerplate::learn(options, self.par_iter().map(|post| post.content))
+    }
+
+    fn scorer<'a>(
+        &self,
+        source: &'a PostData,
+        metric: Metric,
+        content: &'a ContentOptions,
+    ) -> Result<Scorer<'a>> {
+        let stripped = content
+            .boilerplate
+            .as_deref()
+            .map(|boilerplate| (boilerplate.strip(&source.content), boilerplate));
+        // a source made of boilerplate only is compared as is
+        let (stripped, boilerplate) = match stripped {
+            Some((stripped, boilerplate)) if !stripped.trim().is_empty() => {
+                (stripped, Some(boilerplate))
+            }
+            _ => (Cow::Borrowed(source.content.as_str()), None),
+        };
+        let weights = match &stripped {
+            Cow::Owned(stripped) => get_weights(&PostData {
+                title: source.title.clone(),
+                content: stripped.clone(),
+                ..Default::default()
+            })?,
+            Cow::Borrowed(_) => get_weights(source)?,
+        };
+
+        Ok(Scorer {
+            source,
+            content: stripped,
+            weights,
+            metric,
+            chunks: content.chunking.as_ref().map(|chunking| {
+                let mut chunked = ChunkedText::new(&source.content, &chunking.split);
+                if let Some(boilerplate) = boilerplate {
+                    chunked.retain(|chunk| !boilerplate.covers(chunk));
+                }
+                (
+                    chunked,
+                    self.chunk_index(&chunking.split),
+                    chunking.aggregation,
+                )
+            }),
+            boilerplate,
+            facets: content.facets.as_deref().map(|options| {
+                (
+                    options,
+                    options.extractor.extract(&post_text(&source.into())),
+                )
+            }),
+            traces: content
+                .traces
+                .as_deref()
+                .map(|options| (options, options.source(&post_text(&source.into())))),
+            symbols: content
+                .symbols
+                .as_deref()
+                .map(|options| (options, post_symbols(&source.into()), self.symbols())),
+        })
+    }
+
+    /// Applies the threshold to the scored candidates, and returns the `top_n` best ones. Ties
+    /// keep the order of the posts, as in a full scan.
+    fn select(mut scored: Vec<Scored>, top_n: usize) -> Vec<Scored> {
+        scored.sort_unstable_by_key(|scored| scored.index);
+        // 0.5 is the threshold to consider a match
+        scored.retain(|scored| scored.score > 0.5);
+        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        scored.truncate(top_n);
+        scored
+    }
+
+    /// Returns the matches of the selected candidates, with a copy of the posts.
+    fn matches(&self, scored: Vec<Scored>, annotations: Option<&Annotations>) -> Vec<Match> {
+        scored
+            .into_iter()
+            .filter_map(|scored| {
+                Some(Match {
+                    target: self
+                        .get_annotated(scored.index, annotations)?
+                        .to_post_data(),
+                    score: scored.score,
+                    lexical_rank: None,
+                    vector_rank: None,
+                    chunks: scored.chunks,
+                    shared_facets: scored.shared_facets,
+                    fingerprint: scored.fingerprint,
+                    shared_symbols: scored.shared_symbols,
+                })
+            })
+            .collect()
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in this snapshot, scoring the titles
+    /// and the contents with `metric`, or the contents by their chunks if `content` has a
+    /// chunking. Each match then reports the best matching pair of chunks. The boilerplate of
+    /// `content`, if any, is left out of the scores, its facets boost them, and its traces and
+    /// its symbols are compared. When `filter` is given, only the posts whose metadata satisfy
+    /// it are scored.
+    pub fn find_similar_posts_by(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        metric: Metric,
+        content: &ContentOptions,
+    ) -> Result<FindTopNResult> {
+        let start = Instant::now();
+        let annotations = self.annotations(content);
+        let annotations = annotations.as_deref();
+        let scored = self.score_by_in(source, top_n, filter, metric, content, annotations, true)?;
+
+        Ok(FindTopNResult {
+            matches: self.matches(scored, annotations),
+            process_time: start.elapsed(),
+            generation: Some(self.generation),
+            stages: Vec::new(),
+        })
+    }
+
+    /// Scores the posts satisfying `filter` with `metric` and `content`, returns the `top_n`
+    /// best ones, see [PostsSnapshot::find_similar_posts_by].
+    #[allow(clippy::too_many_arguments)]
+    fn score_by_in(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        metric: Metric,
+        content: &ContentOptions,
+        annotations: Option<&Annotations>,
+        parallel: bool,
+    ) -> Result<Vec<Scored>> {
+        let scorer = self.scorer(source, metric, content)?;
+        let score = |i: usize| {
+            let candidate = self.get_annotated(i, annotations)?;
+            if filter.is_some_and(|filter| !filter.matches(candidate.metadata)) {
+                return None;
+            }
+            Some(scorer.score(i, &candidate))
+        };
+        let scored: Vec<Scored> = if parallel {
+            (0..self.positions())
+                .into_par_iter()
+                .filter_map(score)
+                .collect()
+        } else {
+            (0..self.positions()).filter_map(score).collect()
+        };
+
+        Ok(Self::select(scored, top_n))
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in this snapshot in three stages: the
+    /// posts are ranked by the retriever of `pipeline`, the top candidates are scored by its
+    /// rescorer, or by their chunks if `content` has a chunking, then the threshold and `top_n`
+    /// are applied. The boilerplate of `content` is only left out by the rescorer. When
+    /// `filter` is given, only the posts whose metadata satisfy it are retrieved.
+    pub fn find_similar_posts_pipeline(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        pipeline: &PipelineOptions,
+        content: &ContentOptions,
+    ) -> Result<FindTopNResult> {
+        let start = Instant::now();
+        let annotations = self.annotations(content);
+        let annotations = annotations.as_deref();
+        let (scored, stages) =
+            self.score_pipeline_in(source, top_n, filter, pipeline, content, annotations, true)?;
+
+        Ok(FindTopNResult {
+            matches: self.matches(scored, annotations),
+            process_time: start.elapsed(),
+            generation: Some(self.generation),
+            stages,
+        })
+    }
+
+    /// Scores the posts satisfying `filter` in the stages of `pipeline`, returns the `top_n`
+    /// best ones along with the stats of the stages, see
+    /// [PostsSnapshot::find_similar_posts_pipeline].
+    #[allow(clippy::too_many_arguments)]
+    fn score_pipeline_in(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        pipeline: &PipelineOptions,
+        content: &ContentOptions,
+        annotations: Option<&Annotations>,
+        parallel: bool,
+    ) -> Result<(Vec<Scored>, Vec<StageStats>)> {
+        let start = Instant::now();
+        let scorer = self.scorer(source, pipeline.rescorer, content)?;
+        let retrieved = self.text_index(&pipeline.retriever).top_k(
+            &post_text(&PostRef::from(source)),
+            pipeline.candidates(top_n),
+            |i| {
+                filter.is_none_or(|filter| {
+                    self.get_annotated(i, annotations)
+                        .is_some_and(|post| filter.matches(post.metadata))
+                })
+            },
+            parallel,
+        );
+        let retrieve = StageStats {
+            stage: Stage::Retrieve,
+            time: start.elapsed(),
+            input: self.len(),
+            output: retrieved.len(),
+        };
+
+        let rerank_start = Instant::now();
+        let rescore = |&(index, _): &(usize, f64)| {
+            Some(scorer.score(index, &self.get_annotated(index, annotations)?))
+        };
+        let scored: Vec<Scored> = if parallel {
+            retrieved.par_iter().filter_map(rescore).collect()
+        } else {
+            retrieved.iter().filter_map(rescore).collect()
+        };
+        let rerank = StageStats {
+            stage: Stage::Rerank,
+            time: rerank_start.elapsed(),
+            input: retrieved.len(),
+            output: scored.len(),
+        };
+
+        let select_start = Instant::now();
+        let selected = Self::select(scored, top_n);
+        let select = StageStats {
+            stage: Stage::Select,
+            time: select_start.elapsed(),
+            input: rerank.output,
+            output: selected.len(),
+        };
+
+        Ok((selected, vec![retrieve, rerank, select]))
+    }
+
+    /// Inserts the embeddings of the posts from position `start` on into `index`, the posts
+    /// without an embedding are left out.
+    fn index_embeddings(&self, index: &mut Hnsw, start: usize) -> Result<()> {
+        for i in start..self.positions() {
+            if let Some(embedding) = self.get(i).and_then(|post| post.embedding) {
+                index.insert(i, embedding)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index over the embeddings of the posts. It's built on the first call and
+    /// kept along with the snapshot, later calls with other options build a throwaway one.
+    pub fn vector_index(&self, options: &HnswOptions) -> Result<Arc<Hnsw>> {
+        if let Some(index) = self
+            .vectors
+            .get()
+            .filter(|index| index.options() == options)
+        {
+            return Ok(index.clone());
+        }
+
+        let mut index = Hnsw::new(*options);
+        self.index_embeddings(&mut index, 0)?;
+
+        let index = Arc::new(index);
+        let _ = self.vectors.set(index.clone());
+        Ok(index)
+    }
+
+    /// Finds the `top_n` posts whose embeddings are the nearest to `embedding`, the score of a
+    /// match is derived from the distance, see [crate::vector::Distance::score]. When `filter`
+    /// is given, only the posts whose metadata satisfy it are returned.
+    pub fn find_nearest_posts(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+        options: &HnswOptions,
+    ) -> Result<FindTopNResult> {
+        self.find_nearest_posts_in(embedding, top_n, filter, options, None)
+    }
+
+    fn find_nearest_posts_in(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+        options: &HnswOptions,
+        annotations: Option<&Annotations>,
+    ) -> Result<FindTopNResult> {
+        let start = Instant::now();
+        let neighbors = self.nearest_posts(embedding, top_n, filter, options, annotations)?;
+
+        Ok(FindTopNResult {
+            matches: neighbors
+                .into_iter()
+                .filter_map(|(i, score)| {
+                    Some(Match {
+                        target: self.get_annotated(i, annotations)?.to_post_data(),
+                        score,
+                        lexical_rank: None,
+                        vector_rank: None,
+                        chunks: None,
+                        shared_facets: Vec::new(),
+                        fingerprint: None,
+                        shared_symbols: Vec::new(),
+                    })
+                })
+                .collect(),
+            process_time: start.elapsed(),
+            generation: Some(self.generation),
+            stages: Vec::new(),
+        })
+    }
+
+    /// Returns the positions and the scores of the `top_n` posts nearest to `embedding`.
+    fn nearest_posts(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+        options: &HnswOptions,
+        annotations: Option<&Annotations>,
+    ) -> Result<Vec<(usize, f64)>> {
+        let index = self.vector_index(options)?;
+        let neighbors = match filter {
+            Some(filter) => index.search_filtered(embedding, top_n, |i| {
+                self.get_annotated(i, annotations)
+                    .is_some_and(|post| filter.matches(post.metadata))
+            })?,
+            None => index.search(embedding, top_n)?,
+        };
+
+        Ok(neighbors
+            .into_iter()
+            .map(|neighbor| (neighbor.key, options.distance.score(neighbor.distance)))
+            .collect())
+    }
+
+    /// Finds the `top_n` posts most similar to `source` by both its text and its embedding,
+    /// fusing the lexical matches with the nearest embeddings as given by `hybrid`. Each match
+    /// reports its rank in both retrievers, see [crate::hybrid::fuse].
+    pub fn find_similar_posts_hybrid(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        hybrid: &HybridOptions,
+        options: &HnswOptions,
+    ) -> Result<FindTopNResult> {
+        self.find_similar_posts_hybrid_in(source, top_n, filter, hybrid, options, None, |n| {
+            Ok(self
+                .rank_similar_posts_in(source, n, filter, None, true)?
+                .matches
+                .into_iter()
+                .map(Scored::ranked)
+                .collect())
+        })
+    }
+
+    /// Like [PostsSnapshot::find_similar_posts_hybrid], the lexical matches are the `n` best
+    /// ones given by `lexical`, the matches report what their scoring found, such as their
+    /// shared facets.
+    #[allow(clippy::too_many_arguments)]
+    fn find_similar_posts_hybrid_in(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        hybrid: &HybridOptions,
+        options: &HnswOptions,
+        annotations: Option<&Annotations>,
+        lexical: impl FnOnce(usize) -> Result<Vec<Scored>>,
+    ) -> Result<FindTopNResult> {
+        let start = Instant::now();
+        let embedding = source.embedding.as_deref().ok_or_else(|| {
+            Error::InvalidArgument("source must have an embedding in a hybrid query".to_string())
+        })?;
+        let candidates = hybrid.candidates(top_n);
+        let lexical = lexical(candidates)?;
+        let ranking: Vec<(usize, f64)> = lexical
+            .iter()
+            .map(|scored| (scored.index, scored.score))
+            .collect();
+        let mut lexical: HashMap<usize, Scored> = lexical
+            .into_iter()
+            .map(|scored| (scored.index, scored))
+            .collect();
+        let vector = self.nearest_posts(embedding, candidates, filter, options, annotations)?;
+
+        Ok(FindTopNResult {
+            matches: fuse(&ranking, &vector, &hybrid.fusion, top_n)
+                .into_iter()
+                .filter_map(|fused| {
+                    // the posts only found by their embeddings have nothing to report
+                    let scored = lexical.remove(&fused.key).unwrap_or_default();
+
+                    Some(Match {
+                        target: self.get_annotated(fused.key, annotations)?.to_post_data(),
+                        score: fused.score,
+                        lexical_rank: fused.lexical_rank,
+                        vector_rank: fused.vector_rank,
+                        chunks: scored.chunks,
+                        shared_facets: scored.shared_facets,
+                        fingerprint: scored.fingerprint,
+                        shared_symbols: scored.shared_symbols,
+                    })
+                })
+                .collect(),
+            process_time: start.elapsed(),
+            generation: Some(self.generation),
+            stages: Vec::new(),
+        })
+    }
+}
+
+/// A store of posts to be queried many times, cloning it is cheap and the clones share the
+/// posts.
+#[derive(Debug, Clone, Default)]
+pub struct PostStore {
+    posts: Arc<ArcSwap<PostsSnapshot>>,
+    pool: QueryPool,
+    vector_options: HnswOptions,
+    pipeline: Option<PipelineOptions>,
+    content: ContentOptions,
+    metric: Metric,
+}
+
+static SHARED_STORES: Registry<PostStore> = Registry::new();
+
+impl PostStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a handle of the process-global store named `name`, which is created empty on the
+    /// first call and dropped along with its last handle.
+    pub fn shared(name: &str) -> Shared<PostStore> {
+        SHARED_STORES.attach(name)
+    }
+
+    pub fn from_snapshot(snapshot: PostsSnapshot) -> Self {
+        PostStore {
+            posts: Arc::new(ArcSwap::from_pointee(snapshot)),
+            pool: QueryPool::default(),
+            vector_options: HnswOptions::default(),
+            pipeline: None,
+            content: ContentOptions::default(),
+            metric: Metric::default(),
+        }
+    }
+
+    /// Runs the queries of this handle on `pool`, the clones made afterwards share it.
+    pub fn with_pool(mut self, pool: QueryPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// The pool the queries of this handle run on, see [QueryPool::spawn] to run them without
+    /// blocking the calling thread.
+    pub fn pool(&self) -> &QueryPool {
+        &self.pool
+    }
+
+    /// Indexes the embeddings of the posts with `options` for the queries of this handle, the
+    /// clones made afterwards share them.
+    pub fn with_vector_index(mut self, options: HnswOptions) -> Result<Self> {
+        options.validate()?;
+        self.vector_options = options;
+        Ok(self)
+    }
+
+    /// Runs [PostStore::find_similar_posts] of this handle through `pipeline` instead of
+    /// scoring every post, the clones made afterwards share it.
+    pub fn with_pipeline(mut self, pipeline: PipelineOptions) -> Result<Self> {
+        pipeline.validate()?;
+        self.pipeline = Some(pipeline);
+        Ok(self)
+    }
+
+    /// Scores the contents of the posts by their chunks in [PostStore::find_similar_posts] of
+    /// this handle, the clones made afterwards share it. The chunks are split once per snapshot,
+    /// and the ones of appended posts are added to them.
+    pub fn with_chunking(mut self, chunking: ChunkOptions) -> Result<Self> {
+        chunking.validate()?;
+        self.content.chunking = Some(chunking);
+        Ok(self)
+    }
+
+    /// Removes `boilerplate` from the contents of the posts and the source before they are
+    /// scored in [PostStore::find_similar_posts] of this handle, the clones made afterwards
+    /// share it. When scoring by chunks, the chunks made of boilerplate are skipped instead.
+    pub fn with_boilerplate(mut self, boilerplate: Boilerplate) -> Self {
+        self.content.boilerplate = Some(Arc::new(boilerplate));
+        self
+    }
+
+    /// Stops removing the boilerplate in the queries of this handle.
+    pub fn without_boilerplate(mut self) -> Self {
+        self.content.boilerplate = None;
+        self
+    }
+
+    /// The boilerplate ignored by the queries of this handle, if any.
+    pub fn boilerplate(&self) -> Option<&Boilerplate> {
+        self.content.boilerplate.as_deref()
+    }
+
+    /// Extracts the facets of the posts in the queries of this handle, the clones made
+    /// afterwards share it. They're written into the metadata the filters of the queries see,
+    /// once per snapshot, see [PostsSnapshot::annotations]. The matches of
+    /// [PostStore::find_similar_posts] report the facets they share with the source, and are
+    /// boosted by them.
+    pub fn with_facets(mut self, facets: FacetOptions) -> Result<Self> {
+        facets.validate()?;
+        self.content.facets = Some(Arc::new(facets));
+        Ok(self)
+    }
+
+    /// The facets extracted by this handle, if any.
+    pub fn facets(&self) -> Option<&FacetOptions> {
+        self.content.facets.as_deref()
+    }
+
+    /// Parses the stack traces of the posts in the queries of this handle, the clones made
+    /// afterwards share it. They're fingerprinted into the metadata the filters of the queries
+    /// see, once per snapshot, see [PostsSnapshot::annotations]. The queries compare the traces
+    /// of the matches with the one of the source, as given by `traces`, and each match reports
+    /// its fingerprint.
+    pub fn with_traces(mut self, traces: TraceOptions) -> Result<Self> {
+        traces.validate()?;
+        self.content.traces = Some(Arc::new(traces));
+        Ok(self)
+    }
+
+    /// How the stack traces are compared by this handle, if they are.
+    pub fn traces(&self) -> Option<&TraceOptions> {
+        self.content.traces.as_deref()
+    }
+
+    /// Extracts the symbols mentioned by the posts in the queries of this handle, the clones
+    /// made afterwards share it. They're extracted once per snapshot, see
+    /// [PostsSnapshot::symbols], and written into the metadata the filters of the queries see,
+    /// see [PostsSnapshot::annotations]. The queries combine the overlap of the symbols of the
+    /// matches with the ones of the source with their scores, as given by `symbols`, the rarer
+    /// symbols weighing more, and each match reports the symbols it shares.
+    pub fn with_symbols(mut self, symbols: SymbolOptions) -> Result<Self> {
+        symbols.validate()?;
+        self.content.symbols = Some(Arc::new(symbols));
+        Ok(self)
+    }
+
+    /// How the symbols are compared by this handle, if they are.
+    pub fn symbols(&self) -> Option<&SymbolOptions> {
+        self.content.symbols.as_deref()
+    }
+
+    /// Learns the boilerplate of the contents of the posts in the current snapshot, which can be
+    /// edited and given to [PostStore::with_boilerplate]. It isn't updated by later writes.
+    pub fn learn_boilerplate(&self, options: &BoilerplateOptions) -> Result<Boilerplate> {
+        let snapshot = self.snapshot();
+
+        self.pool
+            .run(snapshot.len(), |_| snapshot.learn_boilerplate(options))
+    }
+
+    /// Scores the titles and the contents of the posts with `metric` in
+    /// [PostStore::find_similar_posts] of this handle, the clones made afterwards share it. A
+    /// pipeline scores the candidates it retrieves with its own rescorer.
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    pub fn generation(&self) -> u64 {
+        self.posts.load().generation
+    }
+
+    /// Returns the current snapshot, which isn't affected by later writes.
+    pub fn snapshot(&self) -> Arc<PostsSnapshot> {
+        self.posts.load_full()
+    }
+
+    /// Replaces all the posts in the store, including the ones of an opened snapshot file.
+    pub fn preload(&self, posts: Vec<PostData>) {
+        let posts = Arc::new(posts);
+
+        self.posts.rcu(|current| PostsSnapshot {
+            generation: current.generation + 1,
+            base: None,
+            posts: posts.clone(),
+            ..Default::default()
+        });
+    }
+
+    /// Replaces all the posts with the ones of `other`, such as a store loaded with
+    /// [PostStore::from_db]. The posts are shared rather than copied.
+    pub fn replace_with(&self, other: &PostStore) {
+        let other = other.snapshot();
+
+        self.posts.rcu(|current| PostsSnapshot {
+            generation: current.generation + 1,
+            base: other.base.clone(),
+            posts: other.posts.clone(),
+            removed: other.removed.clone(),
+            ..Default::default()
+        });
+    }
+
+    /// Adds posts to the store, keeping the existing ones. If the embeddings of the existing
+    /// posts have been indexed, the new ones are added to a copy of the index.
+    pub fn append(&self, posts: Vec<PostData>) {
+        self.posts.rcu(|current| {
+            let mut all = Vec::with_capacity(current.posts.len() + posts.len());
+            all.extend_from_slice(&current.posts);
+            all.extend_from_slice(&posts);
+
+            let snapshot = PostsSnapshot {
+                generation: current.generation + 1,
+                base: current.base.clone(),
+                posts: Arc::new(all),
+                removed: current.removed.clone(),
+                ..Default::default()
+            };
+
+            if let Some(index) = current.chunks.get() {
+                let contents = posts.iter().map(|post| post.content.as_str());
+                let _ = snapshot.chunks.set(Arc::new(index.extend(contents)));
+            }
+            if let Some(symbols) = current.symbols.get() {
+                let _ = snapshot.symbols.set(Arc::new(symbols.extend(&posts)));
+            }
+            if let Some(annotations) = current.annotations.get() {
+                let _ = snapshot
+                    .annotations
+                    .set(Arc::new(annotations.extend(&posts)));
+            }
+            if let Some(index) = current.vectors.get() {
+                let mut index = Hnsw::clone(index);
+
+                // an invalid embedding is reported by the next query, which rebuilds the index
+                if snapshot
+                    .index_embeddings(&mut index, current.positions())
+                    .is_ok()
+                {
+                    let _ = snapshot.vectors.set(Arc::new(index));
+                }
+            }
+            snapshot
+        });
+    }
+
+    /// Adds the posts to the store, replacing the existing ones with the same IDs, posts without
+    /// an ID are always added. If a post of an opened snapshot file is replaced, it's marked as
+    /// removed and the file stays mapped.
+    pub fn upsert(&self, posts: Vec<PostData>) {
+        let ids: HashSet<&str> = posts.iter().filter_map(|post| post.id.as_deref()).collect();
+
+        self.posts.rcu(|current| {
+            let (removed, mut all) =
+                current.retain(|post| post.id.is_none_or(|id| !ids.contains(id)));
+            all.extend_from_slice(&posts);
+
+            PostsSnapshot {
+                generation: current.generation + 1,
+                base: current.base.clone(),
+                posts: Arc::new(all),
+                removed,
+                ..Default::default()
+            }
+        });
+    }
+
+    /// Removes the posts with the given IDs, returns how many have been removed. Removing no
+    /// post publishes no new generation.
+    pub fn remove(&self, ids: &[&str]) -> usize {
+        let ids: HashSet<&str> = ids.iter().copied().collect();
+        let mut removed = 0;
+
+        self.posts.rcu(|current| {
+            let (tombstones, posts) =
+                current.retain(|post| post.id.is_none_or(|id| !ids.contains(id)));
+            removed =
+                (tombstones.count - current.removed.count) + (current.posts.len() - posts.len());
+
+            if removed == 0 {
+                current.clone()
+            } else {
+                Arc::new(PostsSnapshot {
+                    generation: current.generation + 1,
+                    base: current.base.clone(),
+                    posts: Arc::new(posts),
+                    removed: tombstones,
+                    ..Default::default()
+                })
+            }
+        });
+        removed
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in the current snapshot. When `filter`
+    /// is given, only the posts whose metadata satisfy it are scored. If the handle has a
+    /// pipeline, only the candidates it retrieves are scored, see
+    /// [PostsSnapshot::find_similar_posts_pipeline]. If it has another metric, scores by chunks,
+    /// ignores a boilerplate, has facets, compares traces or symbols, see
+    /// [PostsSnapshot::find_similar_posts_by].
+    pub fn find_similar_posts(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<FindTopNResult> {
+        let snapshot = self.snapshot();
+
+        self.pool.run(snapshot.len(), |parallel| {
+            if self.scans() {
+                return snapshot.find_similar_posts_in(source, top_n, filter, parallel);
+            }
+
+            let start = Instant::now();
+            let annotations = snapshot.annotations(&self.content);
+            let annotations = annotations.as_deref();
+            let (scored, stages) =
+                self.score_posts(&snapshot, source, top_n, filter, annotations, parallel)?;
+
+            Ok(FindTopNResult {
+                matches: snapshot.matches(scored, annotations),
+                process_time: start.elapsed(),
+                generation: Some(snapshot.generation),
+                stages,
+            })
+        })
+    }
+
+    /// Whether the queries score the posts with the plain scans of [crate::post], that is the
+    /// handle has no pipeline, scores by the edit distance of the whole texts and compares
+    /// nothing else.
+    fn scans(&self) -> bool {
+        matches!(
+            (&self.pipeline, &self.content),
+            (
+                None,
+                ContentOptions {
+                    chunking: None,
+                    boilerplate: None,
+                    facets: None,
+                    traces: None,
+                    symbols: None,
+                },
+            )
+        ) && self.metric == Metric::Levenshtein
+    }
+
+    /// Scores the posts of `snapshot` against `source` as [PostStore::find_similar_posts]
+    /// does, returns the `top_n` best ones along with the stats of the stages of the pipeline,
+    /// if any.
+    fn score_posts(
+        &self,
+        snapshot: &PostsSnapshot,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        annotations: Option<&Annotations>,
+        parallel: bool,
+    ) -> Result<(Vec<Scored>, Vec<StageStats>)> {
+        match &self.pipeline {
+            Some(pipeline) => snapshot.score_pipeline_in(
+                source,
+                top_n,
+                filter,
+                pipeline,
+                &self.content,
+                annotations,
+                parallel,
+            ),
+            None if self.scans() => Ok((
+                snapshot
+                    .rank_similar_posts_in(source, top_n, filter, annotations, parallel)?
+                    .matches
+                    .into_iter()
+                    .map(Scored::ranked)
+                    .collect(),
+                Vec::new(),
+            )),
+            None => Ok((
+                snapshot.score_by_in(
+                    source,
+                    top_n,
+                    filter,
+                    self.metric,
+                    &self.content,
+                    annotations,
+                    parallel,
+                )?,
+                Vec::new(),
+            )),
+        }
+    }
+
+    /// Finds the `top_n` posts most similar to `source` in the current snapshot, which is
+    /// returned along with the matches they refer to, see [PostsSnapshot::rank_similar_posts].
+    /// The posts are scored as by [PostStore::find_similar_posts], with the pipeline, the
+    /// metric and the content options of the handle, the matches only report their scores.
+    pub fn rank_similar_posts(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<(Arc<PostsSnapshot>, RankResult)> {
+        let snapshot = self.snapshot();
+        let result = self.pool.run(snapshot.len(), |parallel| {
+            let start = Instant::now();
+            let annotations = snapshot.annotations(&self.content);
+            let (scored, _) = self.score_posts(
+                &snapshot,
+                source,
+                top_n,
+                filter,
+                annotations.as_deref(),
+                parallel,
+            )?;
+
+            Ok(RankResult {
+                matches: scored
+                    .into_iter()
+                    .map(|scored| RankedMatch {
+                        index: scored.index,
+                        score: scored.score,
+                    })
+                    .collect(),
+                process_time: start.elapsed(),
+                generation: Some(snapshot.generation),
+            })
+        })?;
+
+        Ok((snapshot, result))
+    }
+
+    /// Finds the `top_n` posts most similar to `source` by both its text and its embedding in
+    /// the current snapshot, see [PostsSnapshot::find_similar_posts_hybrid]. The lexical
+    /// matches are scored as by [PostStore::find_similar_posts], with the pipeline, the metric
+    /// and the content options of the handle, and report what their scoring found, such as
+    /// their shared facets.
+    pub fn find_similar_posts_hybrid(
+        &self,
+        source: &PostData,
+        top_n: usize,
+        filter: Option<&Filter>,
+        hybrid: &HybridOptions,
+    ) -> Result<FindTopNResult> {
+        hybrid.validate()?;
+        let snapshot = self.snapshot();
+
+        self.pool.run(snapshot.len(), |parallel| {
+            let annotations = snapshot.annotations(&self.content);
+            let annotations = annotations.as_deref();
+            snapshot.find_similar_posts_hybrid_in(
+                source,
+                top_n,
+                filter,
+                hybrid,
+                &self.vector_options,
+                annotations,
+                |n| {
+                    let (scored, _) =
+                        self.score_posts(&snapshot, source, n, filter, annotations, parallel)?;
+                    Ok(scored)
+                },
+            )
+        })
+    }
+
+    /// Finds the `top_n` posts whose embeddings are the nearest to `embedding` in the current
+    /// snapshot, see [PostsSnapshot::find_nearest_posts]. There is no text to compare, so
+    /// the pipeline, the metric and the content options of the handle are ignored, but for the
+    /// facets, the stack traces and the symbols written into the metadata the `filter` sees.
+    pub fn find_nearest_posts(
+        &self,
+        embedding: &[f32],
+        top_n: usize,
+        filter: Option<&Filter>,
+    ) -> Result<FindTopNResult> {
+        let snapshot = self.snapshot();
+
+        self.pool.run(0, |_| {
+            let annotations = snapshot.annotations(&self.content);
+            snapshot.find_nearest_posts_in(
+                embedding,
+                top_n,
+                filter,
+                &self.vector_options,
+                annotations.as_deref(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(title: &str, content: &str) -> PostData {
+        PostData {
+            id: None,
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: None,
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_post_store_snapshot_generation() {
+        let store = PostStore::new();
+        assert_eq!(store.generation(), 0);
+
+        store.preload(vec![post(
+            "Deno.kill on windows",
+            "SIGINT is not supported",
+        )]);
+        assert_eq!(store.generation(), 1);
+
+        // a reader holding the old snapshot is unaffected by later writes
+        let snapshot = store.snapshot();
+        store.preload(vec![]);
+        assert_eq!(store.generation(), 2);
+        assert_eq!(snapshot.generation, 1);
+        assert_eq!(snapshot.posts.len(), 1);
+
+        let source = post("Deno.kill on windows", "SIGINT is not supported");
+        let result = store.find_similar_posts(&source, 1, None).unwrap();
+        assert_eq!(result.matches.len(), 0);
+        assert_eq!(result.generation, Some(2));
+
+        let result = snapshot.find_similar_posts(&source, 1, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.generation, Some(1));
+    }
+
+    #[test]
+    fn test_post_store_upsert_and_remove() {
+        let with_id = |id: &str, title: &str| PostData {
+            id: Some(id.to_string()),
+            ..post(title, "SIGINT is not supported")
+        };
+        let store = PostStore::new();
+
+        store.upsert(vec![with_id("1", "Deno.kill"), with_id("2", "Deno.exit")]);
+        store.upsert(vec![
+            with_id("1", "Deno.kill on windows"),
+            post("Deno.run", ""),
+        ]);
+        assert_eq!(store.generation(), 2);
+
+        let snapshot = store.snapshot();
+        let titles: Vec<&str> = snapshot.iter().map(|post| post.title).collect();
+        assert_eq!(
+            titles,
+            vec!["Deno.exit", "Deno.kill on windows", "Deno.run"]
+        );
+
+        assert_eq!(store.remove(&["1", "3"]), 1);
+        assert_eq!(store.generation(), 3);
+        assert_eq!(store.remove(&["1"]), 0);
+        assert_eq!(store.generation(), 3);
+        assert_eq!(store.snapshot().len(), 2);
+    }
+
+    #[test]
+    fn test_post_store_replace_with() {
+        let store = PostStore::new();
+        let other = PostStore::new();
+
+        store.preload(vec![post("Deno.kill", "SIGINT is not supported")]);
+        other.preload(vec![post("Deno.exit", ""), post("Deno.run", "")]);
+        store.replace_with(&other);
+
+        assert_eq!(store.generation(), 2);
+        assert_eq!(
+            store.snapshot().iter().map(|p| p.title).collect::<Vec<_>>(),
+            vec!["Deno.exit", "Deno.run"]
+        );
+    }
+
+    #[test]
+    fn test_posts_snapshot_rank_similar_posts() {
+        let store = PostStore::new();
+        store.preload(vec![
+            post("How to embed V8", "Embedding V8 into a Rust program"),
+            post("Deno.kill on windows", "SIGINT is not supported"),
+        ]);
+
+        let snapshot = store.snapshot();
+        let source = post("Deno.kill on Windows", "SIGINT is not supported");
+        let result = snapshot.rank_similar_posts(&source, 5, None).unwrap();
+        let expected = snapshot.find_similar_posts(&source, 5, None).unwrap();
+
+        assert_eq!(result.generation, Some(1));
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].index, 1);
+        assert_eq!(result.matches[0].score, expected.matches[0].score);
+        assert_eq!(
+            snapshot.get(result.matches[0].index).unwrap().title,
+            "Deno.kill on windows"
+        );
+        assert!(snapshot.get(2).is_none());
+    }
+
+    #[test]
+    fn test_post_store_with_pool() {
+        use crate::pool::PoolOptions;
+
+        let posts: Vec<PostData> = (0..10)
+            .map(|i| {
+                post(
+                    &format!("Deno.kill on windows #{}", i),
+                    "SIGINT is not supported",
+                )
+            })
+            .collect();
+        let source = post("Deno.kill on Windows", "SIGINT is not supported");
+        let parallel = PostStore::new().with_pool(
+            QueryPool::new(&PoolOptions {
+                threads: Some(2),
+                sequential_threshold: Some(0),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let sequential = PostStore::new();
+        parallel.preload(posts.clone());
+        sequential.preload(posts);
+
+        // the scan is sequential below the default threshold, but the matches are the same
+        let expected = parallel.find_similar_posts(&source, 5, None).unwrap();
+        let result = sequential.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches, expected.matches);
+
+        let (_, expected) = parallel.rank_similar_posts(&source, 5, None).unwrap();
+        let (snapshot, result) = sequential.rank_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches, expected.matches);
+        assert_eq!(snapshot.generation, 1);
+    }
+
+    #[test]
+    fn test_post_store_find_nearest_posts() {
+        use serde_json::json;
+
+        use crate::vector::Distance;
+
+        let with_embedding = |title: &str, embedding: Vec<f32>, tag: &str| PostData {
+            embedding: Some(embedding),
+            metadata: json!({ "tags": [tag] }).as_object().cloned(),
+            ..post(title, "")
+        };
+        let store = PostStore::new()
+            .with_vector_index(HnswOptions {
+                distance: Distance::L2,
+                ..Default::default()
+            })
+            .unwrap();
+        store.preload(vec![
+            with_embedding("Deno.kill", vec![1.0, 0.0], "windows"),
+            post("Deno.exit", ""),
+            with_embedding("Deno.run", vec![0.0, 1.0], "linux"),
+        ]);
+
+        let result = store.find_nearest_posts(&[0.9, 0.1], 5, None).unwrap();
+        let titles: Vec<&str> = result
+            .matches
+            .iter()
+            .map(|m| m.target.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Deno.kill", "Deno.run"]);
+        assert!(result.matches[0].score > result.matches[1].score);
+        assert_eq!(result.generation, Some(1));
+
+        let filter = Filter::parse(&json!({ "tags": "linux" })).unwrap();
+        let result = store
+            .find_nearest_posts(&[0.9, 0.1], 5, Some(&filter))
+            .unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].target.title, "Deno.run");
+
+        // the index of the previous snapshot is extended rather than rebuilt
+        store.append(vec![with_embedding("Deno.serve", vec![0.8, 0.2], "linux")]);
+        assert_eq!(store.snapshot().vectors.get().unwrap().len(), 3);
+        let result = store.find_nearest_posts(&[0.8, 0.2], 1, None).unwrap();
+        assert_eq!(result.matches[0].target.title, "Deno.serve");
+
+        store.append(vec![with_embedding("Deno.test", vec![1.0], "linux")]);
+        assert!(store.snapshot().vectors.get().is_none());
+        assert!(matches!(
+            store.find_nearest_posts(&[0.8, 0.2], 1, None),
+            Err(crate::Error::InvalidArgument(_))
+        ));
+        assert!(PostStore::new()
+            .with_vector_index(HnswOptions {
+                m: 1,
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_post_store_find_similar_posts_hybrid() {
+        use crate::hybrid::Fusion;
+
+        let with_embedding = |title: &str, embedding: Vec<f32>| PostData {
+            embedding: Some(embedding),
+            ..post(title, "SIGINT is not supported")
+        };
+        let store = PostStore::new();
+        store.preload(vec![
+            with_embedding("Deno.kill on windows", vec![0.0, 1.0]),
+            PostData {
+                content: "Embedding V8 into a Rust program".to_string(),
+                ..with_embedding("How to embed V8", vec![1.0, 0.1])
+            },
+            with_embedding("Deno.kill on linux", vec![0.9, 0.2]),
+        ]);
+
+        let source = with_embedding("Deno.kill on windows", vec![1.0, 0.0]);
+        let hybrid = HybridOptions {
+            fusion: Fusion::Rrf { k: 60.0 },
+            candidates: None,
+        };
+        let result = store
+            .find_similar_posts_hybrid(&source, 3, None, &hybrid)
+            .unwrap();
+        let ranks: Vec<(&str, Option<usize>, Option<usize>)> = result
+            .matches
+            .iter()
+            .map(|m| (m.target.title.as_str(), m.lexical_rank, m.vector_rank))
+            .collect();
+
+        // the posts found by both retrievers come first
+        assert_eq!(
+            ranks,
+            vec![
+                ("Deno.kill on windows", Some(1), Some(3)),
+                ("Deno.kill on linux", Some(2), Some(2)),
+                ("How to embed V8", None, Some(1)),
+            ]
+        );
+        assert_eq!(result.matches[1].score, 2.0 / 62.0);
+
+        let source = post("Deno.kill on windows", "SIGINT is not supported");
+        assert!(matches!(
+            store.find_similar_posts_hybrid(&source, 3, None, &hybrid),
+            Err(crate::Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_post_store_with_pipeline() {
+        use serde_json::json;
+
+        let posts = vec![
+            post("Deno.kill on windows", "SIGINT is not supported"),
+            post("denojs on termux", "A smooth download for Deno.js"),
+            post("Deno.kill on linux", "SIGINT is not supported"),
+            PostData {
+                metadata: json!({ "os": "macos" }).as_object().cloned(),
+                ..post("Deno.kill on macos", "SIGINT is not supported")
+            },
+        ];
+        let source = post("Deno.kill on windows", "SIGINT is not supported");
+        let full = PostStore::new();
+        full.preload(posts.clone());
+        let expected = full.find_similar_posts(&source, 3, None).unwrap();
+        assert!(expected.stages.is_empty());
+
+        for retriever in ["ngram", "bm25", "simhash"] {
+            let store = PostStore::new()
+                .with_pipeline(PipelineOptions {
+                    retriever: Retriever::parse(retriever).unwrap(),
+                    candidates: Some(3),
+                    rescorer: Metric::Levenshtein,
+                })
+                .unwrap();
+            store.preload(posts.clone());
+
+            // the retrieved candidates are scored like in a full scan
+            let result = store.find_similar_posts(&source, 3, None).unwrap();
+            assert_eq!(result.matches, expected.matches, "{}", retriever);
+
+            let counts: Vec<(Stage, usize, usize)> = result
+                .stages
+                .iter()
+                .map(|stats| (stats.stage, stats.input, stats.output))
+                .collect();
+            assert_eq!(
+                counts,
+                vec![
+                    (Stage::Retrieve, 4, 3),
+                    (Stage::Rerank, 3, 3),
+                    (Stage::Select, 3, 3)
+                ],
+                "{}",
+                retriever
+            );
+        }
+
+        let store = PostStore::new()
+            .with_pipeline(PipelineOptions::default())
+            .unwrap();
+        store.preload(posts);
+        let filter = Filter::parse(&json!({ "os": "macos" })).unwrap();
+        let result = store.find_similar_posts(&source, 3, Some(&filter)).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].target.title, "Deno.kill on macos");
+        assert_eq!(result.stages[0].output, 1);
+
+        assert!(PostStore::new()
+            .with_pipeline(PipelineOptions {
+                retriever: Retriever::Bm25 { k1: 1.2, b: 2.0 },
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_post_store_with_chunking() {
+        use crate::chunk::{Aggregation, Split};
+
+        let shared = "Sending a SIGINT OS signal on windows results in a TypeError";
+        let noise = |word: &str| format!("{} is not what this is about. ", word).repeat(8);
+        let long_post = post(
+            "Deno.kill on windows",
+            &format!("{}\n\n{}\n\n{}", noise("Termux"), shared, noise("Node.js")),
+        );
+        let source = post(
+            "Deno.kill not working",
+            &format!("{}\n\n{}", shared, noise("Python")),
+        );
+
+        let plain = PostStore::new();
+        plain.preload(vec![long_post.clone()]);
+        assert!(plain
+            .find_similar_posts(&source, 1, None)
+            .unwrap()
+            .matches
+            .is_empty());
+
+        let chunking = ChunkOptions {
+            split: Split::Paragraph { overlap: 0 },
+            aggregation: Aggregation::Max,
+        };
+        let store = PostStore::new().with_chunking(chunking).unwrap();
+        store.preload(vec![post("denojs on termux", "A smooth download")]);
+        store.snapshot().chunk_index(&chunking.split);
+        store.append(vec![long_post]);
+
+        // the chunks of the appended post are added to the ones split before
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.chunks.get().unwrap().get(1).len(), 3);
+
+        let result = store.find_similar_posts(&source, 1, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        let m = &result.matches[0];
+        let chunks = m.chunks.clone().unwrap();
+        assert_eq!(&source.content[chunks.source], shared);
+        assert_eq!(&m.target.content[chunks.target], shared);
+        assert_eq!(chunks.score, 1.0);
+
+        // the pipeline scores the retrieved candidates by their chunks as well
+        let store = store
+            .with_pipeline(PipelineOptions {
+                retriever: Retriever::Bm25 { k1: 1.2, b: 0.75 },
+                ..Default::default()
+            })
+            .unwrap();
+        let piped = store.find_similar_posts(&source, 1, None).unwrap();
+        assert_eq!(piped.matches, result.matches);
+        assert_eq!(piped.stages.len(), 3);
+    }
+
+    #[test]
+    fn test_post_store_with_boilerplate() {
+        use crate::{
+            boilerplate::Unit,
+            chunk::{Aggregation, Split},
+        };
+
+        let report = |body: &str| {
+            format!(
+                "Version: Deno 2.3.3\nOS: Windows 11\nArch: x86_64\nShell: PowerShell 7.4\n\n{}",
+                body
+            )
+        };
+        let posts = vec![
+            post("Bug report", &report("Deno.kill fails with SIGINT")),
+            post("Bug report", &report("The formatter hangs on big files")),
+            post("Bug report", &report("The LSP crashes on startup")),
+        ];
+        let source = post("Bug report", &report("Deno.kill fails with SIGBREAK"));
+        let store = PostStore::new();
+        store.preload(posts);
+
+        // the template makes every report a match
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches.len(), 3);
+
+        let boilerplate = store
+            .learn_boilerplate(&BoilerplateOptions::default())
+            .unwrap();
+        assert_eq!(boilerplate.unit(), Unit::Line);
+        assert_eq!(boilerplate.entries().len(), 4);
+        assert_eq!(boilerplate.entries()[0].share, 1.0);
+
+        let store = store.with_boilerplate(boilerplate);
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        let contents: Vec<&str> = result
+            .matches
+            .iter()
+            .map(|m| m.target.content.as_str())
+            .collect();
+        assert_eq!(contents, vec![report("Deno.kill fails with SIGINT")]);
+
+        // the paragraph of the template is skipped when scoring by chunks
+        let store = store
+            .with_chunking(ChunkOptions {
+                split: Split::Paragraph { overlap: 0 },
+                aggregation: Aggregation::Max,
+            })
+            .unwrap();
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        let chunks = result.matches[0].chunks.clone().unwrap();
+        assert_eq!(
+            &result.matches[0].target.content[chunks.target],
+            "Deno.kill fails with SIGINT"
+        );
+    }
+
+    #[test]
+    fn test_post_store_with_facets() {
+        use serde_json::json;
+
+        use crate::facet::{facets_of, FacetOptions};
+
+        let report = |os: &str, body: &str| {
+            post(
+                "Deno.kill fails",
+                &format!("Version: Deno 2.3.3\nOS: {}\n\n{}", os, body),
+            )
+        };
+        let posts = vec![
+            report("Windows 11", "Deno.kill fails with SIGINT"),
+            report("Ubuntu 22.04", "Deno.kill fails with SIGINT"),
+        ];
+        let source = report("Windows 11", "Deno.kill fails with SIGBREAK");
+        let plain = PostStore::new();
+        plain.preload(posts.clone());
+        let plain = plain.find_similar_posts(&source, 5, None).unwrap();
+        assert!(plain.matches.iter().all(|m| m.shared_facets.is_empty()));
+
+        // the facets are extracted per snapshot by the queries, the posts are left as is, and
+        // the ones of the appended posts are added to them
+        let store = PostStore::new();
+        store.preload(posts[..1].to_vec());
+        let store = store
+            .with_facets(FacetOptions {
+                boost: 0.06,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(store.generation(), 1);
+        store.find_similar_posts(&source, 5, None).unwrap();
+        store.append(posts[1..].to_vec());
+
+        let snapshot = store.snapshot();
+        let annotations = snapshot.annotations.get().unwrap().clone();
+        let os: Vec<String> = (0..snapshot.len())
+            .map(|i| {
+                let post = snapshot.get_annotated(i, Some(&annotations)).unwrap();
+                facets_of(post.metadata)["os"].clone()
+            })
+            .collect();
+        assert_eq!(os, vec!["windows 11", "ubuntu 22.04"]);
+        assert!(snapshot.iter().all(|post| post.metadata.is_none()));
+
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        let shared: Vec<Vec<String>> = result
+            .matches
+            .iter()
+            .map(|m| m.shared_facets.clone())
+            .collect();
+        assert_eq!(
+            shared,
+            vec![vec!["os", "runtime", "version"], vec!["runtime", "version"]]
+        );
+        // the boost is proportional to the share of the facets of the source
+        assert!((result.matches[0].score - (plain.matches[0].score + 0.06)).abs() < 1e-9);
+        assert!((result.matches[1].score - (plain.matches[1].score + 0.04)).abs() < 1e-9);
+
+        let filter = Filter::parse(&json!({ "facets.os": "ubuntu 22.04" })).unwrap();
+        let result = store.find_similar_posts(&source, 5, Some(&filter)).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(
+            result.matches[0].target,
+            snapshot
+                .get_annotated(1, Some(&annotations))
+                .unwrap()
+                .to_post_data()
+        );
+
+        assert!(PostStore::new()
+            .with_facets(FacetOptions {
+                boost: -0.1,
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_post_store_queries_with_options() {
+        use serde_json::json;
+
+        use crate::{facet::FacetOptions, hybrid::Fusion};
+
+        let report = |os: &str, body: &str, embedding: [f32; 2]| PostData {
+            embedding: Some(embedding.to_vec()),
+            ..post(
+                "Deno.kill fails",
+                &format!("Version: Deno 2.3.3\nOS: {}\n\n{}", os, body),
+            )
+        };
+        let posts = vec![
+            report("Ubuntu 22.04", "Deno.kill fails with SIGINT", [0.0, 1.0]),
+            report("Windows 11", "Deno.kill fails with SIGINT", [1.0, 0.0]),
+        ];
+        let source = report("Windows 11", "Deno.kill fails with SIGBREAK", [1.0, 0.1]);
+        let plain = PostStore::new();
+        plain.preload(posts.clone());
+        let store = plain
+            .clone()
+            .with_facets(FacetOptions {
+                boost: 0.06,
+                ..Default::default()
+            })
+            .unwrap();
+
+        // the posts are ranked as they are found, with the options of the handle
+        let found = store.find_similar_posts(&source, 5, None).unwrap();
+        let (snapshot, ranked) = store.rank_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(ranked.matches.len(), 2);
+        for (found, ranked) in found.matches.iter().zip(&ranked.matches) {
+            assert_eq!(found.score, ranked.score);
+            assert_eq!(
+                found.target.content,
+                snapshot.get(ranked.index).unwrap().content
+            );
+        }
+        let (_, unboosted) = plain.rank_similar_posts(&source, 5, None).unwrap();
+        assert!((ranked.matches[0].score - (unboosted.matches[0].score + 0.06)).abs() < 1e-9);
+
+        let alignment = plain.clone().with_metric(Metric::Alignment);
+        let found = alignment.find_similar_posts(&source, 5, None).unwrap();
+        let (_, ranked) = alignment.rank_similar_posts(&source, 5, None).unwrap();
+        assert!(!found.matches.is_empty());
+        assert_eq!(
+            found.matches.iter().map(|m| m.score).collect::<Vec<_>>(),
+            ranked.matches.iter().map(|m| m.score).collect::<Vec<_>>()
+        );
+
+        // so are the lexical matches of a hybrid query, which report their facets
+        let hybrid = HybridOptions {
+            fusion: Fusion::Rrf { k: 60.0 },
+            candidates: None,
+        };
+        let result = store
+            .find_similar_posts_hybrid(&source, 2, None, &hybrid)
+            .unwrap();
+        assert_eq!(result.matches[0].target.content, posts[1].content);
+        assert_eq!(result.matches[0].lexical_rank, Some(1));
+        assert_eq!(
+            result.matches[0].shared_facets,
+            vec!["os", "runtime", "version"]
+        );
+        assert_eq!(result.matches[1].shared_facets, vec!["runtime", "version"]);
+
+        // there is no text to compare to the nearest posts, but the filters see the facets
+        let nearest = store.find_nearest_posts(&[1.0, 0.1], 2, None).unwrap();
+        let unboosted = plain.find_nearest_posts(&[1.0, 0.1], 2, None).unwrap();
+        assert_eq!(
+            nearest.matches.iter().map(|m| m.score).collect::<Vec<_>>(),
+            unboosted
+                .matches
+                .iter()
+                .map(|m| m.score)
+                .collect::<Vec<_>>()
+        );
+        assert!(nearest.matches.iter().all(|m| m.shared_facets.is_empty()));
+
+        let filter = Filter::parse(&json!({ "facets.os": "ubuntu 22.04" })).unwrap();
+        let result = store
+            .find_nearest_posts(&[1.0, 0.1], 2, Some(&filter))
+            .unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].target.content, posts[0].content);
+    }
+
+    #[test]
+    fn test_post_store_with_traces() {
+        use serde_json::json;
+
+        use crate::trace::{TraceMatching, TraceOptions};
+
+        let report = |title: &str, prose: &str, home: &str, line: u32| {
+            post(
+                title,
+                &format!(
+                    "{prose}
+
+TypeError: Cannot read properties of undefined (reading 'theme')
+    at loadSettings ({home}/app/src/settings.js:{line}:17)
+    at openPanel ({home}/app/src/panel.js:{line}:5)"
+                ),
+            )
+        };
+        let posts = vec![
+            report(
+                "Settings panel is blank",
+                "Opening the settings from the menu shows an empty panel, the theme picker and the \
+                 list of extensions are missing. Restarting the app or clearing its cache \
+                 doesn't help, and the logs only show the error below every time.",
+                "/home/alice",
+                42,
+            ),
+            post(
+                "Settings panel is slow",
+                "Opening the settings takes a few seconds.",
+            ),
+        ];
+        let source = report(
+            "Crash after upgrading",
+            "Since I upgraded to the latest release yesterday, nothing happens when I click on \
+             the gear icon in the toolbar. I tried reinstalling it twice on two different \
+             machines with no luck, so I'm stuck on the previous version for now, which is \
+             a pity since I was waiting for this release. Here is what the console prints:",
+            "C:\\Users\\bob",
+            45,
+        );
+
+        let store = PostStore::new();
+        store.preload(posts.clone());
+        assert!(store
+            .find_similar_posts(&source, 5, None)
+            .unwrap()
+            .matches
+            .is_empty());
+
+        // the same crash is an exact match whatever its prose
+        let store = store
+            .with_traces(TraceOptions {
+                matching: TraceMatching::Bucket,
+                ..Default::default()
+            })
+            .unwrap();
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].score, 1.0);
+        let fingerprint = result.matches[0].fingerprint.clone().unwrap();
+        assert_eq!(
+            store.traces().unwrap().source(&source.content).unwrap().1,
+            fingerprint
+        );
+
+        let filter = Filter::parse(&json!({ "trace.fingerprint": fingerprint })).unwrap();
+        let snapshot = store.snapshot();
+        let annotations = snapshot.annotations(&store.content).unwrap();
+        let bucket: Vec<_> = (0..snapshot.len())
+            .filter_map(|i| snapshot.get_annotated(i, Some(&annotations)))
+            .filter(|post| filter.matches(post.metadata))
+            .collect();
+        assert_eq!(bucket.len(), 1);
+        assert_eq!(bucket[0].title, "Settings panel is blank");
+        assert_eq!(store.generation(), 1);
+
+        let store = PostStore::new()
+            .with_traces(TraceOptions {
+                matching: TraceMatching::Signal { weight: 0.6 },
+                ..Default::default()
+            })
+            .unwrap();
+        store.preload(posts);
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        // the same trace makes up 0.6 of the score, on top of the similarity of the texts
+        assert_eq!(result.matches.len(), 1);
+        assert!(result.matches[0].score > 0.6 && result.matches[0].score < 1.0);
+        assert_eq!(result.matches[0].fingerprint, Some(fingerprint));
+
+        assert!(PostStore::new()
+            .with_traces(TraceOptions {
+                depth: 0,
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_post_store_with_symbols() {
+        use serde_json::json;
+
+        use crate::symbol::SymbolOptions;
+
+        let posts =
+            vec![
+            post(
+                "Deno.kill doesn't stop the child",
+                "Calling `Deno.kill(child.pid, \"SIGTERM\")` leaves the process running on Linux.",
+            ),
+            post(
+                "Signal handlers aren't called",
+                "`Deno.addSignalListener(\"SIGINT\", onExit)` is never called on Ctrl+C.",
+            ),
+            post("Typo in the manual", "The chapter about permissions has a typo."),
+        ];
+        let source = post(
+            "Child process keeps running",
+            "I send it a `Deno.kill(child.pid, \"SIGTERM\")` but it's ignored.",
+        );
+
+        let store = PostStore::new();
+        store.preload(posts.clone());
+        let plain = store.find_similar_posts(&source, 5, None).unwrap();
+        assert!(plain.matches.is_empty());
+
+        // the same symbols make up half of the score, on top of the similarity of the texts
+        let store = store.with_symbols(SymbolOptions { weight: 0.5 }).unwrap();
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(
+            result.matches[0].target.title,
+            "Deno.kill doesn't stop the child"
+        );
+        assert!(result.matches[0].score > 0.5 && result.matches[0].score < 1.0);
+        assert!(result.matches[0]
+            .shared_symbols
+            .iter()
+            .any(|symbol| symbol == "Deno.kill"));
+
+        let filter = Filter::parse(&json!({ "symbols": "Deno.addSignalListener" })).unwrap();
+        let result = store.find_similar_posts(&source, 5, Some(&filter)).unwrap();
+        assert!(result.matches.is_empty());
+        let snapshot = store.snapshot();
+        let annotations = snapshot.annotations(&store.content).unwrap();
+        let listeners: Vec<_> = (0..snapshot.len())
+            .filter_map(|i| snapshot.get_annotated(i, Some(&annotations)))
+            .filter(|post| filter.matches(post.metadata))
+            .collect();
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].title, "Signal handlers aren't called");
+        // rarer symbols weigh more
+        let symbols = snapshot.symbols();
+        let index = symbols.index();
+        assert_eq!(index.documents(), 3);
+        assert!(index.idf("Deno.kill") > index.idf("Deno"));
+
+        // the scans of crate::post extract the symbols alike, whatever the metadata says
+        let mut tagged = posts.clone();
+        tagged[2].metadata = json!({ "symbols": ["Deno.kill"] }).as_object().cloned();
+        let store = PostStore::new()
+            .with_symbols(SymbolOptions { weight: 0.5 })
+            .unwrap();
+        store.preload(tagged.clone());
+        let plain = find_similar_posts_parallel(
+            &source,
+            tagged.par_iter().map(PostRef::from),
+            5,
+            None,
+            Some(&SymbolOptions { weight: 0.5 }),
+        )
+        .unwrap();
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(plain.matches.len(), 1);
+        assert_eq!(result.matches[0].score, plain.matches[0].score);
+        assert_eq!(
+            result.matches[0].shared_symbols,
+            plain.matches[0].shared_symbols
+        );
+
+        // both weigh the symbols by all the posts, whether the filter admits them or not
+        tagged[0].metadata = json!({ "os": "linux" }).as_object().cloned();
+        store.preload(tagged.clone());
+        let filter = Filter::parse(&json!({ "os": "linux" })).unwrap();
+        let source = post(
+            "Child process keeps running",
+            "I send it a `Deno.kill(child.pid, \"SIGTERM\")` but it's ignored, unlike \
+             `Deno.addSignalListener`.",
+        );
+        let plain = find_similar_posts_parallel(
+            &source,
+            tagged.par_iter().map(PostRef::from),
+            5,
+            Some(&filter),
+            Some(&SymbolOptions { weight: 0.5 }),
+        )
+        .unwrap();
+        let result = store.find_similar_posts(&source, 5, Some(&filter)).unwrap();
+        assert_eq!(plain.matches.len(), 1);
+        assert_eq!(result.matches[0].score, plain.matches[0].score);
+
+        assert!(PostStore::new()
+            .with_symbols(SymbolOptions { weight: -0.1 })
+            .is_err());
+    }
+}
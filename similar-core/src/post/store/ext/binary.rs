@@ -0,0 +1,606 @@
+//! The binary snapshot format of a [PostStore].
+//!
+//! All integers are little-endian. The file starts with a fixed size header, followed by a
+//! section table and the section payloads, each payload is aligned to 8 bytes:
+//!
+//! ```text
+//! magic          [u8; 8]   b"FSPSNAP\0"
+//! version        u32       FORMAT_VERSION
+//! section_count  u32
+//! generation     u64       the store generation at the time of saving
+//! post_count     u64
+//! checksum       u32       CRC-32 of the header but this field, and of everything after
+//!                          the header
+//! reserved       u32
+//! sections       section_count * { kind: u32, reserved: u32, offset: u64, len: u64 }
+//! ```
+//!
+//! The posts are split into two sections, a fixed size index holding the `(offset, len)` pairs of
+//! the title, content, JSON encoded metadata, ID and embedding of each post, and a blob holding the
+//! UTF-8 bytes, or the little-endian `f32`s of the embedding, they point to. Since no field
+//! needs to be decoded to locate another, posts can be decoded in parallel, and the text can be
+//! read in place from a memory mapped file.
+//!
+//! The vector index over the embeddings and the text index of the first stage of a pipeline
+//! are saved in two more sections if they have been built, and restored when the snapshot is
+//! loaded or opened, so the first query doesn't rebuild them. They're left out when posts of an
+//! opened snapshot file have been replaced or removed, since they refer to the posts by their
+//! positions. The chunks, the symbols and the annotations of the posts are cheaper to compute
+//! and are rebuilt by the first query which needs them.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rayon::{
+    iter::{IntoParallelIterator, ParallelIterator},
+    slice::ParallelSlice,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    codec::Writer,
+    pipeline::TextIndex,
+    post::{PostData, PostStore, PostsSnapshot},
+    vector::Hnsw,
+    Error, Result,
+};
+
+pub const MAGIC: &[u8; 8] = b"FSPSNAP\0";
+pub const FORMAT_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 40;
+const SECTION_ENTRY_LEN: usize = 24;
+const INDEX_FIELD_LEN: usize = 16;
+/// The `(offset, len)` pairs stored per post in the index, for its title, content, metadata, ID
+/// and embedding.
+const FIELDS_PER_POST: usize = 5;
+const CHECKSUM_CHUNK_LEN: usize = 4 << 20;
+
+/// The range of the checksum within the header.
+const CHECKSUM_FIELD: Range<usize> = 32..36;
+
+const SECTION_POST_INDEX: u32 = 1;
+const SECTION_POST_BLOB: u32 = 2;
+const SECTION_VECTOR_INDEX: u32 = 3;
+const SECTION_TEXT_INDEX: u32 = 4;
+
+impl PostsSnapshot {
+    /// Saves the posts and their indexes to a binary snapshot file, which can be restored with
+    /// [PostStore::load].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_snapshot(path.as_ref(), self)
+    }
+}
+
+impl PostStore {
+    /// Saves the posts of the current snapshot and their indexes to a binary snapshot file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.snapshot().save(path)
+    }
+
+    /// Loads a store from a binary snapshot file created by [PostStore::save].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = fs::read(path).map_err(|e| Error::Io("Cannot read snapshot file", e))?;
+        let layout = SnapshotLayout::parse(&bytes)?;
+        let posts = layout.read_posts(&bytes)?;
+        let snapshot = PostsSnapshot {
+            generation: layout.generation,
+            base: None,
+            posts: Arc::new(posts),
+            ..Default::default()
+        };
+        layout.restore_indexes(&bytes, &snapshot)?;
+
+        Ok(PostStore::from_snapshot(snapshot))
+    }
+}
+
+/// A path next to `path` to write a snapshot to before renaming it into place, unique within the
+/// process and among the processes, so concurrent saves never write to the same file.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    PathBuf::from(name)
+}
+
+/// Writes the snapshot to a temporary file next to `path` and renames it into place, so a crash
+/// while saving never leaves a truncated snapshot behind.
+pub fn write_snapshot(path: &Path, snapshot: &PostsSnapshot) -> Result<()> {
+    let (header, body) = encode_snapshot(snapshot)?;
+    let tmp_path = temp_path(path);
+    let write = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&header)?;
+        writer.write_all(&body)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        Error::Io("Cannot write snapshot file", e)
+    })
+}
+
+/// Encodes the snapshot, returns the header and the body.
+fn encode_snapshot(snapshot: &PostsSnapshot) -> Result<(Vec<u8>, Vec<u8>)> {
+    let post_count = snapshot.len();
+    let mut index = Vec::with_capacity(post_count * FIELDS_PER_POST * INDEX_FIELD_LEN);
+    let mut blob = Vec::new();
+
+    for post in snapshot.iter() {
+        let metadata = match post.metadata {
+            Some(metadata) => serde_json::to_vec(metadata)
+                .map_err(|e| Error::Json("Cannot serialize post metadata", e))?,
+            None => Vec::new(),
+        };
+
+        let id = post.id.unwrap_or_default().as_bytes();
+        let embedding: Vec<u8> = post
+            .embedding
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+
+        for field in [
+            post.title.as_bytes(),
+            post.content.as_bytes(),
+            &metadata,
+            id,
+            &embedding,
+        ] {
+            index.extend_from_slice(&(blob.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(field.len() as u64).to_le_bytes());
+            blob.extend_from_slice(field);
+        }
+    }
+
+    let mut sections = vec![(SECTION_POST_INDEX, index), (SECTION_POST_BLOB, blob)];
+    if snapshot.removed.count == 0 {
+        if let Some(index) = snapshot.vectors.get() {
+            let mut writer = Writer::default();
+            index.encode(&mut writer);
+            sections.push((SECTION_VECTOR_INDEX, writer.bytes));
+        }
+        if let Some(index) = snapshot.texts.get() {
+            let mut writer = Writer::default();
+            index.encode(&mut writer);
+            sections.push((SECTION_TEXT_INDEX, writer.bytes));
+        }
+    }
+
+    let payload_len: usize = sections.iter().map(|(_, payload)| payload.len()).sum();
+    let mut body = Vec::with_capacity(payload_len + sections.len() * (SECTION_ENTRY_LEN + 8));
+    let mut offset = align(HEADER_LEN + sections.len() * SECTION_ENTRY_LEN);
+
+    for (kind, payload) in sections.iter() {
+        body.extend_from_slice(&kind.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&(offset as u64).to_le_bytes());
+        body.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        offset = align(offset + payload.len());
+    }
+
+    for (_, payload) in sections.iter() {
+        body.resize(align(HEADER_LEN + body.len()) - HEADER_LEN, 0);
+        body.extend_from_slice(payload);
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    header.extend_from_slice(&snapshot.generation.to_le_bytes());
+    header.extend_from_slice(&(post_count as u64).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    let checksum = checksum(&header, &body);
+    header[CHECKSUM_FIELD].copy_from_slice(&checksum.to_le_bytes());
+
+    Ok((header, body))
+}
+
+/// The validated layout of a snapshot file, locating the sections within its bytes.
+#[derive(Debug, Clone)]
+pub struct SnapshotLayout {
+    pub generation: u64,
+    pub post_count: usize,
+    index: Range<usize>,
+    blob: Range<usize>,
+    vectors: Option<Range<usize>>,
+    texts: Option<Range<usize>>,
+}
+
+/// The fields of a post as they are stored in a snapshot, borrowed from the snapshot bytes.
+pub struct PostEntry<'a> {
+    pub id: Option<&'a str>,
+    pub title: &'a str,
+    pub content: &'a str,
+    pub metadata: Option<&'a [u8]>,
+    pub embedding: Option<&'a [u8]>,
+}
+
+impl SnapshotLayout {
+    /// Validates the header, the format version and the checksum of the snapshot.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+            return Err(invalid("bad magic number"));
+        }
+
+        let version = read_u32(bytes, 8);
+        if version != FORMAT_VERSION {
+            return Err(Error::InvalidSnapshot(format!(
+                "Unsupported snapshot version {}, expected {}",
+                version, FORMAT_VERSION
+            )));
+        }
+
+        let section_count = read_u32(bytes, 12) as usize;
+        let generation = read_u64(bytes, 16);
+        let post_count = read_u64(bytes, 24) as usize;
+        let expected_checksum = read_u32(bytes, 32);
+
+        let (header, body) = bytes.split_at(HEADER_LEN);
+        if checksum(header, body) != expected_checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let mut index = None;
+        let mut blob = None;
+        let mut vectors = None;
+        let mut texts = None;
+
+        for i in 0..section_count {
+            let entry = HEADER_LEN + i * SECTION_ENTRY_LEN;
+            if entry + SECTION_ENTRY_LEN > bytes.len() {
+                return Err(invalid("truncated section table"));
+            }
+
+            let kind = read_u32(bytes, entry);
+            let offset = read_u64(bytes, entry + 8) as usize;
+            let len = read_u64(bytes, entry + 16) as usize;
+            let payload = match offset.checked_add(len) {
+                Some(end) if end <= bytes.len() => offset..end,
+                _ => return Err(invalid("truncated section")),
+            };
+
+            match kind {
+                SECTION_POST_INDEX => index = Some(payload),
+                SECTION_POST_BLOB => blob = Some(payload),
+                SECTION_VECTOR_INDEX => vectors = Some(payload),
+                SECTION_TEXT_INDEX => texts = Some(payload),
+                // sections unknown to this version are skipped
+                _ => {}
+            }
+        }
+
+        let (index, blob) = match (index, blob) {
+            (Some(index), Some(blob)) => (index, blob),
+            _ => return Err(invalid("missing post sections")),
+        };
+
+        if Some(index.len()) != post_count.checked_mul(FIELDS_PER_POST * INDEX_FIELD_LEN) {
+            return Err(invalid("post index size mismatch"));
+        }
+
+        Ok(SnapshotLayout {
+            generation,
+            post_count,
+            index,
+            blob,
+            vectors,
+            texts,
+        })
+    }
+
+    /// Restores the indexes saved along with the posts into `snapshot`, which holds the posts
+    /// of the file in the same order.
+    pub fn restore_indexes(&self, bytes: &[u8], snapshot: &PostsSnapshot) -> Result<()> {
+        if let Some(vectors) = &self.vectors {
+            let index = Hnsw::decode(&bytes[vectors.clone()], self.post_count)?;
+            let _ = snapshot.vectors.set(Arc::new(index));
+        }
+        if let Some(texts) = &self.texts {
+            let index = TextIndex::decode(&bytes[texts.clone()], self.post_count)?;
+            let _ = snapshot.texts.set(Arc::new(index));
+        }
+        Ok(())
+    }
+
+    /// Returns the byte ranges of the title, content, metadata, ID and embedding of the post at
+    /// `i` within the blob section.
+    pub fn ranges(&self, bytes: &[u8], i: usize) -> Result<[Range<usize>; 5]> {
+        let index = &bytes[self.index.clone()];
+        let blob_len = self.blob.len();
+        let entry = i * FIELDS_PER_POST * INDEX_FIELD_LEN;
+        let range = |field: usize| -> Result<Range<usize>> {
+            let offset = read_u64(index, entry + field * INDEX_FIELD_LEN) as usize;
+            let len = read_u64(index, entry + field * INDEX_FIELD_LEN + 8) as usize;
+
+            match offset.checked_add(len) {
+                Some(end) if end <= blob_len => Ok(offset..end),
+                _ => Err(invalid(&format!("post {} is out of bounds", i))),
+            }
+        };
+
+        Ok([range(0)?, range(1)?, range(2)?, range(3)?, range(4)?])
+    }
+
+    /// The blob section within the bytes of the snapshot, which the ranges of the posts point
+    /// into.
+    pub fn blob<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        &bytes[self.blob.clone()]
+    }
+
+    /// Returns the fields of the post at `i`, borrowed from the bytes of the snapshot.
+    pub fn entry<'a>(&self, bytes: &'a [u8], i: usize) -> Result<PostEntry<'a>> {
+        let [title, content, metadata, id, embedding] = self.ranges(bytes, i)?;
+        let blob = &bytes[self.blob.clone()];
+        let text = |range: Range<usize>| {
+            std::str::from_utf8(&blob[range]).map_err(|e| invalid(&format!("post {}: {}", i, e)))
+        };
+
+        Ok(PostEntry {
+            id: if id.is_empty() { None } else { Some(text(id)?) },
+            title: text(title)?,
+            content: text(content)?,
+            metadata: if metadata.is_empty() {
+                None
+            } else {
+                Some(&blob[metadata])
+            },
+            embedding: if embedding.is_empty() {
+                None
+            } else {
+                Some(&blob[embedding])
+            },
+        })
+    }
+
+    /// Decodes all the posts in parallel.
+    pub fn read_posts(&self, bytes: &[u8]) -> Result<Vec<PostData>> {
+        (0..self.post_count)
+            .into_par_iter()
+            .map(|i| {
+                let PostEntry {
+                    id,
+                    title,
+                    content,
+                    metadata,
+                    embedding,
+                } = self.entry(bytes, i)?;
+
+                Ok(PostData {
+                    id: id.map(|id| id.to_string()),
+                    title: title.to_string(),
+                    content: content.to_string(),
+                    metadata: metadata.map(parse_metadata).transpose()?,
+                    embedding: embedding.map(parse_embedding).transpose()?,
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn parse_metadata(bytes: &[u8]) -> Result<Map<String, Value>> {
+    serde_json::from_slice(bytes).map_err(|e| invalid(&format!("bad metadata: {}", e)))
+}
+
+pub fn parse_embedding(bytes: &[u8]) -> Result<Vec<f32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(invalid("bad embedding: truncated value"));
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidSnapshot(format!("Invalid snapshot file: {}", reason))
+}
+
+/// Computes the CRC-32 of a snapshot, which covers the header but its checksum field, and the
+/// body. The body is hashed in parallel chunks whose results are combined.
+fn checksum(header: &[u8], body: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header[..CHECKSUM_FIELD.start]);
+    hasher.update(&header[CHECKSUM_FIELD.end..]);
+
+    let body = body
+        .par_chunks(CHECKSUM_CHUNK_LEN)
+        .map(|chunk| {
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(chunk);
+            hasher
+        })
+        .reduce(crc32fast::Hasher::new, |mut a, b| {
+            a.combine(&b);
+            a
+        });
+    hasher.combine(&body);
+    hasher.finalize()
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 7) & !7
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{pipeline::Retriever, vector::HnswOptions};
+
+    use super::*;
+
+    fn snapshot() -> PostsSnapshot {
+        PostsSnapshot {
+            generation: 7,
+            base: None,
+            posts: Arc::new(vec![
+                PostData {
+                    id: Some("1".to_string()),
+                    title: "Deno.kill on windows".to_string(),
+                    content: "Windows only supports ctrl-c (SIGINT) and ctrl-break".to_string(),
+                    metadata: match json!({ "tags": ["windows", "signal"], "createdAt": 1 }) {
+                        Value::Object(map) => Some(map),
+                        _ => None,
+                    },
+                    embedding: Some(vec![0.5, -1.25, 3.0]),
+                },
+                PostData {
+                    id: None,
+                    title: "denojs on termux like nodejs".to_string(),
+                    content: "在 Termux 上顺利下载 Deno.js".to_string(),
+                    metadata: None,
+                    embedding: None,
+                },
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-round-trip.snapshot");
+        write_snapshot(&path, &snapshot()).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let layout = SnapshotLayout::parse(&bytes).unwrap();
+        let posts = layout.read_posts(&bytes).unwrap();
+        let expected = snapshot();
+
+        assert_eq!(layout.generation, 7);
+        assert_eq!(posts.len(), 2);
+        for (post, expected) in posts.iter().zip(expected.posts.iter()) {
+            assert_eq!(post.id, expected.id);
+            assert_eq!(post.title, expected.title);
+            assert_eq!(post.content, expected.content);
+            assert_eq!(post.metadata, expected.metadata);
+            assert_eq!(post.embedding, expected.embedding);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-validation.snapshot");
+        write_snapshot(&path, &snapshot()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert!(SnapshotLayout::parse(&corrupted).is_err());
+
+        // the header is covered by the checksum too
+        let mut corrupted = bytes.clone();
+        corrupted[16] ^= 0xff;
+        assert!(SnapshotLayout::parse(&corrupted).is_err());
+
+        let mut future = bytes.clone();
+        future[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(SnapshotLayout::parse(&future).is_err());
+
+        assert!(SnapshotLayout::parse(&bytes[..HEADER_LEN - 1]).is_err());
+        assert!(SnapshotLayout::parse(b"not a snapshot file at all, just text....").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_temp_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("posts.snapshot");
+        let first = temp_path(&path);
+        let second = temp_path(&path);
+
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        assert!(first
+            .to_string_lossy()
+            .starts_with(&*path.to_string_lossy()));
+        assert_eq!(first.extension().unwrap(), "tmp");
+    }
+
+    #[test]
+    fn test_post_store_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-store.snapshot");
+        let store = PostStore::new();
+        store.preload(snapshot().posts.to_vec());
+        store.preload(snapshot().posts.to_vec());
+        store.save(&path).unwrap();
+
+        let loaded = PostStore::load(&path).unwrap();
+
+        assert_eq!(loaded.generation(), 2);
+        assert_eq!(loaded.snapshot().len(), 2);
+    }
+
+    #[test]
+    fn test_post_store_save_and_load_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("posts.snapshot");
+        let retriever = Retriever::Bm25 { k1: 1.2, b: 0.75 };
+        let options = HnswOptions::default();
+        let store = PostStore::new();
+        store.preload(snapshot().posts.to_vec());
+
+        // the indexes which haven't been built aren't saved
+        store.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let layout = SnapshotLayout::parse(&bytes).unwrap();
+        assert!(layout.vectors.is_none());
+        assert!(layout.texts.is_none());
+
+        let current = store.snapshot();
+        let vectors = current.vector_index(&options).unwrap();
+        let texts = current.text_index(&retriever);
+        store.save(&path).unwrap();
+
+        let loaded = PostStore::load(&path).unwrap().snapshot();
+        let restored = loaded.vectors.get().unwrap();
+        assert_eq!(restored.options(), &options);
+        assert_eq!(
+            restored.search(&[0.5, -1.0, 3.0], 2).unwrap(),
+            vectors.search(&[0.5, -1.0, 3.0], 2).unwrap()
+        );
+        assert!(Arc::ptr_eq(
+            &loaded.vector_index(&options).unwrap(),
+            restored
+        ));
+
+        let restored = loaded.texts.get().unwrap();
+        assert_eq!(restored.retriever(), &retriever);
+        assert_eq!(
+            restored.top_k("deno on termux", 2, |_| true, false),
+            texts.top_k("deno on termux", 2, |_| true, false)
+        );
+        assert!(Arc::ptr_eq(&loaded.text_index(&retriever), restored));
+    }
+}
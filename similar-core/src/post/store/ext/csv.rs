@@ -0,0 +1,340 @@
+use std::{collections::BTreeSet, fs::File, path::Path, sync::Arc};
+
+use csv::{ErrorKind, ReaderBuilder, WriterBuilder};
+use serde_json::{Map, Value};
+
+use crate::{
+    load::{LoadError, LoadReport},
+    post::{PostData, PostStore, PostsSnapshot},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// The column holding the title of the post, defaults to `title`.
+    pub title_column: Option<String>,
+    /// The column holding the content of the post, defaults to `content`.
+    pub content_column: Option<String>,
+    /// The column holding the ID of the post, defaults to `id`. Unlike the title and content
+    /// columns, the default one may be absent from the file.
+    pub id_column: Option<String>,
+    /// The field delimiter, defaults to `,`.
+    pub delimiter: Option<u8>,
+}
+
+struct Columns<'a> {
+    title: &'a str,
+    content: &'a str,
+    id: &'a str,
+    id_required: bool,
+    delimiter: u8,
+}
+
+impl<'a> From<&'a CsvOptions> for Columns<'a> {
+    fn from(options: &'a CsvOptions) -> Self {
+        Columns {
+            title: options.title_column.as_deref().unwrap_or("title"),
+            content: options.content_column.as_deref().unwrap_or("content"),
+            id: options.id_column.as_deref().unwrap_or("id"),
+            id_required: options.id_column.is_some(),
+            delimiter: options.delimiter.unwrap_or(b','),
+        }
+    }
+}
+
+impl PostStore {
+    /// Replaces the posts in the store with the ones in a CSV file. Columns other than the
+    /// title, content and ID ones are kept as string metadata. Malformed records are skipped and
+    /// reported with their line numbers.
+    pub fn load_csv(&self, path: impl AsRef<Path>, options: &CsvOptions) -> Result<LoadReport> {
+        let columns = Columns::from(options);
+        let file = File::open(path).map_err(|e| Error::Io("Cannot open CSV file", e))?;
+        let mut rdr = ReaderBuilder::new()
+            .delimiter(columns.delimiter)
+            .from_reader(file);
+        let headers = rdr
+            .headers()
+            .map_err(|e| Error::Csv("Cannot read CSV header", e))?
+            .clone();
+        let find_column = |name: &str| headers.iter().position(|header| header == name);
+        let missing_column = |name: &str| {
+            Error::InvalidArgument(format!("Column '{}' is not found in the CSV header", name))
+        };
+        let title_index =
+            find_column(columns.title).ok_or_else(|| missing_column(columns.title))?;
+        let content_index =
+            find_column(columns.content).ok_or_else(|| missing_column(columns.content))?;
+        let id_index = match find_column(columns.id) {
+            None if columns.id_required => return Err(missing_column(columns.id)),
+            index => index,
+        };
+
+        let mut posts: Vec<PostData> = Vec::new();
+        let mut errors: Vec<LoadError> = Vec::new();
+
+        for record in rdr.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    if let ErrorKind::Io(_) = e.kind() {
+                        return Err(Error::Csv("Cannot read CSV file", e));
+                    }
+
+                    errors.push(LoadError {
+                        line: e.position().map_or(0, |pos| pos.line()),
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let mut metadata = Map::new();
+
+            for (i, (header, field)) in headers.iter().zip(record.iter()).enumerate() {
+                if i != title_index
+                    && i != content_index
+                    && Some(i) != id_index
+                    && !field.is_empty()
+                {
+                    metadata.insert(header.to_string(), Value::String(field.to_string()));
+                }
+            }
+
+            posts.push(PostData {
+                id: id_index
+                    .and_then(|i| record.get(i))
+                    .filter(|id| !id.is_empty())
+                    .map(|id| id.to_string()),
+                title: record[title_index].to_string(),
+                content: record[content_index].to_string(),
+                metadata: if metadata.is_empty() {
+                    None
+                } else {
+                    Some(metadata)
+                },
+                embedding: None,
+            });
+        }
+
+        let loaded = posts.len();
+        let posts = Arc::new(posts);
+
+        self.posts.rcu(|current| PostsSnapshot {
+            generation: current.generation + 1,
+            base: None,
+            posts: posts.clone(),
+            ..Default::default()
+        });
+
+        Ok(LoadReport { loaded, errors })
+    }
+
+    /// Writes the posts of the current snapshot to a CSV file, see [PostsSnapshot::dump_csv].
+    pub fn dump_csv(&self, path: impl AsRef<Path>, options: &CsvOptions) -> Result<()> {
+        self.snapshot().dump_csv(path, options)
+    }
+}
+
+impl PostsSnapshot {
+    /// Writes the posts to a CSV file, using the same column options as [PostStore::load_csv].
+    /// Metadata values which are not strings are written as JSON.
+    pub fn dump_csv(&self, path: impl AsRef<Path>, options: &CsvOptions) -> Result<()> {
+        let columns = Columns::from(options);
+        let has_id = self.iter().any(|post| post.id.is_some());
+        let metadata_keys: BTreeSet<&str> = self
+            .iter()
+            .flat_map(|post| post.metadata.into_iter().flat_map(|map| map.keys()))
+            .map(|key| key.as_str())
+            .filter(|key| *key != columns.title && *key != columns.content && *key != columns.id)
+            .collect();
+
+        let file = File::create(path).map_err(|e| Error::Io("Cannot create CSV file", e))?;
+        let mut wtr = WriterBuilder::new()
+            .delimiter(columns.delimiter)
+            .from_writer(file);
+        let write_error = |e: csv::Error| Error::Csv("Cannot write CSV record", e);
+
+        let mut header: Vec<&str> = Vec::with_capacity(metadata_keys.len() + 3);
+        if has_id {
+            header.push(columns.id);
+        }
+        header.push(columns.title);
+        header.push(columns.content);
+        header.extend(metadata_keys.iter());
+        wtr.write_record(&header).map_err(write_error)?;
+
+        for post in self.iter() {
+            let mut record: Vec<String> = Vec::with_capacity(header.len());
+            if has_id {
+                record.push(post.id.unwrap_or_default().to_string());
+            }
+            record.push(post.title.to_string());
+            record.push(post.content.to_string());
+
+            for key in metadata_keys.iter() {
+                record.push(match post.metadata.and_then(|map| map.get(*key)) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                });
+            }
+
+            wtr.write_record(&record).map_err(write_error)?;
+        }
+
+        wtr.flush()
+            .map_err(|e| Error::Io("Cannot flush CSV writer", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_post_store_load_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-load.csv");
+        fs::write(
+            &path,
+            "key;title;text;author\n\
+             1;Deno.kill on windows;Windows only supports SIGINT and SIGBREAK;alice\n\
+             2;broken row\n\
+             3;denojs on termux;\"A smooth download\nfor Deno.js\";\n",
+        )
+        .unwrap();
+
+        let store = PostStore::new();
+        let report = store
+            .load_csv(
+                &path,
+                &CsvOptions {
+                    title_column: None,
+                    content_column: Some("text".to_string()),
+                    id_column: Some("key".to_string()),
+                    delimiter: Some(b';'),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 3);
+
+        let snapshot = store.snapshot();
+        let posts: Vec<PostData> = snapshot.iter().map(|post| post.to_post_data()).collect();
+        assert_eq!(store.generation(), 1);
+        assert_eq!(posts[0].id.as_deref(), Some("1"));
+        assert_eq!(posts[0].title, "Deno.kill on windows");
+        assert_eq!(
+            posts[0].metadata.as_ref().and_then(|map| map.get("author")),
+            Some(&Value::String("alice".to_string()))
+        );
+        assert_eq!(posts[1].id.as_deref(), Some("3"));
+        assert_eq!(posts[1].content, "A smooth download\nfor Deno.js");
+        assert_eq!(posts[1].metadata, None);
+    }
+
+    #[test]
+    fn test_post_store_load_csv_with_traces() {
+        use crate::trace::{TraceMatching, TraceOptions};
+
+        let trace = |home: &str| {
+            format!(
+                "TypeError: Cannot read properties of undefined (reading 'theme')\n    \
+                 at loadSettings ({}/app/src/settings.js:42:17)",
+                home
+            )
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-load-traces.csv");
+        fs::write(
+            &path,
+            format!(
+                "title,content\nSettings panel is blank,\"{}\"\nSettings panel is slow,It takes seconds\n",
+                trace("/home/alice")
+            ),
+        )
+        .unwrap();
+
+        // the loaded posts are annotated like the ones written afterwards
+        let store = PostStore::new()
+            .with_traces(TraceOptions {
+                matching: TraceMatching::Bucket,
+                ..Default::default()
+            })
+            .unwrap();
+        let report = store.load_csv(&path, &CsvOptions::default()).unwrap();
+        assert_eq!(report.loaded, 2);
+
+        let source = PostData {
+            id: None,
+            title: "Crash after upgrading".to_string(),
+            content: trace("C:\\Users\\bob"),
+            metadata: None,
+            embedding: None,
+        };
+        let result = store.find_similar_posts(&source, 5, None).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].target.title, "Settings panel is blank");
+        assert!(result.matches[0].fingerprint.is_some());
+    }
+
+    #[test]
+    fn test_post_store_load_csv_missing_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-missing-column.csv");
+        fs::write(&path, "title,text\nDeno.kill on windows,SIGINT\n").unwrap();
+
+        let store = PostStore::new();
+        let result = store.load_csv(&path, &CsvOptions::default());
+
+        assert!(result.is_err());
+        assert_eq!(store.generation(), 0);
+    }
+
+    #[test]
+    fn test_post_store_dump_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("find-similar-posts-dump-input.csv");
+        let output = dir.path().join("find-similar-posts-dump-output.csv");
+        fs::write(
+            &input,
+            "id,title,content,tags\n\
+             1,Deno.kill on windows,\"Windows only supports SIGINT, SIGBREAK\",windows\n\
+             2,denojs on termux,A smooth download for Deno.js,\n",
+        )
+        .unwrap();
+
+        let store1 = PostStore::new();
+        store1.load_csv(&input, &CsvOptions::default()).unwrap();
+        store1.dump_csv(&output, &CsvOptions::default()).unwrap();
+
+        let store2 = PostStore::new();
+        let report = store2.load_csv(&output, &CsvOptions::default()).unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert!(report.errors.is_empty());
+
+        let posts1: Vec<PostData> = store1
+            .posts
+            .load()
+            .iter()
+            .map(|p| p.to_post_data())
+            .collect();
+        let posts2: Vec<PostData> = store2
+            .posts
+            .load()
+            .iter()
+            .map(|p| p.to_post_data())
+            .collect();
+        for (post1, post2) in posts1.iter().zip(posts2.iter()) {
+            assert_eq!(post1.id, post2.id);
+            assert_eq!(post1.title, post2.title);
+            assert_eq!(post1.content, post2.content);
+            assert_eq!(post1.metadata, post2.metadata);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::sync::Arc;
+
+use futures_util::TryStreamExt;
+use serde_json::{Map, Number, Value};
+use sqlx::{
+    Column, ColumnIndex, Connection, Database, Decode, Executor, IntoArguments, MySqlConnection,
+    PgConnection, Row, SqliteConnection, Type,
+};
+
+use crate::{
+    load::quote_table,
+    post::{PostData, PostStore, PostsSnapshot},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct DbColumns {
+    /// The column holding the ID of the post, defaults to `id`, which may be absent.
+    pub id: Option<String>,
+    /// The column holding the title of the post, defaults to `title`.
+    pub title: Option<String>,
+    /// The column holding the content of the post, defaults to `content`.
+    pub content: Option<String>,
+    /// The columns copied into the metadata of the post, defaults to all the other columns.
+    pub metadata: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DbOptions {
+    /// The database URL, its scheme (`mysql:`, `postgres:` or `sqlite:`) selects the driver.
+    pub url: String,
+    /// The table to read the posts from, either `table` or `query` must be given. It must be
+    /// a plain or a dotted identifier, such as `posts` or `public.posts`.
+    pub table: Option<String>,
+    /// A `select` statement to read the posts with, takes precedence over `table`.
+    pub query: Option<String>,
+    pub columns: Option<DbColumns>,
+}
+
+/// The positions of the mapped columns in the result set, resolved from the first row.
+struct ColumnIndexes {
+    id: Option<usize>,
+    title: usize,
+    content: usize,
+    metadata: Vec<(String, usize)>,
+}
+
+impl ColumnIndexes {
+    fn resolve(names: &[&str], columns: &DbColumns) -> std::result::Result<Self, sqlx::Error> {
+        let find = |name: &str| names.iter().position(|column| *column == name);
+        let require =
+            |name: &str| find(name).ok_or_else(|| sqlx::Error::ColumnNotFound(name.to_string()));
+        let id = columns.id.as_deref().unwrap_or("id");
+        let title = columns.title.as_deref().unwrap_or("title");
+        let content = columns.content.as_deref().unwrap_or("content");
+        let metadata = match &columns.metadata {
+            Some(metadata) => metadata
+                .iter()
+                .map(|name| Ok((name.clone(), require(name)?)))
+                .collect::<std::result::Result<Vec<_>, sqlx::Error>>()?,
+            None => names
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| **name != id && **name != title && **name != content)
+                .map(|(i, name)| (name.to_string(), i))
+                .collect(),
+        };
+
+        Ok(ColumnIndexes {
+            id: match find(id) {
+                None if columns.id.is_some() => return Err(sqlx::Error::ColumnNotFound(id.into())),
+                index => index,
+            },
+            title: require(title)?,
+            content: require(content)?,
+            metadata,
+        })
+    }
+}
+
+/// Decodes a column into a JSON value, trying the common scalar types in turn, since the
+/// columns of an arbitrary query aren't known upfront.
+fn decode_value<'r, R>(row: &'r R, i: usize) -> Value
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    Option<String>: Decode<'r, R::Database> + Type<R::Database>,
+    Option<i64>: Decode<'r, R::Database> + Type<R::Database>,
+    Option<i32>: Decode<'r, R::Database> + Type<R::Database>,
+    Option<f64>: Decode<'r, R::Database> + Type<R::Database>,
+    Option<bool>: Decode<'r, R::Database> + Type<R::Database>,
+{
+    if let Ok(value) = row.try_get::<Option<String>, _>(i) {
+        value.map_or(Value::Null, Value::String)
+    } else if let Ok(value) = row.try_get::<Option<i64>, _>(i) {
+        value.map_or(Value::Null, Value::from)
+    } else if let Ok(value) = row.try_get::<Option<i32>, _>(i) {
+        value.map_or(Value::Null, Value::from)
+    } else if let Ok(value) = row.try_get::<Option<f64>, _>(i) {
+        value
+            .and_then(Number::from_f64)
+            .map_or(Value::Null, Value::Number)
+    } else if let Ok(value) = row.try_get::<Option<bool>, _>(i) {
+        value.map_or(Value::Null, Value::Bool)
+    } else {
+        Value::Null
+    }
+}
+
+fn text_value(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(value) => Some(value),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Streams the rows of `sql` and maps them to posts, so the raw rows never pile up in memory.
+async fn fetch_posts<'c, DB, E>(
+    db: E,
+    sql: &str,
+    columns: &DbColumns,
+) -> std::result::Result<Vec<PostData>, sqlx::Error>
+where
+    DB: Database,
+    E: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    usize: ColumnIndex<DB::Row>,
+    for<'r> Option<String>: Decode<'r, DB> + Type<DB>,
+    for<'r> Option<i64>: Decode<'r, DB> + Type<DB>,
+    for<'r> Option<i32>: Decode<'r, DB> + Type<DB>,
+    for<'r> Option<f64>: Decode<'r, DB> + Type<DB>,
+    for<'r> Option<bool>: Decode<'r, DB> + Type<DB>,
+{
+    let mut rows = sqlx::query(sql).fetch(db);
+    let mut indexes: Option<ColumnIndexes> = None;
+    let mut posts: Vec<PostData> = Vec::new();
+
+    while let Some(row) = rows.try_next().await? {
+        let indexes = match &mut indexes {
+            Some(indexes) => indexes,
+            None => {
+                let names: Vec<&str> = row.columns().iter().map(|c| c.name()).collect();
+                indexes.insert(ColumnIndexes::resolve(&names, columns)?)
+            }
+        };
+        let mut metadata = Map::new();
+
+        for (name, i) in indexes.metadata.iter() {
+            match decode_value(&row, *i) {
+                Value::Null => {}
+                value => {
+                    metadata.insert(name.clone(), value);
+                }
+            }
+        }
+
+        posts.push(PostData {
+            id: indexes.id.and_then(|i| text_value(decode_value(&row, i))),
+            title: text_value(decode_value(&row, indexes.title)).unwrap_or_default(),
+            content: text_value(decode_value(&row, indexes.content)).unwrap_or_default(),
+            metadata: if metadata.is_empty() {
+                None
+            } else {
+                Some(metadata)
+            },
+            embedding: None,
+        });
+    }
+
+    Ok(posts)
+}
+
+impl PostStore {
+    /// Loads a store from a MySQL, PostgreSQL or SQLite database, mapping the columns of `table`
+    /// or the rows returned by `query` to posts.
+    pub async fn from_db(options: &DbOptions) -> Result<Self> {
+        let DbOptions {
+            url,
+            table,
+            query,
+            columns,
+        } = options;
+        let columns = columns.clone().unwrap_or_default();
+        let sql = match (query, table) {
+            (Some(query), _) => query.clone(),
+            (None, Some(table)) => format!("select * from {}", quote_table(url, table)?),
+            (None, None) => {
+                return Err(Error::InvalidArgument(
+                    "Either table or query must be provided".to_string(),
+                ));
+            }
+        };
+
+        let result = async {
+            if url.starts_with("mysql:") {
+                let mut db = MySqlConnection::connect(url).await?;
+                fetch_posts(&mut db, &sql, &columns).await
+            } else if url.starts_with("postgres:") {
+                let mut db = PgConnection::connect(url).await?;
+                fetch_posts(&mut db, &sql, &columns).await
+            } else if url.starts_with("sqlite:") {
+                let mut db = SqliteConnection::connect(url).await?;
+                fetch_posts(&mut db, &sql, &columns).await
+            } else {
+                let i = url.find(':').unwrap_or(0);
+                let scheme = &url[..i];
+                Err(sqlx::Error::InvalidArgument(format!(
+                    "Unsupported database scheme '{}'",
+                    scheme
+                )))
+            }
+        }
+        .await;
+        let posts = result.map_err(|e| Error::Database("Cannot fetch posts from database", e))?;
+
+        Ok(PostStore::from_snapshot(PostsSnapshot {
+            generation: 0,
+            base: None,
+            posts: Arc::new(posts),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Creates a database in `dir`, returns its URL.
+    async fn create_db(dir: &TempDir) -> String {
+        let path = dir.path().join("posts.db");
+        let url = format!("sqlite:{}?mode=rwc", path.to_string_lossy());
+        let mut db = SqliteConnection::connect(&url).await.unwrap();
+
+        sqlx::query(
+            "create table posts (
+                post_id integer primary key,
+                title text not null,
+                body text,
+                category text,
+                score real,
+                draft boolean
+            )",
+        )
+        .execute(&mut db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into posts values
+                (1, 'Deno.kill on windows', 'Windows only supports SIGINT', 'runtime', 4.5, 0),
+                (2, 'denojs on termux', 'A smooth download for Deno.js', null, null, 1)",
+        )
+        .execute(&mut db)
+        .await
+        .unwrap();
+        db.close().await.unwrap();
+
+        url
+    }
+
+    fn snapshot_posts(store: &PostStore) -> Vec<PostData> {
+        store
+            .snapshot()
+            .iter()
+            .map(|post| post.to_post_data())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_post_store_load_from_db_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = create_db(&dir).await;
+        let store = PostStore::from_db(&DbOptions {
+            url,
+            table: Some("posts".to_string()),
+            query: None,
+            columns: Some(DbColumns {
+                id: Some("post_id".to_string()),
+                content: Some("body".to_string()),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap();
+        let posts = snapshot_posts(&store);
+
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].id.as_deref(), Some("1"));
+        assert_eq!(posts[0].title, "Deno.kill on windows");
+        assert_eq!(posts[0].content, "Windows only supports SIGINT");
+        assert_eq!(
+            posts[0].metadata,
+            json!({ "category": "runtime", "score": 4.5, "draft": 0 })
+                .as_object()
+                .cloned()
+        );
+        assert_eq!(posts[1].id.as_deref(), Some("2"));
+        assert_eq!(
+            posts[1].metadata,
+            json!({ "draft": 1 }).as_object().cloned()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_store_load_from_db_sqlite_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = create_db(&dir).await;
+        let store = PostStore::from_db(&DbOptions {
+            url: url.clone(),
+            table: None,
+            query: Some(
+                "select title, body as content, category from posts where draft = 0".to_string(),
+            ),
+            columns: Some(DbColumns {
+                metadata: Some(vec!["category".to_string()]),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap();
+        let posts = snapshot_posts(&store);
+
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].id, None);
+        assert_eq!(
+            posts[0].metadata,
+            json!({ "category": "runtime" }).as_object().cloned()
+        );
+
+        let result = PostStore::from_db(&DbOptions {
+            url,
+            table: Some("posts".to_string()),
+            query: None,
+            columns: None,
+        })
+        .await;
+        assert!(result.is_err(), "the content column is missing");
+    }
+
+    #[tokio::test]
+    async fn test_post_store_load_from_db_table_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = create_db(&dir).await;
+        let options = |table: &str| DbOptions {
+            url: url.clone(),
+            table: Some(table.to_string()),
+            query: None,
+            columns: Some(DbColumns {
+                content: Some("body".to_string()),
+                ..Default::default()
+            }),
+        };
+
+        let store = PostStore::from_db(&options("main.posts")).await.unwrap();
+        assert_eq!(store.snapshot().len(), 2);
+
+        for table in [
+            "posts; drop table posts",
+            "posts where draft = 0",
+            "main..posts",
+            "1posts",
+            "",
+        ] {
+            let result = PostStore::from_db(&options(table)).await;
+            assert!(
+                matches!(result, Err(Error::InvalidArgument(_))),
+                "{} is rejected",
+                table
+            );
+        }
+        let store = PostStore::from_db(&options("posts")).await.unwrap();
+        assert_eq!(store.snapshot().len(), 2, "the table is not dropped");
+
+        assert_eq!(
+            quote_table("mysql://localhost/db", "db.posts").unwrap(),
+            "`db`.`posts`"
+        );
+        assert_eq!(
+            quote_table("postgres://localhost/db", "public.Posts").unwrap(),
+            "\"public\".\"Posts\""
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    load::{
+        open_lines, parse_object, read_lines, take_embedding, take_metadata, take_string,
+        LoadReport,
+    },
+    post::{PostData, PostStore, PostsSnapshot},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonlOptions {
+    /// The field holding the ID of the post, defaults to `id`.
+    pub id_field: Option<String>,
+    /// The field holding the title of the post, defaults to `title`.
+    pub title_field: Option<String>,
+    /// The field holding the content of the post, defaults to `content`.
+    pub content_field: Option<String>,
+    /// The field holding the metadata object of the post, defaults to `metadata`. Other unknown
+    /// fields are merged into the metadata as well, except `embedding`, which holds the
+    /// embedding of the post as an array of numbers.
+    pub metadata_field: Option<String>,
+    /// Whether the input is gzip-compressed, detected from the content when omitted. Only used
+    /// when loading.
+    pub gzip: Option<bool>,
+}
+
+const EMBEDDING_FIELD: &str = "embedding";
+
+struct Fields<'a> {
+    id: &'a str,
+    title: &'a str,
+    content: &'a str,
+    metadata: &'a str,
+}
+
+impl<'a> From<&'a JsonlOptions> for Fields<'a> {
+    fn from(options: &'a JsonlOptions) -> Self {
+        Fields {
+            id: options.id_field.as_deref().unwrap_or("id"),
+            title: options.title_field.as_deref().unwrap_or("title"),
+            content: options.content_field.as_deref().unwrap_or("content"),
+            metadata: options.metadata_field.as_deref().unwrap_or("metadata"),
+        }
+    }
+}
+
+fn parse_post(line: &str, fields: &Fields) -> std::result::Result<PostData, String> {
+    let mut obj = parse_object(line)?;
+    let id = take_string(&mut obj, fields.id)?;
+    let title = take_string(&mut obj, fields.title)?
+        .ok_or_else(|| format!("field '{}' is missing", fields.title))?;
+    let content = take_string(&mut obj, fields.content)?
+        .ok_or_else(|| format!("field '{}' is missing", fields.content))?;
+    let embedding = take_embedding(&mut obj, EMBEDDING_FIELD)?;
+
+    Ok(PostData {
+        id,
+        title,
+        content,
+        metadata: take_metadata(obj, fields.metadata)?,
+        embedding,
+    })
+}
+
+impl PostStore {
+    /// Replaces the posts in the store with the ones in a JSON Lines file, which is streamed line
+    /// by line. Malformed lines are skipped and reported with their line numbers.
+    pub fn load_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<LoadReport> {
+        let fields = Fields::from(options);
+        let mut reader = open_lines(path.as_ref(), options.gzip)?;
+        let (posts, errors) = read_lines(&mut reader, |line| parse_post(line, &fields))?;
+        let loaded = posts.len();
+        let posts = Arc::new(posts);
+
+        self.posts.rcu(|current| PostsSnapshot {
+            generation: current.generation + 1,
+            base: None,
+            posts: posts.clone(),
+            ..Default::default()
+        });
+
+        Ok(LoadReport { loaded, errors })
+    }
+
+    /// Writes the posts of the current snapshot to a JSON Lines file, see
+    /// [PostsSnapshot::dump_jsonl].
+    pub fn dump_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<()> {
+        self.snapshot().dump_jsonl(path, options)
+    }
+}
+
+impl PostsSnapshot {
+    /// Writes the posts to a JSON Lines file, using the same field options as
+    /// [PostStore::load_jsonl].
+    pub fn dump_jsonl(&self, path: impl AsRef<Path>, options: &JsonlOptions) -> Result<()> {
+        let fields = Fields::from(options);
+        let file = File::create(path).map_err(|e| Error::Io("Cannot create JSONL file", e))?;
+        let mut wtr = BufWriter::new(file);
+        let write_error = |e: std::io::Error| Error::Io("Cannot write JSONL record", e);
+
+        for post in self.iter() {
+            let mut obj = Map::new();
+
+            if let Some(id) = post.id {
+                obj.insert(fields.id.to_string(), Value::String(id.to_string()));
+            }
+            obj.insert(
+                fields.title.to_string(),
+                Value::String(post.title.to_string()),
+            );
+            obj.insert(
+                fields.content.to_string(),
+                Value::String(post.content.to_string()),
+            );
+            if let Some(metadata) = post.metadata {
+                obj.insert(fields.metadata.to_string(), Value::Object(metadata.clone()));
+            }
+            if let Some(embedding) = post.embedding {
+                obj.insert(EMBEDDING_FIELD.to_string(), Value::from(embedding.to_vec()));
+            }
+
+            serde_json::to_writer(&mut wtr, &obj)
+                .map_err(|e| Error::Json("Cannot serialize JSONL record", e))?;
+            wtr.write_all(b"\n").map_err(write_error)?;
+        }
+
+        wtr.flush().map_err(write_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+
+    use super::*;
+
+    const INPUT: &str = r#"{"key":"1","name":"Deno.kill on windows","content":"Windows only supports SIGINT and SIGBREAK","metadata":{"tags":["windows"]},"author":"alice"}
+not json
+
+{"key":"2","name":"denojs on termux"}
+{"key":"3","name":"denojs on termux","content":"A smooth download for Deno.js","embedding":[0.5,-1]}
+"#;
+
+    fn options() -> JsonlOptions {
+        JsonlOptions {
+            id_field: Some("key".to_string()),
+            title_field: Some("name".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn check_loaded(store: &PostStore, report: &LoadReport) {
+        assert_eq!(report.loaded, 2);
+        assert_eq!(
+            report.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+
+        let snapshot = store.snapshot();
+        let posts: Vec<PostData> = snapshot.iter().map(|post| post.to_post_data()).collect();
+        assert_eq!(posts[0].id.as_deref(), Some("1"));
+        assert_eq!(posts[0].title, "Deno.kill on windows");
+        assert_eq!(
+            posts[0].metadata,
+            json!({ "tags": ["windows"], "author": "alice" })
+                .as_object()
+                .cloned()
+        );
+        assert_eq!(posts[1].id.as_deref(), Some("3"));
+        assert_eq!(posts[1].metadata, None);
+        assert_eq!(posts[1].embedding, Some(vec![0.5, -1.0]));
+    }
+
+    #[test]
+    fn test_post_store_load_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-load.jsonl");
+        fs::write(&path, INPUT).unwrap();
+
+        let store = PostStore::new();
+        let report = store.load_jsonl(&path, &options()).unwrap();
+
+        check_loaded(&store, &report);
+        assert_eq!(store.generation(), 1);
+    }
+
+    #[test]
+    fn test_post_store_load_jsonl_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-load.jsonl.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let store = PostStore::new();
+        let report = store.load_jsonl(&path, &options()).unwrap();
+
+        check_loaded(&store, &report);
+    }
+
+    #[test]
+    fn test_post_store_load_jsonl_with_facets() {
+        use crate::{facet::FacetOptions, filter::Filter};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-load-facets.jsonl");
+        fs::write(
+            &path,
+            "{\"title\":\"Deno.kill fails\",\"content\":\"OS: Windows 11\\nSIGINT is ignored\"}\n\
+             {\"title\":\"Deno.kill fails\",\"content\":\"OS: Ubuntu 22.04\\nSIGINT is ignored\"}\n",
+        )
+        .unwrap();
+
+        // the loaded posts are annotated like the ones written afterwards
+        let store = PostStore::new()
+            .with_facets(FacetOptions::default())
+            .unwrap();
+        let report = store.load_jsonl(&path, &JsonlOptions::default()).unwrap();
+        assert_eq!(report.loaded, 2);
+
+        let filter = Filter::parse(&json!({ "facets.os": "ubuntu 22.04" })).unwrap();
+        let source = PostData {
+            id: None,
+            title: "Deno.kill fails".to_string(),
+            content: "OS: Ubuntu 22.04\nSIGINT is ignored".to_string(),
+            metadata: None,
+            embedding: None,
+        };
+        let result = store.find_similar_posts(&source, 5, Some(&filter)).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].shared_facets, vec!["os"]);
+    }
+
+    #[test]
+    fn test_post_store_dump_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("find-similar-posts-dump-input.jsonl");
+        let output = dir.path().join("find-similar-posts-dump-output.jsonl");
+        fs::write(&input, INPUT).unwrap();
+
+        let store1 = PostStore::new();
+        store1.load_jsonl(&input, &options()).unwrap();
+        store1.dump_jsonl(&output, &options()).unwrap();
+
+        let store2 = PostStore::new();
+        let report = store2.load_jsonl(&output, &options()).unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert!(report.errors.is_empty());
+
+        let posts1: Vec<PostData> = store1
+            .posts
+            .load()
+            .iter()
+            .map(|p| p.to_post_data())
+            .collect();
+        let posts2: Vec<PostData> = store2
+            .posts
+            .load()
+            .iter()
+            .map(|p| p.to_post_data())
+            .collect();
+        for (post1, post2) in posts1.iter().zip(posts2.iter()) {
+            assert_eq!(post1.id, post2.id);
+            assert_eq!(post1.title, post2.title);
+            assert_eq!(post1.content, post2.content);
+            assert_eq!(post1.metadata, post2.metadata);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::{fs::File, ops::Range, path::Path, sync::Arc};
+
+use memmap2::Mmap;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::{Map, Value};
+
+use crate::{
+    post::{
+        store::ext::binary::{parse_embedding, parse_metadata, PostEntry, SnapshotLayout},
+        PostRef, PostStore, PostsSnapshot,
+    },
+    Error, Result,
+};
+
+/// The posts of a snapshot file mapped into memory.
+///
+/// The title and content are read straight from the mapping, so processes opening the same file
+/// share the page cache instead of each keeping a heap copy. Only the metadata, which is usually
+/// tiny compared to the text, is decoded upfront, so that filters don't parse JSON on every query.
+/// So are the embeddings, which may be unaligned in the file.
+pub struct MappedPosts {
+    mmap: Mmap,
+    layout: SnapshotLayout,
+    /// The ranges of the ID, title and content of each post within the blob section, which hold
+    /// valid UTF-8 as checked when opening the file.
+    texts: Vec<[Range<usize>; 3]>,
+    metadata: Vec<Option<Map<String, Value>>>,
+    embeddings: Vec<Option<Vec<f32>>>,
+}
+
+impl std::fmt::Debug for MappedPosts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedPosts")
+            .field("layout", &self.layout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MappedPosts {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::Io("Cannot open snapshot file", e))?;
+        // SAFETY: the mapping is read-only, snapshot files are written to a temporary file and
+        // renamed into place, so an existing snapshot file is never modified while mapped.
+        let mmap =
+            unsafe { Mmap::map(&file) }.map_err(|e| Error::Io("Cannot map snapshot file", e))?;
+        let layout = SnapshotLayout::parse(&mmap)?;
+        // reading the entries validates the text as well, so scans never run into a broken one
+        let entries = (0..layout.post_count)
+            .into_par_iter()
+            .map(|i| {
+                let PostEntry {
+                    metadata,
+                    embedding,
+                    ..
+                } = layout.entry(&mmap, i)?;
+                let [title, content, _, id, _] = layout.ranges(&mmap, i)?;
+
+                Ok((
+                    [id, title, content],
+                    metadata.map(parse_metadata).transpose()?,
+                    embedding.map(parse_embedding).transpose()?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut texts = Vec::with_capacity(entries.len());
+        let mut metadata = Vec::with_capacity(entries.len());
+        let mut embeddings = Vec::with_capacity(entries.len());
+        for (text, meta, embedding) in entries {
+            texts.push(text);
+            metadata.push(meta);
+            embeddings.push(embedding);
+        }
+
+        Ok(MappedPosts {
+            mmap,
+            layout,
+            texts,
+            metadata,
+            embeddings,
+        })
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.layout.generation
+    }
+
+    pub fn len(&self) -> usize {
+        self.layout.post_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layout.post_count == 0
+    }
+
+    /// Returns the post at `i`, the entries have been validated when opening the file, so this
+    /// only returns `None` when `i` is out of bounds.
+    pub fn get(&self, i: usize) -> Option<PostRef<'_>> {
+        let [id, title, content] = self.texts.get(i)?;
+        let blob = self.layout.blob(&self.mmap);
+        // SAFETY: `MappedPosts::open` checked that these ranges are within the blob and hold valid
+        // UTF-8 with `SnapshotLayout::entry`, and the mapping is never modified, see there.
+        let text =
+            |range: &Range<usize>| unsafe { std::str::from_utf8_unchecked(&blob[range.clone()]) };
+
+        Some(PostRef {
+            id: if id.is_empty() { None } else { Some(text(id)) },
+            title: text(title),
+            content: text(content),
+            metadata: self.metadata[i].as_ref(),
+            embedding: self.embeddings[i].as_deref(),
+        })
+    }
+}
+
+impl PostStore {
+    /// Opens a binary snapshot file created by [PostStore::save] in read-only mode by memory
+    /// mapping it, and restores the indexes saved along with the posts. Posts added afterwards
+    /// with [PostStore::append] are kept in memory on top of the mapped ones, while
+    /// [PostStore::preload] replaces both.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let base = Arc::new(MappedPosts::open(path)?);
+        let snapshot = PostsSnapshot {
+            generation: base.generation(),
+            base: Some(base.clone()),
+            posts: Arc::new(Vec::new()),
+            ..Default::default()
+        };
+        base.layout.restore_indexes(&base.mmap, &snapshot)?;
+
+        Ok(PostStore::from_snapshot(snapshot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use crate::{filter::Filter, post::PostData};
+
+    use super::*;
+
+    fn post(title: &str, content: &str, metadata: Value) -> PostData {
+        PostData {
+            id: None,
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: match metadata {
+                Value::Object(map) => Some(map),
+                _ => None,
+            },
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_post_store_open_with_delta() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-mapped.snapshot");
+        let snapshot = PostsSnapshot {
+            generation: 3,
+            base: None,
+            posts: Arc::new(vec![
+                post(
+                    "Deno.kill on windows",
+                    "Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK)",
+                    json!({ "tags": ["windows"] }),
+                ),
+                post(
+                    "denojs on termux",
+                    "A smooth download for Deno.js",
+                    Value::Null,
+                ),
+            ]),
+            ..Default::default()
+        };
+        snapshot.save(&path).unwrap();
+
+        let store = PostStore::open(&path).unwrap();
+        assert_eq!(store.generation(), 3);
+
+        store.append(vec![post(
+            "Deno.kill is broken on windows",
+            "Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK)!",
+            json!({ "tags": ["windows"] }),
+        )]);
+        assert_eq!(store.generation(), 4);
+
+        let current = store.snapshot();
+        assert_eq!(current.len(), 3);
+        assert_eq!(
+            current.iter().map(|p| p.title).collect::<Vec<_>>(),
+            vec![
+                "Deno.kill on windows",
+                "denojs on termux",
+                "Deno.kill is broken on windows"
+            ]
+        );
+        assert!(current.iter().nth(1).unwrap().metadata.is_none());
+
+        let filter = Filter::parse(&json!({ "tags": "windows" })).unwrap();
+        let result = store
+            .find_similar_posts(
+                &post(
+                    "Deno.kill not working on windows",
+                    "Windows only supports ctrl-c (SIGINT) and ctrl-break (SIGBREAK)",
+                    Value::Null,
+                ),
+                5,
+                Some(&filter),
+            )
+            .unwrap();
+        assert_eq!(result.matches.len(), 2);
+        assert!(result
+            .matches
+            .iter()
+            .all(|m| m.target.title.starts_with("Deno.kill")));
+        assert_eq!(result.generation, Some(4));
+
+        // saving a layered store writes the mapped and the in-memory posts
+        let copy = dir.path().join("find-similar-posts-mapped-copy.snapshot");
+        current.save(&copy).unwrap();
+
+        let bytes = fs::read(&copy).unwrap();
+        let layout = SnapshotLayout::parse(&bytes).unwrap();
+        assert_eq!(layout.generation, 4);
+        assert_eq!(layout.read_posts(&bytes).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_post_store_open_with_facets() {
+        use crate::facet::FacetOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-mapped-facets.snapshot");
+        let snapshot = PostsSnapshot {
+            generation: 0,
+            base: None,
+            posts: Arc::new(vec![
+                post(
+                    "Deno.kill fails",
+                    "OS: Windows 11\nSIGINT is ignored",
+                    Value::Null,
+                ),
+                post(
+                    "Deno.kill fails",
+                    "OS: Ubuntu 22.04\nSIGINT is ignored",
+                    Value::Null,
+                ),
+            ]),
+            ..Default::default()
+        };
+        snapshot.save(&path).unwrap();
+
+        // the mapped posts are annotated per snapshot, without publishing a new generation
+        let store = PostStore::open(&path)
+            .unwrap()
+            .with_facets(FacetOptions::default())
+            .unwrap();
+        assert_eq!(store.generation(), 0);
+
+        let filter = Filter::parse(&json!({ "facets.os": "ubuntu 22.04" })).unwrap();
+        let source = post(
+            "Deno.kill fails",
+            "OS: Ubuntu 22.04\nSIGINT is ignored",
+            Value::Null,
+        );
+        let result = store.find_similar_posts(&source, 5, Some(&filter)).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].shared_facets, vec!["os"]);
+        assert!(store.snapshot().base.is_some());
+    }
+
+    #[test]
+    fn test_post_store_open_with_upsert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find-similar-posts-mapped-upsert.snapshot");
+        let with_id = |id: &str, title: &str| PostData {
+            id: Some(id.to_string()),
+            ..post(title, "SIGINT is not supported", Value::Null)
+        };
+        let snapshot = PostsSnapshot {
+            generation: 0,
+            base: None,
+            posts: Arc::new(vec![with_id("1", "Deno.kill"), with_id("2", "Deno.exit")]),
+            ..Default::default()
+        };
+        snapshot.save(&path).unwrap();
+
+        // posts with new IDs are layered on top of the mapped ones
+        let store = PostStore::open(&path).unwrap();
+        store.upsert(vec![with_id("3", "Deno.run")]);
+        assert!(store.snapshot().base.is_some());
+
+        // replacing a mapped post keeps the file mapped and skips the replaced post
+        store.upsert(vec![with_id("1", "Deno.kill on windows")]);
+        let current = store.snapshot();
+        assert!(current.base.is_some());
+        assert_eq!(current.len(), 3);
+        assert_eq!(
+            current.iter().map(|p| p.title).collect::<Vec<_>>(),
+            vec!["Deno.exit", "Deno.run", "Deno.kill on windows"]
+        );
+        assert!(current.get(0).is_none());
+        assert_eq!(current.get(1).unwrap().title, "Deno.exit");
+
+        let result = store
+            .find_similar_posts(&with_id("4", "Deno.kill"), 5, None)
+            .unwrap();
+        assert_eq!(result.matches.len(), 3);
+        assert!(result.matches.iter().all(|m| m.target.title != "Deno.kill"));
+
+        assert_eq!(store.remove(&["2"]), 1);
+        assert_eq!(store.remove(&["2"]), 0);
+        let current = store.snapshot();
+        assert!(current.base.is_some());
+        assert_eq!(current.len(), 2);
+        assert_eq!(
+            current.par_iter().map(|p| p.title).collect::<Vec<_>>(),
+            vec!["Deno.run", "Deno.kill on windows"]
+        );
+    }
+
+    #[test]
+    fn test_post_store_open_with_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("posts.snapshot");
+        let copy = dir.path().join("posts-copy.snapshot");
+        let with_embedding = |id: &str, title: &str, embedding: [f32; 2]| PostData {
+            id: Some(id.to_string()),
+            embedding: Some(embedding.to_vec()),
+            ..post(title, "SIGINT is not supported", Value::Null)
+        };
+        let store = PostStore::new();
+        store.preload(vec![
+            with_embedding("1", "Deno.kill", [1.0, 0.0]),
+            with_embedding("2", "Deno.exit", [0.0, 1.0]),
+        ]);
+        store.find_nearest_posts(&[1.0, 0.1], 1, None).unwrap();
+        store.save(&path).unwrap();
+
+        let store = PostStore::open(&path).unwrap();
+        assert_eq!(store.snapshot().vectors.get().unwrap().len(), 2);
+        let result = store.find_nearest_posts(&[1.0, 0.1], 1, None).unwrap();
+        assert_eq!(result.matches[0].target.title, "Deno.kill");
+
+        // once a mapped post is replaced, the positions the indexes refer to no longer match
+        // the saved posts
+        store.upsert(vec![with_embedding(
+            "1",
+            "Deno.kill on windows",
+            [1.0, 0.0],
+        )]);
+        store.find_nearest_posts(&[1.0, 0.1], 1, None).unwrap();
+        store.save(&copy).unwrap();
+
+        let store = PostStore::open(&copy).unwrap();
+        assert!(store.snapshot().vectors.get().is_none());
+        let result = store.find_nearest_posts(&[1.0, 0.1], 1, None).unwrap();
+        assert_eq!(result.matches[0].target.title, "Deno.kill on windows");
+    }
+}
@@ -0,0 +1,5 @@
+pub mod binary;
+pub mod csv;
+pub mod db;
+pub mod jsonl;
+pub mod mapped;
@@ -0,0 +1,169 @@
+//! Process-global registries of named stores, so that the threads of a process, such as the
+//! Node.js worker threads, attach to the same posts and records instead of loading their own
+//! copies.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Deref,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+struct Entry<T> {
+    store: T,
+    refs: usize,
+}
+
+/// A registry of named stores, which are created on the first attachment and dropped when the
+/// last [Shared] handle of them is released.
+pub struct Registry<T> {
+    entries: Mutex<BTreeMap<String, Entry<T>>>,
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Registry<T> {
+    pub const fn new() -> Self {
+        Registry {
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, BTreeMap<String, Entry<T>>> {
+        // the entries are consistent after every statement, so a panic can't corrupt them
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// How many handles of the store are alive, 0 if there is no such store.
+    pub fn refs(&self, name: &str) -> usize {
+        self.entries().get(name).map_or(0, |entry| entry.refs)
+    }
+
+    /// The names of the stores in the registry.
+    pub fn names(&self) -> Vec<String> {
+        self.entries().keys().cloned().collect()
+    }
+
+    fn release(&self, name: &str) {
+        let mut entries = self.entries();
+
+        if let Some(entry) = entries.get_mut(name) {
+            entry.refs -= 1;
+
+            if entry.refs == 0 {
+                entries.remove(name);
+            }
+        }
+    }
+}
+
+impl<T: Clone + Default> Registry<T> {
+    /// Returns a handle of the store named `name`, creating an empty one if there is none.
+    pub fn attach(&'static self, name: &str) -> Shared<T> {
+        let mut entries = self.entries();
+        let entry = entries.entry(name.to_string()).or_insert_with(|| Entry {
+            store: T::default(),
+            refs: 0,
+        });
+        entry.refs += 1;
+
+        Shared {
+            name: name.to_string(),
+            store: entry.store.clone(),
+            registry: self,
+        }
+    }
+}
+
+/// A handle of a store in a [Registry], dereferencing to the store. Dropping it releases the
+/// store, which is removed from the registry along with the last handle.
+pub struct Shared<T: 'static> {
+    name: String,
+    store: T,
+    registry: &'static Registry<T>,
+}
+
+impl<T> Shared<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// How many handles of the store are alive, including this one.
+    pub fn refs(&self) -> usize {
+        self.registry.refs(&self.name)
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.store
+    }
+}
+
+impl<T: Clone + Default> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        self.registry.attach(&self.name)
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.registry.release(&self.name);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("name", &self.name)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::post::{PostData, PostStore};
+
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        static STORES: Registry<PostStore> = Registry::new();
+
+        let a = STORES.attach("blog");
+        let b = std::thread::spawn(|| STORES.attach("blog")).join().unwrap();
+        assert_eq!(a.refs(), 2);
+        assert_eq!(STORES.names(), ["blog"]);
+
+        a.preload(vec![PostData {
+            id: None,
+            title: "Hello".to_string(),
+            content: "World".to_string(),
+            metadata: None,
+            embedding: None,
+        }]);
+        assert_eq!(b.snapshot().len(), 1);
+
+        let c = b.clone();
+        assert_eq!(c.refs(), 3);
+
+        drop(a);
+        drop(b);
+        assert_eq!(c.refs(), 1);
+        assert_eq!(c.snapshot().len(), 1);
+
+        drop(c);
+        assert_eq!(STORES.refs("blog"), 0);
+        assert!(STORES.names().is_empty());
+
+        // a released store starts over
+        assert!(STORES.attach("blog").snapshot().is_empty());
+    }
+}
@@ -0,0 +1,363 @@
+//! Extracting the code identifiers and the API symbols a text mentions, and comparing them.
+//!
+//! Two issues about `Deno.kill` are likely about the same thing, even when one of them is a
+//! short question and the other a long crash report, which the edit distance of their texts
+//! can't tell. The [Symbols] of a text are the dotted names (`Deno.kill`, `std::fs::read`), the
+//! called functions (`addSignalListener()`), the error codes (`ERR_INVALID_ARG_TYPE`, `E0308`)
+//! and the signal names (`SIGTERM`) it mentions, plus the identifiers of its code spans. A store
+//! configured with [SymbolOptions] writes the symbols of the records or the posts its queries see
+//! into their metadata under [SYMBOLS_KEY], which can be filtered on, e.g.
+//! `{ "symbols": "Deno.kill" }`, and compares them with the ones of the source as an extra signal
+//! of the score. The rarer a symbol is in the store, the more it weighs, see [SymbolIndex].
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeSet, HashMap},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{Error, Result};
+
+/// The metadata key the symbols are written to.
+pub const SYMBOLS_KEY: &str = "symbols";
+
+/// The weight of the overlap of the symbols in the score by default.
+pub const DEFAULT_WEIGHT: f64 = 0.25;
+
+/// The symbols mentioned by a text, in order.
+pub type Symbols = BTreeSet<String>;
+
+/// The fenced code blocks and the inline code spans of a markdown text.
+static CODE_SPAN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```[^\n]*\n(.*?)```|`([^`\n]+)`").unwrap());
+
+/// A name qualified by its namespaces or its object, e.g. `Deno.kill` or `std::fs::read`.
+static PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z_$][\w$]*(?:(?:\.|::)[A-Za-z_$][\w$]*)+").unwrap());
+
+/// A function called with parentheses, its name is captured without them.
+static CALL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([A-Za-z_$][\w$]*(?:(?:\.|::)[A-Za-z_$][\w$]*)*)\(").unwrap());
+
+/// The error codes of Node.js (`ERR_*`) and of the Rust compiler (`E0308`).
+static ERROR_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:ERR_[A-Z0-9_]+|E\d{4})\b").unwrap());
+
+/// The names of the POSIX signals, e.g. `SIGTERM`.
+static SIGNAL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bSIG[A-Z]{2,}\b").unwrap());
+
+/// An identifier in a code span.
+static IDENTIFIER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z_$][\w$]*").unwrap());
+
+/// The keywords of the usual languages, which say nothing about the code they're in.
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "def",
+    "else",
+    "enum",
+    "export",
+    "false",
+    "fn",
+    "for",
+    "from",
+    "func",
+    "function",
+    "if",
+    "impl",
+    "import",
+    "in",
+    "let",
+    "match",
+    "mut",
+    "new",
+    "nil",
+    "None",
+    "null",
+    "pub",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "type",
+    "undefined",
+    "use",
+    "var",
+    "void",
+    "while",
+];
+
+/// Whether a path found in prose is an abbreviation such as `e.g`, made of single letters.
+fn is_abbreviation(path: &str) -> bool {
+    path.split('.').all(|segment| segment.len() == 1)
+}
+
+/// Adds `path` and the namespaces or objects qualifying it, e.g. `Deno` for `Deno.kill`.
+fn insert_path(symbols: &mut Symbols, path: &str) {
+    let mut end = path.len();
+
+    while end > 0 {
+        let name = &path[..end];
+        symbols.insert(name.to_string());
+        end = name.rfind(['.', ':']).map_or(0, |i| {
+            // `::` is skipped as a whole
+            if name[..i].ends_with(':') {
+                i - 1
+            } else {
+                i
+            }
+        });
+    }
+}
+
+/// Extracts the symbols mentioned by `text`. The prose only gives the dotted names, the called
+/// functions, the error codes and the signal names, since its plain words aren't identifiers,
+/// the code spans give all their identifiers but the keywords.
+pub fn extract(text: &str) -> Symbols {
+    let mut symbols = Symbols::new();
+
+    for path in PATH.find_iter(text).map(|m| m.as_str()) {
+        if !is_abbreviation(path) {
+            insert_path(&mut symbols, path);
+        }
+    }
+    for call in CALL.captures_iter(text) {
+        insert_path(&mut symbols, &call[1]);
+    }
+    for pattern in [&*ERROR_CODE, &*SIGNAL] {
+        symbols.extend(pattern.find_iter(text).map(|m| m.as_str().to_string()));
+    }
+    for span in CODE_SPAN.captures_iter(text) {
+        let code = span
+            .get(1)
+            .or_else(|| span.get(2))
+            .map_or("", |m| m.as_str());
+        symbols.extend(
+            IDENTIFIER
+                .find_iter(code)
+                .map(|m| m.as_str())
+                .filter(|name| name.len() > 1 && !KEYWORDS.contains(name))
+                .map(str::to_string),
+        );
+    }
+
+    symbols
+}
+
+/// Writes `symbols` into `metadata` under [SYMBOLS_KEY]. The metadata is left as is if there are
+/// none.
+pub fn write_symbols(metadata: &mut Option<Map<String, Value>>, symbols: &Symbols) {
+    if !symbols.is_empty() {
+        metadata.get_or_insert_with(Map::new).insert(
+            SYMBOLS_KEY.to_string(),
+            symbols.iter().cloned().map(Value::String).collect(),
+        );
+    }
+}
+
+/// Reads the symbols written by [write_symbols] from `metadata`.
+pub fn symbols_of(metadata: Option<&Map<String, Value>>) -> Symbols {
+    metadata
+        .and_then(|metadata| metadata.get(SYMBOLS_KEY))
+        .and_then(Value::as_array)
+        .map(|symbols| {
+            symbols
+                .iter()
+                .filter_map(|symbol| Some(symbol.as_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// How many documents of a store mention each symbol, which gives the inverse document
+/// frequency weighting the symbols. An empty index weighs every symbol the same.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    documents: usize,
+    frequencies: HashMap<String, usize>,
+}
+
+impl SymbolIndex {
+    /// Counts the symbols of the documents, given by [symbols_of] or [extract].
+    pub fn build(documents: impl IntoIterator<Item = impl Borrow<Symbols>>) -> Self {
+        let mut index = SymbolIndex::default();
+
+        for symbols in documents {
+            index.add(symbols.borrow());
+        }
+        index
+    }
+
+    /// Counts the symbols of one more document.
+    pub fn add(&mut self, symbols: &Symbols) {
+        self.documents += 1;
+        for symbol in symbols {
+            *self.frequencies.entry(symbol.clone()).or_default() += 1;
+        }
+    }
+
+    /// The number of documents counted.
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// The smoothed inverse document frequency of `symbol`, which is at least 1, so a symbol
+    /// mentioned by every document still counts.
+    pub fn idf(&self, symbol: &str) -> f64 {
+        let frequency = self.frequencies.get(symbol).copied().unwrap_or(0);
+        ((self.documents + 1) as f64 / (frequency + 1) as f64).ln() + 1.0
+    }
+
+    /// The weighted Jaccard similarity of two sets of symbols from 0 to 1, the sum of the
+    /// weights of the shared symbols over the one of all the symbols, along with the shared
+    /// symbols.
+    pub fn overlap(&self, source: &Symbols, target: &Symbols) -> (f64, Vec<String>) {
+        let shared: Vec<String> = source.intersection(target).cloned().collect();
+        if shared.is_empty() {
+            return (0.0, shared);
+        }
+
+        let shared_weight: f64 = shared.iter().map(|symbol| self.idf(symbol)).sum();
+        let total_weight: f64 = source.union(target).map(|symbol| self.idf(symbol)).sum();
+
+        (shared_weight / total_weight, shared)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolOptions {
+    /// The share of the overlap of the symbols in the score, the similarity of the texts makes
+    /// up the rest. Defaults to [DEFAULT_WEIGHT].
+    pub weight: f64,
+}
+
+impl Default for SymbolOptions {
+    fn default() -> Self {
+        SymbolOptions {
+            weight: DEFAULT_WEIGHT,
+        }
+    }
+}
+
+impl SymbolOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.weight) {
+            return Err(Error::InvalidArgument(
+                "weight must be a number from 0 to 1".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Extracts the symbols of `text`, and writes them into `metadata`. The metadata is left as
+    /// is if the text mentions none.
+    pub fn annotate(&self, metadata: &mut Option<Map<String, Value>>, text: &str) {
+        write_symbols(metadata, &extract(text));
+    }
+
+    /// Returns `score` combined with the overlap of the symbols of `target` with the ones of
+    /// `source`, weighted by `index`, along with the shared symbols. The score of a candidate
+    /// is left as is when either of them mentions no symbol.
+    pub fn score(
+        &self,
+        score: f64,
+        source: &Symbols,
+        target: &Symbols,
+        index: &SymbolIndex,
+    ) -> (f64, Vec<String>) {
+        if source.is_empty() || target.is_empty() {
+            return (score, Vec::new());
+        }
+
+        let (overlap, shared) = index.overlap(source, target);
+        (score * (1.0 - self.weight) + overlap * self.weight, shared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(symbols: &Symbols) -> Vec<&str> {
+        symbols.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_extract() {
+        let symbols = extract(
+            "Calling Deno.kill(pid, \"SIGTERM\") throws ERR_INVALID_ARG_TYPE, e.g. when the \
+             pid is negative. See `Deno.addSignalListener` and error E0308 too.\n\
+             ```ts\nconst child = new Deno.Command(\"cat\").spawn();\nawait child.status;\n```",
+        );
+        assert_eq!(
+            names(&symbols),
+            vec![
+                "Command",
+                "Deno",
+                "Deno.Command",
+                "Deno.addSignalListener",
+                "Deno.kill",
+                "E0308",
+                "ERR_INVALID_ARG_TYPE",
+                "SIGTERM",
+                "addSignalListener",
+                "cat",
+                "child",
+                "child.status",
+                "spawn",
+                "status",
+            ]
+        );
+
+        let symbols = extract("The call to std::fs::read_to_string() fails on Windows.");
+        assert_eq!(
+            names(&symbols),
+            vec!["std", "std::fs", "std::fs::read_to_string"]
+        );
+
+        assert!(extract("It crashes when I open the app, i.e. at startup.").is_empty());
+    }
+
+    #[test]
+    fn test_symbol_index_overlap() {
+        let kill = extract("Deno.kill(pid, \"SIGINT\") doesn't stop the child.");
+        let listener = extract("Deno.addSignalListener(\"SIGINT\", handler) is never called.");
+        let unrelated = extract("The docs of fetch() are out of date.");
+
+        let index = SymbolIndex::build([kill.clone(), listener.clone(), unrelated.clone()]);
+        assert_eq!(index.documents(), 3);
+        assert!(index.idf("Deno.kill") > index.idf("Deno"));
+        assert_eq!(SymbolIndex::default().idf("Deno"), 1.0);
+
+        let (overlap, shared) = index.overlap(&kill, &kill);
+        assert_eq!(overlap, 1.0);
+        assert_eq!(shared.len(), kill.len());
+
+        let (overlap, shared) = index.overlap(&kill, &listener);
+        assert!(overlap > 0.0 && overlap < 1.0);
+        assert_eq!(shared, vec!["Deno", "SIGINT"]);
+
+        assert_eq!(index.overlap(&kill, &unrelated), (0.0, Vec::new()));
+
+        let options = SymbolOptions { weight: 0.5 };
+        assert_eq!(options.score(0.5, &kill, &kill, &index).0, 0.75);
+        assert_eq!(options.score(0.5, &kill, &Symbols::new(), &index).0, 0.5);
+        assert_eq!(options.score(0.5, &kill, &unrelated, &index).0, 0.25);
+        assert!(SymbolOptions { weight: 1.5 }.validate().is_err());
+    }
+}
@@ -0,0 +1,662 @@
+//! Parsing the stack traces of crash reports, and fingerprinting them.
+//!
+//! Two reports of the same crash usually share their stack trace, even when they describe it in
+//! other words. A [StackTrace] is parsed from a JavaScript (V8), Rust, Python, Java or Go trace
+//! found in a text, with its error type, its message and its top frames. The addresses, the line
+//! numbers and the paths are left out, so the same crash gives the same fingerprint on every
+//! machine. A store configured with [TraceOptions] writes the trace of the records or the posts
+//! its queries see into their metadata under [TRACE_KEY], which can be filtered on, e.g. `{ "trace.fingerprint": "..." }`, and compares the traces as an extra signal of the
+//! score.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{metric::Metric, Error, Result};
+
+/// The metadata key the trace is written to.
+pub const TRACE_KEY: &str = "trace";
+
+/// How many frames from the top of a trace are kept by default.
+pub const DEFAULT_DEPTH: usize = 5;
+
+/// The weight of the similarity of the traces in the score by default.
+pub const DEFAULT_WEIGHT: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    JavaScript,
+    Rust,
+    Python,
+    Java,
+    Go,
+}
+
+impl Language {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "javascript" => Ok(Language::JavaScript),
+            "rust" => Ok(Language::Rust),
+            "python" => Ok(Language::Python),
+            "java" => Ok(Language::Java),
+            "go" => Ok(Language::Go),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown language '{}', it must be one of javascript, rust, python, java and go",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::JavaScript => "javascript",
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::Java => "java",
+            Language::Go => "go",
+        }
+    }
+}
+
+/// A stack trace without the details varying from a machine to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackTrace {
+    pub language: Language,
+    /// The type of the error, such as `TypeError`, or `panic` for the panics of Rust and Go.
+    pub error: Option<String>,
+    /// The message of the error, with its numbers and addresses replaced and its paths reduced
+    /// to their file names.
+    pub message: Option<String>,
+    /// The functions of the frames from the innermost one, or the file names of the frames of
+    /// anonymous functions. The frames of the runtime and the standard library are skipped.
+    pub frames: Vec<String>,
+}
+
+static JS_ERROR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?m)^\s*(?:Uncaught\s+(?:\(in promise\)\s+)?)?(?P<error>[A-Z][\w$]*(?:Error|Exception))(?::[ \t]*(?P<message>[^\r\n]*))?\s*$",
+    )
+    .unwrap()
+});
+static JS_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?m)^\s*at (?:async )?(?:new )?(?:(?P<function>[^\s(][^(\r\n]*?) \((?P<location>[^)\r\n]*)\)|(?P<bare>\S+))\s*$",
+    )
+    .unwrap()
+});
+static RUST_PANIC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?m)^thread '[^']*' panicked at (?:'(?P<quoted>.*)', (?P<old>\S+?)|(?P<location>\S+?):?[ \t]*\r?\n(?P<message>[^\r\n]*))\s*$",
+    )
+    .unwrap()
+});
+static RUST_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*\d+:\s+(?:0x[0-9a-fA-F]+ - )?(?P<function>\S+)\s*$").unwrap()
+});
+static PYTHON_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*File "(?P<file>[^"]+)", line \d+, in (?P<function>\S+)\s*$"#).unwrap()
+});
+static PYTHON_ERROR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(?P<error>[A-Za-z_][\w.]*)(?::[ \t]*(?P<message>[^\r\n]*))?\s*$").unwrap()
+});
+static JAVA_ERROR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?m)^(?:Exception in thread "[^"]*"\s+)?(?P<error>(?:[a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error|Throwable))(?::[ \t]*(?P<message>[^\r\n]*))?\s*$"#,
+    )
+    .unwrap()
+});
+static JAVA_FRAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*at (?P<function>[\w$.<>/]+)\([^)\r\n]*\)\s*$").unwrap());
+static GO_PANIC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^panic: (?P<message>[^\r\n]+?)(?: \[recovered\])?\s*$").unwrap()
+});
+static GO_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(?P<function>[^\s(]+(?:\(\*?[^\s()]+\)\.[^\s(]+)?)\([^\r\n]*\)\r?\n\t\S+:\d+")
+        .unwrap()
+});
+static ADDRESS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"0x[0-9a-fA-F]+").unwrap());
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d+(?:\.\d+)*\b").unwrap());
+static PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?:[a-z]+://)?(?:[A-Za-z]:)?(?:[^\s'"`()\[\]/\\]*[/\\])+(?P<file>[^\s'"`()\[\]/\\:]+)"#,
+    )
+    .unwrap()
+});
+static RUST_HASH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"::h[0-9a-f]{16}$").unwrap());
+static LAMBDA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\d+").unwrap());
+
+/// Returns the file name of a location, without its directories and its line and column.
+fn file_name(location: &str) -> String {
+    let location = location.trim_end_matches(|c: char| c.is_ascii_digit() || c == ':');
+    let file = location.rsplit(['/', '\\']).next().unwrap_or(location);
+    file.to_string()
+}
+
+/// Replaces the addresses and the numbers of a message, and reduces its paths to their file
+/// names.
+fn normalize_message(message: &str) -> Option<String> {
+    let message = PATH.replace_all(message.trim(), "$file");
+    let message = ADDRESS.replace_all(&message, "<addr>");
+    let message = NUMBER.replace_all(&message, "<n>");
+    let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    (!message.is_empty()).then_some(message)
+}
+
+fn parse_javascript(text: &str) -> Option<StackTrace> {
+    let header = JS_ERROR.captures(text)?;
+    let frames: Vec<String> = JS_FRAME
+        .captures_iter(&text[header.get(0)?.end()..])
+        .filter_map(|frame| {
+            let location = frame.name("location").or_else(|| frame.name("bare"))?;
+            // the frames of the internals of Node.js vary with its version
+            if location.as_str().starts_with("node:internal") {
+                return None;
+            }
+            match frame.name("function") {
+                Some(function) if function.as_str() != "<anonymous>" => {
+                    Some(function.as_str().to_string())
+                }
+                _ => Some(file_name(location.as_str())),
+            }
+        })
+        .collect();
+
+    (!frames.is_empty()).then(|| StackTrace {
+        language: Language::JavaScript,
+        error: Some(header["error"].to_string()),
+        message: header
+            .name("message")
+            .and_then(|message| normalize_message(message.as_str())),
+        frames,
+    })
+}
+
+fn parse_rust(text: &str) -> Option<StackTrace> {
+    let panic = RUST_PANIC.captures(text)?;
+    let (message, location) = match (panic.name("quoted"), panic.name("old")) {
+        (Some(quoted), Some(old)) => (quoted, old),
+        _ => (panic.name("message")?, panic.name("location")?),
+    };
+    let mut frames: Vec<String> = RUST_FRAME
+        .captures_iter(&text[panic.get(0)?.end()..])
+        .map(|frame| RUST_HASH.replace(&frame["function"], "").into_owned())
+        .filter(|function| {
+            ![
+                "std::",
+                "core::",
+                "alloc::",
+                "rust_begin_unwind",
+                "__rust",
+                "_start",
+                "__libc",
+            ]
+            .iter()
+            .any(|prefix| function.starts_with(prefix))
+                && !["main", "<unknown>"].contains(&function.as_str())
+        })
+        .collect();
+    // without a backtrace, the file the panic occurred in is the only frame
+    if frames.is_empty() {
+        frames.push(file_name(location.as_str()));
+    }
+
+    Some(StackTrace {
+        language: Language::Rust,
+        error: Some("panic".to_string()),
+        message: normalize_message(message.as_str()),
+        frames,
+    })
+}
+
+fn parse_python(text: &str) -> Option<StackTrace> {
+    let start = text.find("Traceback (most recent call last):")?;
+    let text = &text[start..];
+    let frames: Vec<_> = PYTHON_FRAME.captures_iter(text).collect();
+    let last = frames.last()?.get(0)?.end();
+    // the error follows the frames and the source lines they quote, which are indented
+    let error = PYTHON_ERROR.captures(&text[last..])?;
+
+    Some(StackTrace {
+        language: Language::Python,
+        error: Some(error["error"].to_string()),
+        message: error
+            .name("message")
+            .and_then(|message| normalize_message(message.as_str())),
+        frames: frames
+            .iter()
+            .rev()
+            .map(|frame| match &frame["function"] {
+                "<module>" | "<lambda>" => file_name(&frame["file"]),
+                function => function.to_string(),
+            })
+            .collect(),
+    })
+}
+
+fn parse_java(text: &str) -> Option<StackTrace> {
+    let header = JAVA_ERROR.captures(text)?;
+    let rest = &text[header.get(0)?.end()..];
+    // the frames of the causes are left out
+    let rest = rest.find("Caused by:").map_or(rest, |end| &rest[..end]);
+    let frames: Vec<String> = JAVA_FRAME
+        .captures_iter(rest)
+        .map(|frame| LAMBDA.replace_all(&frame["function"], "").into_owned())
+        .collect();
+
+    (!frames.is_empty()).then(|| StackTrace {
+        language: Language::Java,
+        error: Some(header["error"].to_string()),
+        message: header
+            .name("message")
+            .and_then(|message| normalize_message(message.as_str())),
+        frames,
+    })
+}
+
+fn parse_go(text: &str) -> Option<StackTrace> {
+    let panic = GO_PANIC.captures(text)?;
+    let frames = GO_FRAME
+        .captures_iter(&text[panic.get(0)?.end()..])
+        .map(|frame| {
+            let function = &frame["function"];
+            function.rsplit('/').next().unwrap_or(function).to_string()
+        })
+        .filter(|function| function != "panic" && !function.starts_with("runtime."))
+        .collect();
+
+    Some(StackTrace {
+        language: Language::Go,
+        error: Some("panic".to_string()),
+        message: normalize_message(&panic["message"]),
+        frames,
+    })
+}
+
+impl StackTrace {
+    /// Finds the first stack trace in `text`, trying the most distinctive formats first.
+    pub fn parse(text: &str) -> Option<Self> {
+        parse_python(text)
+            .or_else(|| parse_go(text))
+            .or_else(|| parse_rust(text))
+            .or_else(|| parse_java(text))
+            .or_else(|| parse_javascript(text))
+    }
+
+    /// A stable hash of the language, the error, the message and the top `depth` frames, so the
+    /// traces of the same crash share it.
+    pub fn fingerprint(&self, depth: usize) -> String {
+        // FNV-1a, which unlike the hasher of the standard library is stable across releases
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let parts = [
+            Some(self.language.name()),
+            self.error.as_deref(),
+            self.message.as_deref(),
+        ]
+        .into_iter()
+        .chain(
+            self.frames
+                .iter()
+                .take(depth)
+                .map(|frame| Some(frame.as_str())),
+        );
+
+        for part in parts {
+            for byte in part.unwrap_or_default().bytes().chain([0]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+
+    /// The similarity of two traces from 0 to 1: 0 for traces of other languages, otherwise
+    /// 0.3 for the same error, 0.2 by the similarity of the messages and 0.5 by the share of the
+    /// top `depth` frames they have in common.
+    pub fn similarity(&self, other: &StackTrace, depth: usize) -> f64 {
+        if self.language != other.language {
+            return 0.0;
+        }
+
+        let error = if self.error == other.error { 1.0 } else { 0.0 };
+        let message = match (&self.message, &other.message) {
+            (Some(a), Some(b)) => Metric::Levenshtein.similarity(a, b),
+            (None, None) => 1.0,
+            _ => 0.0,
+        };
+        let frames = &self.frames[..self.frames.len().min(depth)];
+        let other_frames = &other.frames[..other.frames.len().min(depth)];
+        let frames = match frames.len().max(other_frames.len()) {
+            0 => 1.0,
+            len => {
+                let common = frames
+                    .iter()
+                    .filter(|frame| other_frames.contains(frame))
+                    .count();
+                common as f64 / len as f64
+            }
+        };
+
+        0.3 * error + 0.2 * message + 0.5 * frames
+    }
+
+    /// Writes the trace with its top `depth` frames and its fingerprint into `metadata` under
+    /// [TRACE_KEY].
+    pub fn annotate(&self, metadata: &mut Option<Map<String, Value>>, depth: usize) {
+        let mut trace = Map::new();
+        trace.insert("language".to_string(), self.language.name().into());
+        if let Some(error) = &self.error {
+            trace.insert("error".to_string(), error.as_str().into());
+        }
+        if let Some(message) = &self.message {
+            trace.insert("message".to_string(), message.as_str().into());
+        }
+        trace.insert(
+            "frames".to_string(),
+            self.frames.iter().take(depth).map(String::as_str).collect(),
+        );
+        trace.insert("fingerprint".to_string(), self.fingerprint(depth).into());
+
+        metadata
+            .get_or_insert_with(Map::new)
+            .insert(TRACE_KEY.to_string(), Value::Object(trace));
+    }
+
+    /// Reads the trace written by [StackTrace::annotate] back from `metadata`, along with its
+    /// fingerprint.
+    pub fn from_metadata(metadata: Option<&Map<String, Value>>) -> Option<(Self, String)> {
+        let trace = metadata?.get(TRACE_KEY)?.as_object()?;
+        let text = |key: &str| trace.get(key).and_then(Value::as_str).map(str::to_string);
+
+        Some((
+            StackTrace {
+                language: Language::parse(trace.get("language")?.as_str()?).ok()?,
+                error: text("error"),
+                message: text("message"),
+                frames: trace
+                    .get("frames")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|frame| Some(frame.as_str()?.to_string()))
+                    .collect(),
+            },
+            text("fingerprint")?,
+        ))
+    }
+}
+
+/// How the traces are compared with the one of the source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceMatching {
+    /// The similarity of the traces makes up `weight` of the score, the similarity of the texts
+    /// the rest. The score of a candidate without a trace is left as is.
+    Signal { weight: f64 },
+    /// The candidates with the fingerprint of the source are exact matches with a score of 1,
+    /// the others are scored by their texts.
+    Bucket,
+}
+
+impl Default for TraceMatching {
+    fn default() -> Self {
+        TraceMatching::Signal {
+            weight: DEFAULT_WEIGHT,
+        }
+    }
+}
+
+impl TraceMatching {
+    /// Returns the matching named `name` with its default parameters, a weight of
+    /// [DEFAULT_WEIGHT] for `signal`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "signal" => Ok(TraceMatching::default()),
+            "bucket" => Ok(TraceMatching::Bucket),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown matching '{}', it must be one of signal and bucket",
+                name
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceOptions {
+    /// How many frames from the top of a trace are fingerprinted and compared, defaults to
+    /// [DEFAULT_DEPTH].
+    pub depth: usize,
+    pub matching: TraceMatching,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions {
+            depth: DEFAULT_DEPTH,
+            matching: TraceMatching::default(),
+        }
+    }
+}
+
+impl TraceOptions {
+    pub fn validate(&self) -> Result<()> {
+        match self.matching {
+            _ if self.depth == 0 => Err(Error::InvalidArgument(
+                "depth must be greater than 0".to_string(),
+            )),
+            TraceMatching::Signal { weight } if !(0.0..=1.0).contains(&weight) => Err(
+                Error::InvalidArgument("weight must be a number from 0 to 1".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses the trace of `text`, and writes it into `metadata`. The metadata is left as is if
+    /// the text has no trace.
+    pub fn annotate(&self, metadata: &mut Option<Map<String, Value>>, text: &str) {
+        if let Some(trace) = StackTrace::parse(text) {
+            trace.annotate(metadata, self.depth);
+        }
+    }
+
+    /// Returns `score` combined with the similarity of the trace of `target` to the one of
+    /// `source`, both with their fingerprints.
+    pub fn score(
+        &self,
+        score: f64,
+        source: Option<&(StackTrace, String)>,
+        target: Option<&(StackTrace, String)>,
+    ) -> f64 {
+        let (Some((source, fingerprint)), Some((target, target_fingerprint))) = (source, target)
+        else {
+            return score;
+        };
+
+        match self.matching {
+            TraceMatching::Signal { weight } => {
+                score * (1.0 - weight) + source.similarity(target, self.depth) * weight
+            }
+            TraceMatching::Bucket if fingerprint == target_fingerprint => 1.0,
+            TraceMatching::Bucket => score,
+        }
+    }
+
+    /// Parses the trace of the source of a query, along with its fingerprint.
+    pub fn source(&self, text: &str) -> Option<(StackTrace, String)> {
+        StackTrace::parse(text).map(|trace| {
+            let fingerprint = trace.fingerprint(self.depth);
+            (trace, fingerprint)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(trace: &StackTrace) -> Vec<&str> {
+        trace.frames.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_stack_trace_parse() {
+        let js = StackTrace::parse(
+            "It crashes when I open the settings:
+
+Uncaught TypeError: Cannot read properties of undefined (reading 'theme')
+    at loadSettings (/home/alice/app/src/settings.js:42:17)
+    at /home/alice/app/src/index.js:10:5
+    at async Promise.all (index 0)
+    at node:internal/main/run_main_module:28:49",
+        )
+        .unwrap();
+        assert_eq!(js.language, Language::JavaScript);
+        assert_eq!(js.error.as_deref(), Some("TypeError"));
+        assert_eq!(
+            js.message.as_deref(),
+            Some("Cannot read properties of undefined (reading 'theme')")
+        );
+        assert_eq!(frames(&js), vec!["loadSettings", "index.js", "Promise.all"]);
+
+        let rust = StackTrace::parse(
+            "thread 'main' panicked at src/parse.rs:10:5:
+index out of bounds: the len is 3 but the index is 5
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/1a2b3c/library/std/src/panicking.rs:645:5
+   1: core::panicking::panic_bounds_check
+   2: app::parse::read_header::h0123456789abcdef
+             at ./src/parse.rs:10:5
+   3: app::main
+             at ./src/main.rs:4:5",
+        )
+        .unwrap();
+        assert_eq!(rust.error.as_deref(), Some("panic"));
+        assert_eq!(
+            rust.message.as_deref(),
+            Some("index out of bounds: the len is <n> but the index is <n>")
+        );
+        assert_eq!(frames(&rust), vec!["app::parse::read_header", "app::main"]);
+
+        let rust = StackTrace::parse(
+            "thread 'main' panicked at 'called `Option::unwrap()` on a `None` value', src/main.rs:2:5",
+        )
+        .unwrap();
+        assert_eq!(frames(&rust), vec!["main.rs"]);
+
+        let python = StackTrace::parse(
+            r#"Traceback (most recent call last):
+  File "/srv/app/main.py", line 10, in <module>
+    main()
+  File "/srv/app/main.py", line 6, in main
+    parse("abc")
+ValueError: invalid literal for int() with base 10: 'abc'"#,
+        )
+        .unwrap();
+        assert_eq!(python.error.as_deref(), Some("ValueError"));
+        assert_eq!(frames(&python), vec!["main", "main.py"]);
+
+        let java = StackTrace::parse(
+            r#"Exception in thread "main" java.lang.IllegalStateException: Port 8080 in use
+	at com.example.Server.lambda$start$0(Server.java:31)
+	at com.example.App.main(App.java:12)
+Caused by: java.net.BindException: Address already in use
+	at sun.nio.ch.Net.bind0(Native Method)"#,
+        )
+        .unwrap();
+        assert_eq!(
+            java.error.as_deref(),
+            Some("java.lang.IllegalStateException")
+        );
+        assert_eq!(java.message.as_deref(), Some("Port <n> in use"));
+        assert_eq!(
+            frames(&java),
+            vec!["com.example.Server.lambda$start", "com.example.App.main"]
+        );
+
+        let go = StackTrace::parse(
+            "panic: runtime error: index out of range [5] with length 3
+
+goroutine 1 [running]:
+main.parse(...)
+	/home/bob/app/main.go:10
+main.(*Server).Run(0xc000010000)
+	/home/bob/app/server.go:20 +0x2f
+main.main()
+	/home/bob/app/main.go:5 +0x1d
+exit status 2",
+        )
+        .unwrap();
+        assert_eq!(go.language, Language::Go);
+        assert_eq!(
+            go.message.as_deref(),
+            Some("runtime error: index out of range [<n>] with length <n>")
+        );
+        assert_eq!(
+            frames(&go),
+            vec!["main.parse", "main.(*Server).Run", "main.main"]
+        );
+
+        assert_eq!(
+            StackTrace::parse("The button doesn't work. TypeError?"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stack_trace_fingerprint() {
+        let trace = |path: &str, line: u32, address: &str| {
+            StackTrace::parse(&format!(
+                "thread 'main' panicked at {path}/src/parse.rs:{line}:5:
+failed to read {path}/config.toml at {address}
+stack backtrace:
+   0: app::parse::read_header
+   1: app::main"
+            ))
+            .unwrap()
+        };
+        let a = trace("/home/alice/app", 10, "0x7ffd5e8c");
+        let b = trace("C:\\Users\\bob\\app", 12, "0x5e8c0a14");
+        assert_eq!(
+            a.message.as_deref(),
+            Some("failed to read config.toml at <addr>")
+        );
+        assert_eq!(a.fingerprint(5), b.fingerprint(5));
+        assert_eq!(a.similarity(&b, 5), 1.0);
+
+        let mut c = a.clone();
+        c.frames[1] = "app::run".to_string();
+        assert_ne!(a.fingerprint(5), c.fingerprint(5));
+        // the frames below the depth are ignored
+        assert_eq!(a.fingerprint(1), c.fingerprint(1));
+        assert_eq!(a.similarity(&c, 5), 0.75);
+
+        let mut metadata = None;
+        a.annotate(&mut metadata, 5);
+        assert_eq!(
+            StackTrace::from_metadata(metadata.as_ref()),
+            Some((a.clone(), a.fingerprint(5)))
+        );
+
+        let options = TraceOptions {
+            matching: TraceMatching::Bucket,
+            ..Default::default()
+        };
+        let source = Some((a.clone(), a.fingerprint(5)));
+        assert_eq!(options.score(0.2, source.as_ref(), source.as_ref()), 1.0);
+        let target = Some((c.clone(), c.fingerprint(5)));
+        assert_eq!(options.score(0.2, source.as_ref(), target.as_ref()), 0.2);
+        assert_eq!(
+            TraceOptions::default().score(0.5, source.as_ref(), target.as_ref()),
+            0.5 * 0.7 + 0.75 * 0.3
+        );
+        assert_eq!(options.score(0.2, source.as_ref(), None), 0.2);
+
+        assert!(TraceOptions {
+            depth: 0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+        assert!(TraceMatching::parse("exact").is_err());
+    }
+}
@@ -0,0 +1,636 @@
+//! Approximate nearest neighbor search over the embeddings supplied by the caller.
+//!
+//! No model runs in this crate, the embeddings are computed upstream and passed in along with
+//! the posts and issues. They are indexed with a Hierarchical Navigable Small World graph, which
+//! answers a query by descending from a sparse top layer to the dense bottom one, visiting a
+//! small fraction of the vectors.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+};
+
+use crate::{
+    codec::{Reader, Writer},
+    Error, Result,
+};
+
+/// How the distance between two embeddings is measured, smaller is closer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Distance {
+    /// `1 - cos(a, b)`, the vectors don't need to be normalized.
+    #[default]
+    Cosine,
+    /// The negated dot product, for embeddings trained for it.
+    Dot,
+    /// The Euclidean distance.
+    L2,
+}
+
+impl Distance {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "cosine" => Ok(Distance::Cosine),
+            "dot" => Ok(Distance::Dot),
+            "l2" => Ok(Distance::L2),
+            _ => Err(Error::InvalidArgument(format!(
+                "Unknown distance '{}', it must be one of cosine, dot and l2",
+                name
+            ))),
+        }
+    }
+
+    /// Converts a distance to a score where higher is closer: the cosine similarity, the dot
+    /// product, or `1 / (1 + distance)` for L2.
+    pub fn score(&self, distance: f32) -> f64 {
+        match self {
+            Distance::Cosine => 1.0 - distance as f64,
+            Distance::Dot => -distance as f64,
+            Distance::L2 => 1.0 / (1.0 + distance as f64),
+        }
+    }
+
+    fn between(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            // the vectors are normalized when they are inserted or queried
+            Distance::Cosine => 1.0 - dot(a, b),
+            Distance::Dot => -dot(a, b),
+            Distance::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+
+    fn prepare(&self, vector: &[f32]) -> Vec<f32> {
+        match self {
+            Distance::Cosine => {
+                let norm = dot(vector, vector).sqrt();
+
+                if norm > 0.0 {
+                    vector.iter().map(|x| x / norm).collect()
+                } else {
+                    vector.to_vec()
+                }
+            }
+            _ => vector.to_vec(),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswOptions {
+    pub distance: Distance,
+    /// How many neighbors a node links to on each layer, twice as many on the bottom one.
+    /// Higher values improve the recall at the cost of memory and build time, defaults to 16.
+    pub m: usize,
+    /// How many candidates are considered when linking a new node, defaults to 200.
+    pub ef_construction: usize,
+    /// How many candidates are considered when querying, raised to the number of requested
+    /// neighbors if lower, defaults to 64.
+    pub ef_search: usize,
+}
+
+impl Default for HnswOptions {
+    fn default() -> Self {
+        HnswOptions {
+            distance: Distance::Cosine,
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+impl HnswOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.m < 2 {
+            return Err(Error::InvalidArgument("m must be at least 2".to_string()));
+        }
+        if self.ef_construction == 0 || self.ef_search == 0 {
+            return Err(Error::InvalidArgument(
+                "ef_construction and ef_search must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A neighbor found by [Hnsw::search].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    /// The key the vector was inserted with, such as the position of a post.
+    pub key: usize,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: u32,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    key: usize,
+    vector: Vec<f32>,
+    /// The neighbors on each layer the node is on, from the bottom one up.
+    links: Vec<Vec<u32>>,
+}
+
+/// A Hierarchical Navigable Small World graph over vectors of the same dimensions.
+///
+/// The layer of a node is derived from the order it's inserted in, so the same vectors
+/// inserted in the same order always build the same graph.
+#[derive(Debug, Clone)]
+pub struct Hnsw {
+    options: HnswOptions,
+    dimensions: Option<usize>,
+    nodes: Vec<Node>,
+    entry: Option<u32>,
+}
+
+impl Hnsw {
+    pub fn new(options: HnswOptions) -> Self {
+        Hnsw {
+            options,
+            dimensions: None,
+            nodes: Vec::new(),
+            entry: None,
+        }
+    }
+
+    pub fn options(&self) -> &HnswOptions {
+        &self.options
+    }
+
+    /// The dimensions of the vectors, set by the first insertion.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn check_dimensions(&self, vector: &[f32]) -> Result<()> {
+        if vector.is_empty() {
+            return Err(Error::InvalidArgument("embedding is empty".to_string()));
+        }
+
+        match self.dimensions {
+            Some(dimensions) if dimensions != vector.len() => Err(Error::InvalidArgument(format!(
+                "embedding has {} dimensions, expected {}",
+                vector.len(),
+                dimensions
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Draws the top layer of the `i`th node from an exponential distribution, using a hash of
+    /// `i` as the random number.
+    fn level_of(&self, i: usize) -> usize {
+        // splitmix64
+        let mut x = (i as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^= x >> 31;
+
+        let uniform = ((x >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        let ml = 1.0 / (self.options.m as f64).ln();
+        (-uniform.ln() * ml) as usize
+    }
+
+    fn distance_to(&self, query: &[f32], node: u32) -> f32 {
+        self.options
+            .distance
+            .between(query, &self.nodes[node as usize].vector)
+    }
+
+    /// Returns up to `ef` nodes closest to `query` on the layer, starting from `entries`,
+    /// ordered from the closest.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entries.iter().map(|c| c.node).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> =
+            entries.iter().copied().map(Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entries.iter().copied().collect();
+
+        while let Some(Reverse(closest)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|farthest| closest > *farthest) {
+                break;
+            }
+
+            for &neighbor in &self.nodes[closest.node as usize].links[layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+
+                let candidate = Candidate {
+                    distance: self.distance_to(query, neighbor),
+                    node: neighbor,
+                };
+
+                if found.len() < ef || found.peek().is_some_and(|farthest| candidate < *farthest) {
+                    candidates.push(Reverse(candidate));
+                    found.push(candidate);
+
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    /// Walks down from the entry point to `layer`, returning the closest node on each layer.
+    fn descend(&self, query: &[f32], to_layer: usize) -> Vec<Candidate> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let mut closest = vec![Candidate {
+            distance: self.distance_to(query, entry),
+            node: entry,
+        }];
+        let top = self.nodes[entry as usize].links.len() - 1;
+
+        for layer in (to_layer + 1..=top).rev() {
+            closest = self.search_layer(query, &closest, 1, layer);
+        }
+        closest
+    }
+
+    /// Adds a vector to the graph, `key` is returned along with it by [Hnsw::search].
+    pub fn insert(&mut self, key: usize, vector: &[f32]) -> Result<()> {
+        self.check_dimensions(vector)?;
+        self.dimensions = Some(vector.len());
+
+        let vector = self.options.distance.prepare(vector);
+        let node = self.nodes.len() as u32;
+        let level = self.level_of(node as usize);
+        let top = self
+            .entry
+            .map(|entry| self.nodes[entry as usize].links.len() - 1);
+        let mut entries = self.descend(&vector, level);
+
+        self.nodes.push(Node {
+            key,
+            vector,
+            links: vec![Vec::new(); level + 1],
+        });
+
+        let Some(top) = top else {
+            self.entry = Some(node);
+            return Ok(());
+        };
+
+        for layer in (0..=level.min(top)).rev() {
+            let query = self.nodes[node as usize].vector.clone();
+            let found = self.search_layer(&query, &entries, self.options.ef_construction, layer);
+            let max_links = self.max_links(layer);
+            let neighbors: Vec<u32> = found.iter().take(max_links).map(|c| c.node).collect();
+
+            for &neighbor in &neighbors {
+                self.link(neighbor, node, layer);
+            }
+            self.nodes[node as usize].links[layer] = neighbors;
+            entries = found;
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+        Ok(())
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.options.m * 2
+        } else {
+            self.options.m
+        }
+    }
+
+    /// Links `from` to `to`, dropping the farthest neighbor of `from` when it has too many.
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        let max_links = self.max_links(layer);
+        self.nodes[from as usize].links[layer].push(to);
+
+        if self.nodes[from as usize].links[layer].len() > max_links {
+            let vector = self.nodes[from as usize].vector.clone();
+            let mut links: Vec<Candidate> = self.nodes[from as usize].links[layer]
+                .iter()
+                .map(|&node| Candidate {
+                    distance: self.distance_to(&vector, node),
+                    node,
+                })
+                .collect();
+
+            links.sort();
+            links.truncate(max_links);
+            self.nodes[from as usize].links[layer] = links.into_iter().map(|c| c.node).collect();
+        }
+    }
+
+    /// Finds up to `k` vectors closest to `query`, ordered from the closest.
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<Neighbor>> {
+        self.check_dimensions(query)?;
+
+        if k == 0 || self.nodes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = self.options.distance.prepare(query);
+        let entries = self.descend(&query, 0);
+        let ef = self.options.ef_search.max(k);
+
+        Ok(self
+            .search_layer(&query, &entries, ef, 0)
+            .into_iter()
+            .take(k)
+            .map(|c| Neighbor {
+                key: self.nodes[c.node as usize].key,
+                distance: c.distance,
+            })
+            .collect())
+    }
+
+    /// Like [Hnsw::search], but only returns the vectors whose keys satisfy `keep`. The search
+    /// is widened until `k` of them are found or the whole graph has been searched.
+    pub fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        keep: impl Fn(usize) -> bool,
+    ) -> Result<Vec<Neighbor>> {
+        let mut wanted = k;
+
+        loop {
+            let found = self.search(query, wanted)?;
+            let exhausted = found.len() < wanted;
+            let kept: Vec<Neighbor> = found.into_iter().filter(|n| keep(n.key)).collect();
+
+            if kept.len() >= k || exhausted || wanted >= self.nodes.len() {
+                return Ok(kept.into_iter().take(k).collect());
+            }
+            wanted = (wanted * 2).min(self.nodes.len());
+        }
+    }
+
+    /// Encodes the graph to be saved in a snapshot file, see [Hnsw::decode].
+    pub(crate) fn encode(&self, writer: &mut Writer) {
+        writer.u32(self.options.distance as u32);
+        writer.length(self.options.m);
+        writer.length(self.options.ef_construction);
+        writer.length(self.options.ef_search);
+        writer.length(self.dimensions.unwrap_or(0));
+        writer.length(self.nodes.len());
+
+        for node in &self.nodes {
+            writer.length(node.key);
+            writer.f32s(&node.vector);
+            writer.length(node.links.len());
+            for links in &node.links {
+                writer.length(links.len());
+                for &link in links {
+                    writer.u32(link);
+                }
+            }
+        }
+    }
+
+    /// Restores a graph encoded by [Hnsw::encode] whose keys are below `keys`, checking that
+    /// it's well formed, so searching it or inserting into it never panics.
+    pub(crate) fn decode(bytes: &[u8], keys: usize) -> Result<Self> {
+        let mut reader = Reader::new(bytes, "vector index");
+        let distance = match reader.u32()? {
+            0 => Distance::Cosine,
+            1 => Distance::Dot,
+            2 => Distance::L2,
+            _ => return Err(reader.invalid("unknown distance")),
+        };
+        let options = HnswOptions {
+            distance,
+            m: reader.length(0)?,
+            ef_construction: reader.length(0)?,
+            ef_search: reader.length(0)?,
+        };
+        options
+            .validate()
+            .map_err(|e| reader.invalid(&e.to_string()))?;
+
+        let mut index = Hnsw::new(options);
+        let dimensions = reader.length(0)?;
+        let count = reader.length(8)?;
+
+        for i in 0..count {
+            let key = reader.length(0)?;
+            let vector = reader.f32s(dimensions)?;
+            let layers = reader.length(8)?;
+
+            if key >= keys || dimensions == 0 || layers != index.level_of(i) + 1 {
+                return Err(reader.invalid(&format!("bad node {}", i)));
+            }
+
+            let mut links = Vec::with_capacity(layers);
+            for _ in 0..layers {
+                let len = reader.length(4)?;
+                links.push(
+                    (0..len)
+                        .map(|_| reader.u32())
+                        .collect::<Result<Vec<u32>>>()?,
+                );
+            }
+            index.nodes.push(Node { key, vector, links });
+        }
+
+        // the links point to nodes which are on the same layer
+        for (i, node) in index.nodes.iter().enumerate() {
+            for (layer, links) in node.links.iter().enumerate() {
+                let linked = |&link: &u32| {
+                    index
+                        .nodes
+                        .get(link as usize)
+                        .is_some_and(|node| node.links.len() > layer)
+                };
+                if !links.iter().all(linked) {
+                    return Err(reader.invalid(&format!("bad links of node {}", i)));
+                }
+            }
+        }
+        reader.finish()?;
+
+        // the entry point is the first node on the top layer, as when inserting
+        for (i, node) in index.nodes.iter().enumerate() {
+            let top = index
+                .entry
+                .map(|entry| index.nodes[entry as usize].links.len());
+            if top.is_none_or(|top| node.links.len() > top) {
+                index.entry = Some(i as u32);
+            }
+        }
+        index.dimensions = (!index.nodes.is_empty()).then_some(dimensions);
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Brute force nearest neighbors, to check the recall against.
+    fn exact(vectors: &[Vec<f32>], query: &[f32], k: usize, distance: Distance) -> Vec<usize> {
+        let query = distance.prepare(query);
+        let mut all: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (distance.between(&query, &distance.prepare(v)), i))
+            .collect();
+        all.sort_by(|a, b| a.0.total_cmp(&b.0));
+        all.into_iter().take(k).map(|(_, i)| i).collect()
+    }
+
+    fn vectors(count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        // a deterministic pseudo-random sequence, so the test is reproducible
+        let mut state = 42u32;
+        (0..count)
+            .map(|_| {
+                (0..dimensions)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        (state as f32 / u32::MAX as f32) * 2.0 - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let vectors = vectors(300, 8);
+
+        for distance in [Distance::Cosine, Distance::Dot, Distance::L2] {
+            let mut hnsw = Hnsw::new(HnswOptions {
+                distance,
+                ..Default::default()
+            });
+            for (i, vector) in vectors.iter().enumerate() {
+                hnsw.insert(i, vector).unwrap();
+            }
+
+            let mut hits = 0;
+            for query in vectors.iter().take(25) {
+                let expected = exact(&vectors, query, 10, distance);
+                let found = hnsw.search(query, 10).unwrap();
+                hits += found.iter().filter(|n| expected.contains(&n.key)).count();
+            }
+            assert!(hits >= 225, "{:?} recall is {}/250", distance, hits);
+        }
+    }
+
+    #[test]
+    fn test_hnsw_search() {
+        let mut hnsw = Hnsw::new(HnswOptions::default());
+        hnsw.insert(10, &[1.0, 0.0]).unwrap();
+        hnsw.insert(20, &[0.0, 1.0]).unwrap();
+        hnsw.insert(30, &[1.0, 0.1]).unwrap();
+
+        let found = hnsw.search(&[2.0, 0.0], 2).unwrap();
+        assert_eq!(found.iter().map(|n| n.key).collect::<Vec<_>>(), [10, 30]);
+        assert!(found[0].distance.abs() < 1e-6);
+        assert!((Distance::Cosine.score(found[0].distance) - 1.0).abs() < 1e-6);
+
+        let found = hnsw
+            .search_filtered(&[2.0, 0.0], 1, |key| key == 20)
+            .unwrap();
+        assert_eq!(found.iter().map(|n| n.key).collect::<Vec<_>>(), [20]);
+
+        assert_eq!(
+            hnsw.insert(40, &[1.0]).unwrap_err().to_string(),
+            "embedding has 1 dimensions, expected 2"
+        );
+        assert!(hnsw.search(&[1.0, 2.0, 3.0], 1).is_err());
+        assert_eq!(
+            Distance::parse("manhattan").unwrap_err().to_string(),
+            "Unknown distance 'manhattan', it must be one of cosine, dot and l2"
+        );
+    }
+
+    #[test]
+    fn test_hnsw_encode_and_decode() {
+        let vectors = vectors(100, 8);
+        let mut hnsw = Hnsw::new(HnswOptions {
+            distance: Distance::L2,
+            ..Default::default()
+        });
+        for (i, vector) in vectors.iter().enumerate().take(90) {
+            hnsw.insert(i, vector).unwrap();
+        }
+
+        let mut writer = Writer::default();
+        hnsw.encode(&mut writer);
+        let mut decoded = Hnsw::decode(&writer.bytes, 100).unwrap();
+        assert_eq!(decoded.options(), hnsw.options());
+        assert_eq!(decoded.dimensions(), Some(8));
+        assert_eq!(decoded.entry, hnsw.entry);
+
+        // the decoded graph grows the same way as the original one
+        for (i, vector) in vectors.iter().enumerate().skip(90) {
+            hnsw.insert(i, vector).unwrap();
+            decoded.insert(i, vector).unwrap();
+        }
+        for query in vectors.iter().take(10) {
+            assert_eq!(
+                decoded.search(query, 5).unwrap(),
+                hnsw.search(query, 5).unwrap()
+            );
+        }
+
+        let mut writer = Writer::default();
+        hnsw.encode(&mut writer);
+        assert!(Hnsw::decode(&writer.bytes, 99).is_err());
+        assert!(Hnsw::decode(&writer.bytes[..writer.bytes.len() - 1], 100).is_err());
+
+        let mut writer = Writer::default();
+        Hnsw::new(HnswOptions::default()).encode(&mut writer);
+        let empty = Hnsw::decode(&writer.bytes, 0).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.dimensions(), None);
+    }
+}
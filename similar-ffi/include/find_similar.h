@@ -0,0 +1,210 @@
+/*
+ * The C API of find-similar.
+ *
+ * - Handles are released exactly once with the matching fs_*_free function, and may be used
+ *   from multiple threads at the same time.
+ * - Input strings are borrowed for the duration of the call and must be NUL-terminated UTF-8.
+ * - Strings written to char ** out parameters are owned by the caller and released with
+ *   fs_string_free.
+ * - On failure, functions return a status other than FS_STATUS_OK and, when the error out
+ *   parameter isn't NULL, set it to a message to be released with fs_string_free.
+ */
+
+#ifndef FIND_SIMILAR_H
+#define FIND_SIMILAR_H
+
+/* Generated by cbindgen from similar-ffi, do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The outcome of a call, the errors of the core crate keep their kinds.
+typedef enum {
+  FS_STATUS_OK = 0,
+  // An argument is invalid, such as a `NULL` pointer, a string which isn't UTF-8, malformed
+  // JSON or a source without text.
+  FS_STATUS_INVALID_ARGUMENT = 1,
+  // A metadata filter expression is malformed.
+  FS_STATUS_INVALID_FILTER = 2,
+  // A snapshot file is corrupted or written by a newer version.
+  FS_STATUS_INVALID_SNAPSHOT = 3,
+  FS_STATUS_IO = 4,
+  FS_STATUS_CSV = 5,
+  FS_STATUS_JSON = 6,
+  FS_STATUS_DATABASE = 7,
+  // The store is running and queueing as many queries as it allows.
+  FS_STATUS_OVERLOADED = 8,
+  // The call panicked, which is a bug of this library.
+  FS_STATUS_PANIC = 99,
+} FsStatus;
+
+// A handle of an issue feature store.
+typedef struct FsIssueFeatureStore FsIssueFeatureStore;
+
+// A handle of a post store.
+typedef struct FsPostStore FsPostStore;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Releases a string returned by this library, `NULL` is ignored.
+void fs_string_free(char *value);
+
+// Creates an empty issue feature store.
+FsIssueFeatureStore *fs_issue_store_new(void);
+
+// Releases an issue feature store, `NULL` is ignored.
+void fs_issue_store_free(FsIssueFeatureStore *store);
+
+// The generation of the current snapshot, increased by every write. Returns 0 for `NULL`.
+uint64_t fs_issue_store_generation(const FsIssueFeatureStore *store);
+
+// How many records are in the current snapshot. Returns 0 for `NULL`.
+size_t fs_issue_store_len(const FsIssueFeatureStore *store);
+
+// Loads an issue feature store from a CSV file into `*out`.
+FsStatus fs_issue_store_load_csv(const char *path, FsIssueFeatureStore **out, char **error);
+
+// Loads an issue feature store from a table of a database into `*out`.
+FsStatus fs_issue_store_from_db(const char *url,
+                                const char *table,
+                                FsIssueFeatureStore **out,
+                                char **error);
+
+// Writes the records of the current snapshot to a CSV file.
+FsStatus fs_issue_store_dump_csv(const FsIssueFeatureStore *store, const char *path, char **error);
+
+// Adds the records in a JSON Lines file to the store. `options_json` is an optional
+// `JsonlOptions` object. The `LoadReport` is written to `*report_json` unless it's `NULL`.
+FsStatus fs_issue_store_load_jsonl(const FsIssueFeatureStore *store,
+                                   const char *path,
+                                   const char *options_json,
+                                   char **report_json,
+                                   char **error);
+
+// Writes the records of the current snapshot to a JSON Lines file. `options_json` is an
+// optional `JsonlOptions` object.
+FsStatus fs_issue_store_dump_jsonl(const FsIssueFeatureStore *store,
+                                   const char *path,
+                                   const char *options_json,
+                                   char **error);
+
+// Adds a record to the store, replacing the stored one with the same issue ID. `record_json`
+// is an `IssueFeaturesRecord` object.
+FsStatus fs_issue_store_set_record(const FsIssueFeatureStore *store,
+                                   const char *record_json,
+                                   char **error);
+
+// Writes the record with the issue ID to `*record_json` as an `IssueFeaturesRecord` object,
+// or `NULL` when there is no such record.
+FsStatus fs_issue_store_get_record(const FsIssueFeatureStore *store,
+                                   const char *issue_id,
+                                   char **record_json,
+                                   char **error);
+
+// Removes the record with the issue ID. Whether it existed is written to `*removed` unless
+// it's `NULL`.
+FsStatus fs_issue_store_remove_record(const FsIssueFeatureStore *store,
+                                      const char *issue_id,
+                                      bool *removed,
+                                      char **error);
+
+// Finds the `top_n` records most similar to `features_json`, an `IssueFeatures` object. When
+// `filter_json` is not `NULL`, only the records whose metadata satisfy it are scored. The
+// matches are written to `*result_json` as an array of `SimilarIssueFeaturesRecord` objects.
+FsStatus fs_issue_store_find_similar_records(const FsIssueFeatureStore *store,
+                                             const char *features_json,
+                                             size_t top_n,
+                                             const char *filter_json,
+                                             char **result_json,
+                                             char **error);
+
+// Creates an empty post store.
+FsPostStore *fs_post_store_new(void);
+
+// Releases a post store, `NULL` is ignored.
+void fs_post_store_free(FsPostStore *store);
+
+// The generation of the current snapshot, increased by every write. Returns 0 for `NULL`.
+uint64_t fs_post_store_generation(const FsPostStore *store);
+
+// How many posts are in the current snapshot. Returns 0 for `NULL`.
+size_t fs_post_store_len(const FsPostStore *store);
+
+// Loads a post store from a binary snapshot file into `*out`.
+FsStatus fs_post_store_load(const char *path, FsPostStore **out, char **error);
+
+// Opens a binary snapshot file in read-only mode by memory mapping it, the store is written
+// to `*out`.
+FsStatus fs_post_store_open(const char *path, FsPostStore **out, char **error);
+
+// Loads a post store from a database into `*out`. `options_json` is a `DbOptions` object.
+FsStatus fs_post_store_from_db(const char *options_json, FsPostStore **out, char **error);
+
+// Saves the posts of the current snapshot to a binary snapshot file.
+FsStatus fs_post_store_save(const FsPostStore *store, const char *path, char **error);
+
+// Replaces all the posts in the store. `posts_json` is an array of `PostData` objects.
+FsStatus fs_post_store_preload(const FsPostStore *store, const char *posts_json, char **error);
+
+// Adds posts to the store, keeping the existing ones.
+FsStatus fs_post_store_append(const FsPostStore *store, const char *posts_json, char **error);
+
+// Adds posts to the store, replacing the stored ones with the same IDs.
+FsStatus fs_post_store_upsert(const FsPostStore *store, const char *posts_json, char **error);
+
+// Removes the posts whose IDs are in `ids_json`, an array of strings. How many have been
+// removed is written to `*removed` unless it's `NULL`.
+FsStatus fs_post_store_remove(const FsPostStore *store,
+                              const char *ids_json,
+                              size_t *removed,
+                              char **error);
+
+// Finds the `top_n` posts most similar to `source_json`, a `PostData` object. When
+// `filter_json` is not `NULL`, only the posts whose metadata satisfy it are scored. The result
+// is written to `*result_json` as a `FindTopNResult` object.
+FsStatus fs_post_store_find_similar_posts(const FsPostStore *store,
+                                          const char *source_json,
+                                          size_t top_n,
+                                          const char *filter_json,
+                                          char **result_json,
+                                          char **error);
+
+// Replaces the posts in the store with the ones in a CSV file. `options_json` is an optional
+// `CsvOptions` object. The `LoadReport` is written to `*report_json` unless it's `NULL`.
+FsStatus fs_post_store_load_csv(const FsPostStore *store,
+                                const char *path,
+                                const char *options_json,
+                                char **report_json,
+                                char **error);
+
+// Writes the posts of the current snapshot to a CSV file. `options_json` is an optional
+// `CsvOptions` object.
+FsStatus fs_post_store_dump_csv(const FsPostStore *store,
+                                const char *path,
+                                const char *options_json,
+                                char **error);
+
+// Replaces the posts in the store with the ones in a JSON Lines file. `options_json` is an
+// optional `JsonlOptions` object. The `LoadReport` is written to `*report_json` unless it's
+// `NULL`.
+FsStatus fs_post_store_load_jsonl(const FsPostStore *store,
+                                  const char *path,
+                                  const char *options_json,
+                                  char **report_json,
+                                  char **error);
+
+// Writes the posts of the current snapshot to a JSON Lines file. `options_json` is an
+// optional `JsonlOptions` object.
+FsStatus fs_post_store_dump_jsonl(const FsPostStore *store,
+                                  const char *path,
+                                  const char *options_json,
+                                  char **error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FIND_SIMILAR_H */
@@ -0,0 +1,302 @@
+use std::{ffi::c_char, ptr};
+
+use similar_core::issue;
+
+use crate::{
+    block_on, call,
+    json::{self, IssueFeatures, IssueFeaturesRecord, IssueJsonlOptions},
+    read_handle, read_optional_str, read_str, write_out, write_string, FsStatus,
+};
+
+/// A handle of an issue feature store.
+pub struct FsIssueFeatureStore {
+    inner: issue::IssueFeatureStore,
+}
+
+fn into_handle(inner: issue::IssueFeatureStore) -> *mut FsIssueFeatureStore {
+    Box::into_raw(Box::new(FsIssueFeatureStore { inner }))
+}
+
+/// Creates an empty issue feature store.
+#[no_mangle]
+pub extern "C" fn fs_issue_store_new() -> *mut FsIssueFeatureStore {
+    into_handle(issue::IssueFeatureStore::default())
+}
+
+/// Releases an issue feature store, `NULL` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_free(store: *mut FsIssueFeatureStore) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// The generation of the current snapshot, increased by every write. Returns 0 for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_generation(store: *const FsIssueFeatureStore) -> u64 {
+    store.as_ref().map_or(0, |store| store.inner.generation())
+}
+
+/// How many records are in the current snapshot. Returns 0 for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_len(store: *const FsIssueFeatureStore) -> usize {
+    store
+        .as_ref()
+        .map_or(0, |store| store.inner.snapshot().map.len())
+}
+
+/// Loads an issue feature store from a CSV file into `*out`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_load_csv(
+    path: *const c_char,
+    out: *mut *mut FsIssueFeatureStore,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = issue::IssueFeatureStore::load_csv(read_str(path, "path")?)?;
+        write_out(out, into_handle(store), "out")
+    })
+}
+
+/// Loads an issue feature store from a table of a database into `*out`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_from_db(
+    url: *const c_char,
+    table: *const c_char,
+    out: *mut *mut FsIssueFeatureStore,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let options = issue::DbOptions {
+            url: read_str(url, "url")?.to_string(),
+            table: read_str(table, "table")?.to_string(),
+        };
+        let store = block_on(issue::IssueFeatureStore::from_db(&options))??;
+        write_out(out, into_handle(store), "out")
+    })
+}
+
+/// Writes the records of the current snapshot to a CSV file.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_dump_csv(
+    store: *const FsIssueFeatureStore,
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        Ok(store.inner.dump_csv(read_str(path, "path")?)?)
+    })
+}
+
+/// Adds the records in a JSON Lines file to the store. `options_json` is an optional
+/// `JsonlOptions` object. The `LoadReport` is written to `*report_json` unless it's `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_load_jsonl(
+    store: *const FsIssueFeatureStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    report_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: IssueJsonlOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+        let report = store
+            .inner
+            .load_jsonl(read_str(path, "path")?, &options.into())?;
+
+        if report_json.is_null() {
+            return Ok(());
+        }
+        write_string(
+            report_json,
+            json::load_report_to_json(&report),
+            "report_json",
+        )
+    })
+}
+
+/// Writes the records of the current snapshot to a JSON Lines file. `options_json` is an
+/// optional `JsonlOptions` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_dump_jsonl(
+    store: *const FsIssueFeatureStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: IssueJsonlOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+
+        Ok(store
+            .inner
+            .dump_jsonl(read_str(path, "path")?, &options.into())?)
+    })
+}
+
+/// Adds a record to the store, replacing the stored one with the same issue ID. `record_json`
+/// is an `IssueFeaturesRecord` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_set_record(
+    store: *const FsIssueFeatureStore,
+    record_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let record: IssueFeaturesRecord =
+            json::parse(read_str(record_json, "record_json")?, "record")?;
+        Ok(store.inner.set_record(record.into())?)
+    })
+}
+
+/// Writes the record with the issue ID to `*record_json` as an `IssueFeaturesRecord` object,
+/// or `NULL` when there is no such record.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_get_record(
+    store: *const FsIssueFeatureStore,
+    issue_id: *const c_char,
+    record_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+
+        match store.inner.get_record(read_str(issue_id, "issue_id")?) {
+            Some(record) => write_string(record_json, json::record_to_json(&record), "record_json"),
+            None => write_out(record_json, ptr::null_mut(), "record_json"),
+        }
+    })
+}
+
+/// Removes the record with the issue ID. Whether it existed is written to `*removed` unless
+/// it's `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_remove_record(
+    store: *const FsIssueFeatureStore,
+    issue_id: *const c_char,
+    removed: *mut bool,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let existed = store.inner.remove_record(read_str(issue_id, "issue_id")?);
+
+        if !removed.is_null() {
+            *removed = existed;
+        }
+        Ok(())
+    })
+}
+
+/// Finds the `top_n` records most similar to `features_json`, an `IssueFeatures` object. When
+/// `filter_json` is not `NULL`, only the records whose metadata satisfy it are scored. The
+/// matches are written to `*result_json` as an array of `SimilarIssueFeaturesRecord` objects.
+#[no_mangle]
+pub unsafe extern "C" fn fs_issue_store_find_similar_records(
+    store: *const FsIssueFeatureStore,
+    features_json: *const c_char,
+    top_n: usize,
+    filter_json: *const c_char,
+    result_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let features: IssueFeatures =
+            json::parse(read_str(features_json, "features_json")?, "features")?;
+        let filter = json::parse_filter(read_optional_str(filter_json, "filter_json")?)?;
+        let matches = store
+            .inner
+            .find_similar_records(&features.into(), top_n, filter.as_ref())?;
+
+        write_string(
+            result_json,
+            json::similar_records_to_json(&matches),
+            "result_json",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::tests::take_string;
+
+    #[test]
+    fn test_issue_store() {
+        let records = [
+            r#"{"issueId": "1", "features": {"operation": "click the button", "phenomenon": "app crashes"}}"#,
+            r#"{"issueId": "2", "features": {"operation": "open the settings"}, "metadata": {"component": "settings"}}"#,
+        ];
+        let features =
+            CString::new(r#"{"operation": "click the button", "phenomenon": "the app crashes"}"#)
+                .unwrap();
+        let mut result: *mut c_char = ptr::null_mut();
+        let mut error: *mut c_char = ptr::null_mut();
+
+        unsafe {
+            let store = fs_issue_store_new();
+
+            for record in records {
+                let record = CString::new(record).unwrap();
+                assert_eq!(
+                    fs_issue_store_set_record(store, record.as_ptr(), &mut error),
+                    FsStatus::Ok
+                );
+            }
+            assert_eq!(fs_issue_store_len(store), 2);
+
+            let status = fs_issue_store_find_similar_records(
+                store,
+                features.as_ptr(),
+                5,
+                ptr::null(),
+                &mut result,
+                &mut error,
+            );
+            assert_eq!(status, FsStatus::Ok);
+
+            let matches: Value = serde_json::from_str(&take_string(result)).unwrap();
+            assert_eq!(matches.as_array().unwrap().len(), 1);
+            assert_eq!(matches[0]["issueId"], "1");
+            assert_eq!(matches[0]["generation"], 2);
+
+            let issue_id = CString::new("2").unwrap();
+            let mut record: *mut c_char = ptr::null_mut();
+            fs_issue_store_get_record(store, issue_id.as_ptr(), &mut record, &mut error);
+            let record: Value = serde_json::from_str(&take_string(record)).unwrap();
+            assert_eq!(record["metadata"]["component"], "settings");
+
+            let mut removed = false;
+            fs_issue_store_remove_record(store, issue_id.as_ptr(), &mut removed, &mut error);
+            assert!(removed);
+
+            let mut record: *mut c_char = ptr::null_mut();
+            fs_issue_store_get_record(store, issue_id.as_ptr(), &mut record, &mut error);
+            assert!(record.is_null());
+
+            let filter = CString::new(r#"{"component": {"$foo": 1}}"#).unwrap();
+            let status = fs_issue_store_find_similar_records(
+                store,
+                features.as_ptr(),
+                5,
+                filter.as_ptr(),
+                &mut result,
+                &mut error,
+            );
+            assert_eq!(status, FsStatus::InvalidFilter);
+            assert!(take_string(error).starts_with("Invalid filter"));
+
+            fs_issue_store_free(store);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! The JSON documents exchanged through the C API, in the same shapes as the objects of the
+//! Node.js bindings.
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Map, Value};
+use similar_core::{
+    filter::Filter,
+    issue,
+    load::LoadReport,
+    post::{self, FindTopNResult},
+};
+
+use crate::{FfiError, FfiResult};
+
+/// Parses a JSON argument, `name` is used in the error message.
+pub fn parse<T: DeserializeOwned>(input: &str, name: &str) -> FfiResult<T> {
+    serde_json::from_str(input)
+        .map_err(|e| FfiError::invalid_argument(format!("Cannot parse {}: {}", name, e)))
+}
+
+pub fn parse_filter(input: Option<&str>) -> FfiResult<Option<Filter>> {
+    match input {
+        Some(input) => Ok(Some(Filter::parse(&parse::<Value>(input, "filter")?)?)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostData {
+    pub id: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub metadata: Option<Map<String, Value>>,
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl From<PostData> for post::PostData {
+    fn from(post: PostData) -> Self {
+        post::PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: post.embedding,
+        }
+    }
+}
+
+pub fn parse_posts(input: &str) -> FfiResult<Vec<post::PostData>> {
+    let posts: Vec<PostData> = parse(input, "posts")?;
+    Ok(posts.into_iter().map(post::PostData::from).collect())
+}
+
+fn metadata_to_json(metadata: &Option<Map<String, Value>>) -> Value {
+    metadata.clone().map_or(Value::Null, Value::Object)
+}
+
+pub fn find_top_n_result_to_json(result: &FindTopNResult) -> String {
+    let matches: Vec<Value> = result
+        .matches
+        .iter()
+        .map(|m| {
+            json!({
+                "target": {
+                    "id": m.target.id,
+                    "title": m.target.title,
+                    "content": m.target.content,
+                    "metadata": metadata_to_json(&m.target.metadata),
+                },
+                "score": m.score,
+            })
+        })
+        .collect();
+
+    json!({
+        "matches": matches,
+        "processTime": result.process_time.as_millis() as u64,
+        "generation": result.generation,
+    })
+    .to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueFeatures {
+    pub operation: Option<String>,
+    pub phenomenon: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub actual_behavior: Option<String>,
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl From<IssueFeatures> for issue::IssueFeatures {
+    fn from(features: IssueFeatures) -> Self {
+        issue::IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: features.embedding,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueFeaturesRecord {
+    pub issue_id: String,
+    pub features: IssueFeatures,
+    pub metadata: Option<Map<String, Value>>,
+}
+
+impl From<IssueFeaturesRecord> for issue::IssueFeaturesRecord {
+    fn from(record: IssueFeaturesRecord) -> Self {
+        issue::IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+fn features_to_json(features: &issue::IssueFeatures) -> Value {
+    json!({
+        "operation": features.operation,
+        "phenomenon": features.phenomenon,
+        "expectedBehavior": features.expected_behavior,
+        "actualBehavior": features.actual_behavior,
+    })
+}
+
+pub fn record_to_json(record: &issue::IssueFeaturesRecord) -> String {
+    json!({
+        "issueId": record.issue_id,
+        "features": features_to_json(&record.features),
+        "metadata": metadata_to_json(&record.metadata),
+    })
+    .to_string()
+}
+
+pub fn similar_records_to_json(matches: &[issue::SimilarIssueFeaturesRecord]) -> String {
+    let matches: Vec<Value> = matches
+        .iter()
+        .map(|m| {
+            json!({
+                "issueId": m.issue_id,
+                "features": features_to_json(&m.features),
+                "metadata": metadata_to_json(&m.metadata),
+                "score": m.score,
+                "generation": m.generation,
+            })
+        })
+        .collect();
+
+    Value::Array(matches).to_string()
+}
+
+pub fn load_report_to_json(report: &LoadReport) -> String {
+    let errors: Vec<Value> = report
+        .errors
+        .iter()
+        .map(|e| json!({ "line": e.line, "message": e.message }))
+        .collect();
+
+    json!({ "loaded": report.loaded, "errors": errors }).to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvOptions {
+    pub title_column: Option<String>,
+    pub content_column: Option<String>,
+    pub id_column: Option<String>,
+    pub delimiter: Option<String>,
+}
+
+impl CsvOptions {
+    /// Converts the options to the ones of the core crate, validating the delimiter.
+    pub fn into_core(self) -> FfiResult<post::CsvOptions> {
+        let delimiter = match self.delimiter {
+            Some(delimiter) if delimiter.len() == 1 && delimiter.is_ascii() => {
+                Some(delimiter.as_bytes()[0])
+            }
+            Some(delimiter) => {
+                return Err(FfiError::invalid_argument(format!(
+                    "Invalid CSV delimiter '{}', it must be a single ASCII character",
+                    delimiter
+                )))
+            }
+            None => None,
+        };
+
+        Ok(post::CsvOptions {
+            title_column: self.title_column,
+            content_column: self.content_column,
+            id_column: self.id_column,
+            delimiter,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostJsonlOptions {
+    pub id_field: Option<String>,
+    pub title_field: Option<String>,
+    pub content_field: Option<String>,
+    pub metadata_field: Option<String>,
+    pub gzip: Option<bool>,
+}
+
+impl From<PostJsonlOptions> for post::JsonlOptions {
+    fn from(options: PostJsonlOptions) -> Self {
+        post::JsonlOptions {
+            id_field: options.id_field,
+            title_field: options.title_field,
+            content_field: options.content_field,
+            metadata_field: options.metadata_field,
+            gzip: options.gzip,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueJsonlOptions {
+    pub issue_id_field: Option<String>,
+    pub operation_field: Option<String>,
+    pub phenomenon_field: Option<String>,
+    pub expected_behavior_field: Option<String>,
+    pub actual_behavior_field: Option<String>,
+    pub metadata_field: Option<String>,
+    pub gzip: Option<bool>,
+}
+
+impl From<IssueJsonlOptions> for issue::JsonlOptions {
+    fn from(options: IssueJsonlOptions) -> Self {
+        issue::JsonlOptions {
+            issue_id_field: options.issue_id_field,
+            operation_field: options.operation_field,
+            phenomenon_field: options.phenomenon_field,
+            expected_behavior_field: options.expected_behavior_field,
+            actual_behavior_field: options.actual_behavior_field,
+            metadata_field: options.metadata_field,
+            gzip: options.gzip,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DbColumns {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub metadata: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DbOptions {
+    pub url: String,
+    pub table: Option<String>,
+    pub query: Option<String>,
+    pub columns: Option<DbColumns>,
+}
+
+impl From<DbOptions> for post::DbOptions {
+    fn from(options: DbOptions) -> Self {
+        post::DbOptions {
+            url: options.url,
+            table: options.table,
+            query: options.query,
+            columns: options.columns.map(|columns| post::DbColumns {
+                id: columns.id,
+                title: columns.title,
+                content: columns.content,
+                metadata: columns.metadata,
+            }),
+        }
+    }
+}
+
+/// Parses an optional options argument, `NULL` stands for the defaults.
+pub fn parse_options<T: DeserializeOwned + Default>(input: Option<&str>) -> FfiResult<T> {
+    match input {
+        Some(input) => parse(input, "options"),
+        None => Ok(T::default()),
+    }
+}
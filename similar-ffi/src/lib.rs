@@ -0,0 +1,268 @@
+#![deny(clippy::all)]
+#![allow(clippy::missing_safety_doc)]
+//! The C API of the similarity search, for the services which can't load the Node.js module.
+//! The header is generated into `include/find_similar.h` by the build script.
+//!
+//! Ownership rules:
+//!
+//! - The stores are opaque handles created by `fs_*_new`, `fs_*_load*`, `fs_*_open` or
+//!   `fs_*_from_db`, and must be released exactly once with the matching `fs_*_free`. A handle
+//!   may be used from multiple threads at the same time.
+//! - Strings passed in are borrowed for the duration of the call, they must be NUL-terminated
+//!   UTF-8. Structured inputs, such as posts, records, options and filters, are JSON documents
+//!   in the same shape as the objects of the Node.js bindings.
+//! - Strings passed out through `char **` parameters are owned by the caller and must be released
+//!   with [fs_string_free].
+//! - Every fallible function returns an [FsStatus]. When it isn't `FS_STATUS_OK` and `error` is
+//!   not `NULL`, `*error` is set to a message describing the failure. Panics are caught and
+//!   reported as `FS_STATUS_PANIC`, they never unwind into the caller.
+
+use std::{
+    any::Any,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+mod issue;
+mod json;
+mod post;
+
+/// The outcome of a call, the errors of the core crate keep their kinds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsStatus {
+    Ok = 0,
+    /// An argument is invalid, such as a `NULL` pointer, a string which isn't UTF-8, malformed
+    /// JSON or a source without text.
+    InvalidArgument = 1,
+    /// A metadata filter expression is malformed.
+    InvalidFilter = 2,
+    /// A snapshot file is corrupted or written by a newer version.
+    InvalidSnapshot = 3,
+    Io = 4,
+    Csv = 5,
+    Json = 6,
+    Database = 7,
+    /// The store is running and queueing as many queries as it allows.
+    Overloaded = 8,
+    /// The call panicked, which is a bug of this library.
+    Panic = 99,
+}
+
+/// An error on its way to the caller.
+#[derive(Debug)]
+pub(crate) struct FfiError {
+    status: FsStatus,
+    message: String,
+}
+
+pub(crate) type FfiResult<T> = Result<T, FfiError>;
+
+impl FfiError {
+    pub(crate) fn invalid_argument(message: impl Into<String>) -> Self {
+        FfiError {
+            status: FsStatus::InvalidArgument,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<similar_core::Error> for FfiError {
+    fn from(e: similar_core::Error) -> Self {
+        use similar_core::Error;
+
+        let status = match &e {
+            Error::InvalidArgument(_) => FsStatus::InvalidArgument,
+            Error::InvalidFilter(_) => FsStatus::InvalidFilter,
+            Error::InvalidSnapshot(_) => FsStatus::InvalidSnapshot,
+            Error::Io(..) => FsStatus::Io,
+            Error::Csv(..) => FsStatus::Csv,
+            Error::Json(..) => FsStatus::Json,
+            Error::Database(..) => FsStatus::Database,
+            Error::Overloaded(_) => FsStatus::Overloaded,
+            Error::Panicked(_) => FsStatus::Panic,
+        };
+
+        FfiError {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let reason = match payload.downcast::<String>() {
+        Ok(reason) => *reason,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(reason) => reason.to_string(),
+            Err(_) => "unknown reason".to_string(),
+        },
+    };
+
+    format!("Panicked: {}", reason)
+}
+
+fn into_c_string(value: String) -> CString {
+    // JSON output never holds a raw NUL, but messages might quote the input
+    CString::new(value).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|byte| *byte != 0);
+        CString::new(bytes).unwrap_or_default()
+    })
+}
+
+/// Runs the body of an exported function, turning its errors and panics into a status and an
+/// optional message.
+pub(crate) unsafe fn call(error: *mut *mut c_char, f: impl FnOnce() -> FfiResult<()>) -> FsStatus {
+    if !error.is_null() {
+        *error = ptr::null_mut();
+    }
+
+    let e = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return FsStatus::Ok,
+        Ok(Err(e)) => e,
+        Err(payload) => FfiError {
+            status: FsStatus::Panic,
+            message: panic_message(payload),
+        },
+    };
+
+    if !error.is_null() {
+        *error = into_c_string(e.message).into_raw();
+    }
+
+    e.status
+}
+
+/// Borrows a required string argument.
+pub(crate) unsafe fn read_str<'a>(value: *const c_char, name: &str) -> FfiResult<&'a str> {
+    if value.is_null() {
+        return Err(FfiError::invalid_argument(format!(
+            "{} must not be NULL",
+            name
+        )));
+    }
+
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| FfiError::invalid_argument(format!("{} is not valid UTF-8", name)))
+}
+
+/// Borrows an optional string argument, `NULL` stands for `None`.
+pub(crate) unsafe fn read_optional_str<'a>(
+    value: *const c_char,
+    name: &str,
+) -> FfiResult<Option<&'a str>> {
+    if value.is_null() {
+        Ok(None)
+    } else {
+        read_str(value, name).map(Some)
+    }
+}
+
+/// Borrows the store behind a handle.
+pub(crate) unsafe fn read_handle<'a, T>(handle: *const T, name: &str) -> FfiResult<&'a T> {
+    handle
+        .as_ref()
+        .ok_or_else(|| FfiError::invalid_argument(format!("{} must not be NULL", name)))
+}
+
+/// Writes a value to an out parameter.
+pub(crate) unsafe fn write_out<T>(out: *mut T, value: T, name: &str) -> FfiResult<()> {
+    if out.is_null() {
+        return Err(FfiError::invalid_argument(format!(
+            "{} must not be NULL",
+            name
+        )));
+    }
+
+    *out = value;
+    Ok(())
+}
+
+/// Passes the ownership of a string to the caller through an out parameter.
+pub(crate) unsafe fn write_string(
+    out: *mut *mut c_char,
+    value: String,
+    name: &str,
+) -> FfiResult<()> {
+    if out.is_null() {
+        return Err(FfiError::invalid_argument(format!(
+            "{} must not be NULL",
+            name
+        )));
+    }
+
+    *out = into_c_string(value).into_raw();
+    Ok(())
+}
+
+/// Runs an async loader of the core crate to completion on the current thread.
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> FfiResult<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| FfiError {
+            status: FsStatus::Io,
+            message: format!("Cannot start the runtime: {}", e),
+        })?;
+
+    Ok(runtime.block_on(future))
+}
+
+/// Releases a string returned by this library, `NULL` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn fs_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Takes the ownership of a string returned by the library.
+    pub(crate) unsafe fn take_string(value: *mut c_char) -> String {
+        assert!(!value.is_null());
+        let string = CStr::from_ptr(value).to_str().unwrap().to_string();
+        fs_string_free(value);
+        string
+    }
+
+    #[test]
+    fn test_call() {
+        let mut error: *mut c_char = ptr::null_mut();
+
+        let status = unsafe { call(&mut error, || Ok(())) };
+        assert_eq!(status, FsStatus::Ok);
+        assert!(error.is_null());
+
+        let status = unsafe {
+            call(&mut error, || {
+                Err(
+                    similar_core::Error::InvalidFilter("unknown operator '$foo'".to_string())
+                        .into(),
+                )
+            })
+        };
+        assert_eq!(status, FsStatus::InvalidFilter);
+        assert_eq!(
+            unsafe { take_string(error) },
+            "Invalid filter: unknown operator '$foo'"
+        );
+
+        let status = unsafe { call(&mut error, || panic!("boom")) };
+        assert_eq!(status, FsStatus::Panic);
+        assert_eq!(unsafe { take_string(error) }, "Panicked: boom");
+
+        // the message is optional
+        let status = unsafe {
+            call(ptr::null_mut(), || {
+                read_str(ptr::null(), "path").map(|_| ())
+            })
+        };
+        assert_eq!(status, FsStatus::InvalidArgument);
+    }
+}
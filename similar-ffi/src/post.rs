@@ -0,0 +1,433 @@
+use std::ffi::c_char;
+
+use similar_core::post;
+
+use crate::{
+    block_on, call,
+    json::{self, CsvOptions, DbOptions, PostJsonlOptions},
+    read_handle, read_optional_str, read_str, write_out, write_string, FsStatus,
+};
+
+/// A handle of a post store.
+pub struct FsPostStore {
+    inner: post::PostStore,
+}
+
+fn into_handle(inner: post::PostStore) -> *mut FsPostStore {
+    Box::into_raw(Box::new(FsPostStore { inner }))
+}
+
+/// Creates an empty post store.
+#[no_mangle]
+pub extern "C" fn fs_post_store_new() -> *mut FsPostStore {
+    into_handle(post::PostStore::new())
+}
+
+/// Releases a post store, `NULL` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_free(store: *mut FsPostStore) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// The generation of the current snapshot, increased by every write. Returns 0 for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_generation(store: *const FsPostStore) -> u64 {
+    store.as_ref().map_or(0, |store| store.inner.generation())
+}
+
+/// How many posts are in the current snapshot. Returns 0 for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_len(store: *const FsPostStore) -> usize {
+    store
+        .as_ref()
+        .map_or(0, |store| store.inner.snapshot().len())
+}
+
+/// Loads a post store from a binary snapshot file into `*out`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_load(
+    path: *const c_char,
+    out: *mut *mut FsPostStore,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = post::PostStore::load(read_str(path, "path")?)?;
+        write_out(out, into_handle(store), "out")
+    })
+}
+
+/// Opens a binary snapshot file in read-only mode by memory mapping it, the store is written
+/// to `*out`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_open(
+    path: *const c_char,
+    out: *mut *mut FsPostStore,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = post::PostStore::open(read_str(path, "path")?)?;
+        write_out(out, into_handle(store), "out")
+    })
+}
+
+/// Loads a post store from a database into `*out`. `options_json` is a `DbOptions` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_from_db(
+    options_json: *const c_char,
+    out: *mut *mut FsPostStore,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let options: DbOptions = json::parse(read_str(options_json, "options_json")?, "options")?;
+        let store = block_on(post::PostStore::from_db(&options.into()))??;
+        write_out(out, into_handle(store), "out")
+    })
+}
+
+/// Saves the posts of the current snapshot to a binary snapshot file.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_save(
+    store: *const FsPostStore,
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        Ok(store.inner.save(read_str(path, "path")?)?)
+    })
+}
+
+/// Replaces all the posts in the store. `posts_json` is an array of `PostData` objects.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_preload(
+    store: *const FsPostStore,
+    posts_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        store
+            .inner
+            .preload(json::parse_posts(read_str(posts_json, "posts_json")?)?);
+        Ok(())
+    })
+}
+
+/// Adds posts to the store, keeping the existing ones.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_append(
+    store: *const FsPostStore,
+    posts_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        store
+            .inner
+            .append(json::parse_posts(read_str(posts_json, "posts_json")?)?);
+        Ok(())
+    })
+}
+
+/// Adds posts to the store, replacing the stored ones with the same IDs.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_upsert(
+    store: *const FsPostStore,
+    posts_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        store
+            .inner
+            .upsert(json::parse_posts(read_str(posts_json, "posts_json")?)?);
+        Ok(())
+    })
+}
+
+/// Removes the posts whose IDs are in `ids_json`, an array of strings. How many have been
+/// removed is written to `*removed` unless it's `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_remove(
+    store: *const FsPostStore,
+    ids_json: *const c_char,
+    removed: *mut usize,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let ids: Vec<String> = json::parse(read_str(ids_json, "ids_json")?, "ids")?;
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let count = store.inner.remove(&ids);
+
+        if !removed.is_null() {
+            *removed = count;
+        }
+        Ok(())
+    })
+}
+
+/// Finds the `top_n` posts most similar to `source_json`, a `PostData` object. When
+/// `filter_json` is not `NULL`, only the posts whose metadata satisfy it are scored. The result
+/// is written to `*result_json` as a `FindTopNResult` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_find_similar_posts(
+    store: *const FsPostStore,
+    source_json: *const c_char,
+    top_n: usize,
+    filter_json: *const c_char,
+    result_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let source: json::PostData = json::parse(read_str(source_json, "source_json")?, "source")?;
+        let filter = json::parse_filter(read_optional_str(filter_json, "filter_json")?)?;
+        let result = store
+            .inner
+            .find_similar_posts(&source.into(), top_n, filter.as_ref())?;
+
+        write_string(
+            result_json,
+            json::find_top_n_result_to_json(&result),
+            "result_json",
+        )
+    })
+}
+
+/// Replaces the posts in the store with the ones in a CSV file. `options_json` is an optional
+/// `CsvOptions` object. The `LoadReport` is written to `*report_json` unless it's `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_load_csv(
+    store: *const FsPostStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    report_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: CsvOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+        let report = store
+            .inner
+            .load_csv(read_str(path, "path")?, &options.into_core()?)?;
+
+        if report_json.is_null() {
+            return Ok(());
+        }
+        write_string(
+            report_json,
+            json::load_report_to_json(&report),
+            "report_json",
+        )
+    })
+}
+
+/// Writes the posts of the current snapshot to a CSV file. `options_json` is an optional
+/// `CsvOptions` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_dump_csv(
+    store: *const FsPostStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: CsvOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+
+        Ok(store
+            .inner
+            .dump_csv(read_str(path, "path")?, &options.into_core()?)?)
+    })
+}
+
+/// Replaces the posts in the store with the ones in a JSON Lines file. `options_json` is an
+/// optional `JsonlOptions` object. The `LoadReport` is written to `*report_json` unless it's
+/// `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_load_jsonl(
+    store: *const FsPostStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    report_json: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: PostJsonlOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+        let report = store
+            .inner
+            .load_jsonl(read_str(path, "path")?, &options.into())?;
+
+        if report_json.is_null() {
+            return Ok(());
+        }
+        write_string(
+            report_json,
+            json::load_report_to_json(&report),
+            "report_json",
+        )
+    })
+}
+
+/// Writes the posts of the current snapshot to a JSON Lines file. `options_json` is an
+/// optional `JsonlOptions` object.
+#[no_mangle]
+pub unsafe extern "C" fn fs_post_store_dump_jsonl(
+    store: *const FsPostStore,
+    path: *const c_char,
+    options_json: *const c_char,
+    error: *mut *mut c_char,
+) -> FsStatus {
+    call(error, || {
+        let store = read_handle(store, "store")?;
+        let options: PostJsonlOptions =
+            json::parse_options(read_optional_str(options_json, "options_json")?)?;
+
+        Ok(store
+            .inner
+            .dump_jsonl(read_str(path, "path")?, &options.into())?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, ptr};
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::tests::take_string;
+
+    #[test]
+    fn test_post_store() {
+        let posts = CString::new(
+            r#"[
+                {"id": "1", "title": "Deno.kill on windows", "content": "SIGINT is not supported on Windows", "metadata": {"category": "bug"}},
+                {"id": "2", "title": "How to embed V8", "content": "Embedding V8 into a Rust program"}
+            ]"#,
+        )
+        .unwrap();
+        let source = CString::new(
+            r#"{"title": "Deno.kill not working on windows", "content": "SIGINT on Windows"}"#,
+        )
+        .unwrap();
+        let filter = CString::new(r#"{"category": "bug"}"#).unwrap();
+        let mut result: *mut c_char = ptr::null_mut();
+        let mut error: *mut c_char = ptr::null_mut();
+
+        unsafe {
+            let store = fs_post_store_new();
+            assert_eq!(
+                fs_post_store_preload(store, posts.as_ptr(), &mut error),
+                FsStatus::Ok
+            );
+            assert_eq!(fs_post_store_len(store), 2);
+            assert_eq!(fs_post_store_generation(store), 1);
+
+            let status = fs_post_store_find_similar_posts(
+                store,
+                source.as_ptr(),
+                5,
+                filter.as_ptr(),
+                &mut result,
+                &mut error,
+            );
+            assert_eq!(status, FsStatus::Ok);
+
+            let parsed: Value = serde_json::from_str(&take_string(result)).unwrap();
+            let expected = post::find_similar_posts(
+                &post::PostData {
+                    title: "Deno.kill not working on windows".to_string(),
+                    content: "SIGINT on Windows".to_string(),
+                    ..Default::default()
+                },
+                vec![post::PostData {
+                    title: "Deno.kill on windows".to_string(),
+                    content: "SIGINT is not supported on Windows".to_string(),
+                    ..Default::default()
+                }],
+                5,
+            )
+            .unwrap();
+
+            assert_eq!(parsed["matches"].as_array().unwrap().len(), 1);
+            assert_eq!(parsed["matches"][0]["target"]["id"], "1");
+            assert_eq!(
+                parsed["matches"][0]["target"]["metadata"]["category"],
+                "bug"
+            );
+            assert_eq!(
+                parsed["matches"][0]["score"].as_f64().unwrap().to_bits(),
+                expected.matches[0].score.to_bits()
+            );
+            assert_eq!(parsed["generation"], 1);
+
+            let ids = CString::new(r#"["2", "3"]"#).unwrap();
+            let mut removed = 0;
+            assert_eq!(
+                fs_post_store_remove(store, ids.as_ptr(), &mut removed, &mut error),
+                FsStatus::Ok
+            );
+            assert_eq!(removed, 1);
+
+            let bad = CString::new(r#"{"title": "", "content": ""}"#).unwrap();
+            let status = fs_post_store_find_similar_posts(
+                store,
+                bad.as_ptr(),
+                5,
+                ptr::null(),
+                &mut result,
+                &mut error,
+            );
+            assert_eq!(status, FsStatus::InvalidArgument);
+            assert_eq!(take_string(error), "source is invalid");
+
+            let status = fs_post_store_preload(store, ptr::null(), &mut error);
+            assert_eq!(status, FsStatus::InvalidArgument);
+            assert_eq!(take_string(error), "posts_json must not be NULL");
+
+            fs_post_store_free(store);
+        }
+    }
+
+    #[test]
+    fn test_post_store_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("similar-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = CString::new(dir.join("posts.snapshot").to_str().unwrap()).unwrap();
+        let posts =
+            CString::new(r#"[{"title": "Deno.kill on windows", "content": "SIGINT"}]"#).unwrap();
+        let mut loaded: *mut FsPostStore = ptr::null_mut();
+        let mut error: *mut c_char = ptr::null_mut();
+
+        unsafe {
+            let store = fs_post_store_new();
+            fs_post_store_append(store, posts.as_ptr(), &mut error);
+            assert_eq!(
+                fs_post_store_save(store, path.as_ptr(), &mut error),
+                FsStatus::Ok
+            );
+            assert_eq!(
+                fs_post_store_open(path.as_ptr(), &mut loaded, &mut error),
+                FsStatus::Ok
+            );
+            assert_eq!(fs_post_store_len(loaded), 1);
+            fs_post_store_free(loaded);
+            fs_post_store_free(store);
+
+            let missing = CString::new(dir.join("missing").to_str().unwrap()).unwrap();
+            let status = fs_post_store_load(missing.as_ptr(), &mut loaded, &mut error);
+            assert_eq!(status, FsStatus::Io);
+            assert!(take_string(error).starts_with("Cannot read snapshot file"));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+//! The helpers shared by the Node.js bindings of the stores.
+//!
+//! [spawn_task] runs a query of a store on the threads of its [QueryPool] and settles a promise
+//! with its result from there, so neither the wait for a slot nor the query holds a thread of
+//! the libuv pool, and the query can be cancelled with an `AbortSignal`.
+
+use std::{
+    ptr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use napi::{
+    bindgen_prelude::ToNapiValue, sys, Env, Error, JsBoolean, JsFunction, JsObject, NapiValue,
+    Result, Status, Task,
+};
+use similar_core::pool::QueryPool;
+
+/// Converts an error of the core crate to a JavaScript error with the same message.
+pub fn to_napi_error(e: similar_core::Error) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// Called once when the signal a query was spawned with is aborted.
+type OnAbort = Option<Box<dyn FnOnce() + Send>>;
+
+/// Runs `task` on the threads of `pool` once it may run, and settles the returned promise with
+/// its result, so neither the wait for a slot nor the query holds a thread of the libuv pool.
+/// When `signal` is given and aborted, the promise is rejected with an `AbortError`, and the
+/// task is skipped if it hasn't started yet.
+pub fn spawn_task<T>(
+    env: Env,
+    pool: &QueryPool,
+    mut task: T,
+    signal: Option<JsObject>,
+) -> Result<JsObject>
+where
+    T: Task + Send + 'static,
+    T::JsValue: ToNapiValue,
+{
+    let (deferred, promise) = env.create_deferred()?;
+    let pending = Arc::new(Mutex::new(Some(deferred)));
+    let take = |pending: &Mutex<Option<_>>| {
+        pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    };
+
+    if let Some(signal) = signal {
+        let pending = pending.clone();
+        on_abort(env, signal, move || {
+            if let Some(deferred) = take(&pending) {
+                deferred.reject(Error::new(Status::Cancelled, "AbortError".to_string()));
+            }
+        })?;
+    }
+
+    pool.spawn(
+        {
+            let pending = pending.clone();
+            move || {
+                let aborted = pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_none();
+                if aborted {
+                    return Ok(None);
+                }
+                Ok(Some(task.compute().map(|output| (task, output))))
+            }
+        },
+        move |result| {
+            let result = match result.map_err(to_napi_error) {
+                Ok(Some(computed)) => computed,
+                // skipped, the promise is already rejected
+                Ok(None) => return,
+                Err(err) => Err(err),
+            };
+            if let Some(deferred) = take(&pending) {
+                match result {
+                    Ok((mut task, output)) => {
+                        deferred.resolve(move |env| task.resolve(env, output))
+                    }
+                    Err(err) => deferred.reject(err),
+                }
+            }
+        },
+    );
+
+    Ok(promise)
+}
+
+/// Calls `callback` once when `signal` is aborted, or right away if it already is. The listener
+/// is added with `addEventListener`, so the signal can be shared by several queries or watched
+/// by the caller too.
+fn on_abort(env: Env, signal: JsObject, callback: impl FnOnce() + Send + 'static) -> Result<()> {
+    let aborted: JsBoolean = signal.get_named_property("aborted")?;
+    if aborted.get_value()? {
+        callback();
+        return Ok(());
+    }
+
+    // the listener is bound to an object owning the callback, which is dropped along with it
+    let mut owner = env.create_object()?;
+    env.wrap::<OnAbort>(&mut owner, Some(Box::new(callback)))?;
+    let listener = env.create_function("onabort", abort)?.coerce_to_object()?;
+    let bind: JsFunction = listener.get_named_property("bind")?;
+    let listener = bind.call(Some(&listener), &[owner])?;
+
+    let mut options = env.create_object()?;
+    options.set_named_property("once", env.get_boolean(true)?)?;
+    let add_event_listener: JsFunction = signal.get_named_property("addEventListener")?;
+    add_event_listener.call(
+        Some(&signal),
+        &[
+            env.create_string("abort")?.into_unknown(),
+            listener,
+            options.into_unknown(),
+        ],
+    )?;
+    Ok(())
+}
+
+extern "C" fn abort(env: sys::napi_env, callback_info: sys::napi_callback_info) -> sys::napi_value {
+    let mut this = ptr::null_mut();
+    let status = unsafe {
+        sys::napi_get_cb_info(
+            env,
+            callback_info,
+            &mut 0,
+            ptr::null_mut(),
+            &mut this,
+            ptr::null_mut(),
+        )
+    };
+
+    if status == sys::Status::napi_ok {
+        // SAFETY: `this` is the object `on_abort` bound the listener to
+        let owner = unsafe { JsObject::from_raw_unchecked(env, this) };
+        let env = unsafe { Env::from_raw(env) };
+        if let Some(callback) = env
+            .unwrap::<OnAbort>(&owner)
+            .ok()
+            .and_then(|callback| callback.take())
+        {
+            callback();
+        }
+    }
+    ptr::null_mut()
+}
@@ -0,0 +1,425 @@
+use std::path::PathBuf;
+
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use serde_json::{Map, Value};
+use similar_core::issue::{self, DbOptions, JsonlOptions};
+
+use crate::{block_on, json, parse_filter, to_py_err, LoadReport};
+
+#[pyclass(module = "find_similar", from_py_object)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IssueFeatures {
+    #[pyo3(get, set)]
+    pub operation: Option<String>,
+    #[pyo3(get, set)]
+    pub phenomenon: Option<String>,
+    #[pyo3(get, set)]
+    pub expected_behavior: Option<String>,
+    #[pyo3(get, set)]
+    pub actual_behavior: Option<String>,
+}
+
+#[pymethods]
+impl IssueFeatures {
+    #[new]
+    #[pyo3(signature = (operation=None, phenomenon=None, expected_behavior=None, actual_behavior=None))]
+    fn new(
+        operation: Option<String>,
+        phenomenon: Option<String>,
+        expected_behavior: Option<String>,
+        actual_behavior: Option<String>,
+    ) -> Self {
+        IssueFeatures {
+            operation,
+            phenomenon,
+            expected_behavior,
+            actual_behavior,
+        }
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "IssueFeatures(operation={:?}, phenomenon={:?}, expected_behavior={:?}, \
+             actual_behavior={:?})",
+            self.operation, self.phenomenon, self.expected_behavior, self.actual_behavior
+        )
+    }
+}
+
+impl From<IssueFeatures> for issue::IssueFeatures {
+    fn from(features: IssueFeatures) -> Self {
+        issue::IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: None,
+        }
+    }
+}
+
+impl From<issue::IssueFeatures> for IssueFeatures {
+    fn from(features: issue::IssueFeatures) -> Self {
+        IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+        }
+    }
+}
+
+fn metadata_to_py<'py>(
+    py: Python<'py>,
+    metadata: &Option<Map<String, Value>>,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    metadata
+        .as_ref()
+        .map(|metadata| json::map_to_py(py, metadata))
+        .transpose()
+}
+
+#[pyclass(module = "find_similar", skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct IssueFeaturesRecord {
+    #[pyo3(get, set)]
+    pub issue_id: String,
+    #[pyo3(get, set)]
+    pub features: IssueFeatures,
+    /// Structured attributes of the issue, such as `component`, `labels` and `state`, which can be
+    /// used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+}
+
+#[pymethods]
+impl IssueFeaturesRecord {
+    #[new]
+    #[pyo3(signature = (issue_id, features, metadata=None))]
+    fn new(
+        issue_id: String,
+        features: &Bound<'_, PyAny>,
+        metadata: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        Ok(IssueFeaturesRecord {
+            issue_id,
+            features: extract_features(features)?.into(),
+            metadata: metadata.map(json::map_from_py).transpose()?,
+        })
+    }
+
+    #[getter]
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        metadata_to_py(py, &self.metadata)
+    }
+
+    #[setter]
+    fn set_metadata(&mut self, metadata: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.metadata = metadata.map(json::map_from_py).transpose()?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "IssueFeaturesRecord(issue_id={:?}, features={})",
+            self.issue_id,
+            self.features.__repr__()
+        )
+    }
+}
+
+impl From<IssueFeaturesRecord> for issue::IssueFeaturesRecord {
+    fn from(record: IssueFeaturesRecord) -> Self {
+        issue::IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+impl From<issue::IssueFeaturesRecord> for IssueFeaturesRecord {
+    fn from(record: issue::IssueFeaturesRecord) -> Self {
+        IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+#[pyclass(module = "find_similar", frozen, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct SimilarIssueFeaturesRecord {
+    #[pyo3(get)]
+    pub issue_id: String,
+    #[pyo3(get)]
+    pub features: IssueFeatures,
+    pub metadata: Option<Map<String, Value>>,
+    /// Similarity score `0 - 1`, higher is more similar.
+    #[pyo3(get)]
+    pub score: f64,
+    /// The generation of the store snapshot the query was run against.
+    #[pyo3(get)]
+    pub generation: u64,
+}
+
+#[pymethods]
+impl SimilarIssueFeaturesRecord {
+    #[getter]
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        metadata_to_py(py, &self.metadata)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SimilarIssueFeaturesRecord(issue_id={:?}, score={})",
+            self.issue_id, self.score
+        )
+    }
+}
+
+impl From<issue::SimilarIssueFeaturesRecord> for SimilarIssueFeaturesRecord {
+    fn from(record: issue::SimilarIssueFeaturesRecord) -> Self {
+        SimilarIssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+            score: record.score,
+            generation: record.generation,
+        }
+    }
+}
+
+fn optional_string(dict: &Bound<'_, PyDict>, key: &str) -> PyResult<Option<String>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+        _ => Ok(None),
+    }
+}
+
+/// Extracts issue features given either as an [IssueFeatures] or as a `dict` with the same
+/// keys.
+pub fn extract_features(obj: &Bound<'_, PyAny>) -> PyResult<issue::IssueFeatures> {
+    if let Ok(features) = obj.cast::<IssueFeatures>() {
+        return Ok(features.borrow().clone().into());
+    }
+
+    let dict = obj
+        .cast::<PyDict>()
+        .map_err(|_| PyTypeError::new_err("Issue features must be an IssueFeatures or a dict"))?;
+
+    Ok(issue::IssueFeatures {
+        operation: optional_string(dict, "operation")?,
+        phenomenon: optional_string(dict, "phenomenon")?,
+        expected_behavior: optional_string(dict, "expected_behavior")?,
+        actual_behavior: optional_string(dict, "actual_behavior")?,
+        embedding: None,
+    })
+}
+
+/// Extracts a record given either as an [IssueFeaturesRecord] or as a `dict` with the same
+/// keys.
+pub fn extract_record(obj: &Bound<'_, PyAny>) -> PyResult<issue::IssueFeaturesRecord> {
+    if let Ok(record) = obj.cast::<IssueFeaturesRecord>() {
+        return Ok(record.borrow().clone().into());
+    }
+
+    let dict = obj
+        .cast::<PyDict>()
+        .map_err(|_| PyTypeError::new_err("A record must be an IssueFeaturesRecord or a dict"))?;
+    let issue_id = optional_string(dict, "issue_id")?
+        .ok_or_else(|| PyValueError::new_err("The record is missing the 'issue_id' key"))?;
+    let features = match dict.get_item("features")? {
+        Some(features) => extract_features(&features)?,
+        None => {
+            return Err(PyValueError::new_err(
+                "The record is missing the 'features' key",
+            ))
+        }
+    };
+    let metadata = match dict.get_item("metadata")? {
+        Some(value) if !value.is_none() => Some(json::map_from_py(value.cast::<PyDict>()?)?),
+        _ => None,
+    };
+
+    Ok(issue::IssueFeaturesRecord {
+        issue_id,
+        features,
+        metadata,
+    })
+}
+
+#[pyclass(module = "find_similar", frozen)]
+#[derive(Default)]
+pub struct IssueFeatureStore {
+    inner: issue::IssueFeatureStore,
+}
+
+impl From<issue::IssueFeatureStore> for IssueFeatureStore {
+    fn from(inner: issue::IssueFeatureStore) -> Self {
+        IssueFeatureStore { inner }
+    }
+}
+
+#[pymethods]
+impl IssueFeatureStore {
+    #[new]
+    #[pyo3(signature = (records=None))]
+    fn new(records: Option<Vec<Bound<'_, PyAny>>>) -> PyResult<Self> {
+        let records = records
+            .unwrap_or_default()
+            .iter()
+            .map(extract_record)
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(issue::IssueFeatureStore::new(records).into())
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    #[getter]
+    fn generation(&self) -> u64 {
+        self.inner.generation()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.snapshot().map.len()
+    }
+
+    /// Adds a record to the store, replacing the stored one with the same issue ID.
+    fn set_record(&self, record: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner
+            .set_record(extract_record(record)?)
+            .map_err(to_py_err)
+    }
+
+    fn get_record(&self, issue_id: &str) -> Option<IssueFeaturesRecord> {
+        self.inner
+            .get_record(issue_id)
+            .map(IssueFeaturesRecord::from)
+    }
+
+    /// Removes the record with the issue ID, returning whether it existed.
+    fn remove_record(&self, issue_id: &str) -> bool {
+        self.inner.remove_record(issue_id)
+    }
+
+    /// Finds the `top_n` records most similar to the given `features`. When `filter` is given,
+    /// only the records whose metadata satisfy it are scored. The GIL is released during the
+    /// scan.
+    #[pyo3(signature = (features, top_n=5, filter=None))]
+    fn find_similar_records(
+        &self,
+        py: Python<'_>,
+        features: &Bound<'_, PyAny>,
+        top_n: usize,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Vec<SimilarIssueFeaturesRecord>> {
+        let features = extract_features(features)?;
+        let filter = parse_filter(filter)?;
+
+        py.detach(|| {
+            self.inner
+                .find_similar_records(&features, top_n, filter.as_ref())
+        })
+        .map(|matches| {
+            matches
+                .into_iter()
+                .map(SimilarIssueFeaturesRecord::from)
+                .collect()
+        })
+        .map_err(to_py_err)
+    }
+
+    /// Loads a store from a CSV file with `issue_id`, `operation`, `phenomenon`,
+    /// `expected_behavior` and `actual_behavior` columns.
+    #[staticmethod]
+    fn load_csv(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        py.detach(|| issue::IssueFeatureStore::load_csv(&path))
+            .map(IssueFeatureStore::from)
+            .map_err(to_py_err)
+    }
+
+    /// Writes the records of the current snapshot to a CSV file.
+    fn dump_csv(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        py.detach(|| self.inner.dump_csv(&path)).map_err(to_py_err)
+    }
+
+    /// Adds the records in a JSON Lines file to the store, which may be gzip-compressed. Records
+    /// with an existing issue ID replace the stored ones, malformed lines are skipped and
+    /// reported with their line numbers.
+    #[pyo3(signature = (path, issue_id_field=None, operation_field=None, phenomenon_field=None, expected_behavior_field=None, actual_behavior_field=None, metadata_field=None, gzip=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn load_jsonl(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        issue_id_field: Option<String>,
+        operation_field: Option<String>,
+        phenomenon_field: Option<String>,
+        expected_behavior_field: Option<String>,
+        actual_behavior_field: Option<String>,
+        metadata_field: Option<String>,
+        gzip: Option<bool>,
+    ) -> PyResult<LoadReport> {
+        let options = JsonlOptions {
+            issue_id_field,
+            operation_field,
+            phenomenon_field,
+            expected_behavior_field,
+            actual_behavior_field,
+            metadata_field,
+            gzip,
+        };
+
+        py.detach(|| self.inner.load_jsonl(&path, &options))
+            .map(LoadReport::from)
+            .map_err(to_py_err)
+    }
+
+    /// Writes the records of the current snapshot to a JSON Lines file.
+    #[pyo3(signature = (path, issue_id_field=None, operation_field=None, phenomenon_field=None, expected_behavior_field=None, actual_behavior_field=None, metadata_field=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn dump_jsonl(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        issue_id_field: Option<String>,
+        operation_field: Option<String>,
+        phenomenon_field: Option<String>,
+        expected_behavior_field: Option<String>,
+        actual_behavior_field: Option<String>,
+        metadata_field: Option<String>,
+    ) -> PyResult<()> {
+        let options = JsonlOptions {
+            issue_id_field,
+            operation_field,
+            phenomenon_field,
+            expected_behavior_field,
+            actual_behavior_field,
+            metadata_field,
+            gzip: None,
+        };
+
+        py.detach(|| self.inner.dump_jsonl(&path, &options))
+            .map_err(to_py_err)
+    }
+
+    /// Loads a store from a table of a MySQL, PostgreSQL or SQLite database, the scheme of `url`
+    /// selects the driver.
+    #[staticmethod]
+    fn from_db(py: Python<'_>, url: String, table: String) -> PyResult<Self> {
+        let options = DbOptions { url, table };
+
+        py.detach(|| block_on(issue::IssueFeatureStore::from_db(&options)))?
+            .map(IssueFeatureStore::from)
+            .map_err(to_py_err)
+    }
+}
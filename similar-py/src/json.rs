@@ -0,0 +1,142 @@
+//! Conversions between Python objects and JSON values, used for the metadata and the filters.
+
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple},
+    IntoPyObjectExt,
+};
+use serde_json::{Map, Number, Value};
+
+/// Converts a Python object made of `None`, `bool`, `int`, `float`, `str`, `list`, `tuple` and
+/// `dict` with string keys to a JSON value.
+pub fn from_py(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::Null)
+    } else if obj.is_instance_of::<PyBool>() {
+        Ok(Value::Bool(obj.extract()?))
+    } else if obj.is_instance_of::<PyInt>() {
+        if let Ok(value) = obj.extract::<i64>() {
+            Ok(Value::from(value))
+        } else if let Ok(value) = obj.extract::<u64>() {
+            Ok(Value::from(value))
+        } else {
+            Err(PyValueError::new_err(format!(
+                "Integer {} is out of the range of JSON numbers",
+                obj
+            )))
+        }
+    } else if obj.is_instance_of::<PyFloat>() {
+        let value: f64 = obj.extract()?;
+        Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err(format!("{} is not a valid JSON number", value)))
+    } else if let Ok(value) = obj.cast::<PyString>() {
+        Ok(Value::String(value.to_str()?.to_string()))
+    } else if let Ok(list) = obj.cast::<PyList>() {
+        list.iter().map(|item| from_py(&item)).collect()
+    } else if let Ok(tuple) = obj.cast::<PyTuple>() {
+        tuple.iter().map(|item| from_py(&item)).collect()
+    } else if let Ok(dict) = obj.cast::<PyDict>() {
+        Ok(Value::Object(map_from_py(dict)?))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Object of type {} cannot be converted to JSON",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+/// Converts a `dict` with string keys to a JSON object.
+pub fn map_from_py(dict: &Bound<'_, PyDict>) -> PyResult<Map<String, Value>> {
+    let mut map = Map::new();
+
+    for (key, value) in dict.iter() {
+        let key = key
+            .cast::<PyString>()
+            .map_err(|_| PyTypeError::new_err("The keys of a JSON object must be strings"))?;
+        map.insert(key.to_str()?.to_string(), from_py(&value)?);
+    }
+
+    Ok(map)
+}
+
+/// Converts a JSON value to the equivalent Python object.
+pub fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    match value {
+        Value::Null => Ok(py.None().into_bound(py)),
+        Value::Bool(value) => value.into_bound_py_any(py),
+        Value::Number(number) => {
+            if let Some(value) = number.as_i64() {
+                value.into_bound_py_any(py)
+            } else if let Some(value) = number.as_u64() {
+                value.into_bound_py_any(py)
+            } else {
+                number.as_f64().unwrap_or(f64::NAN).into_bound_py_any(py)
+            }
+        }
+        Value::String(value) => value.into_bound_py_any(py),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+
+            for item in items {
+                list.append(to_py(py, item)?)?;
+            }
+
+            Ok(list.into_any())
+        }
+        Value::Object(map) => Ok(map_to_py(py, map)?.into_any()),
+    }
+}
+
+/// Converts a JSON object to a `dict`.
+pub fn map_to_py<'py>(py: Python<'py>, map: &Map<String, Value>) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+
+    for (key, value) in map {
+        dict.set_item(key, to_py(py, value)?)?;
+    }
+
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        Python::initialize();
+        Python::attach(|py| {
+            let value = json!({
+                "category": "bug",
+                "tags": ["windows", "signal"],
+                "votes": 3,
+                "ratio": 0.5,
+                "closed": false,
+                "assignee": null,
+            });
+            let obj = to_py(py, &value).unwrap();
+
+            assert!(obj.is_instance_of::<PyDict>());
+            assert_eq!(from_py(&obj).unwrap(), value);
+
+            // booleans are ints in Python, they must stay booleans
+            let obj = true.into_bound_py_any(py).unwrap();
+            assert_eq!(from_py(&obj).unwrap(), json!(true));
+
+            let obj = f64::NAN.into_bound_py_any(py).unwrap();
+            assert!(from_py(&obj)
+                .unwrap_err()
+                .is_instance_of::<PyValueError>(py));
+
+            let dict = PyDict::new(py);
+            dict.set_item(1, "one").unwrap();
+            assert!(from_py(dict.as_any())
+                .unwrap_err()
+                .is_instance_of::<PyTypeError>(py));
+        });
+    }
+}
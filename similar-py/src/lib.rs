@@ -0,0 +1,373 @@
+#![deny(clippy::all)]
+//! The Python bindings of the similarity search, built with maturin as the `find_similar`
+//! module. The scoring is done by `similar-core`, so the results are the same as the ones of
+//! the Node.js bindings. The GIL is released while scanning and loading.
+
+use pyo3::{
+    exceptions::{PyOSError, PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use similar_core::{filter::Filter, issue, load, post, Error};
+
+mod issue_store;
+mod json;
+mod post_store;
+
+use issue_store::{
+    extract_features, extract_record, IssueFeatureStore, IssueFeatures, IssueFeaturesRecord,
+    SimilarIssueFeaturesRecord,
+};
+use post_store::{extract_post, extract_posts, FindTopNResult, Match, PostData, PostStore};
+
+/// Converts an error of the core crate to a Python exception with the same message. Invalid
+/// arguments and filters raise `ValueError`, I/O failures raise `OSError`.
+pub(crate) fn to_py_err(e: Error) -> PyErr {
+    match e {
+        Error::InvalidArgument(_) | Error::InvalidFilter(_) => PyValueError::new_err(e.to_string()),
+        Error::Io(..) => PyOSError::new_err(e.to_string()),
+        _ => PyRuntimeError::new_err(e.to_string()),
+    }
+}
+
+/// Parses a metadata filter given as a `dict`, see [Filter] for the syntax.
+pub(crate) fn parse_filter(filter: Option<&Bound<'_, PyAny>>) -> PyResult<Option<Filter>> {
+    filter
+        .map(|filter| Filter::parse(&json::from_py(filter)?).map_err(to_py_err))
+        .transpose()
+}
+
+/// Runs an async loader of the core crate to completion on the current thread.
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> PyResult<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(format!("Cannot start the runtime: {}", e)))?;
+
+    Ok(runtime.block_on(future))
+}
+
+/// A malformed record skipped while loading a file.
+#[pyclass(module = "find_similar", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    /// The 1-based line number where the malformed record starts.
+    pub line: u64,
+    pub message: String,
+}
+
+#[pymethods]
+impl LoadError {
+    fn __repr__(&self) -> String {
+        format!("LoadError(line={}, message={:?})", self.line, self.message)
+    }
+}
+
+/// The outcome of loading a file into a store.
+#[pyclass(module = "find_similar", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    /// How many entries have been loaded into the store.
+    pub loaded: usize,
+    /// The records which have been skipped because they are malformed.
+    pub errors: Vec<LoadError>,
+}
+
+#[pymethods]
+impl LoadReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "LoadReport(loaded={}, errors=[{} items])",
+            self.loaded,
+            self.errors.len()
+        )
+    }
+}
+
+impl From<load::LoadReport> for LoadReport {
+    fn from(report: load::LoadReport) -> Self {
+        LoadReport {
+            loaded: report.loaded,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|e| LoadError {
+                    line: e.line,
+                    message: e.message,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Finds the `top_n` candidates most similar to `source`, scoring them one by one.
+#[pyfunction]
+fn find_similar_posts(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    candidates: Vec<Bound<'_, PyAny>>,
+    top_n: usize,
+) -> PyResult<FindTopNResult> {
+    let source = extract_post(source)?;
+    let candidates = extract_posts(candidates)?;
+
+    py.detach(|| post::find_similar_posts(&source, candidates, top_n))
+        .map(FindTopNResult::from)
+        .map_err(to_py_err)
+}
+
+/// Finds the `top_n` candidates most similar to `source`, scoring them in parallel. When
+/// `filter` is given, only the candidates whose metadata satisfy it are scored.
+#[pyfunction]
+#[pyo3(signature = (source, candidates, top_n, filter=None))]
+fn find_similar_posts_parallel(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    candidates: Vec<Bound<'_, PyAny>>,
+    top_n: usize,
+    filter: Option<&Bound<'_, PyAny>>,
+) -> PyResult<FindTopNResult> {
+    let source = extract_post(source)?;
+    let candidates = extract_posts(candidates)?;
+    let filter = parse_filter(filter)?;
+
+    py.detach(|| {
+        post::find_similar_posts_parallel(
+            &source,
+            candidates.par_iter().map(post::PostRef::from),
+            top_n,
+            filter.as_ref(),
+            None,
+        )
+    })
+    .map(FindTopNResult::from)
+    .map_err(to_py_err)
+}
+
+/// Returns the weights of the title and the content of `source`.
+#[pyfunction]
+fn get_weights(source: &Bound<'_, PyAny>) -> PyResult<(f64, f64)> {
+    post::get_weights(&extract_post(source)?).map_err(to_py_err)
+}
+
+/// Finds the `top_n` records most similar to `features`, scoring them in parallel. When
+/// `filter` is given, only the records whose metadata satisfy it are scored.
+#[pyfunction]
+#[pyo3(signature = (features, records, top_n=5, filter=None))]
+fn find_similar_records(
+    py: Python<'_>,
+    features: &Bound<'_, PyAny>,
+    records: Vec<Bound<'_, PyAny>>,
+    top_n: usize,
+    filter: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Vec<SimilarIssueFeaturesRecord>> {
+    let features = extract_features(features)?;
+    let records = records
+        .iter()
+        .map(extract_record)
+        .collect::<PyResult<Vec<_>>>()?;
+    let filter = parse_filter(filter)?;
+
+    py.detach(|| {
+        let store = issue::IssueFeatureStore::new(records);
+        issue::find_similar_records_in_parallel(
+            &features,
+            &store.snapshot(),
+            top_n,
+            filter.as_ref(),
+            &issue::ScoringOptions::default(),
+        )
+    })
+    .map(|matches| {
+        matches
+            .into_iter()
+            .map(SimilarIssueFeaturesRecord::from)
+            .collect()
+    })
+    .map_err(to_py_err)
+}
+
+/// Returns the weights of the operation, phenomenon, expected behavior and actual behavior
+/// features of `features`.
+#[pyfunction]
+fn get_feature_weights(features: &Bound<'_, PyAny>) -> PyResult<(f64, f64, f64, f64)> {
+    let weights = issue::get_feature_weights(&extract_features(features)?).map_err(to_py_err)?;
+
+    Ok((
+        weights.operation,
+        weights.phenomenon,
+        weights.expected_behavior,
+        weights.actual_behavior,
+    ))
+}
+
+#[pymodule]
+fn find_similar(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PostData>()?;
+    m.add_class::<Match>()?;
+    m.add_class::<FindTopNResult>()?;
+    m.add_class::<PostStore>()?;
+    m.add_class::<IssueFeatures>()?;
+    m.add_class::<IssueFeaturesRecord>()?;
+    m.add_class::<SimilarIssueFeaturesRecord>()?;
+    m.add_class::<IssueFeatureStore>()?;
+    m.add_class::<LoadError>()?;
+    m.add_class::<LoadReport>()?;
+    m.add_function(wrap_pyfunction!(find_similar_posts, m)?)?;
+    m.add_function(wrap_pyfunction!(find_similar_posts_parallel, m)?)?;
+    m.add_function(wrap_pyfunction!(get_weights, m)?)?;
+    m.add_function(wrap_pyfunction!(find_similar_records, m)?)?;
+    m.add_function(wrap_pyfunction!(get_feature_weights, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{types::PyDict, wrap_pymodule};
+
+    use super::*;
+
+    fn post(id: &str, title: &str, content: &str) -> post::PostData {
+        post::PostData {
+            id: Some(id.to_string()),
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: None,
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn test_scores_match_the_core() {
+        let source = post(
+            "0",
+            "Deno.kill not working on windows",
+            "SIGINT on Windows 11",
+        );
+        let candidates = vec![
+            post(
+                "1",
+                "Deno.kill on windows",
+                "SIGINT is not supported on Windows",
+            ),
+            post(
+                "2",
+                "denojs on termux like nodejs",
+                "A smooth download for Deno.js",
+            ),
+        ];
+        let expected = post::find_similar_posts(&source, candidates.clone(), 5).unwrap();
+
+        Python::initialize();
+        Python::attach(|py| {
+            let locals = PyDict::new(py);
+            locals
+                .set_item("find_similar", wrap_pymodule!(find_similar)(py))
+                .unwrap();
+            py.run(
+                cr#"
+source = {"title": "Deno.kill not working on windows", "content": "SIGINT on Windows 11"}
+candidates = [
+    find_similar.PostData("Deno.kill on windows", "SIGINT is not supported on Windows", id="1",
+                          metadata={"category": "bug"}),
+    find_similar.PostData("denojs on termux like nodejs", "A smooth download for Deno.js", id="2"),
+]
+result = find_similar.find_similar_posts(source, candidates, 5)
+parallel = find_similar.find_similar_posts_parallel(source, candidates, 5)
+
+store = find_similar.PostStore()
+store.preload(candidates)
+stored = store.find_similar_posts(source, 5, {"category": "bug"})
+"#,
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+
+            for name in ["result", "parallel"] {
+                let result = locals.get_item(name).unwrap().unwrap();
+                let result = result.cast::<FindTopNResult>().unwrap().get();
+
+                assert_eq!(result.matches.len(), expected.matches.len());
+                for (m, expected) in result.matches.iter().zip(&expected.matches) {
+                    assert_eq!(m.target.id, expected.target.id);
+                    assert_eq!(m.score.to_bits(), expected.score.to_bits());
+                }
+            }
+
+            let stored = locals.get_item("stored").unwrap().unwrap();
+            let stored = stored.cast::<FindTopNResult>().unwrap().get();
+            assert_eq!(stored.generation, Some(1));
+            assert_eq!(
+                stored.matches[0].score.to_bits(),
+                expected.matches[0].score.to_bits()
+            );
+        });
+    }
+
+    #[test]
+    fn test_issue_feature_store() {
+        Python::initialize();
+        Python::attach(|py| {
+            let locals = PyDict::new(py);
+            locals
+                .set_item("find_similar", wrap_pymodule!(find_similar)(py))
+                .unwrap();
+            py.run(
+                cr#"
+store = find_similar.IssueFeatureStore([
+    {"issue_id": "1", "features": {"operation": "click the button", "phenomenon": "app crashes"}},
+])
+store.set_record(find_similar.IssueFeaturesRecord(
+    "2",
+    find_similar.IssueFeatures(operation="open the settings", phenomenon="page is blank"),
+    {"component": "settings"},
+))
+features = {"operation": "click the button", "phenomenon": "the app crashes"}
+matches = store.find_similar_records(features)
+free = find_similar.find_similar_records(features, [store.get_record("1"), store.get_record("2")])
+weights = find_similar.get_feature_weights(features)
+record = store.get_record("2")
+removed = store.remove_record("2")
+size = len(store)
+
+try:
+    store.find_similar_records({})
+except ValueError as e:
+    error = str(e)
+"#,
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+
+            let get = |name: &str| locals.get_item(name).unwrap().unwrap();
+            let matches: Vec<Bound<SimilarIssueFeaturesRecord>> = get("matches").extract().unwrap();
+            let free: Vec<Bound<SimilarIssueFeaturesRecord>> = get("free").extract().unwrap();
+
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].get().issue_id, "1");
+            assert_eq!(matches[0].get().generation, 1);
+            assert_eq!(
+                matches[0].get().score.to_bits(),
+                free[0].get().score.to_bits()
+            );
+
+            let weights: (f64, f64, f64, f64) = get("weights").extract().unwrap();
+            assert_eq!(weights.2, 0.0);
+
+            let record = get("record");
+            let metadata = record.getattr("metadata").unwrap();
+            assert_eq!(
+                json::from_py(&metadata).unwrap(),
+                serde_json::json!({ "component": "settings" })
+            );
+            assert!(get("removed").extract::<bool>().unwrap());
+            assert_eq!(get("size").extract::<usize>().unwrap(), 1);
+            assert_eq!(
+                get("error").extract::<String>().unwrap(),
+                "source is invalid"
+            );
+        });
+    }
+}
@@ -0,0 +1,459 @@
+use std::path::PathBuf;
+
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use serde_json::{Map, Value};
+use similar_core::post::{self, CsvOptions, DbColumns, DbOptions, JsonlOptions};
+
+use crate::{block_on, json, parse_filter, to_py_err, LoadReport};
+
+#[pyclass(module = "find_similar", skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct PostData {
+    /// An optional identifier of the post, such as its primary key in the source data.
+    #[pyo3(get, set)]
+    pub id: Option<String>,
+    #[pyo3(get, set)]
+    pub title: String,
+    #[pyo3(get, set)]
+    pub content: String,
+    /// Structured attributes of the post, such as `category`, `tags`, `author`, `language` and
+    /// `createdAt`, which can be used to filter the candidates before scoring.
+    pub metadata: Option<Map<String, Value>>,
+}
+
+#[pymethods]
+impl PostData {
+    #[new]
+    #[pyo3(signature = (title, content, id=None, metadata=None))]
+    fn new(
+        title: String,
+        content: String,
+        id: Option<String>,
+        metadata: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        Ok(PostData {
+            id,
+            title,
+            content,
+            metadata: metadata.map(json::map_from_py).transpose()?,
+        })
+    }
+
+    #[getter]
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.metadata
+            .as_ref()
+            .map(|metadata| json::map_to_py(py, metadata))
+            .transpose()
+    }
+
+    #[setter]
+    fn set_metadata(&mut self, metadata: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        self.metadata = metadata.map(json::map_from_py).transpose()?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PostData(id={:?}, title={:?})",
+            self.id.as_deref().unwrap_or_default(),
+            self.title
+        )
+    }
+}
+
+impl From<PostData> for post::PostData {
+    fn from(post: PostData) -> Self {
+        post::PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: None,
+        }
+    }
+}
+
+impl From<post::PostData> for PostData {
+    fn from(post: post::PostData) -> Self {
+        PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+        }
+    }
+}
+
+/// Extracts a post given either as a [PostData] or as a `dict` with the same keys.
+pub fn extract_post(obj: &Bound<'_, PyAny>) -> PyResult<post::PostData> {
+    if let Ok(post) = obj.cast::<PostData>() {
+        return Ok(post.borrow().clone().into());
+    }
+
+    let dict = obj
+        .cast::<PyDict>()
+        .map_err(|_| PyTypeError::new_err("A post must be a PostData or a dict"))?;
+    let string = |key: &str| -> PyResult<Option<String>> {
+        match dict.get_item(key)? {
+            Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+            _ => Ok(None),
+        }
+    };
+    let metadata = match dict.get_item("metadata")? {
+        Some(value) if !value.is_none() => Some(json::map_from_py(value.cast::<PyDict>()?)?),
+        _ => None,
+    };
+
+    Ok(post::PostData {
+        id: string("id")?,
+        title: string("title")?
+            .ok_or_else(|| PyValueError::new_err("The post is missing the 'title' key"))?,
+        content: string("content")?
+            .ok_or_else(|| PyValueError::new_err("The post is missing the 'content' key"))?,
+        metadata,
+        embedding: None,
+    })
+}
+
+pub fn extract_posts(posts: Vec<Bound<'_, PyAny>>) -> PyResult<Vec<post::PostData>> {
+    posts.iter().map(extract_post).collect()
+}
+
+#[pyclass(module = "find_similar", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub target: PostData,
+    pub score: f64,
+}
+
+#[pymethods]
+impl Match {
+    fn __repr__(&self) -> String {
+        format!(
+            "Match(target={}, score={})",
+            self.target.__repr__(),
+            self.score
+        )
+    }
+}
+
+#[pyclass(module = "find_similar", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct FindTopNResult {
+    pub matches: Vec<Match>,
+    /// How much time is used for processing, in milliseconds.
+    pub process_time: u64,
+    /// The generation of the store snapshot the query was run against, only set when querying a
+    /// `PostStore`.
+    pub generation: Option<u64>,
+}
+
+impl From<post::FindTopNResult> for FindTopNResult {
+    fn from(result: post::FindTopNResult) -> Self {
+        FindTopNResult {
+            matches: result
+                .matches
+                .into_iter()
+                .map(|m| Match {
+                    target: m.target.into(),
+                    score: m.score,
+                })
+                .collect(),
+            process_time: result.process_time.as_millis() as u64,
+            generation: result.generation,
+        }
+    }
+}
+
+fn csv_options(
+    title_column: Option<String>,
+    content_column: Option<String>,
+    id_column: Option<String>,
+    delimiter: Option<String>,
+) -> PyResult<CsvOptions> {
+    let delimiter = match delimiter {
+        Some(delimiter) if delimiter.len() == 1 && delimiter.is_ascii() => {
+            Some(delimiter.as_bytes()[0])
+        }
+        Some(delimiter) => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid CSV delimiter '{}', it must be a single ASCII character",
+                delimiter
+            )))
+        }
+        None => None,
+    };
+
+    Ok(CsvOptions {
+        title_column,
+        content_column,
+        id_column,
+        delimiter,
+    })
+}
+
+#[pyclass(module = "find_similar", frozen)]
+#[derive(Default)]
+pub struct PostStore {
+    inner: post::PostStore,
+}
+
+impl From<post::PostStore> for PostStore {
+    fn from(inner: post::PostStore) -> Self {
+        PostStore { inner }
+    }
+}
+
+#[pymethods]
+impl PostStore {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// The generation of the current snapshot, increased by every write.
+    #[getter]
+    fn generation(&self) -> u64 {
+        self.inner.generation()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.snapshot().len()
+    }
+
+    /// Replaces all the posts in the store, including the ones of an opened snapshot file.
+    fn preload(&self, posts: Vec<Bound<'_, PyAny>>) -> PyResult<()> {
+        self.inner.preload(extract_posts(posts)?);
+        Ok(())
+    }
+
+    /// Adds posts to the store, keeping the existing ones.
+    fn append(&self, posts: Vec<Bound<'_, PyAny>>) -> PyResult<()> {
+        self.inner.append(extract_posts(posts)?);
+        Ok(())
+    }
+
+    /// Adds posts to the store, replacing the stored ones with the same IDs.
+    fn upsert(&self, posts: Vec<Bound<'_, PyAny>>) -> PyResult<()> {
+        self.inner.upsert(extract_posts(posts)?);
+        Ok(())
+    }
+
+    /// Removes the posts with the given IDs, returning how many have been removed.
+    fn remove(&self, ids: Vec<String>) -> usize {
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.inner.remove(&ids)
+    }
+
+    /// Finds the `top_n` posts most similar to `source`. When `filter` is given, only the posts
+    /// whose metadata satisfy it are scored. The GIL is released during the scan.
+    #[pyo3(signature = (source, top_n, filter=None))]
+    fn find_similar_posts(
+        &self,
+        py: Python<'_>,
+        source: &Bound<'_, PyAny>,
+        top_n: usize,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<FindTopNResult> {
+        let source = extract_post(source)?;
+        let filter = parse_filter(filter)?;
+
+        py.detach(|| {
+            self.inner
+                .find_similar_posts(&source, top_n, filter.as_ref())
+        })
+        .map(FindTopNResult::from)
+        .map_err(to_py_err)
+    }
+
+    /// Saves the posts of the current snapshot to a binary snapshot file.
+    fn save(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        py.detach(|| self.inner.save(&path)).map_err(to_py_err)
+    }
+
+    /// Loads a store from a binary snapshot file created by `save`.
+    #[staticmethod]
+    fn load(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        py.detach(|| post::PostStore::load(&path))
+            .map(PostStore::from)
+            .map_err(to_py_err)
+    }
+
+    /// Opens a binary snapshot file created by `save` in read-only mode by memory mapping it.
+    #[staticmethod]
+    fn open(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        py.detach(|| post::PostStore::open(&path))
+            .map(PostStore::from)
+            .map_err(to_py_err)
+    }
+
+    /// Replaces the posts in the store with the ones in a CSV file. Malformed records are
+    /// skipped and reported with their line numbers.
+    #[pyo3(signature = (path, title_column=None, content_column=None, id_column=None, delimiter=None))]
+    fn load_csv(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        title_column: Option<String>,
+        content_column: Option<String>,
+        id_column: Option<String>,
+        delimiter: Option<String>,
+    ) -> PyResult<LoadReport> {
+        let options = csv_options(title_column, content_column, id_column, delimiter)?;
+
+        py.detach(|| self.inner.load_csv(&path, &options))
+            .map(LoadReport::from)
+            .map_err(to_py_err)
+    }
+
+    /// Writes the posts of the current snapshot to a CSV file.
+    #[pyo3(signature = (path, title_column=None, content_column=None, id_column=None, delimiter=None))]
+    fn dump_csv(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        title_column: Option<String>,
+        content_column: Option<String>,
+        id_column: Option<String>,
+        delimiter: Option<String>,
+    ) -> PyResult<()> {
+        let options = csv_options(title_column, content_column, id_column, delimiter)?;
+
+        py.detach(|| self.inner.dump_csv(&path, &options))
+            .map_err(to_py_err)
+    }
+
+    /// Replaces the posts in the store with the ones in a JSON Lines file, which may be
+    /// gzip-compressed. Malformed lines are skipped and reported with their line numbers.
+    #[pyo3(signature = (path, id_field=None, title_field=None, content_field=None, metadata_field=None, gzip=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn load_jsonl(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        id_field: Option<String>,
+        title_field: Option<String>,
+        content_field: Option<String>,
+        metadata_field: Option<String>,
+        gzip: Option<bool>,
+    ) -> PyResult<LoadReport> {
+        let options = JsonlOptions {
+            id_field,
+            title_field,
+            content_field,
+            metadata_field,
+            gzip,
+        };
+
+        py.detach(|| self.inner.load_jsonl(&path, &options))
+            .map(LoadReport::from)
+            .map_err(to_py_err)
+    }
+
+    /// Writes the posts of the current snapshot to a JSON Lines file.
+    #[pyo3(signature = (path, id_field=None, title_field=None, content_field=None, metadata_field=None))]
+    fn dump_jsonl(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        id_field: Option<String>,
+        title_field: Option<String>,
+        content_field: Option<String>,
+        metadata_field: Option<String>,
+    ) -> PyResult<()> {
+        let options = JsonlOptions {
+            id_field,
+            title_field,
+            content_field,
+            metadata_field,
+            gzip: None,
+        };
+
+        py.detach(|| self.inner.dump_jsonl(&path, &options))
+            .map_err(to_py_err)
+    }
+
+    /// Loads a store from a table or a query of a MySQL, PostgreSQL or SQLite database, the
+    /// scheme of `url` selects the driver.
+    #[staticmethod]
+    #[pyo3(signature = (url, table=None, query=None, id_column=None, title_column=None, content_column=None, metadata_columns=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_db(
+        py: Python<'_>,
+        url: String,
+        table: Option<String>,
+        query: Option<String>,
+        id_column: Option<String>,
+        title_column: Option<String>,
+        content_column: Option<String>,
+        metadata_columns: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let options = DbOptions {
+            url,
+            table,
+            query,
+            columns: Some(DbColumns {
+                id: id_column,
+                title: title_column,
+                content: content_column,
+                metadata: metadata_columns,
+            }),
+        };
+
+        py.detach(|| block_on(post::PostStore::from_db(&options)))?
+            .map(PostStore::from)
+            .map_err(to_py_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::types::PyList;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_post() {
+        Python::initialize();
+        Python::attach(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("id", "1").unwrap();
+            dict.set_item("title", "Deno.kill on windows").unwrap();
+            dict.set_item("content", "SIGINT").unwrap();
+            dict.set_item("metadata", PyDict::new(py)).unwrap();
+
+            let post = extract_post(dict.as_any()).unwrap();
+            assert_eq!(post.id.as_deref(), Some("1"));
+            assert_eq!(post.title, "Deno.kill on windows");
+            assert_eq!(post.metadata, Some(Map::new()));
+
+            let obj = Bound::new(py, PostData::from(post.clone())).unwrap();
+            assert_eq!(extract_post(obj.as_any()).unwrap(), post);
+
+            dict.del_item("content").unwrap();
+            let err = extract_post(dict.as_any()).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+
+            let err = extract_post(PyList::empty(py).as_any()).unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn test_csv_options() {
+        let options = csv_options(None, None, None, Some(";".to_string())).unwrap();
+        assert_eq!(options.delimiter, Some(b';'));
+
+        Python::initialize();
+        let err = csv_options(None, None, None, Some("ab".to_string())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ValueError: Invalid CSV delimiter 'ab', it must be a single ASCII character"
+        );
+    }
+}
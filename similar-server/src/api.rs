@@ -0,0 +1,446 @@
+//! The JSON bodies and handlers of the HTTP API, the bodies use the same camel-cased shapes as
+//! the Node.js bindings.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use similar_core::{
+    filter::Filter,
+    issue::{self, find_similar_records_in_parallel, ScoringOptions},
+    post, Error,
+};
+
+use crate::AppState;
+
+const DEFAULT_TOP_N: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Post {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub title: String,
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl From<Post> for post::PostData {
+    fn from(post: Post) -> Self {
+        post::PostData {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: post.embedding,
+        }
+    }
+}
+
+impl From<post::PostData> for Post {
+    fn from(post: post::PostData) -> Self {
+        Post {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            metadata: post.metadata,
+            embedding: post.embedding,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Match {
+    pub target: Post,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FindTopNResult {
+    pub matches: Vec<Match>,
+    /// How much time is used for processing, in milliseconds.
+    pub process_time: f64,
+    pub generation: Option<u64>,
+}
+
+impl From<post::FindTopNResult> for FindTopNResult {
+    fn from(result: post::FindTopNResult) -> Self {
+        FindTopNResult {
+            matches: result
+                .matches
+                .into_iter()
+                .map(|m| Match {
+                    target: m.target.into(),
+                    score: m.score,
+                })
+                .collect(),
+            process_time: result.process_time.as_secs_f64() * 1000.0,
+            generation: result.generation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostQuery {
+    pub title: String,
+    pub content: String,
+    pub top_n: Option<usize>,
+    pub filter: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueFeatures {
+    pub operation: Option<String>,
+    pub phenomenon: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub actual_behavior: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl From<IssueFeatures> for issue::IssueFeatures {
+    fn from(features: IssueFeatures) -> Self {
+        issue::IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: features.embedding,
+        }
+    }
+}
+
+impl From<issue::IssueFeatures> for IssueFeatures {
+    fn from(features: issue::IssueFeatures) -> Self {
+        IssueFeatures {
+            operation: features.operation,
+            phenomenon: features.phenomenon,
+            expected_behavior: features.expected_behavior,
+            actual_behavior: features.actual_behavior,
+            embedding: features.embedding,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueFeaturesRecord {
+    pub issue_id: String,
+    pub features: IssueFeatures,
+    #[serde(default)]
+    pub metadata: Option<Map<String, Value>>,
+}
+
+impl From<IssueFeaturesRecord> for issue::IssueFeaturesRecord {
+    fn from(record: IssueFeaturesRecord) -> Self {
+        issue::IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+impl From<issue::IssueFeaturesRecord> for IssueFeaturesRecord {
+    fn from(record: issue::IssueFeaturesRecord) -> Self {
+        IssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarIssueFeaturesRecord {
+    pub issue_id: String,
+    pub features: IssueFeatures,
+    pub metadata: Option<Map<String, Value>>,
+    pub score: f64,
+    pub generation: u64,
+}
+
+impl From<issue::SimilarIssueFeaturesRecord> for SimilarIssueFeaturesRecord {
+    fn from(record: issue::SimilarIssueFeaturesRecord) -> Self {
+        SimilarIssueFeaturesRecord {
+            issue_id: record.issue_id,
+            features: record.features.into(),
+            metadata: record.metadata,
+            score: record.score,
+            generation: record.generation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueQuery {
+    pub features: IssueFeatures,
+    pub top_n: Option<usize>,
+    pub filter: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchQuery<Q> {
+    pub queries: Vec<Q>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult<R> {
+    pub results: Vec<R>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpsertResult {
+    pub upserted: usize,
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreStats {
+    pub count: usize,
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub posts: StoreStats,
+    pub issues: StoreStats,
+}
+
+/// An error response, rendered as `{ "error": "..." }`.
+#[derive(Debug)]
+pub struct ApiError(StatusCode, String);
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::InvalidArgument(_) | Error::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+            Error::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        ApiError(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+fn parse_filter(filter: Option<&Value>) -> Result<Option<Filter>, Error> {
+    filter.map(Filter::parse).transpose()
+}
+
+/// Runs the scoring on the blocking pool, so a long scan doesn't stall the async workers.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, ApiError> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result.map_err(ApiError::from),
+        Err(e) => Err(ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+pub async fn stats(State(state): State<AppState>) -> Json<Stats> {
+    let posts = state.posts.snapshot();
+    let issues = state.issues.snapshot();
+
+    Json(Stats {
+        posts: StoreStats {
+            count: posts.len(),
+            generation: posts.generation,
+        },
+        issues: StoreStats {
+            count: issues.map.len(),
+            generation: issues.generation,
+        },
+    })
+}
+
+/// Adds the posts, replacing the stored ones with the same IDs. Every post must have an ID, so
+/// it can be replaced and deleted later.
+pub async fn upsert_posts(
+    State(state): State<AppState>,
+    Json(posts): Json<Vec<Post>>,
+) -> ApiResult<UpsertResult> {
+    if posts
+        .iter()
+        .any(|post| post.id.as_deref().is_none_or(str::is_empty))
+    {
+        return Err(Error::InvalidArgument("every post must have an ID".to_string()).into());
+    }
+
+    let upserted = posts.len();
+    state
+        .posts
+        .upsert(posts.into_iter().map(post::PostData::from).collect());
+
+    Ok(Json(UpsertResult {
+        upserted,
+        generation: state.posts.generation(),
+    }))
+}
+
+pub async fn delete_post(State(state): State<AppState>, Path(id): Path<String>) -> StatusCode {
+    if state.posts.remove(&[&id]) > 0 {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub(crate) fn query_posts_in(
+    snapshot: &post::PostsSnapshot,
+    query: PostQuery,
+) -> Result<FindTopNResult, Error> {
+    let filter = parse_filter(query.filter.as_ref())?;
+    let source = post::PostData {
+        title: query.title,
+        content: query.content,
+        ..Default::default()
+    };
+    let top_n = query.top_n.unwrap_or(DEFAULT_TOP_N);
+
+    snapshot
+        .find_similar_posts(&source, top_n, filter.as_ref())
+        .map(FindTopNResult::from)
+}
+
+pub async fn query_posts(
+    State(state): State<AppState>,
+    Json(query): Json<PostQuery>,
+) -> ApiResult<FindTopNResult> {
+    let snapshot = state.posts.snapshot();
+
+    blocking(move || query_posts_in(&snapshot, query))
+        .await
+        .map(Json)
+}
+
+/// Runs the queries one after another against the same snapshot, so all the results share a
+/// generation.
+pub async fn batch_query_posts(
+    State(state): State<AppState>,
+    Json(batch): Json<BatchQuery<PostQuery>>,
+) -> ApiResult<BatchResult<FindTopNResult>> {
+    let snapshot = state.posts.snapshot();
+    let results = blocking(move || {
+        batch
+            .queries
+            .into_iter()
+            .map(|query| query_posts_in(&snapshot, query))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await?;
+
+    Ok(Json(BatchResult { results }))
+}
+
+/// Adds the records, replacing the stored ones with the same issue IDs. The records are
+/// validated upfront, so a bad one leaves the store untouched.
+pub async fn upsert_issues(
+    State(state): State<AppState>,
+    Json(records): Json<Vec<IssueFeaturesRecord>>,
+) -> ApiResult<UpsertResult> {
+    let records: Vec<issue::IssueFeaturesRecord> = records
+        .into_iter()
+        .map(issue::IssueFeaturesRecord::from)
+        .collect();
+
+    if records.iter().any(|record| record.issue_id.is_empty()) {
+        return Err(Error::InvalidArgument("issue_id must not be empty".to_string()).into());
+    } else if records.iter().any(|record| record.features.is_empty()) {
+        return Err(Error::InvalidArgument("features must not be empty".to_string()).into());
+    }
+
+    let upserted = records.len();
+    for record in records {
+        state.issues.set_record(record)?;
+    }
+
+    Ok(Json(UpsertResult {
+        upserted,
+        generation: state.issues.generation(),
+    }))
+}
+
+pub async fn delete_issue(
+    State(state): State<AppState>,
+    Path(issue_id): Path<String>,
+) -> StatusCode {
+    if state.issues.remove_record(&issue_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub(crate) fn query_issues_in(
+    snapshot: &issue::IssueFeaturesSnapshot,
+    query: IssueQuery,
+) -> Result<Vec<SimilarIssueFeaturesRecord>, Error> {
+    let filter = parse_filter(query.filter.as_ref())?;
+    let top_n = query.top_n.unwrap_or(DEFAULT_TOP_N);
+    let matches = find_similar_records_in_parallel(
+        &query.features.into(),
+        snapshot,
+        top_n,
+        filter.as_ref(),
+        &ScoringOptions::default(),
+    )?;
+
+    Ok(matches
+        .into_iter()
+        .map(SimilarIssueFeaturesRecord::from)
+        .collect())
+}
+
+pub async fn query_issues(
+    State(state): State<AppState>,
+    Json(query): Json<IssueQuery>,
+) -> ApiResult<Vec<SimilarIssueFeaturesRecord>> {
+    let snapshot = state.issues.snapshot();
+
+    blocking(move || query_issues_in(&snapshot, query))
+        .await
+        .map(Json)
+}
+
+/// Runs the queries one after another against the same snapshot, see [batch_query_posts].
+pub async fn batch_query_issues(
+    State(state): State<AppState>,
+    Json(batch): Json<BatchQuery<IssueQuery>>,
+) -> ApiResult<BatchResult<Vec<SimilarIssueFeaturesRecord>>> {
+    let snapshot = state.issues.snapshot();
+    let results = blocking(move || {
+        batch
+            .queries
+            .into_iter()
+            .map(|query| query_issues_in(&snapshot, query))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await?;
+
+    Ok(Json(BatchResult { results }))
+}
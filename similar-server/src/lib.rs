@@ -0,0 +1,339 @@
+//! A local HTTP/JSON service hosting a [PostStore] and an [IssueFeatureStore], for the services
+//! which can't load the Node.js bindings. The same stores can be served over stdio with
+//! JSON-RPC instead, see [rpc].
+//!
+//! | Method   | Path                   | Body                               |
+//! | -------- | ---------------------- | ---------------------------------- |
+//! | `GET`    | `/health`              |                                    |
+//! | `GET`    | `/stats`               |                                    |
+//! | `PUT`    | `/posts`               | `Post[]`                           |
+//! | `DELETE` | `/posts/{id}`          |                                    |
+//! | `POST`   | `/posts/query`         | `{ title, content, topN, filter }` |
+//! | `POST`   | `/posts/query/batch`   | `{ queries: [...] }`               |
+//! | `PUT`    | `/issues`              | `IssueFeaturesRecord[]`            |
+//! | `DELETE` | `/issues/{issueId}`    |                                    |
+//! | `POST`   | `/issues/query`        | `{ features, topN, filter }`       |
+//! | `POST`   | `/issues/query/batch`  | `{ queries: [...] }`               |
+
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
+use similar_core::{
+    issue::{IssueFeatureStore, JsonlOptions},
+    post::PostStore,
+    Error, Result,
+};
+use tokio::net::TcpListener;
+
+pub mod api;
+pub mod rpc;
+
+const POSTS_FILE: &str = "posts.snapshot";
+const ISSUES_FILE: &str = "issues.jsonl";
+
+/// The stores shared by the handlers, cloning it is cheap.
+#[derive(Debug, Clone, Default)]
+pub struct AppState {
+    pub posts: PostStore,
+    pub issues: IssueFeatureStore,
+}
+
+pub struct Server {
+    state: AppState,
+    data_dir: Option<PathBuf>,
+}
+
+impl Server {
+    /// Creates a server with the stores restored from the files saved in `data_dir` on the last
+    /// exit, if any. Without a `data_dir`, the stores start empty and are dropped on exit.
+    pub fn open(data_dir: Option<PathBuf>) -> Result<Self> {
+        let mut state = AppState::default();
+
+        if let Some(dir) = &data_dir {
+            let posts_file = dir.join(POSTS_FILE);
+            let issues_file = dir.join(ISSUES_FILE);
+
+            if posts_file.exists() {
+                state.posts = PostStore::load(&posts_file)?;
+            }
+
+            if issues_file.exists() {
+                let report = state
+                    .issues
+                    .load_jsonl(&issues_file, &JsonlOptions::default())?;
+
+                if let Some(e) = report.errors.first() {
+                    return Err(Error::InvalidSnapshot(format!(
+                        "Malformed issue record at line {} of {}: {}",
+                        e.line,
+                        issues_file.display(),
+                        e.message
+                    )));
+                }
+            }
+        }
+
+        Ok(Server { state, data_dir })
+    }
+
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/health", get(api::health))
+            .route("/stats", get(api::stats))
+            .route("/posts", put(api::upsert_posts))
+            .route("/posts/{id}", delete(api::delete_post))
+            .route("/posts/query", post(api::query_posts))
+            .route("/posts/query/batch", post(api::batch_query_posts))
+            .route("/issues", put(api::upsert_issues))
+            .route("/issues/{issue_id}", delete(api::delete_issue))
+            .route("/issues/query", post(api::query_issues))
+            .route("/issues/query/batch", post(api::batch_query_issues))
+            .with_state(self.state.clone())
+    }
+
+    /// Writes the stores to the `data_dir`, which is a no-op without one. The posts are written
+    /// as a binary snapshot, the issues as JSON Lines.
+    pub fn save(&self) -> Result<()> {
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+
+        std::fs::create_dir_all(dir).map_err(|e| Error::Io("Cannot create data directory", e))?;
+        self.state.posts.save(dir.join(POSTS_FILE))?;
+        save_issues(&self.state.issues, &dir.join(ISSUES_FILE))
+    }
+
+    /// Serves the requests on `listener` until `shutdown` resolves, then waits for the pending
+    /// requests to finish and saves the stores, see [Server::save].
+    pub async fn run(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<()> {
+        axum::serve(listener, self.router())
+            .with_graceful_shutdown(shutdown)
+            .await
+            .map_err(|e| Error::Io("Cannot serve HTTP requests", e))?;
+
+        self.save()
+    }
+
+    /// Serves JSON-RPC requests over stdin and stdout until stdin is closed, then saves the
+    /// stores, see [rpc] for the protocol.
+    pub async fn run_stdio(self) -> Result<()> {
+        let input = tokio::io::BufReader::new(tokio::io::stdin());
+
+        rpc::serve(self.state.clone(), input, tokio::io::stdout())
+            .await
+            .map_err(|e| Error::Io("Cannot serve JSON-RPC requests", e))?;
+
+        self.save()
+    }
+}
+
+/// Writes the issues to a temporary file renamed into place, so a crash while saving doesn't
+/// leave a truncated file behind.
+fn save_issues(issues: &IssueFeatureStore, path: &Path) -> Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+
+    issues.dump_jsonl(&tmp, &JsonlOptions::default())?;
+    std::fs::rename(&tmp, path).map_err(|e| Error::Io("Cannot rename issues file", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn request(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_posts_api() {
+        let server = Server::open(None).unwrap();
+        let router = server.router();
+
+        let (status, body) = request(&router, Method::GET, "/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "status": "ok" }));
+
+        let posts = json!([
+            {
+                "id": "1",
+                "title": "Deno.kill on windows",
+                "content": "SIGINT is not supported on Windows",
+                "metadata": { "tags": ["windows"] },
+            },
+            { "id": "2", "title": "How to embed V8", "content": "Embedding V8 into Rust" },
+        ]);
+        let (status, body) = request(&router, Method::PUT, "/posts", Some(posts)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "upserted": 2, "generation": 1 }));
+
+        let posts = json!([{ "title": "Deno.run", "content": "" }]);
+        let (status, body) = request(&router, Method::PUT, "/posts", Some(posts)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({ "error": "every post must have an ID" }));
+
+        let query = json!({
+            "title": "Deno.kill on windows",
+            "content": "SIGINT is not supported",
+            "filter": { "tags": "windows" },
+        });
+        let (status, body) = request(&router, Method::POST, "/posts/query", Some(query)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["generation"], 1);
+        assert_eq!(body["matches"].as_array().unwrap().len(), 1);
+        assert_eq!(body["matches"][0]["target"]["id"], "1");
+
+        let query = json!({ "title": "", "content": "" });
+        let (status, body) = request(&router, Method::POST, "/posts/query", Some(query)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({ "error": "source is invalid" }));
+
+        let (status, _) = request(&router, Method::DELETE, "/posts/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = request(&router, Method::DELETE, "/posts/1", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let batch = json!({
+            "queries": [
+                { "title": "Deno.kill on windows", "content": "SIGINT is not supported" },
+                { "title": "How to embed V8", "content": "Embedding V8 into Rust", "topN": 1 },
+            ],
+        });
+        let (status, body) =
+            request(&router, Method::POST, "/posts/query/batch", Some(batch)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"][0]["matches"], json!([]));
+        assert_eq!(body["results"][1]["matches"][0]["target"]["id"], "2");
+        assert_eq!(body["results"][1]["generation"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_issues_api() {
+        let server = Server::open(None).unwrap();
+        let router = server.router();
+        let features = json!({
+            "operation": "Turn on the switch",
+            "expectedBehavior": "The device is turned on",
+            "actualBehavior": "The device is not turned on",
+        });
+
+        let records = json!([
+            { "issueId": "1", "features": features, "metadata": { "state": "open" } },
+            { "issueId": "2", "features": features, "metadata": { "state": "closed" } },
+        ]);
+        let (status, body) = request(&router, Method::PUT, "/issues", Some(records)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upserted"], 2);
+
+        let records = json!([
+            { "issueId": "3", "features": features },
+            { "issueId": "4", "features": {} },
+        ]);
+        let (status, _) = request(&router, Method::PUT, "/issues", Some(records)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(server.state().issues.snapshot().map.len(), 2);
+
+        let batch = json!({
+            "queries": [
+                { "features": features, "filter": { "state": "open" } },
+                { "features": features, "filter": { "state": { "$bad": 1 } } },
+            ],
+        });
+        let (status, body) =
+            request(&router, Method::POST, "/issues/query/batch", Some(batch)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid filter"));
+
+        let query = json!({ "features": features, "filter": { "state": "open" } });
+        let (status, body) = request(&router, Method::POST, "/issues/query", Some(query)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["issueId"], "1");
+        assert_eq!(
+            body[0]["features"]["expectedBehavior"],
+            "The device is turned on"
+        );
+
+        let (status, _) = request(&router, Method::DELETE, "/issues/2", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (_, body) = request(&router, Method::GET, "/stats", None).await;
+        assert_eq!(
+            body,
+            json!({
+                "posts": { "count": 0, "generation": 0 },
+                "issues": { "count": 1, "generation": 3 },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let server = Server::open(Some(dir.path().to_path_buf())).unwrap();
+        let router = server.router();
+        let posts = json!([
+            { "id": "1", "title": "Deno.kill on windows", "content": "SIGINT is not supported" },
+        ]);
+        let records = json!([
+            { "issueId": "1", "features": { "operation": "Turn on the switch" } },
+        ]);
+        request(&router, Method::PUT, "/posts", Some(posts)).await;
+        request(&router, Method::PUT, "/issues", Some(records)).await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = tokio::spawn(server.run(listener, async {
+            rx.await.ok();
+        }));
+        tx.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+
+        let server = Server::open(Some(dir.path().to_path_buf())).unwrap();
+        let posts = server.state().posts.snapshot();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts.posts[0].id.as_deref(), Some("1"));
+        assert!(server.state().issues.get_record("1").is_some());
+    }
+}
@@ -0,0 +1,74 @@
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use similar_server::Server;
+use tokio::{net::TcpListener, signal};
+
+#[derive(Debug, Parser)]
+#[command(name = "similar-server", version, about)]
+struct Cli {
+    /// The address to listen on.
+    #[arg(short, long, default_value = "127.0.0.1:7878")]
+    listen: SocketAddr,
+
+    /// Serves JSON-RPC 2.0 requests over stdin and stdout instead of HTTP.
+    #[arg(long, conflicts_with = "listen")]
+    stdio: bool,
+
+    /// The directory to restore the stores from on start and save them to on exit. Without it,
+    /// the stores are kept in memory only.
+    #[arg(short, long)]
+    data_dir: Option<PathBuf>,
+}
+
+/// Resolves on ctrl-c, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.ok();
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut stream) => {
+                stream.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = async {
+        let server = Server::open(cli.data_dir)?;
+
+        if cli.stdio {
+            return server.run_stdio().await;
+        }
+
+        let listener = TcpListener::bind(cli.listen)
+            .await
+            .map_err(|e| similar_core::Error::Io("Cannot listen on the address", e))?;
+
+        eprintln!("similar-server: listening on http://{}", cli.listen);
+        server.run(listener, shutdown_signal()).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("similar-server: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,732 @@
+//! A JSON-RPC 2.0 server over a pair of byte streams, normally stdin and stdout, for the tools
+//! which would rather spawn a long-lived process than talk HTTP.
+//!
+//! Messages are either one JSON object per line, or framed with a `Content-Length` header like
+//! in the Language Server Protocol, the framing of a response follows its request. Requests are
+//! handled concurrently and can be cancelled with the `$/cancelRequest` notification, in which
+//! case they're answered with the `-32800` error right away. The server stops once the input is
+//! closed and the pending requests are answered.
+//!
+//! The methods mirror the ones of the Node.js bindings, with their params as an object:
+//!
+//! - `issues/setRecord` with an `IssueFeaturesRecord`, `issues/getRecord` and
+//!   `issues/removeRecord` with `{ issueId }`, `issues/findSimilarRecords` with
+//!   `{ features, topN, filter }`, `issues/loadCsv` with `{ path }` and `issues/fromDb` with
+//!   `{ url, table }`.
+//! - `posts/preload`, `posts/append` and `posts/upsert` with `{ posts }`, `posts/remove` with
+//!   `{ ids }`, `posts/findSimilarPosts` with `{ title, content, topN, filter }`,
+//!   `posts/loadCsv` and `posts/loadJsonl` with `{ path, options }`, `posts/fromDb` with
+//!   `{ url, table, query, columns }` and `posts/save` with `{ path }`.
+//!
+//! Loading from a file or a database replaces the records of the hosted store.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use similar_core::{
+    issue::{self, IssueFeatureStore},
+    load, post,
+    post::PostStore,
+    Error,
+};
+use tokio::{
+    io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+    task::JoinSet,
+};
+
+use crate::{
+    api::{query_issues_in, query_posts_in, IssueFeaturesRecord, IssueQuery, Post, PostQuery},
+    AppState,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// The implementation-defined error for the failures of the stores, such as a missing file.
+const SERVER_ERROR: i64 = -32000;
+/// The error a cancelled request is answered with, as defined by the Language Server Protocol.
+const REQUEST_CANCELLED: i64 = -32800;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::InvalidArgument(_) | Error::InvalidFilter(_) => INVALID_PARAMS,
+            _ => SERVER_ERROR,
+        };
+
+        RpcError::new(code, e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Lines,
+    Headers,
+}
+
+/// Reads the next message, skipping blank lines between them. Returns `None` at the end of the
+/// input.
+async fn read_message(
+    input: &mut (impl AsyncBufRead + Unpin),
+) -> io::Result<Option<(Framing, String)>> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if input.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let header = line.trim();
+        if header.is_empty() {
+            continue;
+        }
+
+        let Some(length) = header
+            .split_once(':')
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .map(|(_, length)| length.trim())
+        else {
+            return Ok(Some((Framing::Lines, header.to_string())));
+        };
+        let length: usize = length.parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "Invalid Content-Length header")
+        })?;
+
+        // skips the other headers, such as `Content-Type`, up to the blank line
+        loop {
+            line.clear();
+            if input.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            } else if line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut body = vec![0; length];
+        input.read_exact(&mut body).await?;
+        let body = String::from_utf8(body)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Message is not UTF-8"))?;
+
+        return Ok(Some((Framing::Headers, body)));
+    }
+}
+
+fn frame(framing: Framing, message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+
+    match framing {
+        Framing::Lines => format!("{}\n", body).into_bytes(),
+        Framing::Headers => format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes(),
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+}
+
+/// The senders cancelling the pending requests, keyed by the JSON text of their IDs.
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>;
+
+/// Serves the requests read from `input` until it's closed, writing the responses to `output`.
+pub async fn serve(
+    state: AppState,
+    mut input: impl AsyncBufRead + Unpin,
+    mut output: impl AsyncWrite + Unpin + Send + 'static,
+) -> io::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Framing, Value)>();
+    let writer = tokio::spawn(async move {
+        while let Some((framing, message)) = rx.recv().await {
+            output.write_all(&frame(framing, &message)).await?;
+            output.flush().await?;
+        }
+        io::Result::Ok(())
+    });
+    let pending: Pending = Arc::default();
+    let mut tasks = JoinSet::new();
+
+    while let Some((framing, body)) = read_message(&mut input).await? {
+        let message = match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Object(message)) => message,
+            Ok(_) => {
+                let error = RpcError::new(INVALID_REQUEST, "Request must be an object");
+                tx.send((framing, response(Value::Null, Err(error)))).ok();
+                continue;
+            }
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, e.to_string());
+                tx.send((framing, response(Value::Null, Err(error)))).ok();
+                continue;
+            }
+        };
+        let id = message.get("id").cloned();
+        let method = match message.get("method") {
+            Some(Value::String(method)) if message.get("jsonrpc") == Some(&json!("2.0")) => {
+                method.clone()
+            }
+            // a response from the client, no request of ours expects one
+            None if id.is_some() && message.get("jsonrpc").is_some() => continue,
+            _ => {
+                let error = RpcError::new(INVALID_REQUEST, "Invalid JSON-RPC 2.0 request");
+                tx.send((framing, response(id.unwrap_or(Value::Null), Err(error))))
+                    .ok();
+                continue;
+            }
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        if method == "$/cancelRequest" {
+            let key = params.get("id").map(Value::to_string).unwrap_or_default();
+
+            if let Some(cancel) = pending.lock().unwrap().remove(&key) {
+                cancel.send(()).ok();
+            }
+            continue;
+        } else if method.starts_with("$/") && id.is_none() {
+            // the protocol notifications this server doesn't implement may be ignored
+            continue;
+        }
+
+        let state = state.clone();
+        let Some(id) = id else {
+            // a notification, which is handled but never answered
+            tasks.spawn(async move {
+                call(state, &method, params).await.ok();
+            });
+            continue;
+        };
+
+        let key = id.to_string();
+        let (cancel, cancelled) = oneshot::channel();
+        pending.lock().unwrap().insert(key.clone(), cancel);
+
+        let pending = pending.clone();
+        let tx = tx.clone();
+        tasks.spawn(async move {
+            let result = cancellable(call(state, &method, params), cancelled).await;
+
+            pending.lock().unwrap().remove(&key);
+            tx.send((framing, response(id, result))).ok();
+        });
+    }
+
+    while tasks.join_next().await.is_some() {}
+    drop(tx);
+
+    writer.await.map_err(|e| io::Error::other(e.to_string()))?
+}
+
+/// Resolves to the result of `call`, or to the `-32800` error as soon as the request is
+/// cancelled. A scan already running on the blocking pool completes in the background, but its
+/// result is dropped.
+async fn cancellable(
+    call: impl Future<Output = Result<Value, RpcError>>,
+    cancelled: oneshot::Receiver<()>,
+) -> Result<Value, RpcError> {
+    tokio::select! {
+        result = call => result,
+        Ok(()) = cancelled => Err(RpcError::new(REQUEST_CANCELLED, "Request cancelled")),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+/// Runs the work on the blocking pool, as the scans and loaders may take seconds.
+async fn blocking<T: Serialize + Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<Value, RpcError> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => to_value(result?),
+        Err(e) => Err(RpcError::new(INTERNAL_ERROR, e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueIdParams {
+    issue_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathParams<O> {
+    path: String,
+    #[serde(default)]
+    options: Option<O>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostsParams {
+    posts: Vec<Post>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdsParams {
+    ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueDbOptions {
+    url: String,
+    table: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CsvOptions {
+    title_column: Option<String>,
+    content_column: Option<String>,
+    id_column: Option<String>,
+    delimiter: Option<String>,
+}
+
+impl TryFrom<CsvOptions> for post::CsvOptions {
+    type Error = Error;
+
+    fn try_from(options: CsvOptions) -> Result<Self, Error> {
+        let delimiter = match options.delimiter.as_deref() {
+            None => None,
+            Some(delimiter) if delimiter.len() == 1 && delimiter.is_ascii() => {
+                Some(delimiter.as_bytes()[0])
+            }
+            Some(delimiter) => {
+                return Err(Error::InvalidArgument(format!(
+                    "Invalid CSV delimiter '{}', it must be a single ASCII character",
+                    delimiter
+                )));
+            }
+        };
+
+        Ok(post::CsvOptions {
+            title_column: options.title_column,
+            content_column: options.content_column,
+            id_column: options.id_column,
+            delimiter,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonlOptions {
+    id_field: Option<String>,
+    title_field: Option<String>,
+    content_field: Option<String>,
+    metadata_field: Option<String>,
+    gzip: Option<bool>,
+}
+
+impl From<JsonlOptions> for post::JsonlOptions {
+    fn from(options: JsonlOptions) -> Self {
+        post::JsonlOptions {
+            id_field: options.id_field,
+            title_field: options.title_field,
+            content_field: options.content_field,
+            metadata_field: options.metadata_field,
+            gzip: options.gzip,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DbColumns {
+    id: Option<String>,
+    title: Option<String>,
+    content: Option<String>,
+    metadata: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostDbOptions {
+    url: String,
+    table: Option<String>,
+    query: Option<String>,
+    columns: Option<DbColumns>,
+}
+
+impl From<PostDbOptions> for post::DbOptions {
+    fn from(options: PostDbOptions) -> Self {
+        post::DbOptions {
+            url: options.url,
+            table: options.table,
+            query: options.query,
+            columns: options.columns.map(|columns| post::DbColumns {
+                id: columns.id,
+                title: columns.title,
+                content: columns.content,
+                metadata: columns.metadata,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LoadError {
+    line: u64,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LoadReport {
+    loaded: usize,
+    errors: Vec<LoadError>,
+}
+
+impl From<load::LoadReport> for LoadReport {
+    fn from(report: load::LoadReport) -> Self {
+        LoadReport {
+            loaded: report.loaded,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|e| LoadError {
+                    line: e.line,
+                    message: e.message,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn into_post_data(posts: Vec<Post>) -> Vec<post::PostData> {
+    posts.into_iter().map(post::PostData::from).collect()
+}
+
+async fn call(state: AppState, method: &str, params: Value) -> Result<Value, RpcError> {
+    let AppState { posts, issues } = state;
+
+    match method {
+        "issues/setRecord" => {
+            let record: IssueFeaturesRecord = parse_params(params)?;
+            issues.set_record(record.into())?;
+            Ok(Value::Null)
+        }
+        "issues/getRecord" => {
+            let IssueIdParams { issue_id } = parse_params(params)?;
+            to_value(issues.get_record(&issue_id).map(IssueFeaturesRecord::from))
+        }
+        "issues/removeRecord" => {
+            let IssueIdParams { issue_id } = parse_params(params)?;
+            Ok(Value::Bool(issues.remove_record(&issue_id)))
+        }
+        "issues/findSimilarRecords" => {
+            let query: IssueQuery = parse_params(params)?;
+            let snapshot = issues.snapshot();
+            blocking(move || query_issues_in(&snapshot, query)).await
+        }
+        "issues/loadCsv" => {
+            let PathParams::<Value> { path, .. } = parse_params(params)?;
+            blocking(move || {
+                issues.replace_with(&IssueFeatureStore::load_csv(&path)?);
+                Ok(json!({ "loaded": issues.snapshot().map.len() }))
+            })
+            .await
+        }
+        "issues/fromDb" => {
+            let IssueDbOptions { url, table } = parse_params(params)?;
+            let loaded = IssueFeatureStore::from_db(&issue::DbOptions { url, table }).await?;
+
+            issues.replace_with(&loaded);
+            Ok(json!({ "loaded": loaded.snapshot().map.len() }))
+        }
+        "posts/preload" => {
+            let PostsParams { posts: data } = parse_params(params)?;
+            posts.preload(into_post_data(data));
+            Ok(json!({ "generation": posts.generation() }))
+        }
+        "posts/append" => {
+            let PostsParams { posts: data } = parse_params(params)?;
+            posts.append(into_post_data(data));
+            Ok(json!({ "generation": posts.generation() }))
+        }
+        "posts/upsert" => {
+            let PostsParams { posts: data } = parse_params(params)?;
+            posts.upsert(into_post_data(data));
+            Ok(json!({ "generation": posts.generation() }))
+        }
+        "posts/remove" => {
+            let IdsParams { ids } = parse_params(params)?;
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            Ok(json!({ "removed": posts.remove(&ids) }))
+        }
+        "posts/findSimilarPosts" => {
+            let query: PostQuery = parse_params(params)?;
+            let snapshot = posts.snapshot();
+            blocking(move || query_posts_in(&snapshot, query)).await
+        }
+        "posts/loadCsv" => {
+            let PathParams::<CsvOptions> { path, options } = parse_params(params)?;
+            let options = post::CsvOptions::try_from(options.unwrap_or_default())?;
+            blocking(move || posts.load_csv(&path, &options).map(LoadReport::from)).await
+        }
+        "posts/loadJsonl" => {
+            let PathParams::<JsonlOptions> { path, options } = parse_params(params)?;
+            let options = post::JsonlOptions::from(options.unwrap_or_default());
+            blocking(move || posts.load_jsonl(&path, &options).map(LoadReport::from)).await
+        }
+        "posts/fromDb" => {
+            let options: PostDbOptions = parse_params(params)?;
+            let loaded = PostStore::from_db(&options.into()).await?;
+
+            posts.replace_with(&loaded);
+            Ok(json!({ "loaded": loaded.snapshot().len() }))
+        }
+        "posts/save" => {
+            let PathParams::<Value> { path, .. } = parse_params(params)?;
+            let snapshot = posts.snapshot();
+            blocking(move || snapshot.save(&path)).await
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method '{}' not found", method),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tokio::io::{AsyncBufReadExt, BufReader, DuplexStream};
+
+    use super::*;
+
+    /// Starts a server over an in-memory pipe, returns the client ends.
+    fn start() -> (DuplexStream, BufReader<DuplexStream>, AppState) {
+        let state = AppState::default();
+        let (client_input, server_output) = io::duplex(64 * 1024);
+        let (client_output, server_input) = io::duplex(64 * 1024);
+
+        tokio::spawn(serve(
+            state.clone(),
+            BufReader::new(server_input),
+            server_output,
+        ));
+        (client_output, BufReader::new(client_input), state)
+    }
+
+    async fn send_line(output: &mut DuplexStream, message: Value) {
+        output
+            .write_all(format!("{}\n", message).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    async fn receive_line(input: &mut BufReader<DuplexStream>) -> Value {
+        let mut line = String::new();
+        input.read_line(&mut line).await.unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn receive_framed(input: &mut BufReader<DuplexStream>) -> Value {
+        let (framing, body) = read_message(input).await.unwrap().unwrap();
+        assert_eq!(framing, Framing::Headers);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_issue_methods() {
+        let (mut output, mut input, state) = start();
+        let record = json!({
+            "issueId": "1",
+            "features": {
+                "operation": "Turn on the switch",
+                "expectedBehavior": "The device is turned on",
+            },
+            "metadata": { "state": "open" },
+        });
+
+        send_line(
+            &mut output,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "issues/setRecord", "params": record }),
+        )
+        .await;
+        assert_eq!(
+            receive_line(&mut input).await,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": null })
+        );
+
+        send_line(
+            &mut output,
+            json!({
+                "jsonrpc": "2.0",
+                "id": "get",
+                "method": "issues/getRecord",
+                "params": { "issueId": "1" },
+            }),
+        )
+        .await;
+        let message = receive_line(&mut input).await;
+        assert_eq!(message["id"], "get");
+        assert_eq!(message["result"]["issueId"], "1");
+        assert_eq!(message["result"]["features"]["phenomenon"], Value::Null);
+        assert_eq!(message["result"]["metadata"], record["metadata"]);
+
+        send_line(
+            &mut output,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "issues/findSimilarRecords",
+                "params": { "features": record["features"], "filter": { "state": "open" } },
+            }),
+        )
+        .await;
+        let message = receive_line(&mut input).await;
+        assert_eq!(message["result"][0]["issueId"], "1");
+        assert_eq!(message["result"][0]["score"], 1.0);
+
+        send_line(
+            &mut output,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "issues/setRecord",
+                "params": { "issueId": "2", "features": {} },
+            }),
+        )
+        .await;
+        let message = receive_line(&mut input).await;
+        assert_eq!(message["error"]["code"], INVALID_PARAMS);
+        assert_eq!(message["error"]["message"], "features must not be empty");
+
+        // notifications are handled without a response
+        send_line(
+            &mut output,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "issues/removeRecord",
+                "params": { "issueId": "1" },
+            }),
+        )
+        .await;
+        send_line(
+            &mut output,
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "issues/dropAll" }),
+        )
+        .await;
+        let message = receive_line(&mut input).await;
+        assert_eq!(message["id"], 4);
+        assert_eq!(message["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(state.issues.get_record("1"), None);
+
+        output.write_all(b"{ not json\n").await.unwrap();
+        let message = receive_line(&mut input).await;
+        assert_eq!(message["id"], Value::Null);
+        assert_eq!(message["error"]["code"], PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_post_methods_with_headers() {
+        let (mut output, mut input, _) = start();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("similar-server-rpc-posts.csv");
+        fs::write(
+            &path,
+            "id,title,content\n1,Deno.kill on windows,SIGINT is not supported\n2,\"broken\n",
+        )
+        .unwrap();
+
+        let send = |message: Value| {
+            let body = message.to_string();
+            format!(
+                "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        };
+        let request = send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "posts/loadCsv",
+            "params": { "path": path },
+        }));
+        let query = send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "posts/findSimilarPosts",
+            "params": { "title": "Deno.kill on windows", "content": "SIGINT is not supported" },
+        }));
+        output.write_all(request.as_bytes()).await.unwrap();
+
+        let message = receive_framed(&mut input).await;
+        assert_eq!(message["result"]["loaded"], 1);
+        assert_eq!(message["result"]["errors"].as_array().unwrap().len(), 1);
+
+        output.write_all(query.as_bytes()).await.unwrap();
+        let message = receive_framed(&mut input).await;
+        assert_eq!(message["id"], 2);
+        assert_eq!(message["result"]["matches"][0]["target"]["id"], "1");
+        assert_eq!(message["result"]["generation"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let (cancel, cancelled) = oneshot::channel();
+        let pending = tokio::spawn(cancellable(std::future::pending(), cancelled));
+
+        cancel.send(()).unwrap();
+        assert_eq!(
+            pending.await.unwrap(),
+            Err(RpcError::new(REQUEST_CANCELLED, "Request cancelled"))
+        );
+
+        // dropping the sender, as done once a request completes, doesn't cancel it
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        drop(cancel);
+        let result = cancellable(async { Ok(Value::Bool(true)) }, cancelled).await;
+        assert_eq!(result, Ok(Value::Bool(true)));
+
+        // cancelling an unknown or completed request is ignored
+        let (mut output, mut input, _) = start();
+        send_line(
+            &mut output,
+            json!({ "jsonrpc": "2.0", "method": "$/cancelRequest", "params": { "id": 9 } }),
+        )
+        .await;
+        send_line(
+            &mut output,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "posts/remove", "params": { "ids": [] } }),
+        )
+        .await;
+        assert_eq!(
+            receive_line(&mut input).await,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": { "removed": 0 } })
+        );
+    }
+}